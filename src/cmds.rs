@@ -1,9 +1,9 @@
-use crate::{eval::Value, scope::Scope, utils::copy_vars_to_command_env};
+use crate::{eval::Value, scope::Scope, utils::copy_exported_vars_to_command_env};
 use colored::Colorize;
 use std::any::Any;
 use std::borrow::Cow;
 use std::collections::HashMap;
-use std::ffi::OsStr;
+use std::ffi::{OsStr, OsString};
 use std::fmt::Debug;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, LazyLock, Mutex};
@@ -11,14 +11,19 @@ use std::{fs, io};
 use which::which;
 
 mod flags;
+mod walk;
 use flags::CommandFlags;
 // Built-in commands
+mod abbr;
 mod alias;
 mod basename;
 mod cat;
 mod cd;
+mod checksum;
 mod chmod;
 mod clear;
+mod compress;
+mod convert;
 mod cp;
 mod cut;
 mod date;
@@ -29,31 +34,57 @@ mod diff;
 mod du;
 mod echo;
 mod evalargs;
+mod exec;
 mod exit;
+mod export;
+mod fetch;
+mod fields;
+mod file;
 mod find;
+mod free;
 mod grep;
+mod hash;
 mod help;
+mod history;
+mod jobs;
 mod less;
 mod ln;
 mod ls;
 mod mkdir;
 mod mv;
 mod open;
+mod plugin;
 #[cfg(windows)]
 mod power;
 mod ps;
 mod realpath;
 mod rm;
 mod run;
+mod set;
 mod sort;
+mod source;
 mod strings;
+mod sub;
 #[cfg(windows)]
 mod sudo;
+mod top;
 mod touch;
+mod tr;
+mod trash;
+mod uname;
+mod uptime;
 mod vars;
 mod wc;
 #[cfg(windows)]
 mod whois;
+mod xargs;
+mod xxd;
+mod zip;
+
+pub use abbr::{expand_abbreviation, load_abbreviations};
+pub use alias::load_aliases;
+pub use clear::clear_screen;
+pub use plugin::load_plugins;
 
 pub trait Exec {
     fn as_any(&self) -> Option<&dyn Any> {
@@ -97,12 +128,12 @@ impl ShellCommand {
         &self.name
     }
 
-    fn is_alias(&self) -> bool {
+    pub fn alias_expansion(&self) -> Option<String> {
         self.inner
             .as_ref()
             .as_any()
             .and_then(|any| any.downcast_ref::<alias::AliasRunner>())
-            .is_some()
+            .map(|runner| runner.expansion())
     }
 
     fn is_external(&self) -> bool {
@@ -130,7 +161,14 @@ impl Exec for ShellCommand {
     }
 
     fn exec(&self, name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
-        self.inner.exec(name, args, scope)
+        let default_flags = DEFAULT_FLAGS.lock().unwrap().get(&self.name).cloned();
+        match default_flags {
+            Some(flags) => {
+                let args = flags.into_iter().chain(args.iter().cloned()).collect();
+                self.inner.exec(name, &args, scope)
+            }
+            None => self.inner.exec(name, args, scope),
+        }
     }
 
     fn is_script(&self) -> bool {
@@ -147,6 +185,16 @@ unsafe impl Send for ShellCommand {}
 static COMMAND_REGISTRY: LazyLock<Mutex<HashMap<String, ShellCommand>>> =
     LazyLock::new(|| Mutex::new(HashMap::new()));
 
+/// Flags passed to commands by default, before the arguments (configured in config.toml).
+static DEFAULT_FLAGS: LazyLock<Mutex<HashMap<String, Vec<String>>>> = LazyLock::new(Mutex::default);
+
+pub fn set_default_flags(name: &str, flags: Vec<String>) {
+    DEFAULT_FLAGS
+        .lock()
+        .unwrap()
+        .insert(name.to_string(), flags);
+}
+
 pub fn register_command(command: ShellCommand) -> Option<ShellCommand> {
     COMMAND_REGISTRY
         .lock()
@@ -160,22 +208,25 @@ pub fn unregister_command(name: &str) {
 
 pub fn get_command(name: &str) -> Option<ShellCommand> {
     let mut cmd = COMMAND_REGISTRY.lock().unwrap().get(name).cloned();
-    if cmd.is_none() {
-        if let Some(_) = which_executable(Path::new(name)) {
-            // Do not cache the path, as $PATH may change later.
-            register_command(ShellCommand {
-                name: name.to_string(),
-                inner: Arc::new(External {
-                    path: PathBuf::from(name),
-                }),
-            });
-            cmd = COMMAND_REGISTRY.lock().unwrap().get(name).cloned();
-        }
+    if cmd.is_none() && hashed_executable(Path::new(name)).is_some() {
+        // Do not register the path, as $PATH may change later; the lookup is hashed instead.
+        register_command(ShellCommand {
+            name: name.to_string(),
+            inner: Arc::new(External {
+                path: PathBuf::from(name),
+            }),
+        });
+        cmd = COMMAND_REGISTRY.lock().unwrap().get(name).cloned();
     }
 
     cmd
 }
 
+/// Check if the name is a built-in command or alias, without looking it up in $PATH.
+pub fn is_registered(name: &str) -> bool {
+    COMMAND_REGISTRY.lock().unwrap().contains_key(name)
+}
+
 pub fn registered_commands(internal_only: bool) -> Vec<String> {
     let registry = COMMAND_REGISTRY.lock().unwrap();
 
@@ -192,6 +243,102 @@ pub fn registered_commands(internal_only: bool) -> Vec<String> {
     commands
 }
 
+/// Paths of external commands, found by searching $PATH. The cache is
+/// discarded when $PATH (or $PATHEXT on Windows) changes, and stale entries
+/// are looked up again.
+struct PathCache {
+    path_var: Vec<Option<OsString>>,
+    paths: HashMap<String, PathBuf>,
+}
+
+static PATH_CACHE: LazyLock<Mutex<PathCache>> = LazyLock::new(|| {
+    Mutex::new(PathCache {
+        path_var: Vec::new(),
+        paths: HashMap::new(),
+    })
+});
+
+/// Lock the cache, after clearing it if $PATH has changed since it was populated.
+fn path_cache() -> std::sync::MutexGuard<'static, PathCache> {
+    let mut cache = PATH_CACHE.lock().unwrap();
+    let path_var = [
+        "PATH",
+        #[cfg(windows)]
+        "PATHEXT",
+    ]
+    .iter()
+    .map(std::env::var_os)
+    .collect::<Vec<_>>();
+    if cache.path_var != path_var {
+        cache.path_var = path_var;
+        cache.paths.clear();
+    }
+    cache
+}
+
+/// Command names are case-insensitive on Windows.
+fn hash_key(name: &str) -> String {
+    if cfg!(windows) {
+        name.to_lowercase()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Look up an executable, consulting the cache for bare command names.
+/// Names that contain a directory are resolved directly. On Windows, names
+/// without an extension are matched against the extensions in $PATHEXT.
+pub fn hashed_executable<T: AsRef<Path>>(name: T) -> Option<PathBuf> {
+    let name = name.as_ref();
+    let key = match name.to_str() {
+        Some(s) if !name.is_absolute() && name.components().count() == 1 => hash_key(s),
+        _ => return which_executable(name),
+    };
+
+    let mut cache = path_cache();
+    if let Some(path) = cache.paths.get(&key) {
+        if path.is_file() {
+            return Some(path.clone());
+        }
+        cache.paths.remove(&key);
+    }
+
+    let path = which_executable(name)?;
+    cache.paths.insert(key, path.clone());
+    Some(path)
+}
+
+/// Look up an executable, consulting the cache but without adding the result to it.
+pub fn lookup_executable(name: &str) -> Option<PathBuf> {
+    if let Some(path) = path_cache().paths.get(&hash_key(name)) {
+        if path.is_file() {
+            return Some(path.clone());
+        }
+    }
+    which_executable(name)
+}
+
+/// Look up the command and add it to the cache. Return the path, if found.
+pub fn hash_command(name: &str) -> Option<PathBuf> {
+    path_cache().paths.remove(&hash_key(name));
+    hashed_executable(name)
+}
+
+/// Return the cached (name, path) pairs, sorted by name.
+pub fn hashed_commands() -> Vec<(String, PathBuf)> {
+    let mut commands: Vec<_> = path_cache()
+        .paths
+        .iter()
+        .map(|(name, path)| (name.clone(), path.clone()))
+        .collect();
+    commands.sort();
+    commands
+}
+
+pub fn clear_hashed_commands() {
+    path_cache().paths.clear();
+}
+
 fn which_executable<T: AsRef<OsStr>>(path: T) -> Option<PathBuf> {
     match which(path) {
         Ok(path) => {
@@ -241,7 +388,7 @@ impl External {
     fn which_path(&self) -> Cow<'_, Path> {
         if self.path.is_absolute() {
             Cow::Borrowed(&self.path)
-        } else if let Some(path) = which_executable(&self.path) {
+        } else if let Some(path) = hashed_executable(&self.path) {
             Cow::Owned(path)
         } else {
             Cow::Borrowed(&self.path)
@@ -269,17 +416,20 @@ impl Exec for External {
     fn exec(&self, _name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
         use crate::job::*;
 
-        // Resolve the path on each execution, because $PATH may have changed.
+        // Resolve the path on each execution (via the cache), because $PATH may have changed.
         let path = self.which_path();
 
         let mut job = Job::new(scope, &path, &args, false);
-        copy_vars_to_command_env(job.command().unwrap(), &scope);
+        copy_exported_vars_to_command_env(job.command().unwrap(), &scope);
 
         match job.run() {
             Ok(_) => {
                 return Ok(Value::success());
             }
             Err(error) => {
+                if let Some(code) = exit_code(&error) {
+                    scope.set_exit_status(code);
+                }
                 let command = job.command().unwrap();
                 let cmd = std::iter::once(command.get_program())
                     .chain(command.get_args())
@@ -334,10 +484,38 @@ impl Which {
     fn new() -> Self {
         let mut flags = CommandFlags::new();
         flags.add_flag('?', "help", "Display this help message");
+        flags.add_flag('a', "all", "Show all matches, not just the one that runs");
         flags.add_flag('e', "external", "Show external commands only");
 
         Self { flags }
     }
+
+    /// Print what NAME resolves to, in the order used by the interpreter: aliases and
+    /// built-in commands first, then executables found in $PATH. Return false if not found.
+    fn locate(name: &str, all: bool, extern_only: bool) -> Result<bool, String> {
+        let mut found = false;
+
+        if !extern_only {
+            if let Some(cmd) = get_command(name) {
+                if let Some(expansion) = cmd.alias_expansion() {
+                    my_println!("{}: alias for {}", name, expansion)?;
+                    found = true;
+                } else if !cmd.is_external() {
+                    my_println!("{}: built-in", name)?;
+                    found = true;
+                }
+            }
+        }
+
+        if !found || all {
+            if let Some(path) = hashed_executable(name) {
+                my_println!("{}", path.display())?;
+                found = true;
+            }
+        }
+
+        Ok(found)
+    }
 }
 
 impl Exec for Which {
@@ -345,40 +523,37 @@ impl Exec for Which {
         Box::new(self.flags.iter())
     }
 
-    fn exec(&self, _name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
+    fn exec(&self, name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
         let mut flags = self.flags.clone();
-        flags.parse(scope, args)?;
+        let args = flags.parse(scope, args)?;
 
         if flags.is_present("help") {
-            println!("Usage: which [COMMAND]...");
-            println!("Locate a command and display its path.");
+            println!("Usage: {} [OPTIONS] COMMAND...", name);
+            println!("Show how each COMMAND is resolved: alias (and its expansion), built-in,");
+            println!("or external executable (and its full path).");
             println!("\nOptions:");
             print!("{}", flags.help());
             return Ok(Value::success());
         }
 
         if args.is_empty() {
-            return Err("which: missing command name".to_string());
+            return Err(format!("{}: missing command name", name));
         }
 
+        let all = flags.is_present("all");
         let extern_only = flags.is_present("external");
+        let mut not_found = None;
 
-        for command in args {
-            if let Some(cmd) = get_command(command) {
-                if !extern_only && !cmd.is_external() {
-                    if cmd.is_alias() {
-                        my_println!("{}: alias", command)?;
-                    } else {
-                        my_println!("{}: built-in", command)?;
-                    }
-                }
-            }
-            if let Some(path) = which_executable(command) {
-                my_println!("{}", path.display())?;
+        for command in &args {
+            if !Self::locate(command, all, extern_only)? && not_found.is_none() {
+                not_found = Some(command);
             }
         }
 
-        Ok(Value::success())
+        match not_found {
+            Some(command) => Err(format!("{}: not found", scope.err_str(command))),
+            None => Ok(Value::success()),
+        }
     }
 }
 
@@ -388,4 +563,9 @@ fn register() {
         name: "which".to_string(),
         inner: Arc::new(Which::new()),
     });
+
+    register_command(ShellCommand {
+        name: "type".to_string(),
+        inner: Arc::new(Which::new()),
+    });
 }
@@ -0,0 +1,180 @@
+use super::{flags::CommandFlags, register_command, Exec, Flag, ShellCommand};
+use crate::{eval::Value, scope::Scope};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
+
+#[derive(Default)]
+struct Abbreviations {
+    map: BTreeMap<String, String>,
+    path: Option<PathBuf>, // File where the abbreviations are saved
+}
+
+static ABBREVIATIONS: LazyLock<Mutex<Abbreviations>> = LazyLock::new(Mutex::default);
+
+impl Abbreviations {
+    fn save(&self) -> Result<(), String> {
+        if let Some(path) = &self.path {
+            let text = self
+                .map
+                .iter()
+                .map(|(name, expansion)| format!("{}\t{}\n", name, expansion))
+                .collect::<String>();
+
+            fs::write(path, text)
+                .map_err(|e| format!("Could not save {}: {}", path.display(), e))?;
+        }
+        Ok(())
+    }
+}
+
+/// Load the abbreviations saved in the file (if it exists),
+/// and save the abbreviations to it from now on.
+pub fn load_abbreviations(path: &Path) -> Result<(), String> {
+    let mut abbr = ABBREVIATIONS.lock().unwrap();
+
+    match fs::read_to_string(path) {
+        Ok(text) => {
+            for (name, expansion) in text.lines().filter_map(|line| line.split_once('\t')) {
+                abbr.map.insert(name.to_string(), expansion.to_string());
+            }
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(format!("Could not load {}: {}", path.display(), e)),
+    }
+    abbr.path = Some(path.to_path_buf());
+
+    Ok(())
+}
+
+/// If the input ends with an abbreviation in command position, i.e. at the start of
+/// the input or after ; | & or (, return its position and the expansion.
+pub fn expand_abbreviation(input: &str) -> Option<(usize, String)> {
+    let is_separator = |c: char| c.is_whitespace() || matches!(c, ';' | '|' | '&' | '(');
+    let start = input
+        .rfind(is_separator)
+        .map_or(0, |i| i + input[i..].chars().next().unwrap().len_utf8());
+    let word = &input[start..];
+
+    let before = input[..start].trim_end();
+    if word.is_empty() || !(before.is_empty() || before.ends_with(is_separator)) {
+        return None;
+    }
+
+    let abbr = ABBREVIATIONS.lock().unwrap();
+    abbr.map
+        .get(word)
+        .map(|expansion| (start, expansion.clone()))
+}
+
+struct Abbr {
+    flags: CommandFlags,
+}
+
+impl Abbr {
+    fn new() -> Self {
+        let mut flags = CommandFlags::with_help();
+        flags.add_flag('e', "erase", "Erase the abbreviations");
+        flags.add_flag('l', "list", "List the abbreviations");
+
+        Self { flags }
+    }
+}
+
+impl Exec for Abbr {
+    fn cli_flags(&self) -> Box<dyn Iterator<Item = &Flag> + '_> {
+        Box::new(self.flags.iter())
+    }
+
+    fn exec(&self, _name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
+        let mut flags = self.flags.clone();
+        let args = flags.parse(scope, args)?;
+
+        if flags.is_present("help") {
+            println!("Usage: abbr [NAME EXPANSION...]");
+            println!("       abbr -e NAME...");
+            println!("Define abbreviations, which expand in place in the command line when");
+            println!("followed by Space or Enter. Without arguments, list the abbreviations.");
+            println!("\nOptions:");
+            print!("{}", flags.help());
+            println!();
+            println!("Examples:");
+            println!("    abbr gco git checkout");
+            println!("    abbr -e gco");
+            return Ok(Value::success());
+        }
+
+        let mut abbr = ABBREVIATIONS.lock().unwrap();
+
+        if flags.is_present("erase") {
+            for name in &args {
+                if abbr.map.remove(name).is_none() {
+                    return Err(format!("{}: abbreviation not found", scope.err_str(name)));
+                }
+            }
+            abbr.save()?;
+        } else if args.is_empty() || flags.is_present("list") {
+            for (name, expansion) in &abbr.map {
+                my_println!("{}\t{}", name, expansion)?;
+            }
+        } else if args.len() < 2 {
+            return Err("EXPANSION not specified".to_string());
+        } else {
+            let name = &args[0];
+            if name.contains(|c: char| c.is_whitespace() || matches!(c, ';' | '|' | '&' | '(')) {
+                return Err(format!("{}: invalid abbreviation", scope.err_str(name)));
+            }
+            abbr.map.insert(name.clone(), args[1..].join(" "));
+            abbr.save()?;
+        }
+
+        Ok(Value::success())
+    }
+}
+
+#[ctor::ctor]
+fn register() {
+    register_command(ShellCommand {
+        name: "abbr".to_string(),
+        inner: Arc::new(Abbr::new()),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_abbreviation() {
+        let abbr = Abbr::new();
+        let scope = Scope::new();
+        let args = ["gcoabbr", "git", "checkout"].map(String::from).to_vec();
+        abbr.exec("abbr", &args, &scope).unwrap();
+
+        assert_eq!(
+            expand_abbreviation("gcoabbr"),
+            Some((0, "git checkout".to_string()))
+        );
+        assert_eq!(
+            expand_abbreviation("cd src; gcoabbr"),
+            Some((8, "git checkout".to_string()))
+        );
+        assert_eq!(expand_abbreviation("echo gcoabbr"), None);
+        assert_eq!(expand_abbreviation("gcoabbr "), None);
+        assert_eq!(
+            expand_abbreviation("cd src;\u{a0}gcoabbr"),
+            Some((9, "git checkout".to_string()))
+        );
+        assert_eq!(expand_abbreviation("\u{a0}"), None);
+
+        abbr.exec(
+            "abbr",
+            &vec!["-e".to_string(), "gcoabbr".to_string()],
+            &scope,
+        )
+        .unwrap();
+        assert_eq!(expand_abbreviation("gcoabbr"), None);
+    }
+}
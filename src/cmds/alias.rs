@@ -4,11 +4,74 @@ use super::{
     flags::CommandFlags, get_command, register_command, registered_commands, unregister_command,
     Exec, Flag, ShellCommand,
 };
-use crate::{eval::Value, prompt::confirm, prompt::Answer, scope::Scope, utils::format_error};
+use crate::utils::{default_editor, edit_text, format_error};
+use crate::{eval::Value, prompt::confirm, prompt::Answer, scope::Scope};
 use std::any::Any;
+use std::collections::BTreeMap;
+use std::fs;
 use std::io;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+fn read_alias_file(path: &Path) -> Result<BTreeMap<String, String>, String> {
+    match fs::read_to_string(path) {
+        Ok(text) => Ok(text
+            .lines()
+            .filter_map(|line| line.split_once('\t'))
+            .map(|(name, expansion)| (name.to_string(), expansion.to_string()))
+            .collect()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(format!("Could not load {}: {}", path.display(), e)),
+    }
+}
+
+/// Update the aliases saved in the file.
+fn update_alias_file<F: FnOnce(&mut BTreeMap<String, String>)>(
+    path: &Path,
+    update: F,
+) -> Result<(), String> {
+    let mut aliases = read_alias_file(path)?;
+    update(&mut aliases);
+
+    let text = aliases
+        .iter()
+        .map(|(name, expansion)| format!("{}\t{}\n", name, expansion))
+        .collect::<String>();
+    path.parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(path, text))
+        .map_err(|e| format!("Could not save {}: {}", path.display(), e))
+}
+
+fn register_alias(name: String, args: Vec<String>) {
+    register_command(ShellCommand {
+        name,
+        inner: Arc::new(AliasRunner::new(args)),
+    });
+}
+
+/// Register the aliases saved in the file, except for the names that are already
+/// registered (e.g. by the profile).
+fn register_saved_aliases(path: &Path) -> Result<(), String> {
+    for (name, expansion) in read_alias_file(path)? {
+        if get_command(&name).is_none() {
+            register_alias(name, vec![expansion]);
+        }
+    }
+    Ok(())
+}
+
+/// Register the aliases saved in the file, and let `alias --save` save to it.
+pub fn load_aliases(path: &Path) -> Result<(), String> {
+    register_saved_aliases(path)?;
+    register_command(ShellCommand {
+        name: "alias".to_string(),
+        inner: Arc::new(Alias::with_file(path)),
+    });
+
+    Ok(())
+}
+
 pub struct AliasRunner {
     args: Vec<String>,
     cmd: Option<ShellCommand>,
@@ -20,6 +83,11 @@ impl AliasRunner {
         let cmd = get_command(arg);
         Self { args, cmd }
     }
+
+    /// The expression that the alias stands for.
+    pub fn expansion(&self) -> String {
+        self.args.join(" ")
+    }
 }
 
 impl Exec for AliasRunner {
@@ -53,6 +121,7 @@ impl Exec for AliasRunner {
 
 struct Alias {
     flags: CommandFlags,
+    file: Option<PathBuf>, // Where --save writes the aliases, set in interactive mode only
 }
 
 impl Alias {
@@ -60,56 +129,118 @@ impl Alias {
         let mut flags = CommandFlags::with_help();
         flags.add_flag('r', "remove", "Remove an existing alias");
         flags.add_flag('l', "list", "List all aliases");
+        flags.add_flag('e', "edit", "Edit the alias in $EDITOR");
+        flags.add_flag(
+            's',
+            "save",
+            "Save (or remove) the alias in the alias file, or save all aliases if no NAME is given",
+        );
+
+        Self { flags, file: None }
+    }
+
+    fn with_file(path: &Path) -> Self {
+        Self {
+            file: Some(path.to_path_buf()),
+            ..Self::new()
+        }
+    }
 
-        Self { flags }
+    /// Update the saved aliases. Aliases are loaded and saved in interactive mode only.
+    fn update_file<F: FnOnce(&mut BTreeMap<String, String>)>(
+        &self,
+        update: F,
+    ) -> Result<(), String> {
+        match &self.file {
+            Some(path) => update_alias_file(path, update),
+            None => Err("Aliases can be saved in interactive mode only".to_string()),
+        }
     }
 
-    fn add(&self, name: String, args: Vec<String>) -> Result<Value, String> {
+    fn add(&self, name: String, args: Vec<String>, save: bool) -> Result<Value, String> {
         if get_command(&name).is_some() {
             Err(format!("{} already exists", name))
         } else {
             assert!(!args.is_empty());
-            register_command(ShellCommand {
-                name,
-                inner: Arc::new(AliasRunner::new(args)),
-            });
+            let expansion = args.join(" ");
+            register_alias(name.clone(), args);
+            if save {
+                self.update_file(|saved| _ = saved.insert(name, expansion))?;
+            }
 
             Ok(Value::success())
         }
     }
 
-    fn register(&self, name: &str, args: &[&str]) -> Result<Value, String> {
-        self.add(
-            name.to_string(),
-            args.iter().map(|s| s.to_string()).collect(),
-        )
+    /// Return the expansion of the alias, or an error if the name is not an alias.
+    fn expansion(&self, name: &str, scope: &Arc<Scope>, args: &[String]) -> Result<String, String> {
+        match get_command(name) {
+            None => Err(format_error(scope, name, args, "alias not found")),
+            Some(cmd) => cmd
+                .alias_expansion()
+                .ok_or_else(|| format_error(scope, name, args, "not an alias")),
+        }
+    }
+
+    /// Open the expansion of the alias in the editor, and re-register the alias with the edited text.
+    fn edit(
+        &self,
+        name: &str,
+        scope: &Arc<Scope>,
+        args: &[String],
+        save: bool,
+    ) -> Result<Value, String> {
+        let expansion = self.expansion(name, scope, args)?;
+        let edited = edit_text(scope, &default_editor(scope), "shmy-alias-", &expansion)?;
+        let edited = edited.trim();
+
+        if edited.is_empty() {
+            return Err("EXPRESSION not specified".to_string());
+        }
+        if edited != expansion {
+            unregister_command(name);
+            self.add(name.to_string(), vec![edited.to_string()], save)?;
+        }
+        Ok(Value::success())
+    }
+
+    /// Return the names and expansions of all registered aliases.
+    fn aliases(&self) -> Vec<(String, String)> {
+        registered_commands(true)
+            .into_iter()
+            .filter_map(|name| {
+                let expansion = get_command(&name)?.alias_expansion()?;
+                Some((name, expansion))
+            })
+            .collect()
     }
 
     fn list(&self) {
-        let mut count = 0;
+        let aliases = self.aliases();
 
-        for name in registered_commands(true) {
-            let cmd = get_command(&name).unwrap();
-
-            match cmd
-                .inner
-                .as_ref()
-                .as_any()
-                .and_then(|any| any.downcast_ref::<AliasRunner>())
-            {
-                None => {}
-                Some(runner) => {
-                    count += 1;
-                    println!("{}: {}", name, runner.args.join(" "));
-                }
-            }
+        for (name, expansion) in &aliases {
+            println!("{}: {}", name, expansion);
         }
-        if count == 0 {
+        if aliases.is_empty() {
             println!("No aliases found.");
         }
     }
 
-    fn remove(&self, name: &str, scope: &Arc<Scope>, args: &[String]) -> Result<Value, String> {
+    /// Save all registered aliases.
+    fn save_all(&self) -> Result<Value, String> {
+        let aliases = self.aliases();
+        self.update_file(|saved| saved.extend(aliases))?;
+
+        Ok(Value::success())
+    }
+
+    fn remove(
+        &self,
+        name: &str,
+        scope: &Arc<Scope>,
+        args: &[String],
+        save: bool,
+    ) -> Result<Value, String> {
         match get_command(name) {
             None => Err(format_error(scope, name, args, "alias not found")),
             Some(cmd) => {
@@ -123,6 +254,9 @@ impl Alias {
                     let prompt = format!("Remove '{}'", name);
                     if confirm(prompt, &scope, false).ok() == Some(Answer::Yes) {
                         unregister_command(name);
+                        if save {
+                            self.update_file(|saved| _ = saved.remove(name))?;
+                        }
                     }
                     Ok(Value::success())
                 } else {
@@ -135,7 +269,7 @@ impl Alias {
     #[cfg(test)]
     fn remove_all(&self, scope: &Arc<Scope>, args: &[String]) -> Result<Value, String> {
         for name in registered_commands(true) {
-            _ = self.remove(&name, scope, args);
+            _ = self.remove(&name, scope, args, false);
         }
 
         Ok(Value::success())
@@ -152,17 +286,21 @@ impl Exec for Alias {
         let mut parsed_args = flags.parse_relaxed(scope, args);
 
         if flags.is_present("help") {
-            println!("Usage: alias [NAME EXPRESSION] [OPTIONS]");
+            println!("Usage: alias [NAME [EXPRESSION]] [OPTIONS]");
             println!("Register or deregister aliases (expression shortcuts).");
+            println!("With a NAME only, show the expression that the alias stands for.");
             println!("\nOptions:");
             println!("{}", flags.help());
             println!();
             println!("Examples:");
             println!("    alias la ls -al");
             println!("    alias --remove la");
+            println!("    alias --edit la");
+            println!("    alias --save ll ls -l");
             println!("    alias unalias \"alias --remove\"");
             println!();
             println!("Using quotes is recommended when registering aliases.");
+            println!("Saved aliases are kept in ~/.shmy/aliases, and loaded when the shell starts interactively.");
             return Ok(Value::success());
         }
 
@@ -183,12 +321,25 @@ impl Exec for Alias {
             return Ok(Value::success());
         }
 
+        let save = flags.is_present("save");
+
+        if save && parsed_args.is_empty() {
+            return self.save_all();
+        }
+
         if flags.is_present("remove") {
             if parsed_args.is_empty() {
                 return Err("Please specify an alias to remove".to_string());
             }
             let name = &parsed_args[0];
-            return self.remove(&name, scope, args);
+            return self.remove(&name, scope, args, save);
+        }
+
+        if flags.is_present("edit") {
+            if parsed_args.len() != 1 {
+                return Err("Please specify one alias to edit".to_string());
+            }
+            return self.edit(&parsed_args[0], scope, args, save);
         }
 
         // Register new alias
@@ -197,24 +348,21 @@ impl Exec for Alias {
         }
 
         if parsed_args.len() < 2 {
-            return Err("EXPRESSION not specified".to_string());
+            let name = &parsed_args[0];
+            println!("{}: {}", name, self.expansion(name, scope, args)?);
+            return Ok(Value::success());
         }
 
         let name = parsed_args.remove(0);
-        self.add(name, parsed_args)
+        self.add(name, parsed_args, save)
     }
 }
 
 #[ctor::ctor]
 fn register() {
-    let alias = Alias::new();
-
-    _ = alias.register("export", &["eval", "--export"]);
-    _ = alias.register("source", &["eval", "--source"]);
-
     register_command(ShellCommand {
         name: "alias".to_string(),
-        inner: Arc::new(alias),
+        inner: Arc::new(Alias::new()),
     });
 }
 
@@ -241,7 +389,7 @@ mod tests {
         let name = "la".to_string();
         let args = vec!["ls".to_string(), "-al".to_string()];
 
-        let result = alias.add(name.clone(), args);
+        let result = alias.add(name.clone(), args, false);
         assert!(result.is_ok());
         assert!(get_command(&name).is_some());
     }
@@ -253,10 +401,10 @@ mod tests {
         let args = vec!["ls".to_string(), "-al".to_string()];
 
         // First add the alias
-        alias.add(name.clone(), args).unwrap();
+        alias.add(name.clone(), args, false).unwrap();
 
         // Try adding it again
-        let result = alias.add(name.clone(), vec!["another_cmd".to_string()]);
+        let result = alias.add(name.clone(), vec!["another_cmd".to_string()], false);
         assert!(result.is_err());
         assert_eq!(result.err().unwrap(), format!("{} already exists", name));
     }
@@ -267,8 +415,8 @@ mod tests {
         let name = "la".to_string();
         let args = vec!["ls".to_string(), "-al".to_string()];
 
-        alias.add(name.clone(), args).unwrap();
-        let result = alias.remove(&name, &scope, &vec![]);
+        alias.add(name.clone(), args, false).unwrap();
+        let result = alias.remove(&name, &scope, &[], false);
 
         assert!(result.is_ok());
         assert!(get_command(&name).is_none());
@@ -279,7 +427,7 @@ mod tests {
         let (scope, alias) = setup();
         let name = "non_existent".to_string();
 
-        let result = alias.remove(&name, &scope, &vec![]);
+        let result = alias.remove(&name, &scope, &[], false);
         assert!(result.is_err());
         assert_eq!(result.err().unwrap(), name + ": alias not found");
     }
@@ -290,9 +438,9 @@ mod tests {
         let name = "la".to_string();
         let args = vec!["ls".to_string(), "-al".to_string()];
 
-        alias.add(name.clone(), args).unwrap();
+        alias.add(name.clone(), args, false).unwrap();
 
-        let result = alias.remove(&name, &scope, &vec![]);
+        let result = alias.remove(&name, &scope, &[], false);
         assert!(result.is_ok());
         assert!(get_command(&name).is_none());
     }
@@ -303,9 +451,75 @@ mod tests {
         let name = "la".to_string();
         let args = vec!["ls".to_string(), "-al".to_string()];
 
-        alias.add(name.clone(), args).unwrap();
+        alias.add(name.clone(), args, false).unwrap();
 
         let result = alias.exec("alias", &vec!["--list".to_string()], &scope);
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_load_and_save_aliases() {
+        let scope = Scope::new();
+        scope.insert("NO_CONFIRM".to_string(), Value::Int(1));
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("aliases");
+        let alias = Alias::with_file(&path);
+        fs::write(&path, "saved_alias_test\tls -al\n").unwrap();
+
+        register_saved_aliases(&path).unwrap();
+        assert_eq!(
+            get_command("saved_alias_test").and_then(|cmd| cmd.alias_expansion()),
+            Some("ls -al".to_string())
+        );
+
+        let args = ["--save", "saved_alias_test2", "echo", "hello"].map(String::from);
+        alias.exec("alias", &args.to_vec(), &scope).unwrap();
+        let args = ["saved_alias_test3", "echo", "unsaved"].map(String::from);
+        alias.exec("alias", &args.to_vec(), &scope).unwrap();
+        let args = ["--remove", "--save", "saved_alias_test"].map(String::from);
+        alias.exec("alias", &args.to_vec(), &scope).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "saved_alias_test2\techo hello\n"
+        );
+        unregister_command("saved_alias_test2");
+        unregister_command("saved_alias_test3");
+    }
+
+    #[test]
+    fn test_save_without_alias_file() {
+        let scope = Scope::new();
+        let alias = Alias::new();
+        let args = ["--save", "unsaved_alias_test", "ls"].map(String::from);
+
+        assert!(alias.exec("alias", &args.to_vec(), &scope).is_err());
+        unregister_command("unsaved_alias_test");
+    }
+
+    #[test]
+    fn test_show_alias() {
+        let scope = Scope::new();
+        let alias = Alias::new();
+        alias
+            .add(
+                "show_alias_test".to_string(),
+                vec!["ls -al".to_string()],
+                false,
+            )
+            .unwrap();
+
+        assert_eq!(
+            alias.expansion("show_alias_test", &scope, &[]),
+            Ok("ls -al".to_string())
+        );
+        assert_eq!(
+            alias.expansion("ls", &scope, &[]),
+            Err("ls: not an alias".to_string())
+        );
+        assert!(alias
+            .exec("alias", &vec!["show_alias_test".to_string()], &scope)
+            .is_ok());
+        unregister_command("show_alias_test");
+    }
 }
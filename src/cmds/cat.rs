@@ -4,7 +4,7 @@ use crate::{
 };
 use std::collections::VecDeque;
 use std::fs::File;
-use std::io::{self, BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
 use std::path::Path;
 use std::sync::Arc;
 
@@ -15,6 +15,14 @@ enum Mode {
     Tail,
 }
 
+struct Options {
+    number: bool,
+    number_nonblank: bool,
+    show_all: bool,
+    lines: usize,
+    bytes: Option<usize>,
+}
+
 struct CatHeadTail {
     flags: CommandFlags,
     mode: Mode,
@@ -23,10 +31,32 @@ struct CatHeadTail {
 impl CatHeadTail {
     fn new(mode: Mode) -> Self {
         let mut flags = CommandFlags::with_help();
-        flags.add_flag('n', "number", "Number output lines");
+
+        if matches!(mode, Mode::Cat) {
+            flags.add_flag('n', "number", "Number output lines");
+            flags.add_flag(
+                'b',
+                "number-nonblank",
+                "Number non-empty output lines, overrides -n",
+            );
+            flags.add_flag(
+                'A',
+                "show-all",
+                "Show non-printing characters, TAB as ^I, and $ at the end of each line",
+            );
+        }
 
         if matches!(mode, Mode::Head | Mode::Tail) {
-            flags.add_value('l', "lines", "number", "Specify the number of lines to output");
+            flags.add(None, "number", None, "Number output lines");
+            flags.add_value(
+                'n',
+                "lines",
+                "NUMBER",
+                "Specify the number of lines to output (default: 10)",
+            );
+        }
+        if matches!(mode, Mode::Head) {
+            flags.add_value('c', "bytes", "NUMBER", "Output the first NUMBER bytes");
         }
         CatHeadTail { flags, mode }
     }
@@ -52,13 +82,12 @@ impl Exec for CatHeadTail {
         if flags.is_present("help") {
             println!("Usage: {} [OPTION]... [FILE]...", name);
             println!("{}", self.mode_specific_help());
+            println!("With no FILE, or when FILE is -, read standard input.");
             println!("\nOptions:");
             print!("{}", flags.help());
             return Ok(Value::success());
         }
 
-        let line_num: bool = flags.is_present("number");
-
         let lines = flags
             .value("lines")
             .map(|v| {
@@ -67,93 +96,208 @@ impl Exec for CatHeadTail {
             })
             .unwrap_or(Ok(10))?;
 
-        let result = if filenames.is_empty() {
-            scope.show_eof_hint();
+        let bytes = match self.mode {
+            Mode::Head => flags
+                .value("bytes")
+                .map(|v| {
+                    v.parse::<usize>()
+                        .map_err(|e| format_error(scope, v, args, e))
+                })
+                .transpose()?,
+            _ => None,
+        };
 
-            let mode = self.mode.clone();
-            let mut stdin = BufReader::new(io::stdin());
-            process_input(&mut stdin, mode, line_num, lines)
+        let is_cat = matches!(self.mode, Mode::Cat);
+        let opts = Options {
+            number: flags.is_present("number"),
+            number_nonblank: is_cat && flags.is_present("number-nonblank"),
+            show_all: is_cat && flags.is_present("show-all"),
+            lines,
+            bytes,
+        };
+
+        let filenames = if filenames.is_empty() {
+            vec!["-".to_string()]
         } else {
-            let mut result = Ok(());
-            for filename in &filenames {
+            filenames
+        };
+
+        // Head and tail show the name of each file, if there are more than one
+        let show_headers = !is_cat && filenames.len() > 1;
+
+        for (i, filename) in filenames.iter().enumerate() {
+            if show_headers {
+                let name = if filename == "-" {
+                    "standard input"
+                } else {
+                    filename
+                };
+                my_println!("{}==> {} <==", if i > 0 { "\n" } else { "" }, name)?;
+            }
+            if filename == "-" {
+                scope.show_eof_hint();
+
+                let mut stdin = BufReader::new(io::stdin());
+                process_input(&mut stdin, self.mode, &opts)?;
+            } else {
                 let path = Path::new(filename)
                     .dereference()
                     .map_err(|e| format_error(&scope, filename, args, e))?;
 
-                let mode = self.mode.clone();
                 let file =
                     File::open(&path).map_err(|e| format_error(&scope, filename, args, e))?;
 
                 let mut reader = BufReader::new(file);
-                result = process_input(&mut reader, mode, line_num, lines);
-
-                if result.is_err() {
-                    break;
-                }
+                process_input(&mut reader, self.mode, &opts)?;
             }
-            result
-        };
+            if Scope::is_interrupted() {
+                break;
+            }
+        }
 
-        result?;
         Ok(Value::success())
     }
 }
 
+/// Write to stdout, ignoring broken pipe errors.
+fn write_bytes(bytes: &[u8]) -> Result<(), String> {
+    match io::stdout().lock().write_all(bytes) {
+        Err(e) if e.kind() != ErrorKind::BrokenPipe => Err(e.to_string()),
+        _ => Ok(()),
+    }
+}
+
+/// Flush stdout, so that a last line without a newline is not left in the buffer
+/// (and written after the output is no longer redirected). Ignore broken pipe errors.
+fn flush_stdout() -> Result<(), String> {
+    match io::stdout().flush() {
+        Err(e) if e.kind() != ErrorKind::BrokenPipe => Err(e.to_string()),
+        _ => Ok(()),
+    }
+}
+
+/// Render control characters as ^X, DEL as ^?, TAB as ^I and non-ASCII bytes as M-X.
+fn show_nonprinting(bytes: &[u8]) -> String {
+    let mut result = String::new();
+    for &b in bytes {
+        let b = if b >= 0x80 {
+            result.push_str("M-");
+            b - 0x80
+        } else {
+            b
+        };
+        match b {
+            0..=0x1f => {
+                result.push('^');
+                result.push((b + 0x40) as char);
+            }
+            0x7f => result.push_str("^?"),
+            _ => result.push(b as char),
+        }
+    }
+    result
+}
+
+/// Copy at most max_bytes of the input in chunks, without buffering lines.
+fn copy<R: BufRead>(reader: &mut R, max_bytes: usize) -> Result<(), String> {
+    let mut remaining = max_bytes;
+    while remaining > 0 {
+        if Scope::is_interrupted() {
+            break;
+        }
+        let data = reader.fill_buf().map_err(|e| e.to_string())?;
+        if data.is_empty() {
+            break;
+        }
+        let len = data.len().min(remaining);
+        write_bytes(&data[..len])?;
+
+        reader.consume(len);
+        remaining -= len;
+    }
+    flush_stdout()
+}
+
 fn process_input<R: BufRead>(
     reader: &mut R,
     mode: Mode, // Cat, Head or Tail
-    line_numbers: bool,
-    lines: usize,
+    opts: &Options,
 ) -> Result<(), String> {
-    let mut i = 0;
+    if let (Mode::Head, Some(bytes)) = (mode, opts.bytes) {
+        return copy(reader, bytes);
+    }
+    if matches!(mode, Mode::Cat) && !opts.number && !opts.number_nonblank && !opts.show_all {
+        return copy(reader, usize::MAX);
+    }
+
+    let mut count = 0;
+    let mut number = 0;
+    let mut buf = Vec::new();
     let mut tail = VecDeque::new();
 
-    match tail.try_reserve(lines) {
+    match tail.try_reserve(opts.lines) {
         Ok(_) => {}
         Err(e) => {
             return Err(format!("Memory allocation failed: {}", e));
         }
     }
 
-    for line in reader.lines() {
+    loop {
         if Scope::is_interrupted() {
             break;
         }
-        match line {
-            Ok(line) => {
-                i += 1;
-                let line = if line_numbers {
-                    format!("{:>6}: {}", i, line)
-                } else {
-                    line
-                };
-                match mode {
-                    Mode::Cat => my_println!("{line}")?,
-                    Mode::Head => {
-                        if i > lines {
-                            break;
-                        };
-                        my_println!("{line}")?;
-                    }
-                    Mode::Tail => {
-                        if tail.len() == lines {
-                            tail.pop_front();
-                        }
-                        tail.push_back(line);
-                    }
+        buf.clear();
+        if reader
+            .read_until(b'\n', &mut buf)
+            .map_err(|e| e.to_string())?
+            == 0
+        {
+            break;
+        }
+        count += 1;
+        if matches!(mode, Mode::Head) && count > opts.lines {
+            break;
+        }
+
+        let newline = buf.last() == Some(&b'\n');
+        if newline {
+            buf.pop();
+        }
+
+        let mut line = Vec::new();
+        if (opts.number && !opts.number_nonblank) || (opts.number_nonblank && !buf.is_empty()) {
+            number += 1;
+            line.extend_from_slice(format!("{:>6}: ", number).as_bytes());
+        }
+        if opts.show_all {
+            line.extend_from_slice(show_nonprinting(&buf).as_bytes());
+            line.push(b'$');
+        } else {
+            line.extend_from_slice(&buf);
+        }
+        if newline {
+            line.push(b'\n');
+        }
+
+        match mode {
+            Mode::Cat | Mode::Head => write_bytes(&line)?,
+            Mode::Tail => {
+                if opts.lines == 0 {
+                    continue;
                 }
-            }
-            Err(e) => {
-                return Err(e.to_string());
+                if tail.len() == opts.lines {
+                    tail.pop_front();
+                }
+                tail.push_back(line);
             }
         }
     }
 
     for line in tail {
-        my_println!("{line}")?;
+        write_bytes(&line)?;
     }
 
-    Ok(())
+    flush_stdout()
 }
 
 #[ctor::ctor]
@@ -171,3 +315,15 @@ fn register() {
         inner: Arc::new(CatHeadTail::new(Mode::Tail)),
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_show_nonprinting() {
+        assert_eq!(show_nonprinting(b"a\tb\r"), "a^Ib^M");
+        assert_eq!(show_nonprinting(b"\x00\x1b\x7f"), "^@^[^?");
+        assert_eq!(show_nonprinting("é".as_bytes()), "M-CM-)");
+    }
+}
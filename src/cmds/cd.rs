@@ -1,7 +1,8 @@
 use super::{flags::CommandFlags, register_command, Exec, Flag, ShellCommand};
 use crate::{current_dir, eval::Value, scope::Scope, symlnk::SymLink};
 use std::cell::RefCell;
-use std::{env, path::Path, sync::Arc};
+use std::path::{Path, PathBuf};
+use std::{env, sync::Arc};
 
 struct ChangeDir {
     stack: RefCell<Vec<String>>,
@@ -23,12 +24,31 @@ impl ChangeDir {
 
     fn do_chdir(&self, scope: &Arc<Scope>, dir: &str) -> Result<(), String> {
         let path = Path::new(dir).dereference().map_err(|e| e.to_string())?;
+        let old_dir = current_dir()?;
 
         env::set_current_dir(&path)
             .map_err(|e| format!("Change dir to \"{}\": {}", scope.err_str(dir), e))?;
+
+        set_dir_var(scope, "OLDPWD", old_dir);
+        set_dir_var(scope, "PWD", current_dir()?);
         Ok(())
     }
 
+    /// Search the directories listed in $CDPATH for a relative DIR.
+    /// Return the first match, or None if DIR is to be resolved against the current directory.
+    fn search_cdpath(scope: &Arc<Scope>, dir: &str) -> Option<PathBuf> {
+        let path = Path::new(dir);
+        if path.is_absolute() || dir.starts_with('.') || dir.starts_with('~') {
+            return None;
+        }
+        let cdpath = scope.lookup_value("CDPATH")?.to_string();
+
+        env::split_paths(&cdpath)
+            .filter(|base| !base.as_os_str().is_empty())
+            .map(|base| base.join(path))
+            .find(|candidate| candidate.is_dir())
+    }
+
     fn chdir(&self, name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
         let mut flags = self.flags.clone();
         let parsed_args = flags.parse(scope, args)?;
@@ -36,8 +56,12 @@ impl ChangeDir {
         if flags.is_present("help") {
             match name {
                 "cd" | "chdir" => {
-                    println!("Usage: {} [DIR]", name);
-                    println!("Change the current directory to DIR.");
+                    println!("Usage: {} [DIR | -]", name);
+                    println!(
+                        "Change the current directory to DIR (default: $HOME), or to the previous"
+                    );
+                    println!("directory ($OLDPWD) if DIR is -. Relative DIRs are looked up in the");
+                    println!("directories listed in $CDPATH before the current directory.");
                 }
                 "pushd" => {
                     println!("Usage: pushd <DIR>");
@@ -65,7 +89,21 @@ impl ChangeDir {
                 } else {
                     parsed_args.join(" ")
                 };
-                self.do_chdir(scope, &new_dir)?
+
+                if new_dir == "-" {
+                    let old_dir = scope
+                        .lookup_value("OLDPWD")
+                        .map(|v| v.to_string())
+                        .filter(|s| !s.is_empty())
+                        .ok_or_else(|| format!("{}: OLDPWD not set", name))?;
+                    self.do_chdir(scope, &old_dir)?;
+                    my_println!("{}", current_dir()?)?;
+                } else if let Some(path) = Self::search_cdpath(scope, &new_dir) {
+                    self.do_chdir(scope, &path.to_string_lossy())?;
+                    my_println!("{}", current_dir()?)?;
+                } else {
+                    self.do_chdir(scope, &new_dir)?
+                }
             }
             "pushd" => {
                 let new_dir = if parsed_args.is_empty() {
@@ -129,6 +167,19 @@ impl Exec for PrintWorkingDir {
     }
 }
 
+/// Update $PWD or $OLDPWD in the global scope, keeping the variable exported.
+fn set_dir_var(scope: &Arc<Scope>, name: &str, dir: String) {
+    let global = scope.global();
+    if let Some(var) = global.lookup_local(name) {
+        var.assign(Value::from(dir.as_str()));
+        return;
+    }
+    global.insert(name.to_string(), Value::from(dir.as_str()));
+    if let Some(var) = global.lookup_local(name) {
+        var.set_exported(true);
+    }
+}
+
 #[ctor::ctor]
 fn register() {
     let chdir = Arc::new(ChangeDir::new());
@@ -192,6 +243,51 @@ mod tests {
         assert_eq!(env::current_dir().unwrap(), initial_dir);
     }
 
+    #[test]
+    fn test_cd_dash() {
+        let chdir = ChangeDir::new();
+        let scope = Scope::new();
+        let target_dir = env::current_dir().unwrap().to_string_lossy().to_string();
+
+        let result = chdir.exec("cd", &vec!["-".to_string()], &scope);
+        assert_eq!(result.unwrap_err(), "cd: OLDPWD not set".to_string());
+
+        assert!(chdir.exec("cd", &vec![target_dir.clone()], &scope).is_ok());
+        assert_eq!(
+            scope.lookup_value("PWD"),
+            Some(Value::from(target_dir.as_str()))
+        );
+        assert_eq!(
+            scope.lookup_value("OLDPWD"),
+            Some(Value::from(target_dir.as_str()))
+        );
+
+        assert!(chdir.exec("cd", &vec!["-".to_string()], &scope).is_ok());
+        assert_eq!(env::current_dir().unwrap(), Path::new(&target_dir));
+    }
+
+    #[test]
+    fn test_cdpath() {
+        let chdir = ChangeDir::new();
+        let scope = Scope::new();
+        let current = env::current_dir().unwrap();
+        let name = current.file_name().unwrap().to_string_lossy().to_string();
+        let parent = current.parent().unwrap().to_string_lossy().to_string();
+
+        scope.insert("CDPATH".to_string(), Value::from(parent.as_str()));
+        assert_eq!(
+            ChangeDir::search_cdpath(&scope, &name),
+            Some(current.clone())
+        );
+        assert_eq!(
+            ChangeDir::search_cdpath(&scope, &format!("./{}", name)),
+            None
+        );
+
+        assert!(chdir.exec("cd", &vec![name], &scope).is_ok());
+        assert_eq!(env::current_dir().unwrap(), current);
+    }
+
     #[test]
     fn test_popd_empty_stack() {
         let chdir = ChangeDir::new();
@@ -0,0 +1,276 @@
+use super::{flags::CommandFlags, register_command, Exec, Flag, ShellCommand};
+use crate::{
+    digest::{digest, to_hex, Algorithm},
+    eval::Value,
+    scope::Scope,
+    symlnk::SymLink,
+    utils::format_error,
+};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Compute the digest of the file, or of stdin if the name is "-".
+fn digest_file(algorithm: Algorithm, name: &str) -> io::Result<Vec<u8>> {
+    if name == "-" {
+        return digest(algorithm, io::stdin().lock());
+    }
+    let path = Path::new(name).dereference()?;
+    if path.is_dir() {
+        return Err(io::Error::other("Is a directory"));
+    }
+    digest(algorithm, File::open(path)?)
+}
+
+/// Parse a line of a checksum file, in the format output by the command: the hexadecimal
+/// digest, a space, then a space (or * for binary mode) and the file name.
+fn parse_line(line: &str) -> Option<(&str, &str)> {
+    let (hex, rest) = line.split_once(' ')?;
+    let name = rest.strip_prefix(' ').or_else(|| rest.strip_prefix('*'))?;
+
+    if hex.is_empty() || name.is_empty() || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some((hex, name))
+}
+
+/// Return the algorithm that produces digests of the given length (in hex digits): the default
+/// algorithm if it matches, or else the one that does (SHA-256 and BLAKE3 digests have the same
+/// length, prefer the default, then SHA-256).
+fn algorithm_for(hex_len: usize, default: Algorithm) -> Option<Algorithm> {
+    let len = |algorithm: Algorithm| match algorithm {
+        Algorithm::Md5 => 32,
+        Algorithm::Sha1 => 40,
+        Algorithm::Sha256 | Algorithm::Blake3 => 64,
+    };
+    [default, Algorithm::Md5, Algorithm::Sha1, Algorithm::Sha256]
+        .into_iter()
+        .find(|&a| len(a) == hex_len)
+}
+
+struct Checksum {
+    flags: CommandFlags,
+    algorithm: Algorithm, // Default algorithm
+}
+
+impl Checksum {
+    fn new(algorithm: Algorithm) -> Self {
+        let mut flags = CommandFlags::with_help();
+        flags.add_value(
+            'a',
+            "algorithm",
+            "ALGO",
+            "Digest algorithm: md5, sha1, sha256 or blake3",
+        );
+        flags.add_flag(
+            'c',
+            "check",
+            "Read checksums from the FILEs and verify them",
+        );
+        flags.add_flag('q', "quiet", "Do not print OK for verified files");
+        Self { flags, algorithm }
+    }
+
+    /// Verify the checksums listed in the file; return the number of lines, of mismatches,
+    /// of files that could not be read and of improperly formatted lines.
+    fn check(
+        &self,
+        scope: &Arc<Scope>,
+        name: &str,
+        algorithm: Option<Algorithm>,
+        quiet: bool,
+    ) -> Result<[usize; 4], String> {
+        let reader: Box<dyn BufRead> = if name == "-" {
+            Box::new(io::stdin().lock())
+        } else {
+            let path = Path::new(name)
+                .dereference()
+                .map_err(|e| format!("{}: {}", scope.err_str(name), e))?;
+            let file = File::open(&path).map_err(|e| format!("{}: {}", scope.err_str(name), e))?;
+            Box::new(BufReader::new(file))
+        };
+
+        let mut counts = [0; 4];
+        for line in reader.lines() {
+            if Scope::is_interrupted() {
+                break;
+            }
+            let line = line.map_err(|e| format!("{}: {}", scope.err_str(name), e))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            counts[0] += 1;
+
+            let entry = parse_line(&line).and_then(|(hex, file)| {
+                let algorithm = algorithm.or_else(|| algorithm_for(hex.len(), self.algorithm))?;
+                Some((hex, file, algorithm))
+            });
+            let Some((hex, file, algorithm)) = entry else {
+                counts[3] += 1;
+                continue;
+            };
+
+            match digest_file(algorithm, file) {
+                Ok(digest) if to_hex(&digest).eq_ignore_ascii_case(hex) => {
+                    if !quiet {
+                        my_println!("{}: OK", file)?;
+                    }
+                }
+                Ok(_) => {
+                    counts[1] += 1;
+                    my_println!("{}: FAILED", file)?;
+                }
+                Err(_) if Scope::is_interrupted() => break,
+                Err(e) => {
+                    counts[2] += 1;
+                    my_warning!(scope, "{}: {}", scope.err_str(file), e);
+                    my_println!("{}: FAILED open or read", file)?;
+                }
+            }
+        }
+        Ok(counts)
+    }
+}
+
+impl Exec for Checksum {
+    fn cli_flags(&self) -> Box<dyn Iterator<Item = &Flag> + '_> {
+        Box::new(self.flags.iter())
+    }
+
+    fn exec(&self, name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
+        let mut flags = self.flags.clone();
+        let mut files = flags.parse(scope, args)?;
+
+        if flags.is_present("help") {
+            println!("Usage: {} [OPTIONS] [FILE]...", name);
+            println!(
+                "Print or check {} checksums of FILEs (or of standard input).",
+                self.algorithm.name()
+            );
+            println!("With -c, read lines in the output format (DIGEST  FILE) and verify them.");
+            println!("\nOptions:");
+            print!("{}", flags.help());
+            println!("\nExamples:");
+            println!("    {} *.iso > SUMS", name);
+            println!("    {} -c SUMS", name);
+            println!("    checksum -a md5 notes.txt");
+            return Ok(Value::success());
+        }
+
+        let algorithm = flags
+            .value("algorithm")
+            .map(|a| {
+                Algorithm::from_name(a)
+                    .ok_or_else(|| format_error(scope, a, args, "Unknown algorithm"))
+            })
+            .transpose()?;
+
+        if files.is_empty() {
+            files.push("-".to_string());
+        }
+        if files.iter().any(|f| f == "-") {
+            scope.show_eof_hint();
+        }
+
+        if flags.is_present("check") {
+            let quiet = flags.is_present("quiet");
+            let mut totals = [0; 4];
+            for file in &files {
+                let counts = self.check(scope, file, algorithm, quiet)?;
+                for (total, count) in totals.iter_mut().zip(counts) {
+                    *total += count;
+                }
+                if counts[0] == 0 {
+                    my_warning!(scope, "{}: No checksums found", scope.err_str(file));
+                }
+            }
+
+            let [lines, mismatched, unreadable, improper] = totals;
+            if improper > 0 {
+                my_warning!(
+                    scope,
+                    "{} of {} lines are improperly formatted",
+                    improper,
+                    lines
+                );
+            }
+            if unreadable > 0 {
+                my_warning!(scope, "{} listed files could not be read", unreadable);
+            }
+            if mismatched > 0 {
+                return Err(format!("{} computed checksums did NOT match", mismatched));
+            }
+            if unreadable > 0 || lines == 0 || improper == lines {
+                return Err("Verification failed".to_string());
+            }
+            return Ok(Value::success());
+        }
+
+        let algorithm = algorithm.unwrap_or(self.algorithm);
+        let mut errors = 0;
+        for file in &files {
+            match digest_file(algorithm, file) {
+                Ok(digest) => my_println!("{}  {}", to_hex(&digest), file)?,
+                Err(_) if Scope::is_interrupted() => break,
+                Err(e) => {
+                    errors += 1;
+                    my_warning!(scope, "{}: {}", scope.err_str(file), e);
+                }
+            }
+        }
+        if errors > 0 {
+            return Err(format!(
+                "{} of {} files could not be read",
+                errors,
+                files.len()
+            ));
+        }
+
+        Ok(Value::success())
+    }
+}
+
+#[ctor::ctor]
+fn register() {
+    for (name, algorithm) in [
+        ("checksum", Algorithm::Sha256),
+        ("md5sum", Algorithm::Md5),
+        ("sha1sum", Algorithm::Sha1),
+        ("sha256sum", Algorithm::Sha256),
+        ("b3sum", Algorithm::Blake3),
+    ] {
+        register_command(ShellCommand {
+            name: name.to_string(),
+            inner: Arc::new(Checksum::new(algorithm)),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line() {
+        assert_eq!(
+            parse_line("d41d8cd98f00b204e9800998ecf8427e  a b.txt"),
+            Some(("d41d8cd98f00b204e9800998ecf8427e", "a b.txt"))
+        );
+        assert_eq!(
+            parse_line("ABCDEF *image.iso"),
+            Some(("ABCDEF", "image.iso"))
+        );
+        assert_eq!(parse_line("abcdef file"), None);
+        assert_eq!(parse_line("xyz  file"), None);
+        assert_eq!(parse_line("abcdef  "), None);
+
+        assert_eq!(algorithm_for(32, Algorithm::Sha256), Some(Algorithm::Md5));
+        assert_eq!(
+            algorithm_for(64, Algorithm::Blake3),
+            Some(Algorithm::Blake3)
+        );
+        assert_eq!(algorithm_for(64, Algorithm::Md5), Some(Algorithm::Sha256));
+        assert_eq!(algorithm_for(10, Algorithm::Sha1), None);
+    }
+}
@@ -8,6 +8,58 @@ struct Chmod {
     flags: CommandFlags,
 }
 
+/// A clause of a symbolic mode, e.g. the go-w in u+x,go-w
+#[derive(Debug)]
+struct Clause {
+    who: u32,                 // Mask of the affected bits, 0 stands for all
+    ops: Vec<(char, String)>, // Operator (+ - =) and permissions (rwxXst)
+}
+
+#[derive(Debug)]
+enum Mode {
+    Octal(u32),
+    Symbolic(Vec<Clause>),
+}
+
+impl Mode {
+    /// Compute the new mode of a file from its current mode.
+    fn apply(&self, current: u32, is_dir: bool) -> u32 {
+        let clauses = match self {
+            Mode::Octal(mode) => return *mode,
+            Mode::Symbolic(clauses) => clauses,
+        };
+
+        let mut mode = current;
+        for clause in clauses {
+            let who = if clause.who == 0 { 0o7777 } else { clause.who };
+
+            for (op, perms) in &clause.ops {
+                let mut bits = 0;
+                for c in perms.chars() {
+                    bits |= match c {
+                        'r' => 0o444,
+                        'w' => 0o222,
+                        'x' => 0o111,
+                        // Execute only for directories, or if some user can execute already
+                        'X' if is_dir || mode & 0o111 != 0 => 0o111,
+                        's' => 0o6000,
+                        't' => 0o1000,
+                        _ => 0,
+                    };
+                }
+                bits &= who;
+
+                mode = match op {
+                    '+' => mode | bits,
+                    '-' => mode & !bits,
+                    _ => (mode & !who) | bits,
+                };
+            }
+        }
+        mode
+    }
+}
+
 impl Chmod {
     fn new() -> Self {
         let mut flags = CommandFlags::with_help();
@@ -19,20 +71,35 @@ impl Chmod {
 
     fn change_mode(
         path: &Path,
-        mode: u32,
+        mode: &Mode,
         recursive: bool,
         verbose: bool,
         scope: &Arc<Scope>,
     ) -> Result<(), String> {
-        if verbose {
-            println!("changing permissions of '{}' to {:o}", path.display(), mode);
-        }
+        let metadata = fs::metadata(path).map_err(|error| {
+            format!(
+                "Failed to get metadata for {}: {}",
+                scope.err_path(path),
+                error
+            )
+        })?;
 
         #[cfg(unix)]
         {
             use std::os::unix::fs::PermissionsExt;
 
-            fs::set_permissions(path, fs::Permissions::from_mode(mode)).map_err(|error| {
+            let current = metadata.permissions().mode() & 0o7777;
+            let new_mode = mode.apply(current, metadata.is_dir());
+            if verbose {
+                println!(
+                    "changing permissions of '{}' from {:o} to {:o}",
+                    path.display(),
+                    current,
+                    new_mode
+                );
+            }
+
+            fs::set_permissions(path, fs::Permissions::from_mode(new_mode)).map_err(|error| {
                 format!(
                     "Failed to change permissions of {}: {}",
                     scope.err_path(path),
@@ -51,21 +118,30 @@ impl Chmod {
                 FILE_ATTRIBUTE_READONLY, FILE_ATTRIBUTE_SYSTEM, FILE_FLAGS_AND_ATTRIBUTES,
             };
 
-            let metadata = fs::metadata(path).map_err(|error| {
-                format!(
-                    "Failed to get metadata for {}: {}",
-                    scope.err_path(path),
-                    error
-                )
-            })?;
             let mut attributes = metadata.file_attributes();
 
+            // Map the read-only attribute to a mode, apply the change and map it back
+            let current = if attributes & FILE_ATTRIBUTE_READONLY.0 != 0 {
+                0o555
+            } else {
+                0o777
+            };
+            let new_mode = mode.apply(current, metadata.is_dir());
+            if verbose {
+                println!(
+                    "changing permissions of '{}' from {:o} to {:o}",
+                    path.display(),
+                    current,
+                    new_mode
+                );
+            }
+
             // Clear the relevant attributes first
             attributes &=
                 !(FILE_ATTRIBUTE_READONLY.0 | FILE_ATTRIBUTE_HIDDEN.0 | FILE_ATTRIBUTE_SYSTEM.0);
 
-            // Set read-only if write permission is not granted to anyone
-            if mode & 0o222 == 0 {
+            // Set read-only if the owner is not granted write permission
+            if new_mode & 0o200 == 0 {
                 attributes |= FILE_ATTRIBUTE_READONLY.0;
             }
 
@@ -124,71 +200,45 @@ impl Chmod {
         Ok(())
     }
 
-    fn parse_mode(mode_str: &str) -> Result<u32, String> {
-        if mode_str.chars().all(|c| c.is_digit(8)) {
-            // Handle octal mode
+    /// Parse an octal mode, or a comma-separated list of symbolic clauses.
+    fn parse_mode(mode_str: &str) -> Result<Mode, String> {
+        if !mode_str.is_empty() && mode_str.chars().all(|c| c.is_digit(8)) {
             return u32::from_str_radix(mode_str, 8)
-                .map_err(|_| format!("Invalid octal mode: {}", mode_str));
+                .ok()
+                .filter(|mode| *mode <= 0o7777)
+                .map(Mode::Octal)
+                .ok_or_else(|| format!("Invalid octal mode: {}", mode_str));
         }
 
-        let mut result = 0;
-        let mut who = 0;
-        let mut action = ' ';
-        let mut perm = 0;
-
-        for c in mode_str.chars() {
-            match c {
-                'u' | 'g' | 'o' | 'a' => {
-                    who |= match c {
-                        'u' => 0o700,
-                        'g' => 0o070,
-                        'o' => 0o007,
-                        'a' => 0o777,
-                        _ => unreachable!(),
+        let mut clauses = Vec::new();
+        for clause in mode_str.split(',') {
+            let mut who = 0;
+            let mut ops: Vec<(char, String)> = Vec::new();
+
+            for c in clause.chars() {
+                match c {
+                    'u' | 'g' | 'o' | 'a' if ops.is_empty() => {
+                        who |= match c {
+                            'u' => 0o4700,
+                            'g' => 0o2070,
+                            'o' => 0o1007,
+                            _ => 0o7777,
+                        }
                     }
-                }
-                '+' | '-' | '=' => {
-                    if action != ' ' {
-                        Self::apply_change(&mut result, who, action, perm)?;
-                        perm = 0;
+                    '+' | '-' | '=' => ops.push((c, String::new())),
+                    'r' | 'w' | 'x' | 'X' | 's' | 't' if !ops.is_empty() => {
+                        ops.last_mut().unwrap().1.push(c)
                     }
-                    action = c;
+                    _ => return Err(format!("Invalid mode: {}", mode_str)),
                 }
-                'r' => perm |= 0o444,
-                'w' => perm |= 0o222,
-                'x' => perm |= 0o111,
-                'X' => perm |= 0o111, // For simplification, treat 'X' the same as 'x'
-                's' => perm |= 0o4000 | 0o2000,
-                't' => perm |= 0o1000,
-                _ => return Err(format!("Invalid mode character: {}", c)),
             }
+            if ops.is_empty() {
+                return Err(format!("Invalid mode: {}", mode_str));
+            }
+            clauses.push(Clause { who, ops });
         }
 
-        if action != ' ' {
-            Self::apply_change(&mut result, who, action, perm)?;
-        }
-
-        Ok(result)
-    }
-
-    fn apply_change(mode: &mut u32, who: u32, action: char, perm: u32) -> Result<(), String> {
-        if who == 0 {
-            // Default to 'a' if no 'who' is specified
-            *mode = match action {
-                '+' => *mode | (perm & 0o7777),
-                '-' => *mode & !(perm & 0o7777),
-                '=' => perm & 0o7777,
-                _ => return Err(format!("Invalid action: {}", action)),
-            };
-        } else {
-            *mode = match action {
-                '+' => *mode | (who & perm),
-                '-' => *mode & !(who & perm),
-                '=' => (*mode & !who) | (who & perm),
-                _ => return Err(format!("Invalid action: {}", action)),
-            };
-        }
-        Ok(())
+        Ok(Mode::Symbolic(clauses))
     }
 }
 
@@ -201,7 +251,8 @@ fn help_details() {
     println!("  chmod o+r file     # Add read permission for others");
     println!("  chmod 644 file     # Owner: rw-, Group: r--, Others: r--");
     println!("  chmod 400 file     # Owner: r--, Group: ---, Others: --- (read-only)");
-    println!("\nNote: Comma-separated mode lists are not supported.");
+    println!("  chmod u+x,go-w file  # Add execute for owner, remove write for group and others");
+    println!("  chmod -r a+rX dir  # Read for all, execute only for directories");
 }
 
 #[cfg(windows)]
@@ -209,6 +260,7 @@ fn help_details() {
     println!("\nExamples:");
     println!("  chmod u+rw file        # User gets read and write permissions");
     println!("  chmod -w file          # Make file read-only");
+    println!("  chmod 644 file         # Read-only attribute is cleared (owner can write)");
 
     println!("\nLimitations:");
     println!("  Windows does not support Unix-style group and others permissions.");
@@ -242,7 +294,8 @@ impl Exec for Chmod {
             return Err("Missing mode and file arguments".to_string());
         }
 
-        let mode = Self::parse_mode(&paths[0])?;
+        let mode = Self::parse_mode(&paths[0])
+            .map_err(|e| format!("{}: {}", scope.err_path_arg(&paths[0], args), e))?;
         let recursive = flags.is_present("recursive");
         let verbose = flags.is_present("verbose");
 
@@ -251,7 +304,7 @@ impl Exec for Chmod {
                 .dereference()
                 .map_err(|e| format_error(scope, arg, &args, e))?;
 
-            match Self::change_mode(&path, mode, recursive, verbose, scope) {
+            match Self::change_mode(&path, &mode, recursive, verbose, scope) {
                 Ok(_) => {}
                 Err(e) => {
                     return Err(format!("{}: {}", scope.err_path_arg(arg, args), e));
@@ -290,7 +343,7 @@ mod tests {
         let file_path = dir.path().join("testfile");
         fs::write(&file_path, "test content").unwrap();
 
-        let result = Chmod::change_mode(&file_path, 0o644, false, false, &scope);
+        let result = Chmod::change_mode(&file_path, &Mode::Octal(0o644), false, false, &scope);
         assert!(result.is_ok());
 
         let permissions = fs::metadata(&file_path).unwrap().permissions();
@@ -308,7 +361,7 @@ mod tests {
         fs::create_dir(&sub_dir).unwrap();
         fs::write(&file_path, "test content").unwrap();
 
-        let result = Chmod::change_mode(&sub_dir, 0o755, true, false, &scope);
+        let result = Chmod::change_mode(&sub_dir, &Mode::Octal(0o755), true, false, &scope);
         assert!(result.is_ok());
 
         let permissions = fs::metadata(&sub_dir).unwrap().permissions();
@@ -318,6 +371,27 @@ mod tests {
         assert_eq!(file_permissions.mode() & 0o777, 0o755);
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_chmod_help_example() {
+        let scope = Scope::new();
+        let dir = tempdir().unwrap();
+        let sub_dir = dir.path().join("subdir");
+        let file_path = sub_dir.join("testfile");
+
+        fs::create_dir(&sub_dir).unwrap();
+        fs::write(&file_path, "test content").unwrap();
+        fs::set_permissions(&file_path, fs::Permissions::from_mode(0o600)).unwrap();
+        fs::set_permissions(&sub_dir, fs::Permissions::from_mode(0o700)).unwrap();
+
+        let args = ["-r", "a+rX", &sub_dir.to_string_lossy()].map(String::from);
+        assert!(Chmod::new().exec("chmod", &args.to_vec(), &scope).is_ok());
+
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&sub_dir), 0o755);
+        assert_eq!(mode(&file_path), 0o644);
+    }
+
     #[cfg(windows)]
     #[test]
     fn test_chmod_windows_readonly() {
@@ -326,7 +400,7 @@ mod tests {
         let file_path = dir.path().join("testfile");
         fs::write(&file_path, "test content").unwrap();
 
-        let result = Chmod::change_mode(&file_path, 0o444, false, false, &scope);
+        let result = Chmod::change_mode(&file_path, &Mode::Octal(0o444), false, false, &scope);
         assert!(result.is_ok());
 
         let metadata = fs::metadata(&file_path).unwrap();
@@ -348,7 +422,7 @@ mod tests {
         fs::create_dir(&sub_dir).unwrap();
         fs::write(&file_path, "test content").unwrap();
 
-        let result = Chmod::change_mode(&sub_dir, 0o444, true, false, &scope);
+        let result = Chmod::change_mode(&sub_dir, &Mode::Octal(0o444), true, false, &scope);
         assert!(result.is_ok());
 
         let metadata = fs::metadata(&sub_dir).unwrap();
@@ -364,23 +438,33 @@ mod tests {
 
     #[test]
     fn test_parse_mode() {
-        let mode = Chmod::parse_mode("755").unwrap();
-        assert_eq!(mode, 0o755);
-
-        let mode = Chmod::parse_mode("u+rwx").unwrap();
-        assert_eq!(mode, 0o700);
-
-        let mode = Chmod::parse_mode("g+r").unwrap();
-        assert_eq!(mode, 0o040);
-
-        let mode = Chmod::parse_mode("o+r").unwrap();
-        assert_eq!(mode, 0o004);
+        let mode = |s: &str, current: u32| Chmod::parse_mode(s).unwrap().apply(current, false);
+
+        assert_eq!(mode("755", 0o600), 0o755);
+        assert_eq!(mode("u+rwx", 0), 0o700);
+        assert_eq!(mode("g+r", 0), 0o040);
+        assert_eq!(mode("o+r", 0), 0o004);
+
+        // Symbolic modes are relative to the current mode
+        assert_eq!(mode("u+x", 0o644), 0o744);
+        assert_eq!(mode("go-w", 0o666), 0o644);
+        assert_eq!(mode("u+x,go-w", 0o666), 0o744);
+        assert_eq!(mode("-w", 0o666), 0o444);
+        assert_eq!(mode("g=r", 0o676), 0o646);
+        assert_eq!(mode("u=rw,go=", 0o755), 0o600);
+        assert_eq!(mode("u+s", 0o755), 0o4755);
+        assert_eq!(mode("a+X", 0o644), 0o644);
+        assert_eq!(mode("a+X", 0o744), 0o755);
+        assert_eq!(Chmod::parse_mode("a+X").unwrap().apply(0o644, true), 0o755);
     }
 
     #[test]
     fn test_invalid_mode() {
-        let result = Chmod::parse_mode("invalid");
-        assert!(result.is_err());
+        assert!(Chmod::parse_mode("invalid").is_err());
+        assert!(Chmod::parse_mode("u").is_err());
+        assert!(Chmod::parse_mode("u+x,").is_err());
+        assert!(Chmod::parse_mode("+u").is_err());
+        assert!(Chmod::parse_mode("17777").is_err());
     }
 
     #[test]
@@ -401,12 +485,12 @@ mod tests {
         let scope = Scope::new();
 
         // Test setting file as read-only (mode: 0o444)
-        Chmod::change_mode(&file_path, 0o444, false, false, &scope).unwrap();
+        Chmod::change_mode(&file_path, &Mode::Octal(0o444), false, false, &scope).unwrap();
         let metadata = fs::metadata(&file_path).unwrap();
         assert!(metadata.permissions().readonly());
 
         // Test setting write permissions (mode: 0o222)
-        Chmod::change_mode(&file_path, 0o222, false, false, &scope).unwrap();
+        Chmod::change_mode(&file_path, &Mode::Octal(0o222), false, false, &scope).unwrap();
         let metadata = fs::metadata(&file_path).unwrap();
         assert!(!metadata.permissions().readonly()); // Should not be read-only anymore
     }
@@ -423,7 +507,7 @@ mod tests {
         // Test setting file as read-only using symbolic mode (chmod u-w)
         Chmod::change_mode(
             &file_path,
-            Chmod::parse_mode("u+w").unwrap(),
+            &Chmod::parse_mode("u+w").unwrap(),
             false,
             false,
             &scope,
@@ -431,7 +515,7 @@ mod tests {
         .unwrap(); // Set to rw
         Chmod::change_mode(
             &file_path,
-            Chmod::parse_mode("u-w").unwrap(),
+            &Chmod::parse_mode("u-w").unwrap(),
             false,
             false,
             &scope,
@@ -444,7 +528,7 @@ mod tests {
         // Now remove write permissions for group and others
         Chmod::change_mode(
             &file_path,
-            Chmod::parse_mode("g-w").unwrap(),
+            &Chmod::parse_mode("g-w").unwrap(),
             false,
             false,
             &scope,
@@ -452,7 +536,7 @@ mod tests {
         .unwrap(); // Remove write for group
         Chmod::change_mode(
             &file_path,
-            Chmod::parse_mode("o-w").unwrap(),
+            &Chmod::parse_mode("o-w").unwrap(),
             false,
             false,
             &scope,
@@ -479,7 +563,7 @@ mod tests {
         // Use symbolic notation to set read-only for user
         Chmod::change_mode(
             &file_path,
-            Chmod::parse_mode("u+r").unwrap(),
+            &Chmod::parse_mode("u+r").unwrap(),
             false,
             false,
             &scope,
@@ -487,7 +571,7 @@ mod tests {
         .unwrap(); // User gets read
         Chmod::change_mode(
             &file_path,
-            Chmod::parse_mode("u+w").unwrap(),
+            &Chmod::parse_mode("u+w").unwrap(),
             false,
             false,
             &scope,
@@ -500,7 +584,7 @@ mod tests {
         // Now remove write permissions
         Chmod::change_mode(
             &file_path,
-            Chmod::parse_mode("-w").unwrap(),
+            &Chmod::parse_mode("-w").unwrap(),
             false,
             false,
             &scope,
@@ -4,9 +4,20 @@ use crossterm::{
     cursor, execute,
     terminal::{Clear, ClearType},
 };
-use std::io::{stdout, Write};
+use std::io::{self, stdout, Write};
 use std::sync::Arc;
 
+/// Clear the terminal screen, and the scrollback buffer unless keep_scrollback is true.
+pub fn clear_screen(keep_scrollback: bool) -> io::Result<()> {
+    let mut stdout = stdout().lock();
+
+    execute!(stdout, cursor::MoveTo(0, 0), Clear(ClearType::All))?;
+    if !keep_scrollback {
+        execute!(stdout, Clear(ClearType::Purge))?;
+    }
+    stdout.flush()
+}
+
 struct ClearScreen {
     flags: CommandFlags,
 }
@@ -31,24 +42,15 @@ impl Exec for ClearScreen {
         flags.parse(scope, args)?;
 
         if flags.is_present("help") {
-            println!("Usage: clear");
-            println!("Clear the terminal screen.");
+            println!("Usage: clear [OPTIONS]");
+            println!("Clear the terminal screen and the scrollback buffer.");
+            println!("In the interactive shell, Ctrl+L does the same, keeping the command line.");
             println!("\nOptions:");
             print!("{}", flags.help());
             return Ok(Value::success());
         }
 
-        let mut stdout = stdout().lock();
-
-        execute!(stdout, cursor::MoveTo(0, 0), Clear(ClearType::All))
-            .and_then(|_| {
-                if !flags.is_present("keep") {
-                    execute!(stdout, Clear(ClearType::Purge))
-                } else {
-                    Ok(())
-                }
-                .and_then(|_| stdout.flush())
-            })
+        clear_screen(flags.is_present("keep"))
             .map_err(|e| format!("Could not clear screen: {}", e))?;
 
         Ok(Value::success())
@@ -0,0 +1,463 @@
+use super::{flags::CommandFlags, register_command, Exec, Flag, ShellCommand};
+use crate::{
+    eval::Value,
+    prompt::{confirm, Answer},
+    scope::Scope,
+    utils::format_error,
+};
+use filetime::FileTime;
+use flate2::{bufread::GzDecoder, Compression, GzBuilder};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, ErrorKind, IsTerminal, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Gzip,
+    Zstd,
+}
+
+impl Format {
+    fn suffix(&self) -> &'static str {
+        match self {
+            Format::Gzip => ".gz",
+            Format::Zstd => ".zst",
+        }
+    }
+
+    /// Suffix of compressed tar archives, which decompress to .tar
+    fn tar_suffix(&self) -> &'static str {
+        match self {
+            Format::Gzip => ".tgz",
+            Format::Zstd => ".tzst",
+        }
+    }
+
+    fn levels(&self) -> (u32, u32) {
+        match self {
+            Format::Gzip => (6, 9),
+            Format::Zstd => (3, 19),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Format::Gzip => "gzip",
+            Format::Zstd => "zstd",
+        }
+    }
+}
+
+/// Write a gzip member, with the original name (if any) and modification time in the header.
+fn gzip<R: Read, W: Write>(
+    reader: &mut R,
+    writer: W,
+    level: u32,
+    name: Option<&str>,
+    mtime: u32,
+) -> io::Result<W> {
+    let mut builder = GzBuilder::new()
+        .mtime(mtime)
+        .operating_system(if cfg!(windows) { 0 } else { 3 });
+    if let Some(name) = name {
+        builder = builder.filename(name);
+    }
+    let mut encoder = builder.write(writer, Compression::new(level));
+    io::copy(reader, &mut encoder)?;
+    encoder.finish()
+}
+
+/// Decompress all the gzip members from the reader.
+fn gunzip<R: BufRead, W: Write>(reader: &mut R, writer: &mut W) -> io::Result<()> {
+    let invalid = |msg: &str| io::Error::new(ErrorKind::InvalidData, msg.to_string());
+    let mut members = 0;
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() && members > 0 {
+            return Ok(());
+        }
+        if members > 0 && buf[0] == 0 {
+            // Zero padding after the last member
+            let mut rest = Vec::new();
+            reader.read_to_end(&mut rest)?;
+            if rest.iter().all(|&b| b == 0) {
+                return Ok(());
+            }
+            return Err(invalid("Trailing garbage after compressed data"));
+        }
+        if buf.len() >= 2 && !buf.starts_with(&[0x1f, 0x8b]) {
+            return Err(invalid("Not in gzip format"));
+        }
+
+        // The decoder consumes exactly one member, and verifies its CRC and length
+        io::copy(&mut GzDecoder::new(&mut *reader), writer)?;
+        members += 1;
+    }
+}
+
+struct Compress {
+    flags: CommandFlags,
+    format: Format,
+    decompress: bool,
+}
+
+/// Options of one invocation.
+struct Options {
+    decompress: bool,
+    level: u32,
+    to_stdout: bool,
+    keep: bool,
+    force: bool,
+}
+
+impl Compress {
+    fn new(format: Format, decompress: bool) -> Self {
+        let (default, max) = format.levels();
+        let mut flags = CommandFlags::with_help();
+        flags.add_flag('d', "decompress", "Decompress");
+        flags.add_flag(
+            'c',
+            "stdout",
+            "Write to standard output, keep the input files",
+        );
+        flags.add_flag('k', "keep", "Keep the input files");
+        flags.add_flag('f', "force", "Overwrite output files without asking");
+        flags.add(
+            None,
+            "level",
+            Some("N".to_string()),
+            &format!(
+                "Compression level, from 1 (fastest) to {} (best); default: {}, short form: -N",
+                max, default
+            ),
+        );
+        Self {
+            flags,
+            format,
+            decompress,
+        }
+    }
+
+    fn compress<R: Read, W: Write>(
+        &self,
+        reader: &mut R,
+        writer: W,
+        level: u32,
+        name: Option<&str>,
+        mtime: u32,
+    ) -> io::Result<()> {
+        let mut writer = match self.format {
+            Format::Gzip => gzip(reader, writer, level, name, mtime)?,
+            Format::Zstd => {
+                let mut encoder = zstd::Encoder::new(writer, level as i32)?;
+                io::copy(reader, &mut encoder)?;
+                encoder.finish()?
+            }
+        };
+        writer.flush()
+    }
+
+    fn decompress<R: BufRead, W: Write>(&self, reader: &mut R, mut writer: W) -> io::Result<()> {
+        let result = match self.format {
+            Format::Gzip => gunzip(reader, &mut writer),
+            Format::Zstd => zstd::stream::copy_decode(reader, &mut writer),
+        };
+        match result {
+            // Replace "failed to fill whole buffer" with a clearer message
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                Err(io::Error::from(ErrorKind::UnexpectedEof))
+            }
+            Err(e) => Err(e),
+            Ok(_) => writer.flush(),
+        }
+    }
+
+    /// Compress or decompress standard input to standard output.
+    fn process_stdin(&self, opts: &Options) -> io::Result<()> {
+        let mut reader = BufReader::new(io::stdin().lock());
+        let writer = BufWriter::new(io::stdout().lock());
+        ignore_broken_pipe(if opts.decompress {
+            self.decompress(&mut reader, writer)
+        } else {
+            self.compress(&mut reader, writer, opts.level, None, 0)
+        })
+    }
+
+    /// Name of the output file, None if the input does not have a known suffix.
+    fn output_name(&self, name: &str, decompress: bool) -> Option<String> {
+        if !decompress {
+            return Some(format!("{}{}", name, self.format.suffix()));
+        }
+        if let Some(stem) = name.strip_suffix(self.format.tar_suffix()) {
+            return Some(format!("{}.tar", stem));
+        }
+        name.strip_suffix(self.format.suffix())
+            .filter(|stem| !stem.is_empty() && !stem.ends_with(['/', '\\']))
+            .map(String::from)
+    }
+
+    /// Compress or decompress the file; return false if the user chose to quit.
+    fn process_file(
+        &self,
+        scope: &Arc<Scope>,
+        name: &str,
+        opts: &mut Options,
+        many: bool,
+    ) -> Result<bool, String> {
+        let path = Path::new(name);
+        let meta = fs::metadata(path).map_err(|e| e.to_string())?;
+        if meta.is_dir() {
+            my_warning!(scope, "{}: Is a directory, ignored", scope.err_str(name));
+            return Ok(true);
+        }
+        let mut input = BufReader::new(File::open(path).map_err(|e| e.to_string())?);
+
+        if opts.to_stdout {
+            let writer = BufWriter::new(io::stdout().lock());
+            let result = if opts.decompress {
+                self.decompress(&mut input, writer)
+            } else {
+                let base = path.file_name().map(|n| n.to_string_lossy());
+                self.compress(
+                    &mut input,
+                    writer,
+                    opts.level,
+                    base.as_deref(),
+                    mtime(&meta),
+                )
+            };
+            return ignore_broken_pipe(result)
+                .map(|_| true)
+                .map_err(|e| e.to_string());
+        }
+
+        if !opts.decompress && name.ends_with(self.format.suffix()) {
+            my_warning!(
+                scope,
+                "{}: Already has {} suffix, unchanged",
+                scope.err_str(name),
+                self.format.suffix()
+            );
+            return Ok(true);
+        }
+        let Some(out_name) = self.output_name(name, opts.decompress) else {
+            return Err("Unknown suffix, ignored".to_string());
+        };
+        let out_path = Path::new(&out_name);
+
+        if out_path.exists() && !opts.force {
+            match confirm(format!("Overwrite {}", out_name), scope, many)
+                .map_err(|e| e.to_string())?
+            {
+                Answer::Yes => {}
+                Answer::No => return Ok(true),
+                Answer::All => opts.force = true,
+                Answer::Quit => return Ok(false),
+            }
+        }
+
+        let output = File::create(out_path).map_err(|e| format!("{}: {}", out_name, e))?;
+        let writer = BufWriter::new(output);
+        let result = if opts.decompress {
+            self.decompress(&mut input, writer)
+        } else {
+            let base = path.file_name().map(|n| n.to_string_lossy());
+            self.compress(
+                &mut input,
+                writer,
+                opts.level,
+                base.as_deref(),
+                mtime(&meta),
+            )
+        };
+        if let Err(e) = result {
+            _ = fs::remove_file(out_path);
+            return Err(e.to_string());
+        }
+
+        // Carry over the timestamps and permissions, then remove the input
+        let atime = FileTime::from_last_access_time(&meta);
+        let mtime = FileTime::from_last_modification_time(&meta);
+        filetime::set_file_times(out_path, atime, mtime)
+            .and_then(|_| fs::set_permissions(out_path, meta.permissions()))
+            .map_err(|e| format!("{}: {}", out_name, e))?;
+        if !opts.keep {
+            fs::remove_file(path).map_err(|e| e.to_string())?;
+        }
+        Ok(true)
+    }
+}
+
+fn ignore_broken_pipe(result: io::Result<()>) -> io::Result<()> {
+    match result {
+        Err(e) if e.kind() == ErrorKind::BrokenPipe => Ok(()),
+        _ => result,
+    }
+}
+
+/// Modification time for the gzip header, 0 if not available.
+fn mtime(meta: &fs::Metadata) -> u32 {
+    meta.modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .and_then(|d| u32::try_from(d.as_secs()).ok())
+        .unwrap_or(0)
+}
+
+impl Exec for Compress {
+    fn cli_flags(&self) -> Box<dyn Iterator<Item = &Flag> + '_> {
+        Box::new(self.flags.iter())
+    }
+
+    fn exec(&self, name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
+        let mut flags = self.flags.clone();
+        // Accept the customary -1 ... -9 short form of the compression level
+        let dash_args = args
+            .iter()
+            .flat_map(|arg| {
+                if arg.len() > 1
+                    && arg.starts_with('-')
+                    && arg[1..].chars().all(|c| c.is_ascii_digit())
+                {
+                    vec!["--level".to_string(), arg[1..].to_string()]
+                } else {
+                    vec![arg.clone()]
+                }
+            })
+            .collect::<Vec<_>>();
+        let mut files = flags.parse(scope, &dash_args)?;
+
+        let decompress = self.decompress || flags.is_present("decompress");
+        let format = self.format.name();
+
+        if flags.is_present("help") {
+            println!("Usage: {} [OPTIONS] [FILE]...", name);
+            if decompress {
+                println!(
+                    "Decompress {} FILEs in place (or standard input to standard output).",
+                    format
+                );
+            } else {
+                println!(
+                    "Compress FILEs in place with {} (or standard input to standard output).",
+                    format
+                );
+                println!(
+                    "With -d, decompress FILEs ending in {}.",
+                    self.format.suffix()
+                );
+            }
+            println!("\nOptions:");
+            print!("{}", flags.help());
+            println!("\nExamples:");
+            if decompress {
+                println!("    {} notes.txt{}", name, self.format.suffix());
+                println!("    {} -c logs{} | grep error", name, self.format.suffix());
+            } else {
+                println!("    {} -k -9 notes.txt", name);
+                println!(
+                    "    tar cf - src | {} > src.tar{}",
+                    name,
+                    self.format.suffix()
+                );
+            }
+            return Ok(Value::success());
+        }
+
+        let (default, max) = self.format.levels();
+        let level = match flags.value("level") {
+            Some(v) => match v.parse::<u32>() {
+                Ok(n) if (1..=max).contains(&n) => n,
+                _ => {
+                    return Err(format_error(
+                        scope,
+                        v,
+                        args,
+                        format!("Expecting a level from 1 to {}", max),
+                    ))
+                }
+            },
+            None => default,
+        };
+        let mut opts = Options {
+            decompress,
+            level,
+            to_stdout: flags.is_present("stdout"),
+            keep: flags.is_present("keep"),
+            force: flags.is_present("force"),
+        };
+
+        if files.is_empty() {
+            files.push("-".to_string());
+        }
+        let writes_stdout = opts.to_stdout || files.iter().any(|f| f == "-");
+        if writes_stdout && !decompress && !opts.force && io::stdout().is_terminal() {
+            return Err("Compressed data not written to a terminal (use -f to force)".to_string());
+        }
+
+        let mut errors = 0;
+        for file in &files {
+            let result = if file == "-" {
+                scope.show_eof_hint();
+                self.process_stdin(&opts)
+                    .map(|_| true)
+                    .map_err(|e| e.to_string())
+            } else {
+                self.process_file(scope, file, &mut opts, files.len() > 1)
+            };
+            match result {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(_) if Scope::is_interrupted() => break,
+                Err(e) => {
+                    errors += 1;
+                    my_warning!(scope, "{}: {}", scope.err_str(file), e);
+                }
+            }
+        }
+        if errors > 0 {
+            return Err(format!("{} of {} files failed", errors, files.len()));
+        }
+
+        Ok(Value::success())
+    }
+}
+
+#[ctor::ctor]
+fn register() {
+    for (name, format, decompress) in [
+        ("gzip", Format::Gzip, false),
+        ("gunzip", Format::Gzip, true),
+        ("zstd", Format::Zstd, false),
+        ("unzstd", Format::Zstd, true),
+    ] {
+        register_command(ShellCommand {
+            name: name.to_string(),
+            inner: Arc::new(Compress::new(format, decompress)),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gzip_members() {
+        let text = b"Hello, gzip! ".repeat(100);
+        let mut data = gzip(&mut &text[..], Vec::new(), 6, Some("a.txt"), 0).unwrap();
+        let second = gzip(&mut &b"second member"[..], Vec::new(), 1, None, 0).unwrap();
+        data.extend(second);
+        data.extend([0; 4]);
+
+        let mut out = Vec::new();
+        gunzip(&mut &data[..], &mut out).unwrap();
+        assert_eq!(out, [&text[..], b"second member"].concat());
+
+        let len = data.len();
+        data[len - 12] ^= 1; // Corrupt the CRC of the second member
+        assert!(gunzip(&mut &data[..], &mut Vec::new()).is_err());
+    }
+}
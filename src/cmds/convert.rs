@@ -0,0 +1,263 @@
+use super::{flags::CommandFlags, register_command, Exec, Flag, ShellCommand};
+use crate::{eval::Value, scope::Scope, symlnk::SymLink, utils::format_error};
+use serde_json::{Map, Number, Value as Json};
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::Arc;
+use yaml_rust::{yaml::Hash, Yaml, YamlEmitter, YamlLoader};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl Format {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "toml" => Some(Self::Toml),
+            "yaml" | "yml" => Some(Self::Yaml),
+            _ => None,
+        }
+    }
+
+    /// Guess the format of text read from standard input, or from a file without a known extension.
+    fn detect(text: &str) -> Self {
+        if text.trim_start().starts_with(['{', '[']) {
+            Self::Json
+        } else if text.parse::<toml::Table>().is_ok() {
+            Self::Toml
+        } else {
+            Self::Yaml
+        }
+    }
+
+    fn parse(&self, text: &str) -> Result<Json, String> {
+        match self {
+            Self::Json => serde_json::from_str(text).map_err(|e| e.to_string()),
+            Self::Toml => text
+                .parse::<toml::Table>()
+                .map(|table| from_toml(toml::Value::Table(table)))
+                .map_err(|e| e.to_string().trim_end().to_string()),
+            Self::Yaml => {
+                let mut docs = YamlLoader::load_from_str(text).map_err(|e| e.to_string())?;
+                // Multiple documents convert to an array
+                match docs.len() {
+                    0 => Ok(Json::Null),
+                    1 => from_yaml(docs.remove(0)),
+                    _ => docs.into_iter().map(from_yaml).collect(),
+                }
+            }
+        }
+    }
+
+    fn write(&self, value: &Json, compact: bool) -> Result<String, String> {
+        match self {
+            Self::Json if compact => serde_json::to_string(value).map_err(|e| e.to_string()),
+            Self::Json => serde_json::to_string_pretty(value).map_err(|e| e.to_string()),
+            Self::Toml => match value {
+                Json::Object(_) => toml::to_string(value).map_err(|e| format!("TOML: {}", e)),
+                _ => Err("TOML documents must be tables".to_string()),
+            },
+            Self::Yaml => {
+                let mut output = String::new();
+                YamlEmitter::new(&mut output)
+                    .dump(&to_yaml(value))
+                    .map_err(|e| e.to_string())?;
+                Ok(output)
+            }
+        }
+    }
+}
+
+fn from_toml(value: toml::Value) -> Json {
+    match value {
+        toml::Value::String(s) => Json::String(s),
+        toml::Value::Integer(i) => Json::from(i),
+        toml::Value::Float(f) => {
+            Number::from_f64(f).map_or(Json::String(f.to_string()), Json::Number)
+        }
+        toml::Value::Boolean(b) => Json::Bool(b),
+        toml::Value::Datetime(dt) => Json::String(dt.to_string()),
+        toml::Value::Array(items) => Json::Array(items.into_iter().map(from_toml).collect()),
+        toml::Value::Table(table) => {
+            Json::Object(table.into_iter().map(|(k, v)| (k, from_toml(v))).collect())
+        }
+    }
+}
+
+fn from_yaml(value: Yaml) -> Result<Json, String> {
+    Ok(match value {
+        Yaml::Real(s) => match s.parse::<f64>().ok().and_then(Number::from_f64) {
+            Some(n) => Json::Number(n),
+            None => Json::String(s), // .inf, .nan
+        },
+        Yaml::Integer(i) => Json::from(i),
+        Yaml::String(s) => Json::String(s),
+        Yaml::Boolean(b) => Json::Bool(b),
+        Yaml::Array(items) => items.into_iter().map(from_yaml).collect::<Result<_, _>>()?,
+        Yaml::Hash(hash) => {
+            let mut map = Map::new();
+            for (k, v) in hash {
+                let key = match k {
+                    Yaml::String(s) | Yaml::Real(s) => s,
+                    Yaml::Integer(i) => i.to_string(),
+                    Yaml::Boolean(b) => b.to_string(),
+                    Yaml::Null => "null".to_string(),
+                    _ => return Err("Unsupported mapping key (not a scalar)".to_string()),
+                };
+                map.insert(key, from_yaml(v)?);
+            }
+            Json::Object(map)
+        }
+        Yaml::Alias(_) | Yaml::Null | Yaml::BadValue => Json::Null,
+    })
+}
+
+fn to_yaml(value: &Json) -> Yaml {
+    match value {
+        Json::Null => Yaml::Null,
+        Json::Bool(b) => Yaml::Boolean(*b),
+        Json::Number(n) => match n.as_i64() {
+            Some(i) => Yaml::Integer(i),
+            None => Yaml::Real(n.to_string()),
+        },
+        Json::String(s) => Yaml::String(s.clone()),
+        Json::Array(items) => Yaml::Array(items.iter().map(to_yaml).collect()),
+        Json::Object(map) => {
+            let mut hash = Hash::new();
+            for (k, v) in map {
+                hash.insert(Yaml::String(k.clone()), to_yaml(v));
+            }
+            Yaml::Hash(hash)
+        }
+    }
+}
+
+struct Convert {
+    flags: CommandFlags,
+}
+
+impl Convert {
+    fn new() -> Self {
+        let mut flags = CommandFlags::with_help();
+        flags.add_value(
+            'f',
+            "from",
+            "FORMAT",
+            "Input format: json, toml or yaml (default: from the file extension, or detected)",
+        );
+        flags.add_value(
+            't',
+            "to",
+            "FORMAT",
+            "Output format: json, toml or yaml (default: json)",
+        );
+        flags.add_flag('c', "compact", "Print JSON on a single line");
+        Self { flags }
+    }
+}
+
+impl Exec for Convert {
+    fn cli_flags(&self) -> Box<dyn Iterator<Item = &Flag> + '_> {
+        Box::new(self.flags.iter())
+    }
+
+    fn exec(&self, name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
+        let mut flags = self.flags.clone();
+        let operands = flags.parse(scope, args)?;
+
+        if flags.is_present("help") {
+            println!("Usage: {} [OPTIONS] [FILE]", name);
+            println!("Convert FILE (or standard input) between the JSON, TOML and YAML formats.");
+            println!("\nOptions:");
+            print!("{}", flags.help());
+            println!("\nExamples:");
+            println!("    {} Cargo.toml", name);
+            println!("    {} --to yaml package.json", name);
+            println!("    cat config.yaml | {} -c", name);
+            return Ok(Value::success());
+        }
+        if let Some(extra) = operands.get(1) {
+            return Err(format_error(scope, extra, args, "Unexpected argument"));
+        }
+
+        let format_flag = |flag: &str| match flags.value(flag) {
+            Some(value) => Format::from_name(value)
+                .map(Some)
+                .ok_or_else(|| format_error(scope, value, args, "Expecting json, toml or yaml")),
+            None => Ok(None),
+        };
+        let from = format_flag("from")?;
+        let to = format_flag("to")?.unwrap_or(Format::Json);
+
+        let (text, from) = match operands.first().filter(|f| *f != "-") {
+            Some(file) => {
+                let path = Path::new(file)
+                    .dereference()
+                    .map_err(|e| format_error(scope, file, args, e))?;
+                let text =
+                    fs::read_to_string(&path).map_err(|e| format_error(scope, file, args, e))?;
+                let from = from.or_else(|| {
+                    path.extension()
+                        .and_then(|ext| Format::from_name(&ext.to_string_lossy()))
+                });
+                (text, from)
+            }
+            None => {
+                scope.show_eof_hint();
+                let mut text = String::new();
+                io::stdin()
+                    .read_to_string(&mut text)
+                    .map_err(|e| e.to_string())?;
+                (text, from)
+            }
+        };
+        let from = from.unwrap_or_else(|| Format::detect(&text));
+
+        let value = from.parse(&text).map_err(|e| match operands.first() {
+            Some(file) => format_error(scope, file, args, e),
+            None => e,
+        })?;
+        let output = to.write(&value, flags.is_present("compact"))?;
+        my_println!("{}", output.trim_end())?;
+
+        Ok(Value::success())
+    }
+}
+
+#[ctor::ctor]
+fn register() {
+    register_command(ShellCommand {
+        name: "convert".to_string(),
+        inner: Arc::new(Convert::new()),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert() {
+        let toml = "title = \"test\"\n\n[server]\nport = 8080\nhosts = [\"a\", \"b\"]\n";
+        assert_eq!(Format::detect(toml), Format::Toml);
+
+        let value = Format::Toml.parse(toml).unwrap();
+        let json = Format::Json.write(&value, true).unwrap();
+        assert_eq!(
+            json,
+            r#"{"title":"test","server":{"port":8080,"hosts":["a","b"]}}"#
+        );
+
+        let yaml = Format::Yaml.write(&value, false).unwrap();
+        assert_eq!(Format::detect(&yaml), Format::Yaml);
+        assert_eq!(Format::Yaml.parse(&yaml).unwrap(), value);
+        assert_eq!(Format::Toml.write(&value, false).unwrap(), toml);
+
+        assert!(Format::Toml.write(&Json::from(1), false).is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use super::{flags::CommandFlags, register_command, Exec, Flag, ShellCommand};
 use crate::{
+    digest::{digest, to_hex, Algorithm},
     eval::Value,
     prompt::{confirm, Answer},
     scope::Scope,
@@ -7,10 +8,11 @@ use crate::{
     utils::format_error,
 };
 use filetime::FileTime;
+use glob::Pattern;
 use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
 use std::collections::{BTreeMap, HashSet};
-use std::fs::{self, File};
-use std::io::{self, ErrorKind::Other, Read, Write};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, ErrorKind::Other, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
@@ -80,10 +82,17 @@ impl<T> WrapErr<Result<T, io::Error>> for Result<T, io::Error> {
 struct FileCopier<'a> {
     dest: PathBuf, // Destination
     debug: bool,
-    ignore_links: bool,      // Skip symbolic links
-    confirm_overwrite: bool, // Ask for overwrite confirmation?
-    no_hidden: bool,         // Ignore entries starting with '.'
+    dry_run: bool,                 // Show what would be done, without doing it
+    ignore_links: bool,            // Skip symbolic links
+    confirm_overwrite: bool,       // Ask for overwrite confirmation?
+    update: bool,                  // Copy only when the source is newer than the destination
+    resume: bool,                  // Continue copying into partial destination files
+    verify: bool,                  // Compare checksums of source and destination after copying
+    backup: Option<String>,        // Suffix for renaming existing destination files
+    no_hidden: bool,               // Ignore entries starting with '.'
+    exclude: Vec<(Pattern, bool)>, // Glob patterns to skip, and whether they match dirs only
     preserve_metadata: bool,
+    preserve_xattrs: bool, // Extended attributes, or alternate data streams on Windows
     progress: Option<ProgressBar>,
     recursive: bool,
     scope: &'a Arc<Scope>,
@@ -105,10 +114,20 @@ impl<'a> FileCopier<'a> {
             dest: PathBuf::from(paths.last().unwrap()),
             // Command line flags
             debug: flags.is_present("debug"),
+            dry_run: flags.is_present("dry-run"),
             ignore_links: flags.is_present("no-dereference"),
             confirm_overwrite: flags.is_present("interactive"),
+            update: flags.is_present("update"),
+            resume: flags.is_present("resume"),
+            verify: flags.is_present("verify"),
+            backup: flags
+                .value("suffix")
+                .or(flags.value("backup"))
+                .map(String::from),
             no_hidden: flags.is_present("no-hidden"),
+            exclude: Vec::new(),
             preserve_metadata: !flags.is_present("no-preserve"),
+            preserve_xattrs: flags.value("preserve").is_some(),
             recursive: flags.is_present("recursive"),
             // Progress indicator
             progress: if flags.is_present("progress") {
@@ -216,6 +235,41 @@ impl<'a> FileCopier<'a> {
         Ok(())
     }
 
+    /// Parse the --exclude patterns; a trailing slash matches directories only, e.g. target/
+    fn set_exclude(&mut self, patterns: &[&str]) -> Result<(), String> {
+        for spec in patterns {
+            let dir_only = spec.len() > 1 && spec.ends_with(['/', '\\']);
+            let pattern = Pattern::new(spec.trim_end_matches(['/', '\\']))
+                .map_err(|e| format_error(self.scope, spec, self.args, e))?;
+            self.exclude.push((pattern, dir_only));
+        }
+        Ok(())
+    }
+
+    /// Match the exclude patterns against the file name, and against the path relative
+    /// to the parent of the source given in the command line, e.g. src/*.rs
+    fn is_excluded(&self, parent: &Path, path: &Path) -> bool {
+        if self.exclude.is_empty() {
+            return false;
+        }
+        let name = path
+            .file_name()
+            .map(|f| f.to_string_lossy())
+            .unwrap_or_default();
+        let relative = path
+            .strip_prefix(parent)
+            .unwrap_or(path)
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let is_dir = path.is_dir();
+
+        self.exclude.iter().any(|(pattern, dir_only)| {
+            (is_dir || !dir_only) && (pattern.matches(&name) || pattern.matches(&relative))
+        })
+    }
+
     /// Collect info about one path and its size, recurse if directory.
     /// Return Ok(false) if interrupted by Ctrl+C.
     /// Update progress indicator in verbose mode.
@@ -239,6 +293,12 @@ impl<'a> FileCopier<'a> {
             }
             return Ok(true);
         }
+        if self.is_excluded(parent, path) {
+            if self.debug {
+                eprintln!("{}: skip excluded", path.display());
+            }
+            return Ok(true);
+        }
 
         if path.is_symlink() {
             assert!(!self.ignore_links);
@@ -384,6 +444,10 @@ impl<'a> FileCopier<'a> {
             return Ok(());
         }
 
+        if self.dry_run {
+            return self.show_plan().map_err(|e| io::Error::new(Other, e));
+        }
+
         if self.progress.is_some() {
             self.reset_progress_indicator(self.total_size);
         }
@@ -391,6 +455,25 @@ impl<'a> FileCopier<'a> {
         self.do_work()
     }
 
+    /// Print the work items in the order they would be carried out, and the total size.
+    fn show_plan(&self) -> Result<(), String> {
+        for actions in [&[Action::CreateDir, Action::Copy][..], &[Action::Link]] {
+            for (dest, w) in self.work.iter().filter(|(_, w)| actions.contains(&w.act)) {
+                match w.act {
+                    Action::Copy => {
+                        my_println!("COPY   {} -> {}", w.src.display(), dest.display())?
+                    }
+                    Action::CreateDir => my_println!("CREATE {}", dest.display())?,
+                    Action::Link => {
+                        my_println!("LINK   {} -> {}", dest.display(), w.src.display())?
+                    }
+                }
+            }
+        }
+        let files = self.work.values().filter(|w| w.act == Action::Copy).count();
+        my_println!("Total: {} bytes in {} file(s)", self.total_size, files)
+    }
+
     fn do_work_actions(
         &mut self,
         actions: &[Action],
@@ -452,21 +535,53 @@ impl<'a> FileCopier<'a> {
                 }
                 assert!(!dest.is_dir());
 
-                if self.confirm_overwrite && dest.exists() {
-                    match confirm(
-                        format!("Overwrite {}", dest.display()),
-                        self.scope,
-                        count > 1,
-                    )? {
-                        Answer::Yes => {}
-                        Answer::No => return Ok(true), // Continue
-                        Answer::All => {
-                            self.confirm_overwrite = false;
+                if dest.exists() {
+                    if self.update && !self.is_newer(w.top, &w.src, dest)? {
+                        if self.debug {
+                            eprintln!("SKIP: {} is not newer", w.src.display());
                         }
-                        Answer::Quit => return Ok(false), // Cancel all
+                        return Ok(true);
                     }
+                    if self.confirm_overwrite {
+                        match confirm(
+                            format!("Overwrite {}", dest.display()),
+                            self.scope,
+                            count > 1,
+                        )? {
+                            Answer::Yes => {}
+                            Answer::No => return Ok(true), // Continue
+                            Answer::All => {
+                                self.confirm_overwrite = false;
+                            }
+                            Answer::Quit => return Ok(false), // Cancel all
+                        }
+                    }
+                    if let Some(suffix) = &self.backup {
+                        let mut backup = dest.as_os_str().to_owned();
+                        backup.push(suffix);
+                        if self.debug {
+                            eprintln!("BACKUP: {} -> {:?}", dest.display(), backup);
+                        }
+                        fs::rename(dest, backup).wrap_err_with_msg(
+                            self,
+                            w.top,
+                            dest,
+                            Some("Could not back up"),
+                        )?;
+                    }
+                }
+                let offset = if self.resume {
+                    self.partial_len(w.top, &w.src, dest)?
+                } else {
+                    0
+                };
+                if offset > 0 && self.debug {
+                    eprintln!("RESUME: {} at {}", dest.display(), offset);
                 }
-                if !self.copy_file(w.top, &w.src, dest)? {
+                if !self.copy_file(w.top, &w.src, dest, offset)? {
+                    return Ok(false);
+                }
+                if self.verify && !self.verify(w.top, &w.src, dest)? {
                     return Ok(false);
                 }
             }
@@ -488,37 +603,171 @@ impl<'a> FileCopier<'a> {
         Ok(true)
     }
 
-    /// Copy the contents of a regular file.
+    /// Is the source modified more recently than the destination?
+    fn is_newer(&self, top: &str, src: &Path, dest: &Path) -> io::Result<bool> {
+        let modified = |path: &Path| {
+            fs::metadata(path)
+                .and_then(|m| m.modified())
+                .wrap_err(self, top, path)
+        };
+        Ok(modified(src)? > modified(dest)?)
+    }
+
+    /// Return the length of a partially copied destination file, zero if there is
+    /// nothing to resume. A destination that is longer than the source, or that is
+    /// not a prefix of the source, is copied over.
+    fn partial_len(&self, top: &str, src: &Path, dest: &Path) -> io::Result<u64> {
+        let dest_len = match fs::metadata(dest) {
+            Ok(metadata) if metadata.is_file() => metadata.len(),
+            _ => return Ok(0),
+        };
+        let src_len = fs::metadata(src).wrap_err(self, top, src)?.len();
+        if dest_len == 0 || dest_len > src_len {
+            return Ok(0);
+        }
+        let prefix_sum = |path: &Path| {
+            File::open(path)
+                .and_then(|file| digest(Algorithm::Blake3, file.take(dest_len)))
+                .wrap_err(self, top, path)
+        };
+        if prefix_sum(src)? != prefix_sum(dest)? {
+            if self.debug {
+                eprintln!("MISMATCH: {} is not a prefix of the source", dest.display());
+            }
+            return Ok(0);
+        }
+        Ok(dest_len)
+    }
+
+    /// Compare the checksums of the source and the destination.
+    /// Return Ok(false) if interrupted by Ctrl+C.
+    fn verify(&self, top: &str, src: &Path, dest: &Path) -> io::Result<bool> {
+        let checksum =
+            |path: &Path| File::open(path).and_then(|file| digest(Algorithm::Blake3, file));
+        let (src_sum, dest_sum) = match (checksum(src), checksum(dest)) {
+            (Ok(src_sum), Ok(dest_sum)) => (src_sum, dest_sum),
+            (Err(e), _) | (_, Err(e)) if e.kind() == ErrorKind::Interrupted => return Ok(false),
+            (Err(e), _) => return Err(e).wrap_err(self, top, src),
+            (_, Err(e)) => return Err(e).wrap_err(self, top, dest),
+        };
+        if self.debug {
+            eprintln!("VERIFY: {} {}", dest.display(), to_hex(&dest_sum));
+        }
+        if src_sum != dest_sum {
+            return Err(self.error(top, dest, "Checksum mismatch after copy"));
+        }
+        Ok(true)
+    }
+
+    /// Copy the contents of a regular file, starting at the given offset.
     /// Update progress indicator in verbose mode.
-    fn copy_file(&mut self, top: &str, src: &Path, dest: &PathBuf) -> io::Result<bool> {
+    fn copy_file(
+        &mut self,
+        top: &str,
+        src: &Path,
+        dest: &PathBuf,
+        offset: u64,
+    ) -> io::Result<bool> {
         #[cfg(unix)]
         self.handle_unix_special_file(src, dest)?;
 
-        let mut src_file = File::open(src).wrap_err(&self, top, src)?;
-        let mut dst_file = File::create(&dest).wrap_err(&self, top, dest)?;
+        let mut src_file = File::open(src).wrap_err(self, top, src)?;
+        let metadata = src_file.metadata().wrap_err(self, top, src)?;
+        let len = metadata.len();
+
+        // Append to a partial copy, or else try cloning the data first,
+        // on file systems that support copy-on-write.
+        let cloned = if offset > 0 {
+            let mut dst_file = OpenOptions::new()
+                .write(true)
+                .open(dest)
+                .wrap_err(self, top, dest)?;
+            src_file
+                .seek(SeekFrom::Start(offset))
+                .wrap_err(self, top, src)?;
+            dst_file
+                .seek(SeekFrom::Start(offset))
+                .wrap_err(self, top, dest)?;
+            if let Some(pb) = self.progress.as_mut() {
+                pb.inc(offset);
+            }
+            let sparse = cow::is_sparse(&metadata);
+            if !self.copy_data(top, src, dest, &mut src_file, &mut dst_file, sparse)? {
+                return Ok(false);
+            }
+            false
+        } else if !dest.exists() && cow::clone_path(src, dest) {
+            true
+        } else {
+            let mut dst_file = File::create(dest).wrap_err(self, top, dest)?;
+            let sparse = cow::is_sparse(&metadata);
+            if sparse {
+                cow::set_sparse(&dst_file);
+            }
+            if cow::clone_file(&src_file, &dst_file, len) {
+                true
+            } else {
+                if !self.copy_data(top, src, dest, &mut src_file, &mut dst_file, sparse)? {
+                    return Ok(false);
+                }
+                false
+            }
+        };
+
+        if cloned {
+            if self.debug {
+                eprintln!("CLONED: {}", dest.display());
+            }
+            if let Some(pb) = self.progress.as_mut() {
+                pb.inc(len);
+            }
+        }
+
+        if self.preserve_metadata {
+            self.preserve_metadata(top, src, dest)?;
+        }
 
+        Ok(true)
+    }
+
+    /// Copy the file contents with a read/write loop. Runs of zeros in sparse
+    /// source files are skipped over rather than written, to preserve the holes.
+    fn copy_data(
+        &mut self,
+        top: &str,
+        src: &Path,
+        dest: &Path,
+        src_file: &mut File,
+        dst_file: &mut File,
+        sparse: bool,
+    ) -> io::Result<bool> {
         let mut buffer = [0; 8192]; // TODO: allow user to specify buffer size?
         loop {
             if Scope::is_interrupted() {
                 return Ok(false);
             }
-            let n = src_file.read(&mut buffer).wrap_err(&self, top, src)?;
+            let n = src_file.read(&mut buffer).wrap_err(self, top, src)?;
             if n == 0 {
                 break;
             }
-            dst_file
-                .write_all(&buffer[..n])
-                .wrap_err(&self, top, dest)?;
+            if sparse && buffer[..n].iter().all(|&b| b == 0) {
+                dst_file
+                    .seek(SeekFrom::Current(n as i64))
+                    .wrap_err(self, top, dest)?;
+            } else {
+                dst_file.write_all(&buffer[..n]).wrap_err(self, top, dest)?;
+            }
 
             if let Some(pb) = self.progress.as_mut() {
                 pb.inc(n as u64);
             }
         }
 
-        if self.preserve_metadata {
-            self.preserve_metadata(top, src, dest)?;
+        if sparse {
+            // Extend the file over a trailing hole, if any.
+            let len = dst_file.stream_position().wrap_err(self, top, dest)?;
+            dst_file.set_len(len).wrap_err(self, top, dest)?;
         }
-
         Ok(true)
     }
 
@@ -551,6 +800,16 @@ impl<'a> FileCopier<'a> {
             Some("Could not read metadata"),
         )?;
 
+        // Copy extended attributes first, while the destination is still writable.
+        if self.preserve_xattrs {
+            xattr::copy(self.scope, src, dest, &metadata).wrap_err_with_msg(
+                self,
+                top,
+                dest,
+                Some("Could not copy extended attributes"),
+            )?;
+        }
+
         // Set timestamps on destination file
         filetime::set_file_times(
             dest,
@@ -597,6 +856,360 @@ impl<'a> FileCopier<'a> {
     }
 }
 
+/// Copy-on-write cloning and sparse file helpers. The clone functions
+/// return false when cloning is not supported, so that the caller can
+/// fall back to copying the data.
+mod cow {
+    use std::fs::{File, Metadata};
+    use std::path::Path;
+
+    /// Clone src into a new file at dest (APFS).
+    #[cfg(target_os = "macos")]
+    pub fn clone_path(src: &Path, dest: &Path) -> bool {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+
+        match (
+            CString::new(src.as_os_str().as_bytes()),
+            CString::new(dest.as_os_str().as_bytes()),
+        ) {
+            (Ok(src), Ok(dest)) => unsafe {
+                nix::libc::clonefile(src.as_ptr(), dest.as_ptr(), 0) == 0
+            },
+            _ => false,
+        }
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    pub fn clone_path(_src: &Path, _dest: &Path) -> bool {
+        false
+    }
+
+    /// Share the data extents of src with dest (Btrfs, XFS, bcachefs, etc.)
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn clone_file(src: &File, dest: &File, _len: u64) -> bool {
+        use std::os::unix::io::AsRawFd;
+
+        nix::ioctl_write_int!(ficlone, 0x94, 9);
+
+        unsafe { ficlone(dest.as_raw_fd(), src.as_raw_fd() as _).is_ok() }
+    }
+
+    /// Duplicate the extents of src into dest, using block cloning (ReFS).
+    #[cfg(windows)]
+    pub fn clone_file(src: &File, dest: &File, len: u64) -> bool {
+        use std::os::windows::io::AsRawHandle;
+        use windows::Win32::Foundation::HANDLE;
+        use windows::Win32::System::Ioctl::{
+            DUPLICATE_EXTENTS_DATA, FSCTL_DUPLICATE_EXTENTS_TO_FILE,
+        };
+        use windows::Win32::System::IO::DeviceIoControl;
+
+        // Clone regions must be aligned to the cluster size; 64K covers all ReFS
+        // cluster sizes. The chunks must not exceed 4GB, use 1GB to be safe.
+        const ALIGN: u64 = 64 * 1024;
+        const CHUNK: u64 = 1024 * 1024 * 1024;
+
+        if len == 0 || dest.set_len(len.div_ceil(ALIGN) * ALIGN).is_err() {
+            return false;
+        }
+
+        let mut offset = 0;
+        while offset < len {
+            let count = std::cmp::min(CHUNK, len.div_ceil(ALIGN) * ALIGN - offset);
+            let data = DUPLICATE_EXTENTS_DATA {
+                FileHandle: HANDLE(src.as_raw_handle()),
+                SourceFileOffset: offset as i64,
+                TargetFileOffset: offset as i64,
+                ByteCount: count as i64,
+            };
+            let result = unsafe {
+                DeviceIoControl(
+                    HANDLE(dest.as_raw_handle()),
+                    FSCTL_DUPLICATE_EXTENTS_TO_FILE,
+                    Some(&data as *const _ as *const _),
+                    std::mem::size_of::<DUPLICATE_EXTENTS_DATA>() as u32,
+                    None,
+                    0,
+                    None,
+                    None,
+                )
+            };
+            if result.is_err() {
+                _ = dest.set_len(0);
+                return false;
+            }
+            offset += count;
+        }
+
+        dest.set_len(len).is_ok()
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android", windows)))]
+    pub fn clone_file(_src: &File, _dest: &File, _len: u64) -> bool {
+        false
+    }
+
+    /// Check if the file has holes, i.e. it takes up less space on disk than its length.
+    #[cfg(unix)]
+    pub fn is_sparse(metadata: &Metadata) -> bool {
+        use std::os::unix::fs::MetadataExt;
+        metadata.blocks() * 512 < metadata.len()
+    }
+
+    #[cfg(windows)]
+    pub fn is_sparse(metadata: &Metadata) -> bool {
+        use std::os::windows::fs::MetadataExt;
+        use windows::Win32::Storage::FileSystem::FILE_ATTRIBUTE_SPARSE_FILE;
+
+        metadata.file_attributes() & FILE_ATTRIBUTE_SPARSE_FILE.0 != 0
+    }
+
+    /// Mark the file as sparse (NTFS and ReFS), so that skipped ranges become holes.
+    #[cfg(windows)]
+    pub fn set_sparse(file: &File) {
+        use std::os::windows::io::AsRawHandle;
+        use windows::Win32::Foundation::HANDLE;
+        use windows::Win32::System::Ioctl::FSCTL_SET_SPARSE;
+        use windows::Win32::System::IO::DeviceIoControl;
+
+        _ = unsafe {
+            DeviceIoControl(
+                HANDLE(file.as_raw_handle()),
+                FSCTL_SET_SPARSE,
+                None,
+                0,
+                None,
+                0,
+                None,
+                None,
+            )
+        };
+    }
+
+    /// Unix file systems create holes where the data is seeked over, nothing to do.
+    #[cfg(unix)]
+    pub fn set_sparse(_file: &File) {}
+}
+
+/// Extended attributes on Unix; alternate data streams and file attributes on Windows.
+mod xattr {
+    use crate::scope::Scope;
+    use std::fs::Metadata;
+    use std::io;
+    use std::path::Path;
+
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+    pub(super) mod sys {
+        use nix::libc::{self, c_char, c_int, c_void};
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        pub unsafe fn list(path: *const c_char, buf: *mut c_char, size: usize) -> isize {
+            libc::listxattr(path, buf, size)
+        }
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        pub unsafe fn get(
+            path: *const c_char,
+            name: *const c_char,
+            value: *mut c_void,
+            size: usize,
+        ) -> isize {
+            libc::getxattr(path, name, value, size)
+        }
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        pub unsafe fn set(
+            path: *const c_char,
+            name: *const c_char,
+            value: *const c_void,
+            size: usize,
+        ) -> c_int {
+            libc::setxattr(path, name, value, size, 0)
+        }
+
+        #[cfg(target_os = "macos")]
+        pub unsafe fn list(path: *const c_char, buf: *mut c_char, size: usize) -> isize {
+            libc::listxattr(path, buf, size, 0)
+        }
+
+        #[cfg(target_os = "macos")]
+        pub unsafe fn get(
+            path: *const c_char,
+            name: *const c_char,
+            value: *mut c_void,
+            size: usize,
+        ) -> isize {
+            libc::getxattr(path, name, value, size, 0, 0)
+        }
+
+        #[cfg(target_os = "macos")]
+        pub unsafe fn set(
+            path: *const c_char,
+            name: *const c_char,
+            value: *const c_void,
+            size: usize,
+        ) -> c_int {
+            libc::setxattr(path, name, value, size, 0, 0)
+        }
+    }
+
+    /// Call f with an empty buffer to query the size, then again to read the data;
+    /// retry if the data grew in between the calls.
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+    fn read<F: Fn(&mut [u8]) -> isize>(f: F) -> io::Result<Vec<u8>> {
+        loop {
+            let size = f(&mut []);
+            if size < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut buf = vec![0; size as usize];
+            let n = f(&mut buf);
+            if n >= 0 {
+                buf.truncate(n as usize);
+                return Ok(buf);
+            }
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() != Some(nix::libc::ERANGE) {
+                return Err(err);
+            }
+        }
+    }
+
+    /// Copy the extended attributes of src to dest. Attributes that cannot be read or
+    /// written (e.g. security.* without privileges, or unsupported by the destination
+    /// file system) are reported as warnings, and do not fail the copy.
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+    pub fn copy(scope: &Scope, src: &Path, dest: &Path, _metadata: &Metadata) -> io::Result<()> {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+
+        let src_path = src;
+        let src = CString::new(src.as_os_str().as_bytes())?;
+        let dest = CString::new(dest.as_os_str().as_bytes())?;
+
+        let names = match read(|buf| unsafe {
+            sys::list(src.as_ptr(), buf.as_mut_ptr() as _, buf.len())
+        }) {
+            Ok(names) => names,
+            Err(e) if e.raw_os_error() == Some(nix::libc::ENOTSUP) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        for name in names.split(|&b| b == 0).filter(|name| !name.is_empty()) {
+            let name = CString::new(name)?;
+            let result = read(|buf| unsafe {
+                sys::get(
+                    src.as_ptr(),
+                    name.as_ptr(),
+                    buf.as_mut_ptr() as _,
+                    buf.len(),
+                )
+            })
+            .and_then(|value| {
+                if unsafe {
+                    sys::set(
+                        dest.as_ptr(),
+                        name.as_ptr(),
+                        value.as_ptr() as _,
+                        value.len(),
+                    )
+                } < 0
+                {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+            if let Err(e) = result {
+                my_warning!(
+                    scope,
+                    "{}: Could not copy attribute {}: {}",
+                    scope.err_path(src_path),
+                    name.to_string_lossy(),
+                    e
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy the alternate data streams of src to dest, and the hidden and system attributes.
+    #[cfg(windows)]
+    pub fn copy(_scope: &Scope, src: &Path, dest: &Path, metadata: &Metadata) -> io::Result<()> {
+        use std::ffi::OsString;
+        use std::fs::{self, File};
+        use std::os::windows::ffi::OsStrExt;
+        use std::os::windows::fs::MetadataExt;
+        use windows::core::PCWSTR;
+        use windows::Win32::Storage::FileSystem::{
+            FindClose, FindFirstStreamW, FindNextStreamW, FindStreamInfoStandard,
+            SetFileAttributesW, FILE_ATTRIBUTE_HIDDEN, FILE_ATTRIBUTE_SYSTEM,
+            FILE_FLAGS_AND_ATTRIBUTES, WIN32_FIND_STREAM_DATA,
+        };
+
+        let wide =
+            |path: &Path| -> Vec<u16> { path.as_os_str().encode_wide().chain(Some(0)).collect() };
+        let stream_path = |path: &Path, stream: &str| {
+            let mut path = OsString::from(path);
+            path.push(stream);
+            path
+        };
+
+        let src_wide = wide(src);
+        let mut data = WIN32_FIND_STREAM_DATA::default();
+
+        // Fails with ERROR_HANDLE_EOF if there are no streams, e.g. on FAT file systems.
+        if let Ok(handle) = unsafe {
+            FindFirstStreamW(
+                PCWSTR(src_wide.as_ptr()),
+                FindStreamInfoStandard,
+                &mut data as *mut _ as *mut _,
+                0,
+            )
+        } {
+            let result = (|| loop {
+                let len = data.cStreamName.iter().position(|&c| c == 0).unwrap_or(0);
+                let name = String::from_utf16_lossy(&data.cStreamName[..len]);
+
+                // Names look like :name:$DATA, the unnamed stream holds the file contents.
+                if let Some(stream) = name.strip_suffix(":$DATA").filter(|s| *s != ":") {
+                    let mut reader = File::open(stream_path(src, stream))?;
+                    let mut writer = File::create(stream_path(dest, stream))?;
+                    io::copy(&mut reader, &mut writer)?;
+                }
+                if unsafe { FindNextStreamW(handle, &mut data as *mut _ as *mut _) }.is_err() {
+                    return Ok::<_, io::Error>(());
+                }
+            })();
+            _ = unsafe { FindClose(handle) };
+            result?;
+        }
+
+        let attrs =
+            metadata.file_attributes() & (FILE_ATTRIBUTE_HIDDEN.0 | FILE_ATTRIBUTE_SYSTEM.0);
+        if attrs != 0 {
+            let dest_attrs = fs::metadata(dest)?.file_attributes();
+            unsafe {
+                SetFileAttributesW(
+                    PCWSTR(wide(dest).as_ptr()),
+                    FILE_FLAGS_AND_ATTRIBUTES(dest_attrs | attrs),
+                )
+            }?;
+        }
+        Ok(())
+    }
+
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        windows
+    )))]
+    pub fn copy(_scope: &Scope, _src: &Path, _dest: &Path, _metadata: &Metadata) -> io::Result<()> {
+        Ok(())
+    }
+}
+
 struct Cp {
     flags: CommandFlags,
 }
@@ -608,10 +1221,57 @@ impl Cp {
         flags.add_flag('d', "debug", "Show debugging details");
         flags.add_flag('v', "progress", "Show progress bar");
         flags.add_flag('r', "recursive", "Copy directories recursively");
+        flags.add_flag(
+            'n',
+            "dry-run",
+            "Show what would be copied, without making any changes",
+        );
         flags.add_flag_enabled('i', "interactive", "Prompt to overwrite");
         flags.add_alias(Some('f'), "force", "no-interactive");
+        flags.add_flag(
+            'u',
+            "update",
+            "Copy only when SOURCE is newer than the destination file, or it is missing",
+        );
+        flags.add_optional_value(
+            Some('b'),
+            "backup",
+            "SUFFIX",
+            "~",
+            "Rename existing destination files by appending SUFFIX (default: ~), instead of overwriting",
+        );
+        flags.add_value(
+            'S',
+            "suffix",
+            "SUFFIX",
+            "Back up existing files, using SUFFIX instead of ~ (implies --backup)",
+        );
+        flags.add(
+            None,
+            "resume",
+            None,
+            "Continue interrupted copies into partial destination files that match the start of SOURCE",
+        );
+        flags.add(
+            None,
+            "verify",
+            None,
+            "Compare the checksums of the source and destination files after copying",
+        );
         flags.add_flag('P', "no-dereference", "Ignore symbolic links in SOURCE");
         flags.add(None, "no-hidden", None, "Ignore hidden files");
+        flags.add(
+            None,
+            "exclude",
+            Some("GLOB".to_string()),
+            "Skip files and directories matching GLOB (may be repeated)",
+        );
+        flags.add(
+            None,
+            "preserve",
+            Some("ATTRS".to_string()),
+            "Preserve extended attributes: xattr or all (on Windows: streams, hidden and system attributes)",
+        );
         flags.add(
             None,
             "no-preserve",
@@ -646,7 +1306,14 @@ impl Exec for Cp {
             return Err("Missing destination".to_string());
         }
 
+        if let Some(attrs) = flags.value("preserve") {
+            if !matches!(attrs, "all" | "xattr") {
+                return Err(format_error(scope, attrs, args, "Expecting all or xattr"));
+            }
+        }
+
         let mut copier = FileCopier::new(&paths, &flags, scope, &args);
+        copier.set_exclude(&flags.values("exclude"))?;
         copier.copy().map_err(|e| e.to_string())?;
 
         Ok(Value::success())
@@ -788,7 +1455,7 @@ mod tests {
 
         let mut copier = FileCopier::new(&paths, &flags, &scope, &args);
 
-        let result = copier.copy_file(src_file.to_str().unwrap(), &src_file, &dest_file)?;
+        let result = copier.copy_file(src_file.to_str().unwrap(), &src_file, &dest_file, 0)?;
 
         assert!(result);
         assert!(dest_file.exists());
@@ -799,6 +1466,266 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_exclude() -> io::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let src_dir = temp_dir.path().join("proj");
+        fs::create_dir_all(src_dir.join("target/debug"))?;
+        fs::create_dir_all(src_dir.join("src"))?;
+        create_temp_file(&src_dir, "main.o", "")?;
+        create_temp_file(&src_dir, "target.txt", "")?;
+        create_temp_file(&src_dir.join("src"), "lib.rs", "")?;
+        create_temp_file(&src_dir.join("target/debug"), "app", "")?;
+
+        let scope = Scope::new();
+        let src = src_dir.to_str().unwrap().to_string();
+        let dest = temp_dir.path().join("dest");
+        fs::create_dir(&dest)?;
+        let paths = vec![src.clone(), dest.to_str().unwrap().to_string()];
+        let args = vec![
+            "-r".to_string(),
+            "--exclude".to_string(),
+            "target/".to_string(),
+            "--exclude".to_string(),
+            "*.o".to_string(),
+        ];
+
+        let mut flags = CommandFlags::new();
+        flags.add_flag('r', "recursive", "");
+        flags.add(None, "exclude", Some("GLOB".to_string()), "");
+        flags.parse(&scope, &args).unwrap();
+        assert_eq!(flags.values("exclude"), vec!["target/", "*.o"]);
+
+        let mut copier = FileCopier::new(&paths, &flags, &scope, &args);
+        copier.set_exclude(&flags.values("exclude")).unwrap();
+        copier.collect_path_info(&src, temp_dir.path(), &src_dir)?;
+
+        let mut copied = copier
+            .work
+            .keys()
+            .map(|p| {
+                p.strip_prefix(&dest)
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            })
+            .collect::<Vec<_>>();
+        copied.sort();
+        assert_eq!(
+            copied,
+            ["proj", "proj/src", "proj/src/lib.rs", "proj/target.txt"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_and_backup() -> io::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let src = create_temp_file(temp_dir.path(), "src.txt", "new")?;
+        let dest = create_temp_file(temp_dir.path(), "dest.txt", "old")?;
+        let past = FileTime::from_unix_time(1_000_000_000, 0);
+        filetime::set_file_mtime(&src, past)?;
+
+        let scope = Scope::new();
+        let paths = vec![
+            src.to_str().unwrap().to_string(),
+            dest.to_str().unwrap().to_string(),
+        ];
+        let mut flags = Cp::new().flags;
+        flags
+            .parse(
+                &scope,
+                &["-f".to_string(), "-u".to_string(), "-b".to_string()],
+            )
+            .unwrap();
+
+        let mut copier = FileCopier::new(&paths, &flags, &scope, &paths);
+        let work = WorkItem::new(&paths[0], Action::Copy, src.clone());
+
+        // The source is older: nothing is copied
+        assert!(copier.do_work_item(1, &dest, &work)?);
+        assert_eq!(fs::read_to_string(&dest)?, "old");
+
+        filetime::set_file_mtime(&src, FileTime::now())?;
+        assert!(copier.do_work_item(1, &dest, &work)?);
+        assert_eq!(fs::read_to_string(&dest)?, "new");
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("dest.txt~"))?,
+            "old"
+        );
+
+        fs::write(&src, "newer")?;
+        let mut flags = Cp::new().flags;
+        flags
+            .parse(&scope, &["-f".to_string(), "--backup=.bak".to_string()])
+            .unwrap();
+        let mut copier = FileCopier::new(&paths, &flags, &scope, &paths);
+        assert!(copier.do_work_item(1, &dest, &work)?);
+        assert_eq!(fs::read_to_string(&dest)?, "newer");
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("dest.txt.bak"))?,
+            "new"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dry_run() -> io::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let src = create_temp_file(temp_dir.path(), "src.txt", "hello")?;
+        let dest = temp_dir.path().join("dest.txt");
+
+        let scope = Scope::new();
+        let paths = vec![
+            src.to_str().unwrap().to_string(),
+            dest.to_str().unwrap().to_string(),
+        ];
+        let mut flags = Cp::new().flags;
+        flags.parse(&scope, &["-n".to_string()]).unwrap();
+
+        let mut copier = FileCopier::new(&paths, &flags, &scope, &paths);
+        copier.copy()?;
+
+        assert_eq!(copier.work.len(), 1);
+        assert_eq!(copier.total_size, 5);
+        assert!(!dest.exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resume_and_verify() -> io::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let src = create_temp_file(temp_dir.path(), "src.txt", "hello world")?;
+        let dest = create_temp_file(temp_dir.path(), "dest.txt", "hello")?;
+
+        let scope = Scope::new();
+        let paths = vec![
+            src.to_str().unwrap().to_string(),
+            dest.to_str().unwrap().to_string(),
+        ];
+        let mut flags = Cp::new().flags;
+        flags
+            .parse(
+                &scope,
+                &[
+                    "-f".to_string(),
+                    "--resume".to_string(),
+                    "--verify".to_string(),
+                ],
+            )
+            .unwrap();
+
+        let mut copier = FileCopier::new(&paths, &flags, &scope, &paths);
+        let work = WorkItem::new(&paths[0], Action::Copy, src.clone());
+
+        assert_eq!(copier.partial_len(&paths[0], &src, &dest)?, 5);
+        assert!(copier.do_work_item(1, &dest, &work)?);
+        assert_eq!(fs::read_to_string(&dest)?, "hello world");
+
+        // A partial file with different contents is copied over, not appended to
+        fs::write(&dest, "HELLO")?;
+        assert_eq!(copier.partial_len(&paths[0], &src, &dest)?, 0);
+        assert!(copier.do_work_item(1, &dest, &work)?);
+        assert_eq!(fs::read_to_string(&dest)?, "hello world");
+
+        // The partial file is backed up, rather than resumed
+        fs::write(&dest, "hello")?;
+        copier.backup = Some(".bak".to_string());
+        assert!(copier.do_work_item(1, &dest, &work)?);
+        assert_eq!(fs::read_to_string(&dest)?, "hello world");
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("dest.txt.bak"))?,
+            "hello"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_preserve_xattrs() -> io::Result<()> {
+        use std::ffi::CString;
+
+        let temp_dir = TempDir::new()?;
+        let src = create_temp_file(temp_dir.path(), "src.txt", "hello")?;
+        let dest = temp_dir.path().join("dest.txt");
+
+        let path = CString::new(src.to_str().unwrap())?;
+        let name = CString::new("user.test")?;
+        if unsafe { xattr::sys::set(path.as_ptr(), name.as_ptr(), b"value".as_ptr() as _, 5) } < 0 {
+            return Ok(()); // File system does not support user attributes
+        }
+
+        let scope = Scope::new();
+        let paths = vec![
+            src.to_str().unwrap().to_string(),
+            dest.to_str().unwrap().to_string(),
+        ];
+        let mut flags = Cp::new().flags;
+        flags
+            .parse(&scope, &["--preserve=xattr".to_string()])
+            .unwrap();
+
+        let mut copier = FileCopier::new(&paths, &flags, &scope, &paths);
+        assert!(copier.copy_file(&paths[0], &src, &dest, 0)?);
+
+        let path = CString::new(dest.to_str().unwrap())?;
+        let mut value = [0u8; 16];
+        let n = unsafe {
+            xattr::sys::get(
+                path.as_ptr(),
+                name.as_ptr(),
+                value.as_mut_ptr() as _,
+                value.len(),
+            )
+        };
+        assert_eq!(&value[..n as usize], b"value");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_sparse_file() -> io::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let src = temp_dir.path().join("sparse.bin");
+        let dest = temp_dir.path().join("copy.bin");
+
+        // Data, followed by a hole, more data, and a trailing hole
+        let mut file = File::create(&src)?;
+        file.write_all(b"head")?;
+        file.seek(SeekFrom::Start(1024 * 1024))?;
+        file.write_all(b"tail")?;
+        file.set_len(2 * 1024 * 1024)?;
+        drop(file);
+
+        let scope = Scope::new();
+        let paths = vec![
+            src.to_str().unwrap().to_string(),
+            dest.to_str().unwrap().to_string(),
+        ];
+        let flags = CommandFlags::new();
+        let mut copier = FileCopier::new(&paths, &flags, &scope, &paths);
+
+        assert!(copier.copy_file(&paths[0], &src, &dest, 0)?);
+        assert_eq!(fs::metadata(&dest)?.len(), 2 * 1024 * 1024);
+        assert_eq!(fs::read(&dest)?, fs::read(&src)?);
+
+        // The holes are not filled in, if the file system supports them
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let allocated = |path: &Path| fs::metadata(path).map(|m| m.blocks() * 512);
+            if allocated(&src)? < 1024 * 1024 {
+                assert!(allocated(&dest)? < 1024 * 1024);
+            }
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_error_handling() {
         let temp_dir = TempDir::new().unwrap();
@@ -1,9 +1,112 @@
 use super::{flags::CommandFlags, register_command, Exec, Flag, ShellCommand};
-use crate::{eval::Value, scope::Scope, utils::format_error};
+use crate::{eval::Value, scope::Scope, symlnk::SymLink, utils::format_error};
+use chrono::format::{Item, StrftimeItems};
 use chrono::prelude::*;
-use chrono::{DateTime, Local, Utc};
+use chrono::{DateTime, Duration, Local, Months, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use regex::Regex;
+use std::fs;
+use std::path::Path;
 use std::sync::Arc;
 
+/// Interpret the date and time in UTC, or else in the local time zone.
+fn from_naive(naive: NaiveDateTime, utc: bool) -> Result<DateTime<Utc>, String> {
+    if utc {
+        Ok(naive.and_utc())
+    } else {
+        Local
+            .from_local_datetime(&naive)
+            .earliest()
+            .map(|t| t.with_timezone(&Utc))
+            .ok_or_else(|| "Invalid local time".to_string())
+    }
+}
+
+/// Parse common date expressions: now, today, yesterday, tomorrow, @SECONDS since the epoch,
+/// relative times such as "3 days ago" or "+2 hours", RFC 3339 and RFC 2822 dates, and dates
+/// and times such as "2024-05-01 13:30" or "13:30", interpreted in the local time zone
+/// (or in UTC when utc is true).
+fn parse_date(expr: &str, now: DateTime<Utc>, utc: bool) -> Result<DateTime<Utc>, String> {
+    let expr = expr.trim();
+    let invalid = || format!("Invalid date: {}", expr);
+
+    match expr.to_lowercase().as_str() {
+        "now" | "today" => return Ok(now),
+        "yesterday" => return Ok(now - Duration::days(1)),
+        "tomorrow" => return Ok(now + Duration::days(1)),
+        _ => {}
+    }
+
+    if let Some(seconds) = expr.strip_prefix('@') {
+        let seconds = seconds.parse::<i64>().map_err(|_| invalid())?;
+        return DateTime::from_timestamp(seconds, 0).ok_or_else(invalid);
+    }
+
+    let relative = Regex::new(
+        r"(?i)^([+-]?\d+)\s*(sec|second|min|minute|hour|day|week|month|year)s?(\s+ago)?$",
+    )
+    .unwrap();
+    if let Some(caps) = relative.captures(expr) {
+        let mut n = caps[1].parse::<i64>().map_err(|_| invalid())?;
+        if caps.get(3).is_some() {
+            n = -n;
+        }
+        let time = match caps[2].to_lowercase().as_str() {
+            "sec" | "second" => Duration::try_seconds(n).map(|d| now + d),
+            "min" | "minute" => Duration::try_minutes(n).map(|d| now + d),
+            "hour" => Duration::try_hours(n).map(|d| now + d),
+            "day" => Duration::try_days(n).map(|d| now + d),
+            "week" => Duration::try_weeks(n).map(|d| now + d),
+            unit => {
+                let months = if unit == "year" { n * 12 } else { n };
+                let delta =
+                    Months::new(u32::try_from(months.unsigned_abs()).map_err(|_| invalid())?);
+                if months < 0 {
+                    now.checked_sub_months(delta)
+                } else {
+                    now.checked_add_months(delta)
+                }
+            }
+        };
+        return time.ok_or_else(invalid);
+    }
+
+    if let Ok(time) = DateTime::parse_from_rfc3339(expr) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    if let Ok(time) = DateTime::parse_from_rfc2822(expr) {
+        return Ok(time.with_timezone(&Utc));
+    }
+
+    for format in [
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y/%m/%d %H:%M:%S",
+        "%Y/%m/%d %H:%M",
+    ] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(expr, format) {
+            return from_naive(naive, utc);
+        }
+    }
+    for format in ["%Y-%m-%d", "%Y/%m/%d", "%m/%d/%Y", "%d %b %Y", "%b %d %Y"] {
+        if let Ok(date) = NaiveDate::parse_from_str(expr, format) {
+            return from_naive(date.and_time(NaiveTime::MIN), utc);
+        }
+    }
+    for format in ["%H:%M:%S", "%H:%M"] {
+        if let Ok(time) = NaiveTime::parse_from_str(expr, format) {
+            let today = if utc {
+                now.date_naive()
+            } else {
+                now.with_timezone(&Local).date_naive()
+            };
+            return from_naive(today.and_time(time), utc);
+        }
+    }
+    Err(invalid())
+}
+
 struct Date {
     flags: CommandFlags,
 }
@@ -12,7 +115,7 @@ impl Date {
     fn new() -> Self {
         let mut flags = CommandFlags::with_help();
         flags.add_flag('u', "utc", "Display time in UTC instead of local time");
-        flags.add_flag('r', "rfc2822", "Display date and time in RFC 2822 format");
+        flags.add_flag('R', "rfc2822", "Display date and time in RFC 2822 format");
         flags.add_flag('I', "iso8601", "Display date in ISO 8601 format");
         flags.add_value(
             'z',
@@ -20,6 +123,18 @@ impl Date {
             "zone",
             "Specify the zone (e.g., America/New_York) to display local time",
         );
+        flags.add_value(
+            'd',
+            "date",
+            "STRING",
+            "Display the time described by STRING (e.g. \"yesterday\", \"2 hours ago\", \"2024-05-01 13:30\")",
+        );
+        flags.add_value(
+            'r',
+            "reference",
+            "FILE",
+            "Display the last modification time of FILE",
+        );
 
         Self { flags }
     }
@@ -29,16 +144,16 @@ impl Date {
         scope: &Arc<Scope>,
         args: &[String],
         zone: &str,
+        time: DateTime<Utc>,
     ) -> Result<DateTime<FixedOffset>, String> {
         match tzdb::tz_by_name(zone) {
             Some(tz) => {
-                let local_time = Local::now().timestamp();
                 let local_time_type = tz
-                    .find_local_time_type(local_time)
+                    .find_local_time_type(time.timestamp())
                     .map_err(|e| format_error(scope, zone, args, e))?;
 
                 match chrono::FixedOffset::east_opt(local_time_type.ut_offset()) {
-                    Some(offset) => Ok(Utc::now().with_timezone(&offset)),
+                    Some(offset) => Ok(time.with_timezone(&offset)),
                     None => Err(format_error(
                         scope,
                         zone,
@@ -51,16 +166,28 @@ impl Date {
         }
     }
 
-    fn format_time<Tz: TimeZone>(&self, time: DateTime<Tz>, flags: &CommandFlags) -> String
+    fn format_time<Tz: TimeZone>(
+        &self,
+        time: DateTime<Tz>,
+        flags: &CommandFlags,
+        format: Option<&str>,
+    ) -> Result<String, String>
     where
         Tz::Offset: std::fmt::Display,
     {
-        if flags.is_present("rfc2822") {
-            time.to_rfc2822()
+        if let Some(format) = format {
+            // Invalid specifiers would make formatting panic, check first
+            let items = StrftimeItems::new(format).collect::<Vec<_>>();
+            if items.iter().any(|item| matches!(item, Item::Error)) {
+                return Err(format!("Invalid format: {}", format));
+            }
+            Ok(time.format_with_items(items.into_iter()).to_string())
+        } else if flags.is_present("rfc2822") {
+            Ok(time.to_rfc2822())
         } else if flags.is_present("iso8601") {
-            time.to_rfc3339()
+            Ok(time.to_rfc3339())
         } else {
-            time.format("%Y-%m-%d %H:%M:%S %z").to_string()
+            Ok(time.format("%Y-%m-%d %H:%M:%S %z").to_string())
         }
     }
 }
@@ -72,26 +199,57 @@ impl Exec for Date {
 
     fn exec(&self, _name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
         let mut flags = self.flags.clone();
-        let _args = flags.parse(scope, args)?;
+        let date_args = flags.parse(scope, args)?;
 
         if flags.is_present("help") {
-            println!("Usage: date [OPTIONS]");
-            println!("Display the current date and time.");
+            println!("Usage: date [OPTIONS] [+FORMAT]");
+            println!("Display the current date and time, or the time given with -d or -r.");
+            println!("FORMAT uses strftime specifiers, e.g. %Y-%m-%d, %H:%M:%S, %a, %b, %s.");
             println!("\nOptions:");
             print!("{}", flags.help());
+            println!("\nExamples:");
+            println!("    date \"+%Y%m%d\"");
+            println!("    date -u -d \"3 days ago\" \"+%A %d %B\"");
+            println!("    date -r Cargo.toml");
             return Ok(Value::success());
         }
 
-        let formatted_time = if flags.is_present("utc") {
-            let utc_time = Utc::now();
-            self.format_time(utc_time, &flags)
+        let mut format = None;
+        for arg in &date_args {
+            match arg.strip_prefix('+') {
+                Some(f) if format.is_none() => format = Some(f),
+                _ => return Err(format_error(scope, arg, args, "Extra operand")),
+            }
+        }
+
+        let utc = flags.is_present("utc");
+        let time = match (flags.value("date"), flags.value("reference")) {
+            (Some(_), Some(_)) => {
+                return Err("Options -d and -r are mutually exclusive".to_string())
+            }
+            (Some(expr), None) => {
+                parse_date(expr, Utc::now(), utc).map_err(|e| format_error(scope, expr, args, e))?
+            }
+            (None, Some(file)) => Path::new(file)
+                .dereference()
+                .and_then(|path| fs::metadata(path)?.modified())
+                .map(DateTime::<Utc>::from)
+                .map_err(|e| format_error(scope, file, args, e))?,
+            (None, None) => Utc::now(),
+        };
+
+        let formatted_time = if utc {
+            self.format_time(time, &flags, format)
         } else if let Some(tz) = flags.value("timezone") {
-            let tz_time = self.get_time_in_timezone(scope, args, tz)?;
-            self.format_time(tz_time, &flags)
+            let tz_time = self.get_time_in_timezone(scope, args, tz, time)?;
+            self.format_time(tz_time, &flags, format)
         } else {
-            let local_time = Local::now();
-            self.format_time(local_time, &flags)
+            self.format_time(time.with_timezone(&Local), &flags, format)
         };
+        let formatted_time = formatted_time.map_err(|e| match format {
+            Some(f) => format_error(scope, &format!("+{}", f), args, e),
+            None => e,
+        })?;
 
         println!("{}", formatted_time);
         Ok(Value::success())
@@ -105,3 +263,47 @@ fn register() {
         inner: Arc::new(Date::new()),
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_date() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 31, 12, 0, 0).unwrap();
+        let parse = |expr: &str| parse_date(expr, now, true).map(|t| t.to_rfc3339());
+
+        assert_eq!(parse("now"), Ok(now.to_rfc3339()));
+        assert_eq!(
+            parse("yesterday"),
+            Ok("2024-03-30T12:00:00+00:00".to_string())
+        );
+        assert_eq!(
+            parse("3 days ago"),
+            Ok("2024-03-28T12:00:00+00:00".to_string())
+        );
+        assert_eq!(
+            parse("+2 hours"),
+            Ok("2024-03-31T14:00:00+00:00".to_string())
+        );
+        assert_eq!(
+            parse("1 month ago"),
+            Ok("2024-02-29T12:00:00+00:00".to_string())
+        );
+        assert_eq!(parse("@0"), Ok("1970-01-01T00:00:00+00:00".to_string()));
+        assert_eq!(
+            parse("2024-05-01 13:30"),
+            Ok("2024-05-01T13:30:00+00:00".to_string())
+        );
+        assert_eq!(
+            parse("2024/05/01"),
+            Ok("2024-05-01T00:00:00+00:00".to_string())
+        );
+        assert_eq!(parse("08:15"), Ok("2024-03-31T08:15:00+00:00".to_string()));
+        assert_eq!(
+            parse("2024-05-01T13:30:00+02:00"),
+            Ok("2024-05-01T11:30:00+00:00".to_string())
+        );
+        assert!(parse("next blue moon").is_err());
+    }
+}
@@ -1,10 +1,16 @@
+use super::walk::{Key, WorkQueue};
 use super::{flags::CommandFlags, register_command, Exec, ShellCommand, Flag};
-use crate::{eval::Value, scope::Scope, symlnk::SymLink, utils::format_error, utils::format_size};
+use crate::utils::{format_error, format_size, parse_size};
+use crate::{eval::Value, scope::Scope, symlnk::SymLink};
+use serde_json::json;
+use std::cmp::{Ordering, Reverse};
 use std::collections::HashSet;
-use std::fs;
-use std::io::Error;
-use std::path::Path;
-use std::sync::Arc;
+use std::fs::{self, DirEntry};
+use std::io::{self, Error, Write};
+use std::ops::AddAssign;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
 
 struct DiskUtilization {
     flags: CommandFlags,
@@ -41,6 +47,12 @@ impl Exec for DiskUtilization {
             })
             .transpose()?; // Propagate error if present
 
+        let threshold = flags
+            .value("threshold")
+            .map(|s| parse_size(s).map_err(|e| format_error(scope, s, args, e)))
+            .transpose()?
+            .unwrap_or(0);
+
         let opts = Options {
             all: flags.is_present("all"),
             apparent: flags.is_present("apparent"),
@@ -49,26 +61,70 @@ impl Exec for DiskUtilization {
             block_size: 1024,
             max_depth,
             unique_ids: flags.is_present("unique"),
+            threshold,
+            sort: flags.is_present("sort"),
+            json: flags.is_present("json"),
+            null: flags.is_present("null"),
+            one_file_system: flags.is_present("one-file-system"),
+        };
+
+        let threads = match flags.value("threads") {
+            Some(v) => match v.parse::<usize>() {
+                Ok(0) => return Err(format_error(scope, v, args, "expecting at least 1")),
+                Ok(n) => n,
+                Err(e) => return Err(format_error(scope, v, args, e)),
+            },
+            None => thread::available_parallelism().map_or(1, |n| n.get()),
         };
 
+        // Sizes collected for sorting, when --sort is specified.
+        let mut sizes = Vec::new();
+
         let follow = flags.is_present("follow-links");
 
         for p in &paths {
             // Set the argument index in case there's an error
             scope.err_path_arg(p, args);
 
-            let mut file_ids: HashSet<(u64, u64)> = HashSet::new();
-
             let path = Path::new(p)
                 .resolve(follow)
                 .map_err(|e| format_error(&scope, p, args, e))?;
 
-            let size = du_size(&path, &opts, scope, 0, &mut file_ids)?;
+            let device = match opts.one_file_system {
+                true => Some(device_id(&path).map_err(|e| format_error(scope, p, args, e))?),
+                false => None,
+            };
+
+            let walker = Walker::new(&opts, threads, device);
+            let entries = walker.walk(&path, scope)?;
+            if Scope::is_interrupted() {
+                break;
+            }
+
+            let usage = entries.last().map_or(Usage::default(), |(_, e)| e.usage);
 
             if opts.summarize {
-                print_size(&path, size, &opts)?;
+                report_size(&path, usage, &opts, &mut sizes)?;
+            } else {
+                for (key, entry) in &entries {
+                    // The key of the root is empty, its depth is zero
+                    let depth = key.len();
+                    if depth <= opts.max_depth.unwrap_or(usize::MAX) && (opts.all || entry.is_dir) {
+                        report_size(&entry.path, entry.usage, &opts, &mut sizes)?;
+                    }
+                }
             }
         }
+
+        // Largest first; keep the traversal order for equal sizes.
+        sizes.sort_by_key(|(_, usage)| Reverse(usage.size(&opts)));
+        for (path, usage) in &sizes {
+            print_size(path, *usage, &opts)?;
+        }
+        if opts.null {
+            // The last record does not end with a newline, flush it out
+            io::stdout().flush().map_err(|e| e.to_string())?;
+        }
         Ok(Value::success())
     }
 }
@@ -101,6 +157,36 @@ impl DiskUtilization {
         );
 
         flags.add_flag('u', "unique", "Avoid double-counting hard links");
+        flags.add_value(
+            't',
+            "threshold",
+            "SIZE",
+            "Exclude entries smaller than SIZE (e.g., 100K, 1.5G)",
+        );
+        flags.add(
+            None,
+            "sort",
+            None,
+            "Print the results after the traversal, sorted by size, largest first",
+        );
+        flags.add(
+            None,
+            "threads",
+            Some("N".to_string()),
+            "Number of threads walking directories (default: number of CPUs)",
+        );
+        flags.add(
+            None,
+            "json",
+            None,
+            "Print a JSON object per entry, with the path, bytes and apparent_bytes",
+        );
+        flags.add_flag('0', "null", "End each output line with NUL, not newline");
+        flags.add_flag(
+            'x',
+            "one-file-system",
+            "Skip directories on different file systems (or volumes)",
+        );
 
         Self { flags }
     }
@@ -114,54 +200,246 @@ struct Options {
     block_size: u64,
     max_depth: Option<usize>,
     unique_ids: bool, // use unique ids to avoid double-counting
+    threshold: u64,   // do not show entries smaller than this size
+    sort: bool,       // collect the sizes and show them sorted
+    json: bool,       // print JSON records instead of text lines
+    null: bool,       // terminate records with NUL
+    one_file_system: bool,
 }
 
-fn du_size(
-    path: &Path,
-    opts: &Options,
-    scope: &Scope,
-    depth: usize,
-    file_ids: &mut HashSet<(u64, u64)>,
-) -> Result<u64, String> {
-    // Skip symbolic links
-    if path.is_symlink() {
-        return Ok(0);
+/// Disk usage and apparent size, in bytes.
+#[derive(Clone, Copy, Default)]
+struct Usage {
+    disk: u64,
+    apparent: u64,
+}
+
+impl Usage {
+    /// The size that is displayed, sorted by, and compared with the threshold.
+    fn size(&self, opts: &Options) -> u64 {
+        if opts.apparent {
+            self.apparent
+        } else {
+            self.disk
+        }
     }
-    let mut size = estimate_disk_size(&opts, file_ids, path)
-        .map_err(|e| format!("{}: {}", scope.err_path(path), e))?;
+}
 
-    if path.is_dir() {
-        match fs::read_dir(path) {
+impl AddAssign for Usage {
+    fn add_assign(&mut self, other: Self) {
+        self.disk += other.disk;
+        self.apparent += other.apparent;
+    }
+}
+
+struct Entry {
+    path: PathBuf,
+    usage: Usage, // Directories: the usage of the files directly inside, then the total
+
+    is_dir: bool,
+}
+
+/// Multi-threaded directory walker, like the one used by grep.
+/// Sizes are added up into directory totals after the walk.
+struct Walker<'a> {
+    opts: &'a Options,
+    device: Option<u64>, // Do not descend into other devices, when walking one file system
+    work: WorkQueue<(Key, PathBuf)>,
+    file_ids: Mutex<HashSet<(u64, u64)>>,
+    entries: Mutex<Vec<(Key, Entry)>>,
+    warnings: Mutex<Vec<(Key, PathBuf, String)>>,
+    error: Mutex<Option<(PathBuf, String)>>,
+}
+
+impl<'a> Walker<'a> {
+    fn new(opts: &'a Options, threads: usize, device: Option<u64>) -> Self {
+        Self {
+            opts,
+            device,
+            work: WorkQueue::new(threads),
+            file_ids: Mutex::new(HashSet::new()),
+            entries: Mutex::new(Vec::new()),
+            warnings: Mutex::new(Vec::new()),
+            error: Mutex::new(None),
+        }
+    }
+
+    fn fail(&self, path: &Path, error: String) {
+        self.error
+            .lock()
+            .unwrap()
+            .get_or_insert((path.to_path_buf(), error));
+        self.work.stop();
+    }
+
+    /// Estimate the size of the path; stop the walk on error.
+    fn usage(&self, path: &Path) -> Option<Usage> {
+        match estimate_disk_size(self.opts, &self.file_ids, path) {
+            Ok(usage) => Some(usage),
             Err(e) => {
-                my_warning!(scope, "{}: {}", scope.err_path(path), e);
+                self.fail(path, e.to_string());
+                None
             }
-            Ok(dir) => {
-                for entry in dir {
-                    if Scope::is_interrupted() {
-                        return Ok(size);
+        }
+    }
+
+    fn visit(&self, worker: usize, key: Key, path: PathBuf) {
+        // Skip symbolic links
+        if path.is_symlink() {
+            return;
+        }
+        let Some(mut usage) = self.usage(&path) else {
+            return;
+        };
+        let is_dir = path.is_dir();
+
+        if is_dir {
+            match fs::read_dir(&path) {
+                Err(e) => {
+                    let warning = (key.clone(), path.clone(), e.to_string());
+                    self.warnings.lock().unwrap().push(warning);
+                }
+                Ok(dir) => {
+                    for (i, entry) in dir.enumerate() {
+                        let entry = match entry {
+                            Ok(entry) => entry,
+                            Err(e) => return self.fail(&path, e.to_string()),
+                        };
+                        match self.visit_entry(worker, &key, i, entry) {
+                            Some(entry_usage) => usage += entry_usage,
+                            None => return,
+                        }
                     }
+                }
+            }
+        }
+        let entry = Entry {
+            path,
+            usage,
+            is_dir,
+        };
+        self.entries.lock().unwrap().push((key, entry));
+    }
 
-                    let entry = &entry.map_err(|e| format!("{}: {}", scope.err_path(path), e))?;
-                    size += du_size(&entry.path(), &opts, scope, depth + 1, file_ids)?;
+    /// Queue the subdirectories, and return the usage of the files, which are not queued.
+    fn visit_entry(
+        &self,
+        worker: usize,
+        key: &Key,
+        index: usize,
+        entry: DirEntry,
+    ) -> Option<Usage> {
+        let mut key = key.clone();
+        key.push(index);
+        let path = entry.path();
+
+        match entry.file_type() {
+            Err(e) => {
+                self.fail(&path, e.to_string());
+                None
+            }
+            Ok(file_type) if file_type.is_symlink() => Some(Usage::default()),
+            Ok(file_type) if file_type.is_dir() => {
+                if let Some(device) = self.device {
+                    match device_id(&path) {
+                        Ok(id) if id != device => return Some(Usage::default()), // Mount point
+                        Ok(_) => {}
+                        Err(e) => {
+                            self.fail(&path, e.to_string());
+                            return None;
+                        }
+                    }
+                }
+                self.work.push(worker, (key, path));
+                Some(Usage::default())
+            }
+            Ok(_) => {
+                let usage = self.usage(&path)?;
+                if self.opts.all {
+                    let entry = Entry {
+                        path,
+                        usage,
+                        is_dir: false,
+                    };
+                    self.entries.lock().unwrap().push((key, entry));
                 }
+                Some(usage)
             }
         }
     }
 
-    if !opts.summarize && depth <= opts.max_depth.unwrap_or(usize::MAX) {
-        if opts.all || path.is_dir() {
-            print_size(path, size, opts)?;
+    /// Walk the path, and return the entries found, with the total sizes of the directories,
+    /// in the order of a sequential walk that shows the contents before the directory.
+    fn walk(&self, path: &Path, scope: &Scope) -> Result<Vec<(Key, Entry)>, String> {
+        self.work.push(0, (vec![], path.to_path_buf()));
+        thread::scope(|s| {
+            for worker in 0..self.work.threads() {
+                s.spawn(move || {
+                    self.work
+                        .run(worker, |(key, path)| self.visit(worker, key, path))
+                });
+            }
+        });
+
+        let mut warnings = std::mem::take(&mut *self.warnings.lock().unwrap());
+        warnings.sort_by(|a, b| a.0.cmp(&b.0));
+        for (_, path, e) in &warnings {
+            my_warning!(scope, "{}: {}", scope.err_path(path), e);
+        }
+        if let Some((path, e)) = self.error.lock().unwrap().take() {
+            return Err(format!("{}: {}", scope.err_path(&path), e));
+        }
+
+        let mut entries = std::mem::take(&mut *self.entries.lock().unwrap());
+        aggregate(&mut entries);
+        Ok(entries)
+    }
+}
+
+/// Add the sizes of the contents to the directory totals, and sort the entries
+/// so that the contents of a directory come before it.
+fn aggregate(entries: &mut [(Key, Entry)]) {
+    // Pre-order: the contents of each directory follow it.
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    // Walk backwards, so that the contents are totaled before their directory.
+    // The sizes of the files are already included in the size of their directory.
+    let mut totals: Vec<(usize, Usage)> = Vec::new(); // depth, usage
+    for (key, entry) in entries.iter_mut().rev().filter(|(_, entry)| entry.is_dir) {
+        while let Some((_, usage)) = totals.pop_if(|(depth, _)| *depth > key.len()) {
+            entry.usage += usage;
         }
+        totals.push((key.len(), entry.usage));
     }
 
-    Ok(size)
+    entries.sort_by(|a, b| post_order(&a.0, &b.0));
+}
+
+fn post_order(a: &Key, b: &Key) -> Ordering {
+    match a.iter().zip(b.iter()).find(|(x, y)| x != y) {
+        Some((x, y)) => x.cmp(y),
+        None => b.len().cmp(&a.len()), // Contents first
+    }
+}
+
+/// The id of the device (Unix) or the serial number of the volume (Windows) of the path.
+fn device_id(path: &Path) -> Result<u64, Error> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Ok(fs::metadata(path)?.dev())
+    }
+    #[cfg(windows)]
+    {
+        win::volume_serial_number(path)
+    }
 }
 
 fn estimate_disk_size(
     opts: &Options,
-    file_ids: &mut HashSet<(u64, u64)>,
+    file_ids: &Mutex<HashSet<(u64, u64)>>,
     path: &Path,
-) -> Result<u64, Error> {
+) -> Result<Usage, Error> {
     #[cfg(unix)]
     {
         unix_disk_size(opts, file_ids, path)
@@ -176,9 +454,9 @@ fn estimate_disk_size(
 #[cfg(unix)]
 fn unix_disk_size(
     opts: &Options,
-    file_ids: &mut HashSet<(u64, u64)>,
+    file_ids: &Mutex<HashSet<(u64, u64)>>,
     path: &Path,
-) -> Result<u64, Error> {
+) -> Result<Usage, Error> {
     use std::os::unix::fs::MetadataExt;
 
     let metadata = fs::metadata(path)?;
@@ -186,20 +464,19 @@ fn unix_disk_size(
     if opts.unique_ids {
         // Avoid double-counting hard links
         let inode = (metadata.dev(), metadata.ino());
-        if !file_ids.insert(inode) {
-            return Ok(0);
+        if !file_ids.lock().unwrap().insert(inode) {
+            return Ok(Usage::default());
         }
     }
 
-    if opts.apparent {
-        Ok(metadata.len())
-    } else {
-        // https://doc.rust-lang.org/std/os/unix/fs/trait.MetadataExt.html#tymethod.blocks
-        // num_blocks is in 512 units, NOT metadata.blksize()
-        let num_blocks = metadata.blocks();
+    // https://doc.rust-lang.org/std/os/unix/fs/trait.MetadataExt.html#tymethod.blocks
+    // num_blocks is in 512 units, NOT metadata.blksize()
+    let num_blocks = metadata.blocks();
 
-        Ok(num_blocks * 512)
-    }
+    Ok(Usage {
+        disk: num_blocks * 512,
+        apparent: metadata.len(),
+    })
 }
 
 #[cfg(windows)]
@@ -224,26 +501,25 @@ mod win {
     pub fn disk_size(
         blk_sz: &mut HashMap<PathBuf, u64>,
         opts: &Options,
-        file_ids: &mut HashSet<(u64, u64)>,
+        file_ids: &Mutex<HashSet<(u64, u64)>>,
         path: &Path,
-    ) -> Result<u64, Error> {
+    ) -> Result<Usage, Error> {
         let metadata = fs::metadata(path)?;
 
         if opts.unique_ids {
             let id: (u64, u64) = unique_file_id(path)?;
 
             // Check if we've seen this file before, avoid double-counting hard links
-            if !file_ids.insert(id) {
-                return Ok(0);
+            if !file_ids.lock().unwrap().insert(id) {
+                return Ok(Usage::default());
             }
         }
 
-        if opts.apparent {
-            Ok(metadata.len())
-        } else {
-            let block_size = block_size(blk_sz, &root_path(&path))?;
-            Ok(((metadata.file_size() + block_size - 1) / block_size) * block_size)
-        }
+        let block_size = block_size(blk_sz, &root_path(&path))?;
+        Ok(Usage {
+            disk: ((metadata.file_size() + block_size - 1) / block_size) * block_size,
+            apparent: metadata.len(),
+        })
     }
 
     fn block_size(blk_sz: &mut HashMap<PathBuf, u64>, root_path: &Path) -> Result<u64, Error> {
@@ -280,9 +556,7 @@ mod win {
         Ok(block_size)
     }
 
-    /// Build a unique id from the volume serial number and the file index.
-    /// Used with a hash set to avoid double counting of links.
-    fn unique_file_id(path: &Path) -> Result<(u64, u64), Error> {
+    fn file_information(path: &Path) -> Result<BY_HANDLE_FILE_INFORMATION, Error> {
         let file = OpenOptions::new()
             .read(true)
             .custom_flags(FILE_FLAG_BACKUP_SEMANTICS.0)
@@ -293,6 +567,18 @@ mod win {
 
         unsafe { GetFileInformationByHandle(handle, &mut file_info) }?;
 
+        Ok(file_info)
+    }
+
+    pub fn volume_serial_number(path: &Path) -> Result<u64, Error> {
+        Ok(file_information(path)?.dwVolumeSerialNumber as u64)
+    }
+
+    /// Build a unique id from the volume serial number and the file index.
+    /// Used with a hash set to avoid double counting of links.
+    fn unique_file_id(path: &Path) -> Result<(u64, u64), Error> {
+        let file_info = file_information(path)?;
+
         let volume_serial_number = file_info.dwVolumeSerialNumber as u64;
         let file_index =
             ((file_info.nFileIndexHigh as u64) << 32) | (file_info.nFileIndexLow as u64);
@@ -301,12 +587,43 @@ mod win {
     }
 }
 
-fn print_size(path: &Path, size: u64, opts: &Options) -> Result<(), String> {
-    my_println!(
-        "{}\t{}",
-        format_size(size, opts.block_size, opts.human),
-        path.display()
-    )
+/// Print the size of the path right away, or save it for sorting.
+fn report_size(
+    path: &Path,
+    usage: Usage,
+    opts: &Options,
+    sizes: &mut Vec<(PathBuf, Usage)>,
+) -> Result<(), String> {
+    if usage.size(opts) < opts.threshold {
+        Ok(())
+    } else if opts.sort {
+        sizes.push((path.to_path_buf(), usage));
+        Ok(())
+    } else {
+        print_size(path, usage, opts)
+    }
+}
+
+fn print_size(path: &Path, usage: Usage, opts: &Options) -> Result<(), String> {
+    let record = if opts.json {
+        json!({
+            "path": path.to_string_lossy(),
+            "bytes": usage.disk,
+            "apparent_bytes": usage.apparent,
+        })
+        .to_string()
+    } else {
+        format!(
+            "{}\t{}",
+            format_size(usage.size(opts), opts.block_size, opts.human),
+            path.display()
+        )
+    };
+    if opts.null {
+        my_print!("{}\0", record)
+    } else {
+        my_println!("{}", record)
+    }
 }
 
 #[ctor::ctor]
@@ -316,3 +633,47 @@ fn register() {
         inner: Arc::new(DiskUtilization::new()),
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aggregate() {
+        let entry = |path: &str, size, is_dir| Entry {
+            path: PathBuf::from(path),
+            usage: Usage {
+                disk: size,
+                apparent: size / 2,
+            },
+            is_dir,
+        };
+        // As found by the walker threads, in no particular order
+        let mut entries = vec![
+            (vec![1], entry("a/c", 4, true)),
+            (vec![], entry("a", 4, true)),
+            (vec![0, 1], entry("a/b/f", 8, false)),
+            (vec![1, 0], entry("a/c/d", 4, true)),
+            (vec![0], entry("a/b", 12, true)), // Includes a/b/f
+            (vec![1, 0, 0], entry("a/c/d/e", 4, true)),
+        ];
+        aggregate(&mut entries);
+
+        let sizes = entries
+            .iter()
+            .map(|(_, e)| (e.path.to_str().unwrap(), e.usage.disk))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sizes,
+            [
+                ("a/b/f", 8),
+                ("a/b", 12),
+                ("a/c/d/e", 4),
+                ("a/c/d", 8),
+                ("a/c", 12),
+                ("a", 28),
+            ]
+        );
+        assert_eq!(entries.last().unwrap().1.usage.apparent, 14);
+    }
+}
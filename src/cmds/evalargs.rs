@@ -125,7 +125,8 @@ impl Exec for Evaluate {
                         let global_scope = scope.global();
                         // Export variables from the eval scope to the global scope
                         for (key, var) in eval_scope.vars().iter() {
-                            if !key.is_special_var() {
+                            if !key.is_special_var() && !var.is_local() {
+                                var.set_exported(true);
                                 global_scope.vars_mut().insert(key.clone(), var.clone());
                             }
                         }
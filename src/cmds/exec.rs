@@ -0,0 +1,86 @@
+use super::{flags::CommandFlags, get_command, register_command, Exec, Flag, ShellCommand};
+use crate::{eval::Value, scope::Scope, utils::copy_exported_vars_to_command_env};
+use std::process::Command;
+use std::sync::Arc;
+
+struct Execute {
+    flags: CommandFlags,
+}
+
+impl Execute {
+    fn new() -> Self {
+        let flags = CommandFlags::with_help();
+        Self { flags }
+    }
+
+    /// Replace the shell process with the command. Return only if that fails.
+    #[cfg(unix)]
+    fn replace_process(command: &mut Command) -> std::io::Error {
+        use std::os::unix::process::CommandExt;
+        command.exec()
+    }
+
+    /// There is no execvp on Windows: run the command, and exit with its exit code.
+    #[cfg(windows)]
+    fn replace_process(command: &mut Command) -> std::io::Error {
+        match command.status() {
+            Ok(status) => std::process::exit(status.code().unwrap_or(1)),
+            Err(e) => e,
+        }
+    }
+}
+
+impl Exec for Execute {
+    fn cli_flags(&self) -> Box<dyn Iterator<Item = &Flag> + '_> {
+        Box::new(self.flags.iter())
+    }
+
+    fn exec(&self, name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
+        let mut flags = self.flags.clone();
+        let command_args = flags.parse_relaxed(scope, args);
+
+        if flags.is_present("help") {
+            println!("Usage: {} [COMMAND [ARGS]...]", name);
+            println!("Replace the shell with the specified external command.");
+            println!("Without COMMAND, apply the redirections to the rest of the session.");
+            println!("\nOptions:");
+            print!("{}", flags.help());
+            println!();
+            println!("Examples:");
+            println!("    exec ssh myhost");
+            println!("    exec > session.log");
+            println!("    exec >&-");
+            return Ok(Value::success());
+        }
+
+        // Nothing to do without a command; the evaluator keeps the redirections, if any.
+        let Some(cmd_name) = command_args.first() else {
+            return Ok(Value::success());
+        };
+
+        let cmd = get_command(cmd_name)
+            .ok_or_else(|| format!("Command not found: {}", scope.err_str(cmd_name)))?;
+
+        if !cmd.is_external() {
+            return Err(format!(
+                "{} is not an external command",
+                scope.err_str(cmd_name)
+            ));
+        }
+
+        let mut command = Command::new(cmd.path().as_ref());
+        command.args(&command_args[1..]);
+        copy_exported_vars_to_command_env(&mut command, scope);
+
+        let error = Self::replace_process(&mut command);
+        Err(format!("{}: {}", scope.err_str(cmd_name), error))
+    }
+}
+
+#[ctor::ctor]
+fn register() {
+    register_command(ShellCommand {
+        name: "exec".to_string(),
+        inner: Arc::new(Execute::new()),
+    });
+}
@@ -0,0 +1,141 @@
+use super::{flags::CommandFlags, register_command, Exec, Flag, ShellCommand};
+use crate::{
+    eval::Value,
+    scope::{Ident, Scope, Variable},
+    utils::sync_env_vars,
+};
+use std::sync::Arc;
+
+struct Export {
+    flags: CommandFlags,
+}
+
+impl Export {
+    fn new() -> Self {
+        let mut flags = CommandFlags::with_help();
+        flags.add_flag('n', "remove", "Remove the export property from each NAME");
+
+        Self { flags }
+    }
+
+    fn is_identifier(name: &str) -> bool {
+        let mut chars = name.chars();
+        chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+    }
+
+    /// Strip surrounding double quotes, as in: export "PATH = \"$PATH;C:\\Tools\""
+    fn unquote(value: &str) -> &str {
+        value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .unwrap_or(value)
+    }
+
+    fn list(scope: &Arc<Scope>) -> Result<Value, String> {
+        for (key, var) in scope.visible_vars() {
+            if var.is_exported() && !key.is_special_var() {
+                my_println!("{}={}", key, var.value())?;
+            }
+        }
+        Ok(Value::success())
+    }
+
+    /// Export variable, and set its value if specified. The variable is made global,
+    /// so that it outlives the (command line, or script) scope where it was exported.
+    fn export(scope: &Arc<Scope>, name: &str, value: Option<Value>) -> Result<(), String> {
+        let var = match (scope.lookup(name), value) {
+            (Some(var), Some(_)) if var.is_readonly() => {
+                return Err(format!(
+                    "Cannot assign to read-only variable: {}",
+                    scope.err_str(name)
+                ));
+            }
+            (Some(var), value) => {
+                if let Some(value) = value {
+                    var.assign(value);
+                }
+                // Also export the variable where it is found, it may shadow the global one.
+                var.set_exported(true);
+                var.clone()
+            }
+            (None, Some(value)) => Variable::new(value),
+            (None, None) => return Err(format!("{} is undefined", scope.err_str(name))),
+        };
+        var.set_exported(true);
+        scope.global().vars_mut().insert(Ident::from(name), var);
+
+        Ok(())
+    }
+
+    fn unexport(scope: &Arc<Scope>, name: &str) -> Result<(), String> {
+        let var = scope
+            .lookup(name)
+            .ok_or_else(|| format!("{} is undefined", scope.err_str(name)))?;
+        var.set_exported(false);
+        Ok(())
+    }
+}
+
+impl Exec for Export {
+    fn cli_flags(&self) -> Box<dyn Iterator<Item = &Flag> + '_> {
+        Box::new(self.flags.iter())
+    }
+
+    fn exec(&self, _name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
+        let mut flags = self.flags.clone();
+        let args = flags.parse(scope, args)?;
+
+        if flags.is_present("help") {
+            println!("Usage: export [NAME[=VALUE]]...");
+            println!("Mark variables to be passed in the environment of external commands.");
+            println!("Without arguments, list the exported variables.");
+            println!("\nOptions:");
+            print!("{}", flags.help());
+            println!();
+            println!("Examples:");
+            println!("    export PATH=$HOME/bin:$PATH");
+            println!("    export -n EDITOR");
+            return Ok(Value::success());
+        }
+
+        if args.is_empty() {
+            return Self::list(scope);
+        }
+
+        let remove = flags.is_present("remove");
+
+        for arg in &args {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(Self::unquote(value.trim()))),
+                None => (arg.as_str(), None),
+            };
+            if !Self::is_identifier(name) {
+                return Err(format!("{}: not a valid identifier", scope.err_str(name)));
+            }
+            if remove {
+                Self::unexport(scope, name)?;
+            } else {
+                let value = value
+                    .map(|v| v.parse::<Value>().map_err(|e| e.to_string()))
+                    .transpose()?;
+                Self::export(scope, name, value)?;
+            }
+        }
+
+        // Synchronize environment with global scope
+        sync_env_vars(scope.global());
+
+        Ok(Value::success())
+    }
+}
+
+#[ctor::ctor]
+fn register() {
+    register_command(ShellCommand {
+        name: "export".to_string(),
+        inner: Arc::new(Export::new()),
+    });
+}
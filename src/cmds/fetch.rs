@@ -0,0 +1,497 @@
+use super::{flags::CommandFlags, register_command, Exec, Flag, ShellCommand};
+use crate::{
+    digest::{digest, to_hex, Algorithm},
+    eval::Value,
+    scope::Scope,
+    utils::format_error,
+};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, ErrorKind, Read, Write};
+use std::sync::Arc;
+use std::time::Duration;
+use ureq::{
+    http::{self, header, HeaderName, HeaderValue, Method, StatusCode},
+    tls::TlsConfig,
+    Agent,
+};
+
+/// Where the body of the request comes from.
+enum Data {
+    Bytes(Vec<u8>),
+    File(File),
+    Stdin,
+}
+
+/// Copy the response body to the writer, advancing the progress bar (if any).
+fn copy_body<R: Read, W: Write>(
+    mut reader: R,
+    writer: &mut W,
+    progress: Option<&ProgressBar>,
+) -> io::Result<()> {
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        if Scope::is_interrupted() {
+            return Err(io::Error::from(ErrorKind::Interrupted));
+        }
+        match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => {
+                writer.write_all(&buffer[..n])?;
+                if let Some(pb) = progress {
+                    pb.inc(n as u64);
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    writer.flush()
+}
+
+fn progress_bar(scope: &Scope, name: &str, size: Option<u64>) -> ProgressBar {
+    let colors = scope.use_colors(&io::stdout());
+    let style = match size {
+        Some(_) => ProgressStyle::default_bar()
+            .template(if colors {
+                "{spinner:.green} [{elapsed_precise}] {msg:>30.cyan.bright} [{bar:45.green/}] {bytes}/{total_bytes} ({eta})"
+            } else {
+                "{spinner} [{elapsed_precise}] {msg:>30} [{bar:45}] {bytes}/{total_bytes} ({eta})"
+            })
+            .unwrap()
+            .progress_chars("=> "),
+        None => ProgressStyle::default_spinner()
+            .template(if colors {
+                "{spinner:.green} [{elapsed_precise}] {msg:>30.cyan.bright} {bytes} ({bytes_per_sec})"
+            } else {
+                "{spinner} [{elapsed_precise}] {msg:>30} {bytes} ({bytes_per_sec})"
+            })
+            .unwrap(),
+    };
+    let pb = ProgressBar::with_draw_target(size, ProgressDrawTarget::stdout());
+    pb.set_style(style);
+    pb.set_message(name.to_string());
+    pb
+}
+
+struct Fetch {
+    flags: CommandFlags,
+}
+
+impl Fetch {
+    fn new() -> Self {
+        let mut flags = CommandFlags::with_help();
+        flags.add_value(
+            'X',
+            "method",
+            "METHOD",
+            "Request method (default: GET, or POST when sending data)",
+        );
+        flags.add_value(
+            'H',
+            "header",
+            "HEADER",
+            "Add a request header, in \"Name: value\" form (may be repeated)",
+        );
+        flags.add_value(
+            'd',
+            "data",
+            "DATA",
+            "Send DATA in the request body; @FILE sends the contents of FILE, @- standard input",
+        );
+        flags.add_value(
+            'o',
+            "output",
+            "FILE",
+            "Write the response body to FILE, showing a progress bar",
+        );
+        flags.add_flag(
+            'c',
+            "continue",
+            "Resume a partial download of the output FILE, and keep partial downloads on errors",
+        );
+        flags.add(
+            None,
+            "checksum",
+            Some("ALGO:DIGEST".to_string()),
+            "Verify the downloaded FILE; ALGO is one of md5, sha1, sha256, blake3",
+        );
+        flags.add_flag('i', "include", "Print the response status line and headers");
+        flags.add_flag('k', "insecure", "Do not verify TLS certificates");
+        flags.add_flag('q', "quiet", "Do not show the progress bar");
+        flags.add(
+            None,
+            "max-redirects",
+            Some("N".to_string()),
+            "Follow at most N redirects (0: do not follow); default: 10",
+        );
+        flags.add(
+            None,
+            "timeout",
+            Some("SECONDS".to_string()),
+            "Give up if the request does not complete in SECONDS",
+        );
+        Self { flags }
+    }
+}
+
+impl Exec for Fetch {
+    fn cli_flags(&self) -> Box<dyn Iterator<Item = &Flag> + '_> {
+        Box::new(self.flags.iter())
+    }
+
+    fn exec(&self, name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
+        let mut flags = self.flags.clone();
+        let operands = flags.parse(scope, args)?;
+
+        if flags.is_present("help") {
+            println!("Usage: {} [OPTIONS] URL", name);
+            println!(
+                "Send an HTTP request to URL, and write the response body to standard output."
+            );
+            println!("Redirects are followed. If the server responds with an error (status 400 or");
+            println!("above) the command fails, and $? is set to the status code.");
+            println!("\nOptions:");
+            print!("{}", flags.help());
+            println!("\nExamples:");
+            println!(
+                "    {} -o rust.tar.gz https://example.com/rust.tar.gz",
+                name
+            );
+            println!(
+                "    {} -c -o big.iso --checksum sha256:9f86d08... https://example.com/big.iso",
+                name
+            );
+            println!(
+                "    {} -X PUT -H \"Content-Type: application/json\" -d @item.json https://example.com/api/items/1",
+                name
+            );
+            return Ok(Value::success());
+        }
+        let Some(arg) = operands.first() else {
+            return Err("Missing URL".to_string());
+        };
+        if let Some(extra) = operands.get(1) {
+            return Err(format_error(scope, extra, args, "Unexpected argument"));
+        }
+        let url = if arg.contains("://") {
+            arg.clone()
+        } else {
+            format!("http://{}", arg)
+        };
+
+        let data = match flags.value("data") {
+            None => None,
+            Some("@-") => Some(Data::Stdin),
+            Some(value) => match value.strip_prefix('@') {
+                Some(path) => Some(Data::File(
+                    File::open(path).map_err(|e| format_error(scope, value, args, e))?,
+                )),
+                None => Some(Data::Bytes(value.as_bytes().to_vec())),
+            },
+        };
+        let method = match flags.value("method") {
+            Some(value) => Method::from_bytes(value.to_uppercase().as_bytes())
+                .map_err(|e| format_error(scope, value, args, e))?,
+            None if data.is_some() => Method::POST,
+            None => Method::GET,
+        };
+
+        let max_redirects = match flags.value("max-redirects") {
+            Some(value) => value
+                .parse::<u32>()
+                .map_err(|e| format_error(scope, value, args, e))?,
+            None => 10,
+        };
+        let timeout = match flags.value("timeout") {
+            Some(value) => Some(
+                value
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                    .filter(|d| !d.is_zero())
+                    .ok_or_else(|| {
+                        format_error(scope, value, args, "Expecting a positive number")
+                    })?,
+            ),
+            None => None,
+        };
+
+        let output = flags.value("output");
+        let resume = flags.is_present("continue");
+        let checksum = match flags.value("checksum") {
+            Some(value) => {
+                let (name, hex) = value.split_once(':').unwrap_or(("", ""));
+                let Some(algorithm) = Algorithm::from_name(name) else {
+                    return Err(format_error(
+                        scope,
+                        value,
+                        args,
+                        "Expecting ALGO:DIGEST, with ALGO one of md5, sha1, sha256, blake3",
+                    ));
+                };
+                if hex.is_empty() || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(format_error(scope, value, args, "Invalid hex digest"));
+                }
+                Some((algorithm, hex.to_lowercase()))
+            }
+            None => None,
+        };
+        if output.is_none() && (resume || checksum.is_some()) {
+            return Err("--continue and --checksum require --output FILE".to_string());
+        }
+
+        // Ask for the rest of the file when resuming
+        let offset = match output {
+            Some(path) if resume => fs::metadata(path).map_or(0, |meta| meta.len()),
+            _ => 0,
+        };
+
+        let mut request = http::Request::builder().method(method).uri(&url);
+        if offset > 0 {
+            request = request.header(header::RANGE, format!("bytes={}-", offset));
+        }
+        for header in flags.values("header") {
+            let Some((key, value)) = header.split_once(':') else {
+                return Err(format_error(
+                    scope,
+                    header,
+                    args,
+                    "Expecting \"Name: value\"",
+                ));
+            };
+            let key = HeaderName::try_from(key.trim())
+                .map_err(|e| format_error(scope, header, args, e))?;
+            let value = HeaderValue::try_from(value.trim())
+                .map_err(|e| format_error(scope, header, args, e))?;
+            request = request.header(key, value);
+        }
+
+        let mut config = Agent::config_builder()
+            .http_status_as_error(false)
+            .allow_non_standard_methods(true)
+            .max_redirects(max_redirects)
+            .max_redirects_will_error(max_redirects > 0)
+            .user_agent(format!("shmy/{}", env!("CARGO_PKG_VERSION")))
+            .timeout_global(timeout);
+        if flags.is_present("insecure") {
+            config = config.tls_config(TlsConfig::builder().disable_verification(true).build());
+        }
+        let agent: Agent = config.build().into();
+
+        let url_error = |e: &dyn std::fmt::Display| format_error(scope, arg, args, e);
+        let mut response = match data {
+            None => request.body(()).map(|r| agent.run(r)),
+            Some(Data::Bytes(bytes)) => request.body(bytes).map(|r| agent.run(r)),
+            Some(Data::File(file)) => request.body(file).map(|r| agent.run(r)),
+            Some(Data::Stdin) => request.body(io::stdin()).map(|r| agent.run(r)),
+        }
+        .map_err(|e| url_error(&e))?
+        .map_err(|e| url_error(&e))?;
+
+        let status = response.status();
+        if flags.is_present("include") {
+            my_println!("{:?} {}", response.version(), status)?;
+            for (key, value) in response.headers() {
+                my_println!("{}: {}", key, String::from_utf8_lossy(value.as_bytes()))?;
+            }
+            my_println!()?;
+        }
+        // Nothing left to download if the range starts past the end of the file
+        let complete = offset > 0 && status == StatusCode::RANGE_NOT_SATISFIABLE;
+        let failed = (status.is_client_error() || status.is_server_error()) && !complete;
+
+        // Append to the partial file if the server sent the requested range,
+        // start over if it ignored the Range header and sent the whole file.
+        let append = offset > 0 && status == StatusCode::PARTIAL_CONTENT;
+        if append {
+            let expected = format!("bytes {}-", offset);
+            let content_range = response
+                .headers()
+                .get(header::CONTENT_RANGE)
+                .and_then(|v| v.to_str().ok());
+            if !content_range.is_some_and(|r| r.starts_with(&expected)) {
+                return Err(url_error(&"Unexpected Content-Range in response"));
+            }
+        }
+        let start = if append { offset } else { 0 };
+        let size = response.body().content_length();
+        let reader = response.body_mut().as_reader();
+
+        match output {
+            // Do not save error pages
+            Some(_) if failed || complete => {}
+            Some(path) => {
+                let progress = (!flags.is_present("quiet"))
+                    .then(|| progress_bar(scope, path, size.map(|n| n + start)));
+                if let Some(pb) = &progress {
+                    pb.set_position(start);
+                    pb.reset_eta();
+                }
+                let result = OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .append(append)
+                    .truncate(!append)
+                    .open(path)
+                    .and_then(|file| {
+                        copy_body(reader, &mut BufWriter::new(file), progress.as_ref())
+                    });
+                if let Some(pb) = progress {
+                    pb.finish();
+                }
+                if let Err(e) = result {
+                    // Keep what was downloaded so far, for resuming later
+                    if !resume {
+                        _ = fs::remove_file(path);
+                    }
+                    return Err(format_error(scope, path, args, e));
+                }
+            }
+            None => match copy_body(reader, &mut io::stdout().lock(), None) {
+                Err(e) if e.kind() == ErrorKind::BrokenPipe => {}
+                Err(e) => return Err(url_error(&e)),
+                Ok(_) => {}
+            },
+        }
+
+        if failed {
+            scope.set_exit_status(status.as_u16() as _);
+            return Err(url_error(&status));
+        }
+
+        if let (Some(path), Some((algorithm, expected))) = (output, checksum) {
+            let actual = File::open(path)
+                .and_then(|file| digest(algorithm, file))
+                .map_err(|e| format_error(scope, path, args, e))?;
+            let actual = to_hex(&actual);
+            if actual != expected {
+                // Do not resume from corrupt data
+                _ = fs::remove_file(path);
+                return Err(format_error(
+                    scope,
+                    path,
+                    args,
+                    format!("{} checksum mismatch, got {}", algorithm.name(), actual),
+                ));
+            }
+        }
+        Ok(Value::success())
+    }
+}
+
+#[ctor::ctor]
+fn register() {
+    register_command(ShellCommand {
+        name: "fetch".to_string(),
+        inner: Arc::new(Fetch::new()),
+    });
+
+    register_command(ShellCommand {
+        name: "http".to_string(),
+        inner: Arc::new(Fetch::new()),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    /// Serve the responses, one per connection, and return the address of the server.
+    fn serve(responses: &'static [&'static str]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+                    line.clear();
+                }
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    #[test]
+    fn test_fetch() {
+        let url = serve(&[
+            "HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 4\r\nConnection: close\r\n\r\noops",
+        ]);
+        let scope = Scope::new();
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("out.txt").display().to_string();
+        let fetch = Fetch::new();
+
+        let args = vec![
+            "-q".to_string(),
+            "-o".to_string(),
+            path.clone(),
+            url.clone(),
+        ];
+        fetch.exec("fetch", &args, &scope).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "hello");
+
+        fs::remove_file(&path).unwrap();
+        assert!(fetch.exec("fetch", &args, &scope).is_err());
+        assert!(!Path::new(&path).exists());
+        assert_eq!(scope.lookup_value("?"), Some(Value::Int(404)));
+    }
+
+    #[test]
+    fn test_resume() {
+        let url = serve(&[
+            "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 3-4/5\r\nContent-Length: 2\r\nConnection: close\r\n\r\nlo",
+            "HTTP/1.1 416 Range Not Satisfiable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        ]);
+        let scope = Scope::new();
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("out.txt").display().to_string();
+        fs::write(&path, "hel").unwrap();
+
+        let sha256 = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        let args = |digest: &str| -> Vec<String> {
+            ["-qc", "--checksum", digest, "-o", &path, &url]
+                .iter()
+                .map(|s| s.to_string())
+                .collect()
+        };
+        let fetch = Fetch::new();
+        fetch
+            .exec("fetch", &args(&format!("sha256:{}", sha256)), &scope)
+            .unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "hello");
+
+        // Already complete, but does not match the checksum
+        assert!(fetch.exec("fetch", &args("md5:0123"), &scope).is_err());
+        assert!(!Path::new(&path).exists());
+    }
+
+    #[test]
+    fn test_header_args() {
+        let args = [
+            "-H",
+            "A: 1",
+            "-HB: 2",
+            "--header",
+            "C: 3",
+            "http://localhost",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>();
+        let mut flags = Fetch::new().flags;
+        assert_eq!(
+            flags.parse(&Scope::new(), &args).unwrap(),
+            vec!["http://localhost"]
+        );
+        assert_eq!(flags.values("header"), vec!["A: 1", "B: 2", "C: 3"]);
+    }
+}
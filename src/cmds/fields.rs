@@ -0,0 +1,277 @@
+use super::{register_command, Exec, Flag, ShellCommand};
+use crate::{
+    cmds::flags::CommandFlags, eval::Value, scope::Scope, symlnk::SymLink, utils::format_error,
+};
+use regex::Regex;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+
+#[derive(Debug, PartialEq)]
+enum Part {
+    Text(String),
+    Field(isize), // 0 is the whole line, negative numbers count from the last field
+    LineNumber,
+    FieldCount,
+}
+
+/// Parse the output format: $N is the Nth field ($0 the line, $-1 the last field), $NR is
+/// the line number, $NF the number of fields, and $$ a dollar sign. Braces, as in ${1},
+/// separate the field from the text that follows.
+fn parse_format(format: &str) -> Result<Vec<Part>, String> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut rest = format;
+
+    while let Some(pos) = rest.find('$') {
+        text.push_str(&rest[..pos]);
+        rest = &rest[pos + 1..];
+
+        if let Some(after) = rest.strip_prefix('$') {
+            text.push('$');
+            rest = after;
+            continue;
+        }
+        let (name, after) = match rest.strip_prefix('{') {
+            Some(inner) => {
+                let end = inner
+                    .find('}')
+                    .ok_or_else(|| format!("Missing closing brace: {}", format))?;
+                (&inner[..end], &inner[end + 1..])
+            }
+            None => {
+                let end = rest
+                    .char_indices()
+                    .find(|&(i, c)| !(c.is_ascii_digit() || (i == 0 && c == '-')))
+                    .map_or(rest.len(), |(i, _)| i);
+                if end == 0 && (rest.starts_with("NR") || rest.starts_with("NF")) {
+                    (&rest[..2], &rest[2..])
+                } else {
+                    (&rest[..end], &rest[end..])
+                }
+            }
+        };
+        let part = match name {
+            "NR" => Part::LineNumber,
+            "NF" => Part::FieldCount,
+            _ => {
+                let shown = if name.is_empty() { rest } else { name };
+                let n = name
+                    .parse::<isize>()
+                    .map_err(|_| format!("Invalid field: ${}", shown))?;
+                Part::Field(n)
+            }
+        };
+        if !text.is_empty() {
+            parts.push(Part::Text(std::mem::take(&mut text)));
+        }
+        parts.push(part);
+        rest = after;
+    }
+    text.push_str(rest);
+    if !text.is_empty() {
+        parts.push(Part::Text(text));
+    }
+    Ok(parts)
+}
+
+/// Format one line; fields that do not exist are empty.
+fn format_line(parts: &[Part], line: &str, delimiter: &Regex, line_number: usize) -> String {
+    // Split like cut does, ignoring leading whitespace
+    let trimmed = line.trim_start();
+    let fields: Vec<&str> = if trimmed.is_empty() {
+        Vec::new()
+    } else {
+        delimiter.split(trimmed).collect()
+    };
+
+    let mut result = String::new();
+    for part in parts {
+        match part {
+            Part::Text(text) => result.push_str(text),
+            Part::Field(0) => result.push_str(line),
+            Part::Field(n) => {
+                let index = if *n > 0 {
+                    Some(*n as usize - 1)
+                } else {
+                    fields.len().checked_sub(n.unsigned_abs())
+                };
+                if let Some(field) = index.and_then(|i| fields.get(i)) {
+                    result.push_str(field);
+                }
+            }
+            Part::LineNumber => result.push_str(&line_number.to_string()),
+            Part::FieldCount => result.push_str(&fields.len().to_string()),
+        }
+    }
+    result
+}
+
+struct Fields {
+    flags: CommandFlags,
+}
+
+struct Options {
+    parts: Vec<Part>,
+    delimiter: Regex,
+    filter: Option<Regex>,
+}
+
+impl Fields {
+    fn new() -> Self {
+        let mut flags = CommandFlags::with_help();
+        flags.add_value(
+            'd',
+            "delimiter",
+            "REGEX",
+            "Split lines on REGEX (default: whitespace)",
+        );
+        flags.add_value(
+            'm',
+            "match",
+            "REGEX",
+            "Output only the lines that match REGEX",
+        );
+        Self { flags }
+    }
+
+    /// Process the lines of the input, return the updated line count.
+    fn process<R: BufRead>(reader: R, opts: &Options, mut count: usize) -> Result<usize, String> {
+        for line in reader.lines() {
+            if Scope::is_interrupted() {
+                break;
+            }
+            let line = line.map_err(|e| e.to_string())?;
+            count += 1;
+
+            if opts.filter.as_ref().is_some_and(|re| !re.is_match(&line)) {
+                continue;
+            }
+            my_println!(
+                "{}",
+                format_line(&opts.parts, &line, &opts.delimiter, count)
+            )?;
+        }
+        Ok(count)
+    }
+}
+
+impl Exec for Fields {
+    fn cli_flags(&self) -> Box<dyn Iterator<Item = &Flag> + '_> {
+        Box::new(self.flags.iter())
+    }
+
+    fn exec(&self, name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
+        let mut flags = self.flags.clone();
+        let fields_args = flags.parse(scope, args)?;
+
+        if flags.is_present("help") {
+            println!("Usage: {} [OPTIONS] FORMAT [FILE]...", name);
+            println!(
+                "Split the lines of the FILEs (or stdin) into fields, and output them in FORMAT."
+            );
+            println!("\nIn FORMAT, $N is the Nth field, $0 the whole line, $-1 the last field,");
+            println!("$NR the line number, $NF the number of fields and $$ a dollar sign;");
+            println!(
+                "use ${{N}} when the field is followed by digits. Use raw strings, r\"(...)\","
+            );
+            println!("so that the shell does not expand the fields as variables.");
+            println!("\nOptions:");
+            print!("{}", flags.help());
+            println!("\nExamples:");
+            println!("    fields -d, r\"($3 $1)\" data.csv");
+            println!("    ps | fields r\"($NR: $-1)\"");
+            println!("    fields -d: -m /bin/bash r\"($1 uses $7)\" /etc/passwd");
+            return Ok(Value::success());
+        }
+
+        let Some(format) = fields_args.first() else {
+            return Err("Missing output format".to_string());
+        };
+        let parts = parse_format(format).map_err(|e| format_error(scope, format, args, e))?;
+
+        let delimiter = flags.value("delimiter").unwrap_or(r"\s+");
+        let delimiter =
+            Regex::new(delimiter).map_err(|e| format_error(scope, delimiter, args, e))?;
+
+        let filter = flags
+            .value("match")
+            .map(|v| Regex::new(v).map_err(|e| format_error(scope, v, args, e)))
+            .transpose()?;
+
+        let opts = Options {
+            parts,
+            delimiter,
+            filter,
+        };
+
+        let files = &fields_args[1..];
+        if files.is_empty() {
+            scope.show_eof_hint();
+            Self::process(io::stdin().lock(), &opts, 0)?;
+        } else {
+            // Line numbers continue across files, like NR in awk
+            let mut count = 0;
+            for filename in files {
+                let file = Path::new(filename)
+                    .dereference()
+                    .and_then(|path| File::open(&path))
+                    .map_err(|e| format_error(scope, filename, args, e))?;
+
+                count = Self::process(BufReader::new(file), &opts, count)?;
+            }
+        }
+
+        Ok(Value::success())
+    }
+}
+
+#[ctor::ctor]
+fn register() {
+    register_command(ShellCommand {
+        name: "fields".to_string(),
+        inner: Arc::new(Fields::new()),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!(
+            parse_format("$3, $1 ${2}0 $NR/$NF $-1 $$5").unwrap(),
+            vec![
+                Part::Field(3),
+                Part::Text(", ".to_string()),
+                Part::Field(1),
+                Part::Text(" ".to_string()),
+                Part::Field(2),
+                Part::Text("0 ".to_string()),
+                Part::LineNumber,
+                Part::Text("/".to_string()),
+                Part::FieldCount,
+                Part::Text(" ".to_string()),
+                Part::Field(-1),
+                Part::Text(" $5".to_string()),
+            ]
+        );
+        assert!(parse_format("$x").is_err());
+        assert!(parse_format("${1").is_err());
+    }
+
+    #[test]
+    fn test_format_line() {
+        let spaces = Regex::new(r"\s+").unwrap();
+        let commas = Regex::new(",").unwrap();
+
+        let parts = parse_format("$2 $1 [$5] $-1 $NR/$NF").unwrap();
+        assert_eq!(format_line(&parts, "  a  b c", &spaces, 7), "b a [] c 7/3");
+        assert_eq!(format_line(&parts, "", &spaces, 1), "  []  1/0");
+
+        let parts = parse_format("$3:$0").unwrap();
+        assert_eq!(format_line(&parts, "x,,z", &commas, 1), "z:x,,z");
+    }
+}
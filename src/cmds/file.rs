@@ -0,0 +1,548 @@
+use super::{flags::CommandFlags, register_command, Exec, Flag, ShellCommand};
+use crate::{eval::Value, scope::Scope, symlnk::SymLink};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::Arc;
+
+/// How many bytes to read from the start of files; enough for the magic numbers,
+/// and a sample of text files to tell ASCII from UTF-8 from binary data.
+const SAMPLE_SIZE: usize = 8192;
+
+fn u16_at(data: &[u8], pos: usize, big_endian: bool) -> Option<u16> {
+    let bytes = data.get(pos..pos + 2)?.try_into().ok()?;
+    Some(if big_endian {
+        u16::from_be_bytes(bytes)
+    } else {
+        u16::from_le_bytes(bytes)
+    })
+}
+
+fn u32_at(data: &[u8], pos: usize, big_endian: bool) -> Option<u32> {
+    let bytes = data.get(pos..pos + 4)?.try_into().ok()?;
+    Some(if big_endian {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    })
+}
+
+/// Position-independent executables are shared objects that request a program interpreter
+/// (the dynamic loader) in their program headers.
+fn has_interpreter(data: &[u8], is_64: bool, big_endian: bool) -> bool {
+    let headers = if is_64 {
+        let offset = data.get(32..40).and_then(|b| b.try_into().ok()).map(|b| {
+            if big_endian {
+                u64::from_be_bytes(b)
+            } else {
+                u64::from_le_bytes(b)
+            }
+        });
+        offset.zip(u16_at(data, 54, big_endian).zip(u16_at(data, 56, big_endian)))
+    } else {
+        u32_at(data, 28, big_endian)
+            .map(u64::from)
+            .zip(u16_at(data, 42, big_endian).zip(u16_at(data, 44, big_endian)))
+    };
+    let Some((offset, (size, count))) = headers else {
+        return false;
+    };
+    (0..count as usize).any(|i| {
+        let pos = offset as usize + i * size as usize;
+        u32_at(data, pos, big_endian) == Some(3) // PT_INTERP
+    })
+}
+
+fn describe_elf(data: &[u8]) -> Option<(String, &'static str)> {
+    let bits = match data.get(4)? {
+        1 => "32-bit",
+        2 => "64-bit",
+        _ => return None,
+    };
+    let big_endian = *data.get(5)? == 2;
+    let (kind, mime) = match u16_at(data, 16, big_endian)? {
+        1 => ("relocatable", "application/x-object"),
+        2 => ("executable", "application/x-executable"),
+        3 if has_interpreter(data, bits == "64-bit", big_endian) => {
+            ("pie executable", "application/x-pie-executable")
+        }
+        3 => ("shared object", "application/x-sharedlib"),
+        4 => ("core file", "application/x-coredump"),
+        _ => ("unknown type", "application/octet-stream"),
+    };
+    let machine = match u16_at(data, 18, big_endian)? {
+        0x03 => "Intel 80386",
+        0x08 => "MIPS",
+        0x14 => "PowerPC",
+        0x15 => "64-bit PowerPC",
+        0x28 => "ARM",
+        0x3e => "x86-64",
+        0xb7 => "ARM aarch64",
+        0xf3 => "RISC-V",
+        _ => "unknown machine",
+    };
+    let order = if big_endian { "MSB" } else { "LSB" };
+    Some((
+        format!("ELF {} {} {}, {}", bits, order, kind, machine),
+        mime,
+    ))
+}
+
+fn describe_pe(data: &[u8]) -> (String, &'static str) {
+    let dos = ("MS-DOS executable".to_string(), "application/x-dosexec");
+    let Some(pe) = u32_at(data, 0x3c, false).map(|offset| offset as usize) else {
+        return dos;
+    };
+    if data.get(pe..pe + 4) != Some(b"PE\0\0") {
+        return dos;
+    }
+    let machine = match u16_at(data, pe + 4, false) {
+        Some(0x14c) => "Intel 80386",
+        Some(0x8664) => "x86-64",
+        Some(0xaa64) => "Aarch64",
+        Some(0x1c4) => "ARMv7 Thumb",
+        _ => "unknown machine",
+    };
+    let dll = u16_at(data, pe + 22, false).is_some_and(|c| c & 0x2000 != 0);
+    let format = match u16_at(data, pe + 24, false) {
+        Some(0x20b) => "PE32+",
+        _ => "PE32",
+    };
+    let kind = match u16_at(data, pe + 24 + 68, false) {
+        _ if dll => "(DLL) ",
+        Some(2) => "(GUI) ",
+        Some(3) => "(console) ",
+        _ => "",
+    };
+    (
+        format!("{} executable {}{}, for MS Windows", format, kind, machine),
+        "application/vnd.microsoft.portable-executable",
+    )
+}
+
+/// Describe text in the sample, or return None if it looks like binary data.
+fn describe_text(data: &[u8]) -> Option<(String, &'static str)> {
+    let boms: [(&[u8], &str, &str); 5] = [
+        (
+            b"\xef\xbb\xbf",
+            "UTF-8 Unicode (with BOM) text",
+            "text/plain; charset=utf-8",
+        ),
+        (
+            b"\xff\xfe\0\0",
+            "UTF-32 Unicode (little-endian) text",
+            "text/plain; charset=utf-32le",
+        ),
+        (
+            b"\0\0\xfe\xff",
+            "UTF-32 Unicode (big-endian) text",
+            "text/plain; charset=utf-32be",
+        ),
+        (
+            b"\xff\xfe",
+            "UTF-16 Unicode (little-endian) text",
+            "text/plain; charset=utf-16le",
+        ),
+        (
+            b"\xfe\xff",
+            "UTF-16 Unicode (big-endian) text",
+            "text/plain; charset=utf-16be",
+        ),
+    ];
+    for (bom, desc, mime) in boms {
+        if data.starts_with(bom) {
+            return Some((desc.to_string(), mime));
+        }
+    }
+
+    if data.contains(&0) {
+        return None;
+    }
+    // The sample may end in the middle of a character
+    let valid = match std::str::from_utf8(data) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    };
+    if !valid {
+        return None;
+    }
+    let control = |b: &u8| b.is_ascii_control() && !b"\n\r\t\x0c\x1b\x08".contains(b);
+    if data.iter().any(control) {
+        return None;
+    }
+
+    let ascii = data.is_ascii();
+    let (mut desc, mut mime) = if ascii {
+        ("ASCII text".to_string(), "text/plain; charset=us-ascii")
+    } else {
+        (
+            "UTF-8 Unicode text".to_string(),
+            "text/plain; charset=utf-8",
+        )
+    };
+
+    if let Some(shebang) = data.strip_prefix(b"#!") {
+        let line = String::from_utf8_lossy(shebang.split(|&b| b == b'\n').next().unwrap());
+        let mut words = line.split_whitespace();
+        let mut interpreter = words.next().unwrap_or_default();
+        if interpreter.ends_with("/env") {
+            interpreter = words.next().unwrap_or_default();
+        }
+        let name = interpreter.rsplit('/').next().unwrap_or_default();
+        mime = match name {
+            "sh" | "bash" | "zsh" | "dash" | "ksh" => "text/x-shellscript",
+            _ if name.starts_with("python") => "text/x-script.python",
+            "perl" => "text/x-perl",
+            _ => mime,
+        };
+        desc = format!("{} script, {} executable", line.trim(), desc);
+    }
+    if data.windows(2).any(|w| w == b"\r\n") {
+        desc.push_str(", with CRLF line terminators");
+    }
+    Some((desc, mime))
+}
+
+/// Identify the file type from the first bytes of its content.
+/// Return a description and the MIME type.
+fn detect(data: &[u8]) -> (String, &'static str) {
+    if data.is_empty() {
+        return ("empty".to_string(), "inode/x-empty");
+    }
+    if data.starts_with(b"\x7fELF") {
+        if let Some(elf) = describe_elf(data) {
+            return elf;
+        }
+    }
+    if data.starts_with(b"MZ") {
+        return describe_pe(data);
+    }
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        let size = u32_at(data, 16, true).zip(u32_at(data, 20, true));
+        let desc = match size {
+            Some((w, h)) => format!("PNG image data, {} x {}", w, h),
+            None => "PNG image data".to_string(),
+        };
+        return (desc, "image/png");
+    }
+    if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        let version = String::from_utf8_lossy(&data[3..6]);
+        let desc = match u16_at(data, 6, false).zip(u16_at(data, 8, false)) {
+            Some((w, h)) => format!("GIF image data, version {}, {} x {}", version, w, h),
+            None => format!("GIF image data, version {}", version),
+        };
+        return (desc, "image/gif");
+    }
+    if data.starts_with(b"RIFF") {
+        let formats: [(&[u8], &str, &str); 3] = [
+            (b"WEBP", "Web/P image", "image/webp"),
+            (b"WAVE", "WAVE audio", "audio/x-wav"),
+            (b"AVI ", "AVI", "video/x-msvideo"),
+        ];
+        let format = formats
+            .iter()
+            .find(|(tag, _, _)| data.get(8..12) == Some(tag));
+        if let Some((_, kind, mime)) = format {
+            return (format!("RIFF (little-endian) data, {}", kind), mime);
+        }
+    }
+    if data.starts_with(b"BM") && data.len() >= 26 {
+        let size = u32_at(data, 18, false).zip(u32_at(data, 22, false));
+        if let Some((w, h)) = size {
+            let h = (h as i32).unsigned_abs(); // Negative for top-down bitmaps
+            return (format!("PC bitmap, {} x {}", w, h), "image/bmp");
+        }
+    }
+    if data.starts_with(b"%PDF-") {
+        let version = data[5..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit() || **b == b'.')
+            .map(|&b| b as char)
+            .collect::<String>();
+        return (
+            format!("PDF document, version {}", version),
+            "application/pdf",
+        );
+    }
+    if data.get(257..262) == Some(b"ustar") {
+        return ("POSIX tar archive".to_string(), "application/x-tar");
+    }
+    if data.starts_with(b"\xca\xfe\xba\xbe") {
+        // Java class files have the version here, fat Mach-O binaries the number of architectures
+        return match u16_at(data, 6, true) {
+            Some(v) if v >= 45 => (
+                "compiled Java class data".into(),
+                "application/x-java-applet",
+            ),
+            _ => (
+                "Mach-O universal binary".into(),
+                "application/x-mach-binary",
+            ),
+        };
+    }
+
+    let magic: [(&[u8], &str, &str); 20] = [
+        (b"PK\x03\x04", "Zip archive data", "application/zip"),
+        (b"PK\x05\x06", "Zip archive data (empty)", "application/zip"),
+        (b"\x1f\x8b", "gzip compressed data", "application/gzip"),
+        (b"BZh", "bzip2 compressed data", "application/x-bzip2"),
+        (b"\xfd7zXZ\0", "XZ compressed data", "application/x-xz"),
+        (
+            b"\x28\xb5\x2f\xfd",
+            "Zstandard compressed data",
+            "application/zstd",
+        ),
+        (
+            b"7z\xbc\xaf\x27\x1c",
+            "7-zip archive data",
+            "application/x-7z-compressed",
+        ),
+        (b"Rar!\x1a\x07", "RAR archive data", "application/x-rar"),
+        (b"!<arch>\n", "current ar archive", "application/x-archive"),
+        (b"\xff\xd8\xff", "JPEG image data", "image/jpeg"),
+        (b"II*\0", "TIFF image data, little-endian", "image/tiff"),
+        (b"MM\0*", "TIFF image data, big-endian", "image/tiff"),
+        (
+            b"\0\0\x01\0",
+            "MS Windows icon resource",
+            "image/vnd.microsoft.icon",
+        ),
+        (
+            b"SQLite format 3\0",
+            "SQLite 3.x database",
+            "application/vnd.sqlite3",
+        ),
+        (
+            b"\xfe\xed\xfa\xce",
+            "Mach-O 32-bit executable",
+            "application/x-mach-binary",
+        ),
+        (
+            b"\xce\xfa\xed\xfe",
+            "Mach-O 32-bit executable",
+            "application/x-mach-binary",
+        ),
+        (
+            b"\xfe\xed\xfa\xcf",
+            "Mach-O 64-bit executable",
+            "application/x-mach-binary",
+        ),
+        (
+            b"\xcf\xfa\xed\xfe",
+            "Mach-O 64-bit executable",
+            "application/x-mach-binary",
+        ),
+        (
+            b"\0asm",
+            "WebAssembly (wasm) binary module",
+            "application/wasm",
+        ),
+        (b"OggS", "Ogg data", "audio/ogg"),
+    ];
+    for (prefix, desc, mime) in magic {
+        if data.starts_with(prefix) {
+            return (desc.to_string(), mime);
+        }
+    }
+
+    describe_text(data).unwrap_or_else(|| ("data".to_string(), "application/octet-stream"))
+}
+
+#[cfg(unix)]
+fn special_file_type(file_type: &fs::FileType) -> (&'static str, &'static str) {
+    use std::os::unix::fs::FileTypeExt;
+    if file_type.is_block_device() {
+        ("block special", "inode/blockdevice")
+    } else if file_type.is_char_device() {
+        ("character special", "inode/chardevice")
+    } else if file_type.is_fifo() {
+        ("fifo (named pipe)", "inode/fifo")
+    } else if file_type.is_socket() {
+        ("socket", "inode/socket")
+    } else {
+        ("special file", "application/octet-stream")
+    }
+}
+
+#[cfg(not(unix))]
+fn special_file_type(_: &fs::FileType) -> (&'static str, &'static str) {
+    ("special file", "application/octet-stream")
+}
+
+/// Read the start of the file.
+fn read_sample(path: &Path) -> io::Result<Vec<u8>> {
+    let mut data = Vec::with_capacity(SAMPLE_SIZE);
+    File::open(path)?
+        .take(SAMPLE_SIZE as u64)
+        .read_to_end(&mut data)?;
+    Ok(data)
+}
+
+struct FileType {
+    flags: CommandFlags,
+}
+
+impl FileType {
+    fn new() -> Self {
+        let mut flags = CommandFlags::with_help();
+        flags.add_flag('b', "brief", "Do not prepend file names to the output");
+        flags.add_flag('i', "mime", "Output MIME types instead of descriptions");
+        flags.add_flag('L', "dereference", "Follow symbolic links");
+        Self { flags }
+    }
+
+    /// Return the description and MIME type of the file.
+    fn identify(path: &Path, follow_links: bool) -> io::Result<(String, &'static str)> {
+        let meta = fs::symlink_metadata(path)?;
+        if !follow_links && (meta.is_symlink() || path.is_wsl_link().unwrap_or(false)) {
+            let target = fs::read_link(path).or_else(|_| path.dereference().map(|p| p.into()))?;
+            return Ok((
+                format!("symbolic link to {}", target.display()),
+                "inode/symlink",
+            ));
+        }
+
+        let path = path.dereference()?;
+        let meta = fs::metadata(&path)?;
+        if meta.is_dir() {
+            Ok(("directory".to_string(), "inode/directory"))
+        } else if meta.is_file() {
+            Ok(detect(&read_sample(&path)?))
+        } else {
+            let (desc, mime) = special_file_type(&meta.file_type());
+            Ok((desc.to_string(), mime))
+        }
+    }
+}
+
+impl Exec for FileType {
+    fn cli_flags(&self) -> Box<dyn Iterator<Item = &Flag> + '_> {
+        Box::new(self.flags.iter())
+    }
+
+    fn exec(&self, name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
+        let mut flags = self.flags.clone();
+        let files = flags.parse(scope, args)?;
+
+        if flags.is_present("help") {
+            println!("Usage: {} [OPTIONS] FILE...", name);
+            println!(
+                "Determine the types of FILEs from their content (magic numbers, text encoding)."
+            );
+            println!("\nOptions:");
+            print!("{}", flags.help());
+            println!("\nExamples:");
+            println!("    file *");
+            println!("    file --mime -b setup.exe");
+            return Ok(Value::success());
+        }
+        if files.is_empty() {
+            return Err("Missing file operand".to_string());
+        }
+
+        let brief = flags.is_present("brief");
+        let mime = flags.is_present("mime");
+        let follow = flags.is_present("dereference");
+        let width = files.iter().map(|f| f.chars().count()).max().unwrap_or(0) + 1;
+
+        for file in &files {
+            if Scope::is_interrupted() {
+                break;
+            }
+            let result = match Self::identify(Path::new(file), follow) {
+                Ok((desc, mime_type)) => {
+                    if mime {
+                        mime_type.to_string()
+                    } else {
+                        desc
+                    }
+                }
+                Err(e) => {
+                    my_warning!(scope, "{}: {}", scope.err_str(file), e);
+                    continue;
+                }
+            };
+            if brief {
+                my_println!("{}", result)?;
+            } else {
+                my_println!("{:<width$} {}", format!("{}:", file), result, width = width)?;
+            }
+        }
+
+        Ok(Value::success())
+    }
+}
+
+#[ctor::ctor]
+fn register() {
+    register_command(ShellCommand {
+        name: "file".to_string(),
+        inner: Arc::new(FileType::new()),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        let mut elf = b"\x7fELF\x02\x01\x01".to_vec();
+        elf.resize(16, 0);
+        elf.extend([3, 0, 0x3e, 0]);
+        assert_eq!(
+            detect(&elf),
+            (
+                "ELF 64-bit LSB shared object, x86-64".to_string(),
+                "application/x-sharedlib"
+            )
+        );
+
+        let mut pe = b"MZ".to_vec();
+        pe.resize(0x3c, 0);
+        pe.extend(64u32.to_le_bytes());
+        pe.resize(64, 0);
+        pe.extend(b"PE\0\0");
+        pe.extend(0x8664u16.to_le_bytes());
+        pe.resize(64 + 24, 0);
+        pe.extend(0x20bu16.to_le_bytes());
+        pe.resize(64 + 24 + 68, 0);
+        pe.extend(3u16.to_le_bytes());
+        assert_eq!(
+            detect(&pe).0,
+            "PE32+ executable (console) x86-64, for MS Windows"
+        );
+
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        png.extend(640u32.to_be_bytes());
+        png.extend(480u32.to_be_bytes());
+        assert_eq!(detect(&png).0, "PNG image data, 640 x 480");
+        assert_eq!(detect(b"PK\x03\x04\x14\0").1, "application/zip");
+        assert_eq!(detect(b"\x1f\x8b\x08\0").0, "gzip compressed data");
+        assert_eq!(detect(b"\x00\x01\x02\x03").0, "data");
+    }
+
+    #[test]
+    fn test_detect_text() {
+        assert_eq!(
+            detect(b"hello\r\n"),
+            (
+                "ASCII text, with CRLF line terminators".to_string(),
+                "text/plain; charset=us-ascii"
+            )
+        );
+        assert_eq!(detect("café\n".as_bytes()).0, "UTF-8 Unicode text");
+        // Truncated in the middle of a multibyte character
+        assert_eq!(detect(&"café".as_bytes()[..4]).0, "UTF-8 Unicode text");
+        assert_eq!(detect(b"\xef\xbb\xbfhi").0, "UTF-8 Unicode (with BOM) text");
+        assert_eq!(detect(b"\xff\xfeh\0i\0").1, "text/plain; charset=utf-16le");
+        assert_eq!(
+            detect(b"#!/usr/bin/env python3\nprint(1)\n"),
+            (
+                "/usr/bin/env python3 script, ASCII text executable".to_string(),
+                "text/x-script.python"
+            )
+        );
+        assert_eq!(detect(b"\xc3\x28 invalid").0, "data");
+    }
+}
@@ -1,12 +1,164 @@
-use super::{flags::CommandFlags, register_command, Exec, Flag, ShellCommand};
-use crate::{eval::Value, scope::Scope, symlnk::SymLink, utils::format_error};
+use super::walk::{Key, WorkQueue};
+use super::{flags::CommandFlags, get_command, register_command, Exec, Flag, ShellCommand};
+use crate::{
+    eval::{Interp, Value},
+    prompt::{confirm, Answer},
+    scope::Scope,
+    symlnk::SymLink,
+    utils::{format_error, parse_size},
+};
+use chrono::{DateTime, Local};
+use glob::{MatchOptions, Pattern};
 use regex::Regex;
 use std::borrow::Cow;
 use std::collections::HashSet;
-use std::ffi::OsStr;
+use std::ffi::{OsStr, OsString};
 use std::fs;
-use std::path::Path;
-use std::sync::Arc;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::SystemTime;
+
+/// Numeric test: +N is more than N, -N is less than N, and N is exactly N.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Compare {
+    Less(u64),
+    Equal(u64),
+    Greater(u64),
+}
+
+impl Compare {
+    fn parse<F: Fn(&str) -> Result<u64, String>>(s: &str, parse: F) -> Result<Self, String> {
+        if let Some(n) = s.strip_prefix('+') {
+            Ok(Compare::Greater(parse(n)?))
+        } else if let Some(n) = s.strip_prefix('-') {
+            Ok(Compare::Less(parse(n)?))
+        } else {
+            Ok(Compare::Equal(parse(s)?))
+        }
+    }
+
+    fn matches(&self, value: u64) -> bool {
+        match *self {
+            Compare::Less(n) => value < n,
+            Compare::Equal(n) => value == n,
+            Compare::Greater(n) => value > n,
+        }
+    }
+}
+
+/// Tests that entries must pass, in addition to matching the name pattern.
+#[derive(Debug, Default)]
+struct Filter {
+    names: Vec<(Pattern, MatchOptions)>, // --name and --iname globs
+    file_type: Option<char>,
+    size: Option<Compare>,
+    mtime: Option<Compare>, // Days since last modified
+    newer: Option<SystemTime>,
+}
+
+impl Filter {
+    fn new(flags: &CommandFlags, scope: &Arc<Scope>, args: &[String]) -> Result<Self, String> {
+        let mut filter = Filter::default();
+
+        for (flag, case_sensitive) in [("name", true), ("iname", false)] {
+            if let Some(glob) = flags.value(flag) {
+                let pattern = Pattern::new(glob).map_err(|e| format_error(scope, glob, args, e))?;
+                let options = MatchOptions {
+                    case_sensitive,
+                    ..MatchOptions::new()
+                };
+                filter.names.push((pattern, options));
+            }
+        }
+
+        if let Some(t) = flags.value("type") {
+            match t {
+                "f" | "d" | "l" => filter.file_type = t.chars().next(),
+                _ => return Err(format_error(scope, t, args, "Expecting f, d or l")),
+            }
+        }
+        if let Some(size) = flags.value("size") {
+            filter.size = Some(
+                Compare::parse(size, parse_size).map_err(|e| format_error(scope, size, args, e))?,
+            );
+        }
+        if let Some(days) = flags.value("mtime") {
+            filter.mtime = Some(
+                Compare::parse(days, |n| n.parse::<u64>().map_err(|e| e.to_string()))
+                    .map_err(|e| format_error(scope, days, args, e))?,
+            );
+        }
+        if let Some(file) = flags.value("newer") {
+            filter.newer = Some(
+                fs::metadata(file)
+                    .and_then(|m| m.modified())
+                    .map_err(|e| format_error(scope, file, args, e))?,
+            );
+        }
+        Ok(filter)
+    }
+
+    fn matches_name(&self, name: &str) -> bool {
+        self.names
+            .iter()
+            .all(|(pattern, options)| pattern.matches_with(name, *options))
+    }
+
+    fn is_empty(&self) -> bool {
+        self.file_type.is_none()
+            && self.size.is_none()
+            && self.mtime.is_none()
+            && self.newer.is_none()
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        let Ok(metadata) = fs::symlink_metadata(path) else {
+            return false;
+        };
+        if let Some(file_type) = self.file_type {
+            let actual = if metadata.is_symlink() {
+                'l'
+            } else if metadata.is_dir() {
+                'd'
+            } else {
+                'f'
+            };
+            if actual != file_type {
+                return false;
+            }
+        }
+        // Test the size and time of the link target, since links are followed.
+        let metadata = if metadata.is_symlink() {
+            fs::metadata(path).unwrap_or(metadata)
+        } else {
+            metadata
+        };
+        if let Some(size) = &self.size {
+            if !size.matches(metadata.len()) {
+                return false;
+            }
+        }
+        let Ok(modified) = metadata.modified() else {
+            return self.mtime.is_none() && self.newer.is_none();
+        };
+        if let Some(mtime) = &self.mtime {
+            let age = SystemTime::now()
+                .duration_since(modified)
+                .unwrap_or_default()
+                .as_secs();
+            if !mtime.matches(age / 86400) {
+                return false;
+            }
+        }
+        self.newer.is_none_or(|newer| modified > newer)
+    }
+}
 
 struct Find {
     flags: CommandFlags,
@@ -14,61 +166,544 @@ struct Find {
 
 impl Find {
     fn new() -> Self {
-        let flags = CommandFlags::with_help();
+        let mut flags = CommandFlags::with_help();
+        flags.add(
+            None,
+            "name",
+            Some("GLOB".to_string()),
+            "Match names against GLOB, e.g. \"*.rs\" (PATTERN may be omitted, see below)",
+        );
+        flags.add(
+            None,
+            "iname",
+            Some("GLOB".to_string()),
+            "Like --name, but case insensitive",
+        );
+        flags.add(
+            None,
+            "type",
+            Some("TYPE".to_string()),
+            "Match only files (f), directories (d) or symbolic links (l)",
+        );
+        flags.add(
+            None,
+            "size",
+            Some("[+-]SIZE".to_string()),
+            "Match files larger (+) or smaller (-) than SIZE, e.g. +10M",
+        );
+        flags.add(
+            None,
+            "mtime",
+            Some("[+-]DAYS".to_string()),
+            "Match entries modified more (+) or less (-) than DAYS ago",
+        );
+        flags.add(
+            None,
+            "newer",
+            Some("FILE".to_string()),
+            "Match entries modified more recently than FILE",
+        );
+        flags.add(
+            None,
+            "maxdepth",
+            Some("N".to_string()),
+            "Descend at most N levels below the starting directories",
+        );
+        flags.add(
+            None,
+            "mindepth",
+            Some("N".to_string()),
+            "Match only entries at least N levels below the starting directories",
+        );
+        flags.add(
+            None,
+            "delete",
+            None,
+            "Delete the matching files, and directories with all of their contents",
+        );
+        flags.add_flag(
+            '0',
+            "null",
+            "Terminate the paths with NUL instead of newline, for xargs --null",
+        );
+        flags.add(
+            None,
+            "printf",
+            Some("FORMAT".to_string()),
+            "Print FORMAT for each match, instead of the path",
+        );
+        flags.add(
+            None,
+            "unordered",
+            None,
+            "Output the matches as soon as found, rather than in directory order",
+        );
+        flags.add(
+            None,
+            "threads",
+            Some("N".to_string()),
+            "Number of threads searching directories (default: number of CPUs)",
+        );
+        flags.add_flag('i', "interactive", "Prompt before each deletion");
         Self { flags }
     }
+}
 
-    fn search(
-        &self,
+/// The --exec action: run a command for each match, or for batches of matches.
+struct Command {
+    args: Vec<String>, // Command name and arguments, with {} placeholders
+    batch: bool,       // Terminated by {} + rather than ;
+    pending: Vec<String>,
+    count: usize,
+    failed: usize,
+}
+
+impl Command {
+    /// Extract --exec CMD [ARGS...] ; (or {} +) from the command line, since the
+    /// arguments of the command may look like flags of find. Return the other arguments.
+    fn parse(
         scope: &Arc<Scope>,
-        file_name: &OsStr,
-        path: &Path,
-        regex: &Regex,
-        visited: &mut HashSet<String>,
-    ) -> Result<(), String> {
+        args: &[String],
+    ) -> Result<(Vec<String>, Option<Command>), String> {
+        let Some(start) = args.iter().position(|a| a == "--exec") else {
+            return Ok((args.to_vec(), None));
+        };
+        let end = (start + 1..args.len())
+            .find(|&i| args[i] == ";" || (args[i] == "+" && args[i - 1] == "{}"))
+            .ok_or_else(|| {
+                scope.set_err_arg(start);
+                "--exec requires a terminating \";\" or \"{} +\"".to_string()
+            })?;
+        if end == start + 1 {
+            scope.set_err_arg(start);
+            return Err("--exec requires a command".to_string());
+        }
+
+        let command = Command {
+            args: args[start + 1..end].to_vec(),
+            batch: args[end] == "+",
+            pending: Vec::new(),
+            count: 0,
+            failed: 0,
+        };
+        let rest = args[..start].iter().chain(&args[end + 1..]).cloned();
+
+        Ok((rest.collect(), Some(command)))
+    }
+
+    fn add(&mut self, scope: &Arc<Scope>, path: &Path) {
+        let path = path.to_string_lossy().to_string();
+        if self.batch {
+            self.pending.push(path);
+            if self.pending.len() >= BATCH_SIZE {
+                self.flush(scope);
+            }
+        } else {
+            let args = self.args.iter().map(|a| a.replace("{}", &path)).collect();
+            self.run(scope, args);
+        }
+    }
+
+    /// Run the command on the pending batch of matches, if any.
+    fn flush(&mut self, scope: &Arc<Scope>) {
+        if self.pending.is_empty() {
+            return;
+        }
+        let pending = std::mem::take(&mut self.pending);
+        let mut args = self.args[..self.args.len() - 1].to_vec(); // Drop the trailing {}
+        args.extend(pending);
+        self.run(scope, args);
+    }
+
+    /// Evaluate the command line through the interpreter, so that aliases resolve like
+    /// at the prompt. The arguments are wrapped in raw strings, as for aliases.
+    fn run(&mut self, scope: &Arc<Scope>, args: Vec<String>) {
         if Scope::is_interrupted() {
-            return Ok(());
+            return;
         }
+        self.count += 1;
 
-        let search_path = path.dereference().unwrap_or(Cow::Owned(path.into()));
+        let name = &args[0];
+        let expr = std::iter::once(name.clone())
+            .chain(args[1..].iter().map(|arg| format!("r\"({})\"", arg)))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let mut interp = Interp::new(Arc::clone(scope));
+        if let Err(e) = interp.eval_status(&expr, None) {
+            my_warning!(scope, "{}: {}", scope.err_str(name), e);
+            self.failed += 1;
+        }
+    }
+}
 
-        if !visited.insert(search_path.to_string_lossy().to_string()) {
-            return Ok(()); // Already seen
+/// The maximum number of matches passed to one invocation in batch mode.
+const BATCH_SIZE: usize = 1000;
+
+/// Directives of the --printf format.
+#[derive(Debug, PartialEq)]
+enum Part {
+    Text(String),
+    Path,   // %p
+    Name,   // %f
+    Parent, // %h
+    Size,   // %s
+    Time,   // %t
+    Depth,  // %d
+    Type,   // %y
+}
+
+fn parse_format(format: &str) -> Result<Vec<Part>, String> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut chars = format.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '%' => {
+                let part = match chars.next() {
+                    Some('%') => {
+                        text.push('%');
+                        continue;
+                    }
+                    Some('p') => Part::Path,
+                    Some('f') => Part::Name,
+                    Some('h') => Part::Parent,
+                    Some('s') => Part::Size,
+                    Some('t') => Part::Time,
+                    Some('d') => Part::Depth,
+                    Some('y') => Part::Type,
+                    Some(c) => return Err(format!("Unknown format directive: %{}", c)),
+                    None => return Err("Missing format directive after %".to_string()),
+                };
+                if !text.is_empty() {
+                    parts.push(Part::Text(std::mem::take(&mut text)));
+                }
+                parts.push(part);
+            }
+            '\\' => match chars.next() {
+                Some('n') => text.push('\n'),
+                Some('t') => text.push('\t'),
+                Some('0') => text.push('\0'),
+                Some(c) => {
+                    text.push('\\');
+                    text.push(c);
+                }
+                None => text.push('\\'),
+            },
+            _ => text.push(c),
         }
+    }
+    if !text.is_empty() {
+        parts.push(Part::Text(text));
+    }
+    Ok(parts)
+}
+
+fn format_entry(parts: &[Part], path: &Path, depth: usize) -> String {
+    let link_metadata = fs::symlink_metadata(path).ok();
+    let metadata = fs::metadata(path).ok().or_else(|| link_metadata.clone());
+
+    parts
+        .iter()
+        .map(|part| match part {
+            Part::Text(text) => text.clone(),
+            Part::Path => path.display().to_string(),
+            Part::Name => path.file_name().map_or(path.display().to_string(), |name| {
+                name.to_string_lossy().to_string()
+            }),
+            Part::Parent => path
+                .parent()
+                .map_or(".".to_string(), |dir| dir.display().to_string()),
+            Part::Size => metadata.as_ref().map_or(0, |m| m.len()).to_string(),
+            Part::Time => metadata
+                .as_ref()
+                .and_then(|m| m.modified().ok())
+                .map(|t| {
+                    DateTime::<Local>::from(t)
+                        .format("%Y-%m-%d %H:%M:%S")
+                        .to_string()
+                })
+                .unwrap_or_default(),
+            Part::Depth => depth.to_string(),
+            Part::Type => match &link_metadata {
+                Some(m) if m.is_symlink() => "l",
+                Some(m) if m.is_dir() => "d",
+                _ => "f",
+            }
+            .to_string(),
+        })
+        .collect()
+}
+
+/// Split the trailing regex off the directories to search. The regex is optional when
+/// matching names with globs, as long as there is no ambiguity: find --name "*.rs" [DIR]
+fn split_pattern<'a>(
+    filter: &Filter,
+    args: &'a [String],
+) -> Result<(Option<Regex>, &'a [String]), String> {
+    let glob_only = !filter.names.is_empty()
+        && match args {
+            [] => true,
+            [dir] => Path::new(dir).is_dir(),
+            _ => false,
+        };
+    if glob_only {
+        return Ok((None, args));
+    }
+    let Some((pattern, dirs)) = args.split_last() else {
+        return Err("Missing search pattern".to_string());
+    };
+    let regex = Regex::new(pattern).map_err(|e| format!("Invalid regex: {}", e))?;
+    Ok((Some(regex), dirs))
+}
+
+/// What the walker threads report to the main thread.
+enum Found {
+    Match(PathBuf, usize), // Path and depth
+    Warning(PathBuf, String),
+}
+
+/// Multi-threaded directory walker, like the one used by grep.
+struct Walker<'a> {
+    regex: Option<&'a Regex>,
+    filter: &'a Filter,
+    min_depth: usize,
+    max_depth: Option<usize>,
+    prune: bool, // Do not descend into matching directories (which get deleted)
+    work: WorkQueue<(Key, OsString, PathBuf)>,
+    visited: Mutex<HashSet<PathBuf>>,
+}
+
+impl<'a> Walker<'a> {
+    fn new(regex: Option<&'a Regex>, filter: &'a Filter, threads: usize) -> Self {
+        Self {
+            regex,
+            filter,
+            min_depth: 0,
+            max_depth: None,
+            prune: false,
+            work: WorkQueue::new(threads),
+            visited: Mutex::new(HashSet::new()),
+        }
+    }
+
+    fn visit(
+        &self,
+        worker: usize,
+        key: Key,
+        file_name: &OsStr,
+        path: &Path,
+        sender: &Sender<(Key, Found)>,
+    ) {
+        let depth = key.len() - 1;
+        let name = file_name.to_string_lossy();
 
         // Check if the current directory or file matches the pattern
-        if regex.is_match(&file_name.to_string_lossy()) {
-            println!("{}", path.display());
-        }
-
-        if search_path.is_dir() {
-            match fs::read_dir(search_path) {
-                Ok(entries) => {
-                    for entry in entries {
-                        match entry {
-                            Ok(entry) => {
-                                self.search(
-                                    scope,
-                                    &entry.file_name(),
-                                    &entry.path(),
-                                    regex,
-                                    visited,
-                                )?;
-                            }
-                            Err(e) => {
-                                my_warning!(scope, "{}: {}", scope.err_path(path), e);
-                            }
+        if depth >= self.min_depth
+            && self.regex.is_none_or(|regex| regex.is_match(&name))
+            && self.filter.matches_name(&name)
+            && self.filter.matches(path)
+        {
+            _ = sender.send((key.clone(), Found::Match(path.to_path_buf(), depth)));
+            if self.prune {
+                return;
+            }
+        }
+
+        if self.max_depth.is_some_and(|max| depth >= max) {
+            return;
+        }
+
+        // Links are matched like any other entry, but each directory is searched only once.
+        let search_path = path.dereference().unwrap_or(Cow::Owned(path.into()));
+        if !search_path.is_dir()
+            || !self
+                .visited
+                .lock()
+                .unwrap()
+                .insert(search_path.to_path_buf())
+        {
+            return;
+        }
+
+        match fs::read_dir(&search_path) {
+            Ok(dir) => {
+                let mut entries = Vec::new();
+                for entry in dir {
+                    match entry {
+                        Ok(entry) => entries.push((entry.file_name(), entry.path())),
+                        Err(e) => {
+                            let warning = Found::Warning(path.to_path_buf(), e.to_string());
+                            _ = sender.send((key.clone(), warning));
                         }
                     }
                 }
-                Err(e) => {
-                    my_warning!(scope, "{}: {}", scope.err_path(path), e);
+                entries.sort();
+
+                for (i, (name, entry)) in entries.into_iter().enumerate() {
+                    let mut entry_key = key.clone();
+                    entry_key.push(i);
+                    self.work.push(worker, (entry_key, name, entry));
+                }
+            }
+            Err(e) => {
+                _ = sender.send((key, Found::Warning(path.to_path_buf(), e.to_string())));
+            }
+        }
+    }
+
+    /// Walk the directories on multiple threads, and pass what is found to the callback
+    /// on the calling thread: as soon as found, or in sequential order when done.
+    fn walk<F>(&self, dirs: Vec<(OsString, PathBuf)>, ordered: bool, mut callback: F)
+    where
+        F: FnMut(Found) -> bool,
+    {
+        let count = self.work.threads();
+        for (i, (name, path)) in dirs.into_iter().enumerate() {
+            self.work.push(i % count, (vec![i], name, path));
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let mut results = Vec::new();
+
+        thread::scope(|s| {
+            for worker in 0..count {
+                let sender = sender.clone();
+                s.spawn(move || {
+                    self.work.run(worker, |(key, name, path)| {
+                        self.visit(worker, key, &name, &path, &sender)
+                    })
+                });
+            }
+            drop(sender);
+
+            for (key, found) in receiver {
+                if ordered {
+                    results.push((key, found));
+                } else if !callback(found) {
+                    self.work.stop();
+                }
+            }
+        });
+
+        results.sort_by(|a, b| a.0.cmp(&b.0));
+        for (_, found) in results {
+            if !callback(found) {
+                break;
+            }
+        }
+    }
+}
+
+/// Carry out the actions on the matches, on the main thread.
+struct Search<'a> {
+    scope: &'a Arc<Scope>,
+    format: Option<Vec<Part>>, // --printf
+    null: bool,                // Terminate paths with NUL rather than newline
+    command: Option<Command>,
+    delete: bool,
+    interactive: bool, // Confirm deletions
+    deleted: usize,
+    failed: usize, // Failed deletions
+    quit: bool,
+}
+
+impl Search<'_> {
+    /// Remove the file, link, or directory with all of its contents.
+    fn delete(&mut self, path: &Path) -> Result<(), String> {
+        let Ok(metadata) = fs::symlink_metadata(path) else {
+            return Ok(());
+        };
+        let is_dir = metadata.is_dir();
+
+        if self.interactive {
+            let prompt = if is_dir {
+                format!("Remove {} and all of its contents", path.display())
+            } else {
+                format!("Remove {}", path.display())
+            };
+            match confirm(prompt, self.scope, true).map_err(|e| e.to_string())? {
+                Answer::Yes => {}
+                Answer::All => self.interactive = false,
+                Answer::No => return Ok(()),
+                Answer::Quit => {
+                    self.quit = true;
+                    return Ok(());
                 }
             }
         }
 
+        let result = if is_dir {
+            fs::remove_dir_all(path)
+        } else if metadata.is_symlink() {
+            #[cfg(windows)]
+            {
+                crate::utils::win::remove_link(path)
+            }
+            #[cfg(not(windows))]
+            {
+                fs::remove_file(path)
+            }
+        } else {
+            fs::remove_file(path)
+        };
+
+        match result {
+            Ok(_) => self.deleted += 1,
+            Err(e) => {
+                my_warning!(self.scope, "{}: {}", self.scope.err_path(path), e);
+                self.failed += 1;
+            }
+        }
         Ok(())
     }
+
+    fn print(&self, path: &Path, depth: usize) -> Result<(), String> {
+        match &self.format {
+            Some(parts) => my_print!("{}", format_entry(parts, path, depth)),
+            None if self.null => my_print!("{}\0", path.display()),
+            None => my_println!("{}", path.display()),
+        }
+    }
+
+    /// Handle a match or a warning from the walker. Return Ok(false) to stop searching.
+    fn found(&mut self, found: Found) -> Result<bool, String> {
+        match found {
+            Found::Match(path, depth) => {
+                if self.delete {
+                    self.delete(&path)?;
+                } else {
+                    match self.command.as_mut() {
+                        Some(command) => command.add(self.scope, &path),
+                        None => self.print(&path, depth)?,
+                    }
+                }
+            }
+            Found::Warning(path, e) => {
+                my_warning!(self.scope, "{}: {}", self.scope.err_path(&path), e);
+            }
+        }
+        Ok(!self.quit)
+    }
+
+    /// Search the directories, stop at the first error.
+    fn search(
+        &mut self,
+        walker: &Walker,
+        dirs: Vec<(OsString, PathBuf)>,
+        ordered: bool,
+    ) -> Result<(), String> {
+        let mut result = Ok(());
+        walker.walk(dirs, ordered, |found| match self.found(found) {
+            Ok(more) => more,
+            Err(e) => {
+                result = Err(e);
+                false
+            }
+        });
+        result
+    }
 }
 
 impl Exec for Find {
@@ -77,38 +712,134 @@ impl Exec for Find {
     }
 
     fn exec(&self, _name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
+        let (find_args, command) = Command::parse(scope, args)?;
         let mut flags = self.flags.clone();
-        let search_args = flags.parse(scope, args)?;
+        let search_args = flags.parse(scope, &find_args)?;
 
         if flags.is_present("help") {
-            println!("Usage: find [OPTIONS] [DIRS...] PATTERN");
-            println!("Recursively search and print paths matching PATTERN.");
+            println!("Usage: find [OPTIONS] [DIRS...] PATTERN [--exec COMMAND [ARGS...] ;]");
+            println!("Recursively search and print paths with names matching the PATTERN regex.");
             println!("\nOptions:");
             print!("{}", flags.help());
+            println!("    --exec COMMAND ;       Run COMMAND for each match, replacing {{}} in the arguments");
+            println!("    --exec COMMAND {{}} +    Run COMMAND on batches of matches");
+            println!("\nWith --name or --iname, PATTERN may be omitted when no DIRS are given, or");
+            println!("when the only other argument is an existing directory.");
+            println!("\nFormat directives:");
+            println!("    %p path, %f file name, %h parent directory, %s size in bytes,");
+            println!("    %t modification time, %d depth, %y type (f, d, l), %% percent sign");
+            println!("\nExamples:");
+            println!("    find src --name \"*.rs\"");
+            println!("    find src --type f --size +10K \"\\.rs$\"");
+            println!("    find --type d --mtime -7 --maxdepth 2 .");
+            println!("    find --mtime r\"(+30)\" .    # raw string, +30 would evaluate to 30");
+            println!("    find --type f \"\\.log$\" --exec gzip {{}} \";\"");
+            println!("    find --type f \"\\.rs$\" --exec wc -l {{}} +");
+            println!("    find --type d -i --delete \"^target$\"");
+            println!("    find --name \"*.tmp\" --null | xargs --null rm");
+            println!("    find --type f --printf \"%s\\t%t\\t%p\\n\" .");
             return Ok(Value::success());
         }
 
-        if search_args.is_empty() {
-            return Err("Missing search pattern".to_string());
-        }
+        let filter = Filter::new(&flags, scope, args)?;
 
-        let pattern = search_args.last().unwrap(); // Last argument is the search pattern
-        let regex = Regex::new(pattern).map_err(|e| format!("Invalid regex: {}", e))?;
+        let (regex, dirs) = split_pattern(&filter, &search_args)?;
 
-        let dirs = if search_args.len() > 1 {
-            &search_args[..search_args.len() - 1] // All except the last
-        } else {
+        if let Some(command) = &command {
+            if get_command(&command.args[0]).is_none() {
+                return Err(format!(
+                    "Command not found: {}",
+                    scope.err_str(&command.args[0])
+                ));
+            }
+        }
+
+        let dirs = if dirs.is_empty() {
             &vec![String::from(".")] // Default to current directory
+        } else {
+            dirs
+        };
+
+        let delete = flags.is_present("delete");
+        if delete && command.is_some() {
+            return Err("--delete and --exec cannot be used together".to_string());
+        }
+
+        let parse_depth = |flag: &str| {
+            flags
+                .value(flag)
+                .map(|v| {
+                    v.parse::<usize>()
+                        .map_err(|e| format_error(scope, v, args, e))
+                })
+                .transpose()
+        };
+
+        let threads = match flags.value("threads") {
+            Some(v) => match v.parse::<usize>() {
+                Ok(n) if n > 0 => n,
+                Ok(_) => return Err(format_error(scope, v, args, "expecting at least 1")),
+                Err(e) => return Err(format_error(scope, v, args, e)),
+            },
+            None => thread::available_parallelism().map_or(1, |n| n.get()),
         };
 
-        let mut visited = HashSet::new();
+        let mut walker = Walker::new(regex.as_ref(), &filter, threads);
+        walker.min_depth = parse_depth("mindepth")?.unwrap_or(0);
+        walker.max_depth = parse_depth("maxdepth")?;
+        let interactive = flags.is_present("interactive");
+        // Matching directories are searched in case the user declines to delete them.
+        walker.prune = delete && !interactive;
 
+        let format = flags
+            .value("printf")
+            .map(|f| parse_format(f).map_err(|e| format_error(scope, f, args, e)))
+            .transpose()?;
+
+        let mut search = Search {
+            scope,
+            format,
+            null: flags.is_present("null"),
+            command,
+            delete,
+            interactive,
+            deleted: 0,
+            failed: 0,
+            quit: false,
+        };
+
+        let mut start = Vec::new();
         for dir in dirs {
             let path = Path::new(dir)
                 .dereference()
                 .map_err(|e| format_error(&scope, dir, args, e))?;
 
-            self.search(scope, OsStr::new(dir), &path, &regex, &mut visited)?;
+            start.push((OsString::from(dir), path.to_path_buf()));
+        }
+
+        search.search(&walker, start, !flags.is_present("unordered"))?;
+
+        // The last entry may not end with a newline, flush it out
+        io::stdout().flush().map_err(|e| e.to_string())?;
+
+        if search.failed > 0 {
+            scope.set_exit_status(1);
+            return Err(format!(
+                "{} of {} deletions failed",
+                search.failed,
+                search.deleted + search.failed
+            ));
+        }
+
+        if let Some(command) = search.command.as_mut() {
+            command.flush(scope);
+            if command.failed > 0 {
+                scope.set_exit_status(1);
+                return Err(format!(
+                    "{} of {} invocations failed",
+                    command.failed, command.count
+                ));
+            }
         }
 
         Ok(Value::success())
@@ -122,3 +853,218 @@ fn register() {
         inner: Arc::new(Find::new()),
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare() {
+        let size = Compare::parse("+10K", parse_size).unwrap();
+        assert_eq!(size, Compare::Greater(10240));
+        assert!(size.matches(10241));
+        assert!(!size.matches(10240));
+
+        let days = |s| Compare::parse(s, |n| n.parse::<u64>().map_err(|e| e.to_string()));
+        assert_eq!(days("-7"), Ok(Compare::Less(7)));
+        assert_eq!(days("3"), Ok(Compare::Equal(3)));
+        assert!(days("x").is_err());
+    }
+
+    #[test]
+    fn test_matches_name() {
+        let scope = Scope::new();
+        let mut flags = Find::new().flags;
+        let args = ["--iname".to_string(), "readme*".to_string()];
+        flags.parse(&scope, &args).unwrap();
+
+        let filter = Filter::new(&flags, &scope, &args).unwrap();
+        assert!(filter.matches_name("README.md"));
+        assert!(filter.matches_name("readme"));
+        assert!(!filter.matches_name("main.rs"));
+        assert!(Filter::default().matches_name("anything"));
+    }
+
+    #[test]
+    fn test_split_pattern() {
+        let src = env!("CARGO_MANIFEST_DIR").to_string();
+        let with_regex = vec![src.clone(), "^main".to_string()];
+        let dir_only = vec![src.clone()];
+        let regex_only = vec!["^main".to_string()];
+        let mut filter = Filter::default();
+
+        let (regex, dirs) = split_pattern(&filter, &with_regex).unwrap();
+        assert_eq!(regex.unwrap().as_str(), "^main");
+        assert_eq!(dirs, [src.clone()]);
+        assert!(split_pattern(&filter, &[]).is_err());
+
+        filter
+            .names
+            .push((Pattern::new("*.rs").unwrap(), MatchOptions::new()));
+        let (regex, dirs) = split_pattern(&filter, &dir_only).unwrap();
+        assert!(regex.is_none());
+        assert_eq!(dirs, [src.clone()]);
+        assert!(split_pattern(&filter, &[]).unwrap().0.is_none());
+
+        // The regex applies in addition to the globs
+        let (regex, dirs) = split_pattern(&filter, &with_regex).unwrap();
+        assert_eq!(regex.unwrap().as_str(), "^main");
+        assert_eq!(dirs, [src]);
+
+        let (regex, dirs) = split_pattern(&filter, &regex_only).unwrap();
+        assert_eq!(regex.unwrap().as_str(), "^main");
+        assert!(dirs.is_empty());
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!(
+            parse_format("%s\\t%p%%\\n").unwrap(),
+            [
+                Part::Size,
+                Part::Text("\t".to_string()),
+                Part::Path,
+                Part::Text("%\n".to_string())
+            ]
+        );
+        assert!(parse_format("%q").is_err());
+        assert!(parse_format("100%").is_err());
+
+        let parts = parse_format("%f %d %y").unwrap();
+        assert_eq!(
+            format_entry(&parts, Path::new("src/main.rs"), 1),
+            "main.rs 1 f"
+        );
+    }
+
+    #[test]
+    fn test_parse_exec() {
+        let scope = Scope::new();
+        let args = |items: &[&str]| items.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        let (rest, command) = Command::parse(
+            &scope,
+            &args(&["src", "--exec", "rm", "-f", "{}", ";", "x"]),
+        )
+        .unwrap();
+        let command = command.unwrap();
+        assert_eq!(rest, args(&["src", "x"]));
+        assert_eq!(command.args, args(&["rm", "-f", "{}"]));
+        assert!(!command.batch);
+
+        let (_, command) = Command::parse(&scope, &args(&["--exec", "ls", "{}", "+"])).unwrap();
+        assert!(command.unwrap().batch);
+
+        assert!(Command::parse(&scope, &args(&["--exec", "ls", "{}"])).is_err());
+        assert!(Command::parse(&scope, &args(&["--exec", ";"])).is_err());
+    }
+
+    #[test]
+    fn test_exec() -> std::io::Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let root = temp_dir.path();
+        let src = root.join("with space.txt");
+        fs::write(&src, "hello")?;
+        let dest = root.join("dest.txt");
+
+        let scope = Scope::new();
+        let args = |items: &[&str]| items.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let dest_arg = dest.to_string_lossy().to_string();
+        let (_, command) =
+            Command::parse(&scope, &args(&["--exec", "cp", "-f", "{}", &dest_arg, ";"])).unwrap();
+        let mut command = command.unwrap();
+
+        command.add(&scope, &src);
+        assert_eq!(command.failed, 0);
+        assert_eq!(fs::read_to_string(&dest)?, "hello");
+
+        command.add(&scope, &root.join("missing.txt"));
+        assert_eq!((command.count, command.failed), (2, 1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_delete() -> std::io::Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("a/target/debug"))?;
+        fs::write(root.join("a/target/debug/app"), "")?;
+        fs::write(root.join("a/main.rs"), "")?;
+
+        let scope = Scope::new();
+        let mut search = Search {
+            scope: &scope,
+            format: None,
+            null: false,
+            command: None,
+            delete: true,
+            interactive: false,
+            deleted: 0,
+            failed: 0,
+            quit: false,
+        };
+        let regex = Regex::new("^target$").unwrap();
+        let filter = Filter::default();
+        let mut walker = Walker::new(Some(&regex), &filter, 2);
+        walker.prune = true;
+        walker.max_depth = Some(0);
+        let start = || vec![(OsString::from("a"), root.join("a"))];
+
+        // a/target is too deep
+        search.search(&walker, start(), true).unwrap();
+        assert_eq!(search.deleted, 0);
+
+        walker.max_depth = Some(1);
+        walker.visited.lock().unwrap().clear();
+        search.search(&walker, start(), true).unwrap();
+
+        assert_eq!(search.deleted, 1);
+        assert!(!root.join("a/target").exists());
+        assert!(root.join("a/main.rs").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_walk_ordered() -> std::io::Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let root = temp_dir.path();
+        for dir in ["b/d", "a/c", "a/b"] {
+            fs::create_dir_all(root.join(dir))?;
+            fs::write(root.join(dir).join("x.txt"), "")?;
+        }
+
+        let regex = Regex::new("").unwrap();
+        let filter = Filter::default();
+        let walker = Walker::new(Some(&regex), &filter, 4);
+        let mut paths = Vec::new();
+        walker.walk(vec![(OsString::from("root"), root.into())], true, |found| {
+            if let Found::Match(path, _) = found {
+                let path = path
+                    .strip_prefix(root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string();
+                paths.push(path.replace('\\', "/"));
+            }
+            true
+        });
+
+        assert_eq!(
+            paths,
+            [
+                "",
+                "a",
+                "a/b",
+                "a/b/x.txt",
+                "a/c",
+                "a/c/x.txt",
+                "b",
+                "b/d",
+                "b/d/x.txt"
+            ]
+        );
+        Ok(())
+    }
+}
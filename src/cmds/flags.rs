@@ -6,7 +6,9 @@ use std::sync::Arc;
 pub struct CommandFlags {
     flags: BTreeMap<String, Flag>,
     values: BTreeMap<String, String>,
-    aliases: HashMap<String, String>, // Map aliases to the actual flag
+    repeated: BTreeMap<String, Vec<String>>, // Every value given to a value flag
+    aliases: HashMap<String, String>,        // Map aliases to the actual flag
+    implicit: HashMap<String, String>,       // Values of optional-value flags given bare
     index: usize,
 }
 
@@ -17,7 +19,9 @@ impl CommandFlags {
         CommandFlags {
             flags: BTreeMap::new(),
             values: BTreeMap::new(),
+            repeated: BTreeMap::new(),
             aliases: HashMap::new(),
+            implicit: HashMap::new(),
             index: 0,
         }
     }
@@ -113,6 +117,20 @@ impl CommandFlags {
         self.add(Some(short), long, Some(name.to_string()), help);
     }
 
+    /// Add flag that takes an optional value, which can only be given in the --long=VALUE
+    /// form; `implicit` is the value used when the flag is given as -s or --long.
+    pub fn add_optional_value(
+        &mut self,
+        short: Option<char>,
+        long: &str,
+        name: &str,
+        implicit: &str,
+        help: &str,
+    ) {
+        self.add(short, long, Some(name.to_string()), help);
+        self.implicit.insert(long.to_string(), implicit.to_string());
+    }
+
     /// Parse command-line arguments and categorize them into flags and non-flag arguments.
     ///
     // Parameters:
@@ -132,11 +150,10 @@ impl CommandFlags {
             self.index = i;
             if arg.starts_with("--") && arg != "--" {
                 self.handle_long_flag(scope, arg, &mut args_iter)?;
-            } else if arg.starts_with('-') {
-                if arg != "-" {
-                    self.handle_short_flags(scope, arg, &mut args_iter)?;
-                }
+            } else if arg.starts_with('-') && arg != "-" {
+                self.handle_short_flags(scope, arg, &mut args_iter)?;
             } else {
+                // Includes the lone dash, which conventionally stands for stdin
                 non_flag_args.push(arg.clone());
             }
         }
@@ -176,6 +193,7 @@ impl CommandFlags {
     }
 
     fn set_defaults(&mut self) {
+        self.repeated.clear();
         for (k, f) in &self.flags {
             if let Some(value) = &f.default_value {
                 self.values.insert(k.clone(), value.clone());
@@ -207,7 +225,12 @@ impl CommandFlags {
         arg: &str,
         args_iter: &mut ArgsIter,
     ) -> Result<(), String> {
-        if let Some((flag, is_negation)) = self.resolve_name(&arg[2..]) {
+        // Accept both --name value and --name=value
+        let (name, inline_value) = match arg[2..].split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (&arg[2..], None),
+        };
+        if let Some((flag, is_negation)) = self.resolve_name(name) {
             if flag.takes_value.is_some() {
                 if is_negation {
                     scope.set_err_arg(self.index);
@@ -216,13 +239,20 @@ impl CommandFlags {
                         flag.long
                     ));
                 }
-                if let Some((i, value)) = args_iter.next() {
+                if let Some(value) = inline_value {
+                    self.set_value(&flag.long, value.to_string());
+                } else if let Some(value) = self.implicit.get(&flag.long).cloned() {
+                    self.set_value(&flag.long, value);
+                } else if let Some((i, value)) = args_iter.next() {
                     self.index = i;
-                    self.values.insert(flag.long.clone(), value.clone());
+                    self.set_value(&flag.long, value.clone());
                 } else {
                     scope.set_err_arg(self.index);
                     return Err(format!("Flag --{} requires a value", flag.long));
                 }
+            } else if inline_value.is_some() {
+                scope.set_err_arg(self.index);
+                return Err(format!("Flag --{} does not take a value", flag.long));
             } else if is_negation {
                 self.values.remove(&flag.long);
             } else {
@@ -249,7 +279,9 @@ impl CommandFlags {
                 let (flag, is_negation) =
                     self.resolve_name(&flag.long).expect("unknown short flag");
 
-                if flag.takes_value.is_some() {
+                if let Some(value) = self.implicit.get(&flag.long).cloned() {
+                    self.set_value(&flag.long, value);
+                } else if flag.takes_value.is_some() {
                     let value = if i + 1 < chars.len() {
                         // Case: -d2
                         chars[i + 1..].iter().collect::<String>()
@@ -271,7 +303,7 @@ impl CommandFlags {
                         value
                     };
 
-                    self.values.insert(flag.long.clone(), value);
+                    self.set_value(&flag.long, value);
                     break; // Exit the loop as we've consumed the rest of the argument
                 } else if is_negation {
                     self.values.remove(&flag.long);
@@ -287,6 +319,14 @@ impl CommandFlags {
         Ok(())
     }
 
+    fn set_value(&mut self, name: &str, value: String) {
+        self.repeated
+            .entry(name.to_string())
+            .or_default()
+            .push(value.clone());
+        self.values.insert(name.to_string(), value);
+    }
+
     pub fn is_empty(&self) -> bool {
         self.values.is_empty()
     }
@@ -308,6 +348,15 @@ impl CommandFlags {
         self.values.get(name).map(|s| s.as_str())
     }
 
+    /// Query all values, in command line order, for flags that may be repeated.
+    /// Falls back to the default value (if any) when the flag is not given.
+    pub fn values(&self, name: &str) -> Vec<&str> {
+        match self.repeated.get(name) {
+            Some(values) => values.iter().map(|s| s.as_str()).collect(),
+            None => self.value(name).into_iter().collect(),
+        }
+    }
+
     pub fn help(&self) -> String {
         let mut help_text = String::new();
 
@@ -324,6 +373,9 @@ impl CommandFlags {
             };
 
             let long_text = match &flag.takes_value {
+                Some(name) if self.implicit.contains_key(&flag.long) => {
+                    format!("{}[={}]", flag.long, name)
+                }
                 Some(name) => format!("{} <{}>", flag.long, name),
                 None => flag.long.to_string(),
             };
@@ -356,6 +408,15 @@ mod tests {
         flags
     }
 
+    #[test]
+    fn test_lone_dash_is_positional() {
+        let mut flags = create_test_flags();
+        let args = vec!["-v".to_string(), "-".to_string(), "file.txt".to_string()];
+        let result = flags.parse(&Scope::new(), &args);
+        assert_eq!(result.unwrap(), vec!["-", "file.txt"]);
+        assert!(flags.is_present("verbose"));
+    }
+
     #[test]
     fn test_default_values() {
         let mut flags = create_test_flags();
@@ -380,6 +441,41 @@ mod tests {
         assert_eq!(flags.value("output"), Some("file.txt"));
     }
 
+    #[test]
+    fn test_parse_long_flag_with_equals() {
+        let mut flags = create_test_flags();
+        let scope = Arc::new(Scope::new());
+        let args = vec!["--output=file.txt".to_string(), "--debug=".to_string()];
+        assert!(flags.parse(&scope, &args).unwrap().is_empty());
+        assert_eq!(flags.value("output"), Some("file.txt"));
+        assert_eq!(flags.value("debug"), Some(""));
+
+        assert!(flags.parse(&scope, &["--verbose=1".to_string()]).is_err());
+        assert!(flags.parse(&scope, &["--no-output=x".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_optional_value() {
+        let mut flags = create_test_flags();
+        flags.add_optional_value(Some('b'), "backup", "SUFFIX", "~", "Make backups");
+        let scope = Arc::new(Scope::new());
+
+        let args = vec!["--backup".to_string(), "file.txt".to_string()];
+        assert_eq!(flags.parse(&scope, &args).unwrap(), vec!["file.txt"]);
+        assert_eq!(flags.value("backup"), Some("~"));
+
+        let args = vec!["-vb".to_string(), "file.txt".to_string()];
+        assert_eq!(flags.parse(&scope, &args).unwrap(), vec!["file.txt"]);
+        assert!(flags.is_present("verbose"));
+        assert_eq!(flags.value("backup"), Some("~"));
+
+        let args = vec!["--backup=.bak".to_string(), "file.txt".to_string()];
+        assert_eq!(flags.parse(&scope, &args).unwrap(), vec!["file.txt"]);
+        assert_eq!(flags.value("backup"), Some(".bak"));
+
+        assert!(flags.help().contains("--backup[=SUFFIX]"));
+    }
+
     #[test]
     fn test_parse_short_flags() {
         let mut flags = create_test_flags();
@@ -439,6 +535,24 @@ mod tests {
         assert_eq!(flags.value("debug"), Some("2"));
     }
 
+    #[test]
+    fn test_repeated_values() {
+        let mut flags = create_test_flags();
+        let scope = Arc::new(Scope::new());
+        let args = vec![
+            "-o".to_string(),
+            "a.txt".to_string(),
+            "-ob.txt".to_string(),
+            "--output".to_string(),
+            "c.txt".to_string(),
+        ];
+        assert!(flags.parse(&scope, &args).is_ok());
+        assert_eq!(flags.value("output"), Some("c.txt"));
+        assert_eq!(flags.values("output"), vec!["a.txt", "b.txt", "c.txt"]);
+        assert_eq!(flags.values("debug"), vec!["0"]);
+        assert!(flags.values("verbose").is_empty());
+    }
+
     #[test]
     fn test_help_output() {
         let flags = create_test_flags();
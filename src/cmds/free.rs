@@ -0,0 +1,110 @@
+use super::{flags::CommandFlags, register_command, Exec, Flag, ShellCommand};
+use crate::{eval::Value, scope::Scope, utils::format_size};
+use std::sync::Arc;
+use sysinfo::{MemoryRefreshKind, System};
+
+/// Units for the sizes: flag name and block size.
+const UNITS: [(&str, u64); 4] = [
+    ("bytes", 1),
+    ("kibi", 1024),
+    ("mebi", 1024 * 1024),
+    ("gibi", 1024 * 1024 * 1024),
+];
+
+struct Free {
+    flags: CommandFlags,
+}
+
+impl Free {
+    fn new() -> Self {
+        let mut flags = CommandFlags::with_help();
+        flags.add_flag(
+            'h',
+            "human-readable",
+            "Print sizes in human readable format (e.g., 1.1G)",
+        );
+        flags.add_flag('b', "bytes", "Print sizes in bytes");
+        flags.add_flag('k', "kibi", "Print sizes in kibibytes (default)");
+        flags.add_flag('m', "mebi", "Print sizes in mebibytes");
+        flags.add_flag('g', "gibi", "Print sizes in gibibytes");
+        flags.add_flag('t', "total", "Print a line with the RAM and swap totals");
+        Self { flags }
+    }
+}
+
+impl Exec for Free {
+    fn cli_flags(&self) -> Box<dyn Iterator<Item = &Flag> + '_> {
+        Box::new(self.flags.iter())
+    }
+
+    fn exec(&self, name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
+        let mut flags = self.flags.clone();
+        flags.parse(scope, args)?;
+
+        if flags.is_present("help") {
+            println!("Usage: {} [OPTIONS]", name);
+            println!("Display the amount of used and available memory (RAM) and swap space.");
+            println!("\nOptions:");
+            print!("{}", flags.help());
+            return Ok(Value::success());
+        }
+
+        let human_readable = flags.is_present("human-readable");
+        // Use the largest unit specified; default to kibibytes.
+        let block_size = UNITS
+            .iter()
+            .rev()
+            .find(|(unit, _)| flags.is_present(unit))
+            .map_or(1024, |(_, size)| *size);
+
+        let mut system = System::new();
+        system.refresh_memory_specifics(MemoryRefreshKind::everything());
+
+        let size = |n: u64| format_size(n, block_size, human_readable);
+
+        my_println!(
+            "{:<6} {:>12} {:>12} {:>12} {:>12}",
+            "",
+            "total",
+            "used",
+            "free",
+            "available"
+        )?;
+        my_println!(
+            "{:<6} {:>12} {:>12} {:>12} {:>12}",
+            "Mem:",
+            size(system.total_memory()),
+            size(system.used_memory()),
+            size(system.free_memory()),
+            size(system.available_memory())
+        )?;
+        my_println!(
+            "{:<6} {:>12} {:>12} {:>12}",
+            "Swap:",
+            size(system.total_swap()),
+            size(system.used_swap()),
+            size(system.free_swap())
+        )?;
+        if flags.is_present("total") {
+            my_println!(
+                "{:<6} {:>12} {:>12} {:>12}",
+                "Total:",
+                size(system.total_memory() + system.total_swap()),
+                size(system.used_memory() + system.used_swap()),
+                size(system.free_memory() + system.free_swap())
+            )?;
+        }
+
+        Ok(Value::success())
+    }
+}
+
+#[ctor::ctor]
+fn register() {
+    for name in ["free", "meminfo"] {
+        register_command(ShellCommand {
+            name: name.to_string(),
+            inner: Arc::new(Free::new()),
+        });
+    }
+}
@@ -1,18 +1,189 @@
+use super::walk::{Key, WorkQueue};
 use super::{flags::CommandFlags, register_command, Exec, Flag, ShellCommand};
-use crate::{eval::Value, scope::Scope, symlnk::SymLink};
+use crate::{eval::Value, scope::Scope, symlnk::SymLink, utils::format_error};
 use colored::*;
 use regex::Regex;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::fs::{self, File};
 use std::io::{self, BufRead, BufReader, IsTerminal};
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering::SeqCst};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
 use url::Url;
 
 struct Grep {
     flags: CommandFlags,
 }
 
+#[derive(Clone, Copy)]
+struct Options {
+    ignore_case: bool,
+    invert_match: bool,
+    line_number: bool,
+    show_filename: bool,
+    use_color: bool,
+    use_hyperlink: bool,
+}
+
+/// Problems found by the worker threads, which cannot use the scope to show them.
+#[derive(Debug)]
+enum Warning {
+    Resolve(PathBuf, String),
+    Symlink(PathBuf),
+    Dereference(PathBuf, String),
+    ReadDir(PathBuf, String),
+    Directory(PathBuf),
+    Open(PathBuf, String),
+    Read(PathBuf, String),
+}
+
+impl Warning {
+    fn show(&self, scope: &Scope, silent: bool) {
+        match self {
+            Warning::Symlink(path) => my_warning!(
+                scope,
+                "Omitting symlink (-L/--follow-links option not set): {}",
+                scope.err_path(path)
+            ),
+            Warning::Directory(path) => my_warning!(
+                scope,
+                "Omitting directory (-r/--recursive option not set): {}",
+                scope.err_path(path)
+            ),
+            _ if silent => {}
+            Warning::Resolve(path, e) => {
+                my_warning!(scope, "Could not resolve {}: {}", scope.err_path(path), e)
+            }
+            Warning::Dereference(path, e) => {
+                my_warning!(
+                    scope,
+                    "Could not dereference {}: {}",
+                    scope.err_path(path),
+                    e
+                )
+            }
+            Warning::ReadDir(path, e) => {
+                my_warning!(
+                    scope,
+                    "Could not read directory {}: {}",
+                    scope.err_path(path),
+                    e
+                )
+            }
+            Warning::Open(path, e) => {
+                my_warning!(scope, "Could not open {}: {}", scope.err_path(path), e)
+            }
+            Warning::Read(path, e) => my_warning!(scope, "{}: {}", scope.err_path(path), e),
+        }
+    }
+}
+
+/// Multi-threaded directory walker, collecting the files to search.
+struct Walker {
+    follow: bool,
+    hidden: bool,
+    recursive: bool,
+    work: WorkQueue<(Key, PathBuf)>,
+    visited: Mutex<HashSet<PathBuf>>,
+    files: Mutex<Vec<(Key, PathBuf)>>,
+    warnings: Mutex<Vec<(Key, Warning)>>,
+}
+
+impl Walker {
+    fn new(follow: bool, hidden: bool, recursive: bool, threads: usize) -> Self {
+        Self {
+            follow,
+            hidden,
+            recursive,
+            work: WorkQueue::new(threads),
+            visited: Mutex::new(HashSet::new()),
+            files: Mutex::new(Vec::new()),
+            warnings: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn warn(&self, key: Key, warning: Warning) {
+        self.warnings.lock().unwrap().push((key, warning));
+    }
+
+    fn visit(&self, worker: usize, key: Key, path: &Path) {
+        if path.is_symlink() {
+            if !self.follow {
+                self.warn(key, Warning::Symlink(path.to_path_buf()));
+            } else {
+                match path.dereference() {
+                    Ok(resolved) => self.visit(worker, key, &resolved),
+                    Err(e) => self.warn(key, Warning::Resolve(path.to_path_buf(), e.to_string())),
+                }
+            }
+        } else if path.is_file() {
+            self.files.lock().unwrap().push((key, path.to_path_buf()));
+        } else if path.is_dir() {
+            if !self.recursive {
+                self.warn(key, Warning::Directory(path.to_path_buf()));
+                return;
+            }
+            match path.dereference() {
+                Ok(resolved) => {
+                    if !self.visited.lock().unwrap().insert(resolved.to_path_buf()) {
+                        return;
+                    }
+                }
+                Err(e) => {
+                    let warning = Warning::Dereference(path.to_path_buf(), e.to_string());
+                    return self.warn(key, warning);
+                }
+            }
+            match fs::read_dir(path) {
+                Ok(dir) => {
+                    let mut entries = dir
+                        .filter_map(Result::ok)
+                        .filter(|entry| {
+                            self.hidden || !entry.file_name().to_string_lossy().starts_with(".")
+                        })
+                        .map(|entry| entry.path())
+                        .collect::<Vec<_>>();
+                    entries.sort();
+
+                    for (i, entry) in entries.into_iter().enumerate() {
+                        let mut entry_key = key.clone();
+                        entry_key.push(i);
+                        self.work.push(worker, (entry_key, entry));
+                    }
+                }
+                Err(e) => self.warn(key, Warning::ReadDir(path.to_path_buf(), e.to_string())),
+            }
+        }
+    }
+
+    /// Walk the paths, return the files found and the warnings, in sequential order.
+    fn walk(&self, paths: &[String]) -> (Vec<PathBuf>, Vec<Warning>) {
+        let count = self.work.threads();
+        for (i, path) in paths.iter().enumerate() {
+            self.work.push(i % count, (vec![i], PathBuf::from(path)));
+        }
+        thread::scope(|s| {
+            for worker in 0..count {
+                s.spawn(move || {
+                    self.work
+                        .run(worker, |(key, path)| self.visit(worker, key, &path))
+                });
+            }
+        });
+
+        let mut files = std::mem::take(&mut *self.files.lock().unwrap());
+        files.sort();
+        let mut warnings = std::mem::take(&mut *self.warnings.lock().unwrap());
+        warnings.sort_by(|a, b| a.0.cmp(&b.0));
+
+        (
+            files.into_iter().map(|(_, path)| path).collect(),
+            warnings.into_iter().map(|(_, warning)| warning).collect(),
+        )
+    }
+}
+
 impl Grep {
     fn new() -> Self {
         let mut flags = CommandFlags::with_follow_links();
@@ -53,138 +224,26 @@ impl Grep {
             None,
             "Include hidden (starting with a dot) files and directories",
         );
+        flags.add(
+            None,
+            "threads",
+            Some("N".to_string()),
+            "Number of threads searching files (default: number of CPUs)",
+        );
         flags.add_with_default(None, "messages", None, "Show error messages", Some("true"));
         flags.add_alias(Some('s'), "silent", "no-messages");
 
         Self { flags }
     }
 
-    fn collect_files(
-        &self,
-        scope: &Arc<Scope>,
-        args: &[String], // Original args, for finding bad arg index in case of error
-        paths: &[String],
-        follow: bool,
-        hidden: bool,
-        recursive: bool,
-        silent: bool,
-        visited: &mut HashSet<String>,
-    ) -> Vec<PathBuf> {
-        // Files to processs
-        let mut files = Vec::new();
-        for p in paths {
-            // Handle Ctrl+C
-            if Scope::is_interrupted() {
-                return files;
-            }
-
-            let path = Path::new(p);
-
-            if path.is_symlink() {
-                if follow {
-                    match path.dereference() {
-                        Ok(path) => files.extend(self.collect_files(
-                            scope,
-                            args,
-                            &[path.to_string_lossy().to_string()],
-                            follow,
-                            hidden,
-                            recursive,
-                            silent,
-                            visited,
-                        )),
-                        Err(e) => {
-                            if !silent {
-                                my_warning!(scope, "Could not resolve {}: {}", scope.err_str(p), e);
-                            }
-                        }
-                    }
-                } else {
-                    my_warning!(
-                        scope,
-                        "Omitting symlink (-L/--follow-links option not set): {}",
-                        scope.err_str(p)
-                    );
-                }
-            } else if path.is_file() {
-                files.push(path.to_path_buf());
-            } else if path.is_dir() {
-                if recursive {
-                    match path.dereference() {
-                        Ok(path) => {
-                            if !visited.insert(path.to_string_lossy().to_string()) {
-                                continue;
-                            }
-                        }
-                        Err(e) => {
-                            if !silent {
-                                my_warning!(
-                                    scope,
-                                    "Could not dereference {}: {}",
-                                    scope.err_str(p),
-                                    e
-                                );
-                            }
-                            continue;
-                        }
-                    }
-
-                    match fs::read_dir(path) {
-                        Err(e) => {
-                            if !silent {
-                                my_warning!(
-                                    scope,
-                                    "Could not read directory {}: {}",
-                                    scope.err_path(path),
-                                    e
-                                );
-                            }
-                            continue;
-                        }
-                        Ok(dir) => {
-                            files.extend(dir.filter_map(Result::ok).flat_map(|entry| {
-                                if !hidden && entry.file_name().to_string_lossy().starts_with(".") {
-                                    vec![]
-                                } else {
-                                    self.collect_files(
-                                        scope,
-                                        args,
-                                        &[entry.path().to_string_lossy().to_string()],
-                                        follow,
-                                        hidden,
-                                        recursive,
-                                        silent,
-                                        visited,
-                                    )
-                                }
-                            }));
-                        }
-                    }
-                } else {
-                    my_warning!(
-                        scope,
-                        "Omitting directory (-r/--recursive option not set): {}",
-                        scope.err_str(p)
-                    );
-                }
-            }
-        }
-        files
-    }
-
-    fn process_line(
+    fn format_line(
         filename: Option<&Path>,
         line_number: usize,
         line: &str,
         regex: &Regex,
-        line_number_flag: bool,
-        ignore_case: bool,
-        show_filename: bool,
-        use_color: bool,
-        use_hyperlink: bool,
-        invert_match: bool,
-    ) {
-        let line_to_check = if ignore_case {
+        opts: &Options,
+    ) -> Option<String> {
+        let line_to_check = if opts.ignore_case {
             line.to_lowercase()
         } else {
             line.to_string()
@@ -192,49 +251,132 @@ impl Grep {
 
         let matches = regex.is_match(&line_to_check);
 
-        if matches != invert_match {
-            let mut output = String::new();
-
-            // Handle hyperlinks and filename output
-            if use_hyperlink {
+        if matches == opts.invert_match {
+            return None;
+        }
+        let mut output = String::new();
+
+        // Handle hyperlinks and filename output
+        if opts.use_hyperlink {
+            if let Some(name) = filename {
+                let path = name.canonicalize().unwrap_or_else(|_| name.to_path_buf());
+                let url = Url::from_file_path(path).unwrap();
+                let text = format!("{}:{}", name.display(), line_number + 1);
+                let hyperlink = format!(
+                    "\x1B]8;;{}?line={}\x1B\\{}\x1B]8;;\x1B\\",
+                    url,
+                    line_number + 1,
+                    text
+                );
+                output.push_str(&hyperlink);
+            }
+        } else {
+            if opts.show_filename {
                 if let Some(name) = filename {
-                    let path = name.canonicalize().unwrap_or_else(|_| name.to_path_buf());
-                    let url = Url::from_file_path(path).unwrap();
-                    let text = format!("{}:{}", name.display(), line_number + 1);
-                    let hyperlink = format!(
-                        "\x1B]8;;{}?line={}\x1B\\{}\x1B]8;;\x1B\\",
-                        url,
-                        line_number + 1,
-                        text
-                    );
-                    output.push_str(&hyperlink);
-                }
-            } else {
-                if show_filename {
-                    if let Some(name) = filename {
-                        if use_color {
-                            output.push_str(&format!("{}:", name.to_string_lossy().magenta()));
-                        } else {
-                            output.push_str(&format!("{}:", name.to_string_lossy().normal()));
-                        }
+                    if opts.use_color {
+                        output.push_str(&format!("{}:", name.to_string_lossy().magenta()));
+                    } else {
+                        output.push_str(&format!("{}:", name.to_string_lossy().normal()));
                     }
                 }
-                if line_number_flag {
-                    output.push_str(&format!("{}:", line_number + 1));
+            }
+            if opts.line_number {
+                output.push_str(&format!("{}:", line_number + 1));
+            }
+        }
+
+        if opts.use_color {
+            let colored_line = regex.replace_all(line, |caps: &regex::Captures| {
+                caps[0].red().bold().to_string()
+            });
+            output.push_str(&colored_line);
+        } else {
+            output.push_str(line);
+        }
+
+        Some(output)
+    }
+
+    /// Search the file, return the matching lines and the error that stopped the search, if any.
+    fn search_file(path: &Path, regex: &Regex, opts: &Options) -> (String, Option<Warning>) {
+        let mut output = String::new();
+
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) => return (output, Some(Warning::Open(path.into(), e.to_string()))),
+        };
+        for (line_number, line) in BufReader::new(file).lines().enumerate() {
+            if Scope::is_interrupted() {
+                break;
+            }
+            match line {
+                Ok(line) => {
+                    if let Some(line) =
+                        Self::format_line(Some(path), line_number, &line, regex, opts)
+                    {
+                        output.push_str(&line);
+                        output.push('\n');
+                    }
                 }
+                Err(e) => return (output, Some(Warning::Read(path.into(), e.to_string()))),
             }
+        }
+        (output, None)
+    }
 
-            if use_color {
-                let colored_line = regex.replace_all(line, |caps: &regex::Captures| {
-                    caps[0].red().bold().to_string()
+    /// Search the files in parallel, and print the results in the order of the files.
+    fn search_files(
+        scope: &Arc<Scope>,
+        files: &[PathBuf],
+        regex: &Regex,
+        opts: &Options,
+        silent: bool,
+        threads: usize,
+    ) -> Result<(), String> {
+        let next = AtomicUsize::new(0);
+        let stop = AtomicBool::new(false);
+        let (sender, receiver) = mpsc::channel();
+
+        thread::scope(|s| {
+            for _ in 0..threads.min(files.len()) {
+                let sender = sender.clone();
+                let (next, stop) = (&next, &stop);
+
+                s.spawn(move || loop {
+                    let i = next.fetch_add(1, SeqCst);
+                    if i >= files.len() || stop.load(SeqCst) || Scope::is_interrupted() {
+                        break;
+                    }
+                    if sender
+                        .send((i, Self::search_file(&files[i], regex, opts)))
+                        .is_err()
+                    {
+                        break;
+                    }
                 });
-                output.push_str(&colored_line);
-            } else {
-                output.push_str(line);
             }
+            drop(sender);
 
-            println!("{}", output);
-        }
+            // Results that arrived ahead of the ones for preceding files
+            let mut pending = BTreeMap::new();
+            let mut index = 0;
+
+            for (i, result) in receiver {
+                pending.insert(i, result);
+
+                while let Some((output, warning)) = pending.remove(&index) {
+                    index += 1;
+                    if let Err(e) = my_print!("{}", output) {
+                        stop.store(true, SeqCst);
+                        return Err(e);
+                    }
+                    if let Some(warning) = warning {
+                        warning.show(scope, silent);
+                    }
+                }
+            }
+            Ok(())
+        })
     }
 }
 
@@ -250,8 +392,12 @@ impl Exec for Grep {
         if flags.is_present("help") {
             println!("Usage: grep [OPTIONS] PATTERN [FILE]...");
             println!("Search for PATTERN in each FILE (or stdin if no FILE is given).");
+            println!("Files are searched in parallel; the output is in the order of the files.");
             println!("\nOptions:");
             print!("{}", flags.help());
+            println!("\nExamples:");
+            println!("    grep -rn TODO src");
+            println!("    grep -r --threads 4 -i error /var/log");
             return Ok(Value::success());
         }
 
@@ -260,20 +406,33 @@ impl Exec for Grep {
         }
 
         let pattern = &grep_args[0];
-        let invert_match = flags.is_present("invert-match");
 
         let follow = flags.is_present("follow-links");
         let hidden = flags.is_present("hidden");
-        let ignore_case = flags.is_present("ignore-case");
-        let line_number_flag = flags.is_present("line-number");
         let no_filename = flags.is_present("no-filename");
         let recursive = flags.is_present("recursive");
         let silent = !flags.is_present("messages");
-        let use_color = scope.lookup("NO_COLOR").is_none() && std::io::stdout().is_terminal();
         let use_filename = flags.is_present("with-filename");
-        let use_hyperlink = flags.is_present("hyperlink");
 
-        let regex = if ignore_case {
+        let mut opts = Options {
+            ignore_case: flags.is_present("ignore-case"),
+            invert_match: flags.is_present("invert-match"),
+            line_number: flags.is_present("line-number"),
+            show_filename: false,
+            use_color: scope.lookup("NO_COLOR").is_none() && std::io::stdout().is_terminal(),
+            use_hyperlink: flags.is_present("hyperlink"),
+        };
+
+        let threads = match flags.value("threads") {
+            Some(v) => match v.parse::<usize>() {
+                Ok(0) => return Err(format_error(scope, v, args, "expecting at least 1")),
+                Ok(n) => n,
+                Err(e) => return Err(format_error(scope, v, args, e)),
+            },
+            None => thread::available_parallelism().map_or(1, |n| n.get()),
+        };
+
+        let regex = if opts.ignore_case {
             Regex::new(&format!("(?i){}", pattern)).map_err(|e| e.to_string())?
         } else {
             Regex::new(pattern).map_err(|e| e.to_string())?
@@ -291,33 +450,19 @@ impl Exec for Grep {
                 }
 
                 let line = line.map_err(|e| e.to_string())?;
-                Self::process_line(
-                    None,
-                    line_number,
-                    &line,
-                    &regex,
-                    line_number_flag,
-                    ignore_case,
-                    false,
-                    use_color,
-                    use_hyperlink,
-                    invert_match,
-                );
+                if let Some(line) = Self::format_line(None, line_number, &line, &regex, &opts) {
+                    println!("{}", line);
+                }
             }
         } else {
-            let mut visited = HashSet::new();
-            let files_to_process = self.collect_files(
-                scope,
-                args,
-                files,
-                follow,
-                hidden,
-                recursive,
-                silent,
-                &mut visited,
-            );
-
-            let show_filename = if no_filename {
+            let walker = Walker::new(follow, hidden, recursive, threads);
+            let (files_to_process, warnings) = walker.walk(files);
+
+            for warning in &warnings {
+                warning.show(scope, silent);
+            }
+
+            opts.show_filename = if no_filename {
                 false
             } else if use_filename || files_to_process.len() > 1 {
                 true
@@ -325,47 +470,7 @@ impl Exec for Grep {
                 false
             };
 
-            for path in &files_to_process {
-                if Scope::is_interrupted() {
-                    break;
-                }
-                match File::open(&path) {
-                    Ok(file) => {
-                        let reader = BufReader::new(file);
-                        for (line_number, line) in reader.lines().enumerate() {
-                            if Scope::is_interrupted() {
-                                break;
-                            }
-
-                            match line {
-                                Ok(line) => Self::process_line(
-                                    Some(path),
-                                    line_number,
-                                    &line,
-                                    &regex,
-                                    line_number_flag,
-                                    ignore_case,
-                                    show_filename,
-                                    use_color,
-                                    use_hyperlink,
-                                    invert_match,
-                                ),
-                                Err(e) => {
-                                    if !silent {
-                                        my_warning!(scope, "{}: {}", scope.err_path(path), e);
-                                    }
-                                    break;
-                                }
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        if !silent {
-                            my_warning!(scope, "Could not open {}: {}", scope.err_path(path), e);
-                        }
-                    }
-                }
-            }
+            Self::search_files(scope, &files_to_process, &regex, &opts, silent, threads)?;
         }
 
         Ok(Value::success())
@@ -503,4 +608,35 @@ mod tests {
 
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_parallel_walk() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        for dir in ["a/x", "a/y", "b", ".hidden"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in ["a/x/1", "a/x/2", "a/y/1", "a/0", "b/1", ".hidden/1", "c"] {
+            File::create(root.join(file)).unwrap();
+        }
+
+        let walker = Walker::new(false, false, true, 4);
+        let (files, warnings) = walker.walk(&[root.to_string_lossy().to_string()]);
+        assert!(warnings.is_empty());
+        assert_eq!(
+            files,
+            ["a/0", "a/x/1", "a/x/2", "a/y/1", "b/1", "c"]
+                .iter()
+                .map(|file| root.join(file))
+                .collect::<Vec<_>>()
+        );
+
+        let walker = Walker::new(false, true, false, 2);
+        let (files, warnings) = walker.walk(&[root.join("c").to_string_lossy().to_string()]);
+        assert_eq!(files, vec![root.join("c")]);
+        assert!(warnings.is_empty());
+
+        let (_, warnings) = walker.walk(&[root.to_string_lossy().to_string()]);
+        assert!(matches!(warnings[..], [Warning::Directory(_)]));
+    }
 }
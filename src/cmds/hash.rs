@@ -0,0 +1,67 @@
+use super::{
+    clear_hashed_commands, flags::CommandFlags, hash_command, hashed_commands, register_command,
+    Exec, Flag, ShellCommand,
+};
+use crate::{eval::Value, scope::Scope};
+use std::sync::Arc;
+
+struct Hash {
+    flags: CommandFlags,
+}
+
+impl Hash {
+    fn new() -> Self {
+        let mut flags = CommandFlags::with_help();
+        flags.add_flag('r', "reset", "Forget all remembered locations");
+
+        Self { flags }
+    }
+}
+
+impl Exec for Hash {
+    fn cli_flags(&self) -> Box<dyn Iterator<Item = &Flag> + '_> {
+        Box::new(self.flags.iter())
+    }
+
+    fn exec(&self, _name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
+        let mut flags = self.flags.clone();
+        let names = flags.parse(scope, args)?;
+
+        if flags.is_present("help") {
+            println!("Usage: hash [-r] [NAME]...");
+            println!("Remember the full paths of external commands, to avoid searching $PATH.");
+            println!("Without arguments, list the remembered commands and their locations.");
+            println!("\nOptions:");
+            print!("{}", flags.help());
+            println!();
+            println!("Examples:");
+            println!("    hash git cargo");
+            println!("    hash -r");
+            return Ok(Value::success());
+        }
+
+        if flags.is_present("reset") {
+            clear_hashed_commands();
+        } else if names.is_empty() {
+            for (name, path) in hashed_commands() {
+                my_println!("{}\t{}", name, path.display())?;
+            }
+        }
+
+        for name in &names {
+            if hash_command(name).is_none() {
+                return Err(format!("{}: not found", scope.err_str(name)));
+            }
+        }
+
+        Ok(Value::success())
+    }
+}
+
+#[ctor::ctor]
+fn register() {
+    register_command(ShellCommand {
+        name: "hash".to_string(),
+        inner: Arc::new(Hash::new()),
+    });
+}
@@ -41,16 +41,29 @@ impl Help {
         println!("EXPRESSIONS");
         println!("    Variable Assignment and Evaluation");
         println!("        Example: i = 5; echo $i");
+        println!("        Example: local i = 5   # confined to the current scope, not exported");
+        println!("        Example: readonly N = 10   # assigning $N afterwards is an error");
         println!("    Conditional Statements");
         println!("        Example: if ($i > 0) (echo \"i is positive\") else (echo \"i is not positive\")");
         println!("    Loops");
         println!("        Example: while ($i > 0) (echo $i; $i = $i - 1)");
         println!("        Example: for f in *.rs; (echo $f; ls -l $f)");
+        println!("        Example: for line in (cat notes.txt); (echo $line)");
+        println!("    Return");
+        println!("        Example: if ($# == 0) (return 1)   # exit script or alias, and set $? to 1");
+        println!("    Time");
+        println!("        Example: time (ls -R | wc -l)   # print real, user and sys times");
         println!();
         println!("    Arithmetic Operators");
         println!("        '+': add, '-': subtract, '/': divide, '//': divide integers, '%': modulo, '*': multiply, '^': exponent");
         println!("    Logical Operators");
         println!("        '||': or, '&&': and");
+        println!("    Lists");
+        println!("        Example: l = [1, 2, \"a b\"]; echo $l[0] $l[-1] $l[1:] (len $l); l = $l + [3]");
+        println!("    Maps");
+        println!("        Example: m = [a = 1, b = 2]; echo $m[a]; $m[c] = 3; $m[b] =; for k in $m; (echo $k)");
+        println!("    Brace Expansion");
+        println!("        Example: cp file.{{txt,bak}} dest/; mkdir day{{01..31}}");
         println!();
         println!("COMMAND EXECUTION");
         println!("    A command evaluates to true if it succeeds (exit code 0) and false otherwise.");
@@ -60,6 +73,12 @@ impl Help {
         println!("REDIRECTS");
         println!("    Output to file: <command> => <file path>");
         println!("    Append to file: <command> =>> <file path>");
+        println!("    After command arguments, > and >> also work, as well as:");
+        println!("    Error output to file: <command> 2> <file path>");
+        println!("    Append error output to file: <command> 2>> <file path>");
+        println!("    Error output to standard output: <command> 2>&1");
+        println!("    Input from file: <command> < <file path>");
+        println!("    Input from heredoc: <command> <<EOF;  followed by lines of text, and EOF");
         println!();
         println!("PIPES");
         println!("    Pipe output between commands:");
@@ -67,6 +86,12 @@ impl Help {
         println!("    Example:");
         println!("        ls -al | (echo \"\\t\\tHEADER\"; cat; echo \"\\t\\tFOOTER\")");
         println!();
+        println!("BACKGROUND JOBS");
+        println!("    Evaluate in the background: <expression> &");
+        println!("    List jobs: jobs");
+        println!("    Wait for job: fg [%N]");
+        println!("    Continue stopped job in the background: bg [%N]  (Unix, Ctrl+Z stops external commands)");
+        println!();
         println!("COMMAND HISTORY");
         println!("    The command history is saved in ~/.shmy/history.txt");
         println!("    The maximum number of entries saved in the history is controlled by $HISTFILESIZE");
@@ -94,6 +119,9 @@ impl Help {
         println!("        __stderr = log.txt; ls -al");
         println!("        __stderr = __stdout; ls -al /");
         println!("        __stdout = some/path/file.txt; __stderr = 1; ls -al");
+        println!("    Exit status of the last command: $?");
+        println!("    Example:");
+        println!("        ls missing.txt || echo \"ls failed with status $?\"");
         println!();
         Self::print_available_commands(4, 4);
         println!("SEE ALSO");
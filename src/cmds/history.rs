@@ -0,0 +1,287 @@
+use super::{flags::CommandFlags, get_command, register_command, Exec, Flag, ShellCommand};
+use crate::eval::Value;
+use crate::history::{self, Entry};
+use crate::scope::Scope;
+use crate::utils::{default_editor, edit_text};
+use regex::Regex;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// The history file loaded by the line editor in interactive mode.
+fn history_path(scope: &Arc<Scope>) -> Result<PathBuf, String> {
+    scope
+        .lookup_value("HISTORY")
+        .map(|v| PathBuf::from(v.to_string()))
+        .filter(|p| !p.as_os_str().is_empty())
+        .ok_or_else(|| "History file not set ($HISTORY)".to_string())
+}
+
+struct HistoryCommand {
+    flags: CommandFlags,
+}
+
+impl HistoryCommand {
+    fn new() -> Self {
+        let mut flags = CommandFlags::with_help();
+        flags.add_flag('c', "clear", "Clear the history");
+        flags.add_value('d', "delete", "N", "Delete the history entry at position N");
+        flags.add_value('s', "show", "N", "Show only the last N entries");
+        flags.add_flag('t', "time", "Show the time and directory of each entry");
+
+        Self { flags }
+    }
+
+    fn parse_number(scope: &Arc<Scope>, value: &str) -> Result<usize, String> {
+        value
+            .parse::<usize>()
+            .map_err(|e| format!("{}: {}", scope.err_str(value), e))
+    }
+}
+
+impl Exec for HistoryCommand {
+    fn cli_flags(&self) -> Box<dyn Iterator<Item = &Flag> + '_> {
+        Box::new(self.flags.iter())
+    }
+
+    fn exec(&self, _name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
+        let mut flags = self.flags.clone();
+        let patterns = flags.parse(scope, args)?;
+
+        if flags.is_present("help") {
+            println!("Usage: history [OPTIONS] [PATTERN]");
+            println!("List the command line history, or the entries matching the regex PATTERN.");
+            println!("\nOptions:");
+            print!("{}", flags.help());
+            println!();
+            println!("Examples:");
+            println!("    history -s 20");
+            println!("    history cargo");
+            println!("    history -d 42");
+            return Ok(Value::success());
+        }
+
+        let path = history_path(scope)?;
+
+        if flags.is_present("clear") {
+            return history::save(&path, &[]).map(|_| Value::success());
+        }
+
+        let mut entries = history::load(&path)?;
+
+        if let Some(n) = flags.value("delete") {
+            let index = Self::parse_number(scope, n)?;
+            if index == 0 || index > entries.len() {
+                return Err(format!(
+                    "{}: history position out of range",
+                    scope.err_str(n)
+                ));
+            }
+            entries.remove(index - 1);
+
+            return history::save(&path, &entries).map(|_| Value::success());
+        }
+
+        let regex = match patterns.as_slice() {
+            [] => None,
+            [pattern] => Some(Regex::new(pattern).map_err(|e| format!("Invalid regex: {}", e))?),
+            _ => return Err("Too many arguments".to_string()),
+        };
+
+        let skip = match flags.value("show") {
+            Some(n) => entries.len().saturating_sub(Self::parse_number(scope, n)?),
+            None => 0,
+        };
+        let show_time = flags.is_present("time");
+
+        for (i, entry) in entries.iter().enumerate().skip(skip) {
+            if regex.as_ref().is_none_or(|re| re.is_match(&entry.command)) {
+                if show_time {
+                    my_println!(
+                        "{:>5}  {:<19}  {}  {}",
+                        i + 1,
+                        entry.time_str(),
+                        entry.dir,
+                        entry.command
+                    )?;
+                } else {
+                    my_println!("{:>5}  {}", i + 1, entry.command)?;
+                }
+            }
+        }
+
+        Ok(Value::success())
+    }
+}
+
+struct FixCommand {
+    flags: CommandFlags,
+}
+
+impl FixCommand {
+    fn new() -> Self {
+        let mut flags = CommandFlags::with_help();
+        flags.add_value(
+            'e',
+            "editor",
+            "EDITOR",
+            "Use EDITOR instead of $FCEDIT or $EDITOR",
+        );
+        flags.add_flag('l', "list", "List the entries instead of editing them");
+        flags.add_flag('n', "no-numbers", "Do not show entry numbers when listing");
+        flags.add_flag('r', "reverse", "Reverse the order of the entries");
+        flags.add_flag(
+            's',
+            "substitute",
+            "Re-execute the command after replacing OLD with NEW, without editing",
+        );
+
+        Self { flags }
+    }
+
+    /// Find the index of the entry specified by a number (negative numbers are
+    /// relative to the end of the history) or by the prefix of a command.
+    fn find(scope: &Arc<Scope>, entries: &[Entry], spec: &str) -> Result<usize, String> {
+        let last = entries.len() - 1;
+        match spec.parse::<i64>() {
+            Ok(n) if n > 0 => Ok((n as usize - 1).min(last)),
+            Ok(n) => Ok(entries
+                .len()
+                .saturating_sub(n.unsigned_abs().max(1) as usize)),
+            Err(_) => entries
+                .iter()
+                .rposition(|entry| entry.command.starts_with(spec))
+                .ok_or_else(|| format!("{}: event not found", scope.err_str(spec))),
+        }
+    }
+
+    fn editor(flags: &CommandFlags, scope: &Arc<Scope>) -> String {
+        flags
+            .value("editor")
+            .map(String::from)
+            .or_else(|| scope.lookup_value("FCEDIT").map(|v| v.to_string()))
+            .filter(|editor| !editor.trim().is_empty())
+            .unwrap_or_else(|| default_editor(scope))
+    }
+}
+
+impl Exec for FixCommand {
+    fn cli_flags(&self) -> Box<dyn Iterator<Item = &Flag> + '_> {
+        Box::new(self.flags.iter())
+    }
+
+    fn exec(&self, name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
+        let mut flags = self.flags.clone();
+        // Relaxed parsing keeps negative numbers, e.g. fc -3
+        let mut fc_args = flags.parse_relaxed(scope, args);
+
+        if flags.is_present("help") {
+            println!("Usage: {} [-e EDITOR] [-r] [FIRST [LAST]]", name);
+            println!("       {} -l [-nr] [FIRST [LAST]]", name);
+            println!("       {} -s [OLD=NEW] [FIRST]", name);
+            println!("Edit the history entries from FIRST to LAST (default: the last command),");
+            println!("then evaluate the edited commands. FIRST and LAST are entry numbers,");
+            println!("offsets from the end of the history if negative, or command prefixes.");
+            println!("\nOptions:");
+            print!("{}", flags.help());
+            println!();
+            println!("Examples:");
+            println!("    fc -l -5");
+            println!("    fc -s cargo");
+            println!("    fc -s build=test");
+            return Ok(Value::success());
+        }
+
+        let path = history_path(scope)?;
+        let mut entries = history::load(&path)?;
+
+        // The fc command itself is added to the history before it runs.
+        if entries
+            .last()
+            .is_some_and(|entry| entry.command.split_whitespace().next() == Some(name))
+        {
+            entries.pop();
+        }
+        if entries.is_empty() {
+            return Err("History is empty".to_string());
+        }
+
+        let substitute = flags.is_present("substitute");
+        let replace = if substitute {
+            fc_args
+                .iter()
+                .position(|arg| arg.contains('='))
+                .map(|i| fc_args.remove(i))
+        } else {
+            None
+        };
+
+        let list = flags.is_present("list");
+        let first = match fc_args.first() {
+            Some(spec) => Self::find(scope, &entries, spec)?,
+            None if list => entries.len().saturating_sub(16),
+            None => entries.len() - 1,
+        };
+        let last = match fc_args.get(1) {
+            Some(spec) if !substitute => Self::find(scope, &entries, spec)?,
+            _ if list => entries.len() - 1,
+            _ => first,
+        };
+
+        let mut range = (first.min(last)..=first.max(last)).collect::<Vec<_>>();
+        if (first > last) != flags.is_present("reverse") {
+            range.reverse();
+        }
+
+        if list {
+            for i in range {
+                if flags.is_present("no-numbers") {
+                    my_println!("{}", entries[i].command)?;
+                } else {
+                    my_println!("{:>5}  {}", i + 1, entries[i].command)?;
+                }
+            }
+            return Ok(Value::success());
+        }
+
+        let text = range
+            .iter()
+            .map(|&i| entries[i].command.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let text = match replace {
+            Some(replace) => {
+                let (old, new) = replace.split_once('=').unwrap();
+                text.replace(old, new)
+            }
+            None if substitute => text,
+            None => edit_text(scope, &Self::editor(&flags, scope), "shmy-fc-", &text)?,
+        };
+
+        let text = text.trim().to_string();
+        if text.is_empty() {
+            return Ok(Value::success());
+        }
+
+        // Replace the fc command with the commands that it evaluates.
+        entries.push(Entry::new(&text));
+        history::save(&path, &entries)?;
+
+        eprintln!("{}", text);
+        let eval = get_command("eval").expect("eval command not registered");
+        eval.exec(name, &vec![text.clone()], scope)
+    }
+}
+
+#[ctor::ctor]
+fn register() {
+    register_command(ShellCommand {
+        name: "history".to_string(),
+        inner: Arc::new(HistoryCommand::new()),
+    });
+
+    register_command(ShellCommand {
+        name: "fc".to_string(),
+        inner: Arc::new(FixCommand::new()),
+    });
+}
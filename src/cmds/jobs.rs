@@ -0,0 +1,136 @@
+use super::{flags::CommandFlags, register_command, Exec, Flag, ShellCommand};
+use crate::job::{JobStatus, JOBS};
+use crate::{eval::Value, scope::Scope};
+use std::sync::Arc;
+
+struct Jobs {
+    flags: CommandFlags,
+}
+
+impl Jobs {
+    fn new() -> Self {
+        let flags = CommandFlags::with_help();
+        Self { flags }
+    }
+
+    /// Parse job specification, %N or N
+    fn parse_job_id(scope: &Arc<Scope>, args: &[String]) -> Result<Option<usize>, String> {
+        match args.first() {
+            None => Ok(None),
+            Some(spec) => spec
+                .trim_start_matches('%')
+                .parse::<usize>()
+                .map(Some)
+                .map_err(|_| format!("{}: invalid job specification", scope.err_str(spec))),
+        }
+    }
+
+    fn list(&self) -> Result<Value, String> {
+        for job in JOBS.lock().unwrap().jobs() {
+            my_println!("{}", job)?;
+        }
+        Ok(Value::success())
+    }
+
+    fn foreground(&self, scope: &Arc<Scope>, args: &[String]) -> Result<Value, String> {
+        let id = Self::parse_job_id(scope, args)?;
+
+        // Take the job out of the table while waiting, so that the table is not locked.
+        let mut job = JOBS
+            .lock()
+            .unwrap()
+            .remove(id)
+            .ok_or_else(|| "No such job".to_string())?;
+
+        eprintln!("{}", job.command);
+
+        match job.wait().map_err(|e| e.to_string())? {
+            JobStatus::Done(0) => Ok(Value::success()),
+            JobStatus::Done(code) => {
+                scope.set_exit_status(code as _);
+                Err(format!("exit code: {} (0x{:X})", code, code))
+            }
+            JobStatus::Running | JobStatus::Stopped => {
+                // Stopped again with Ctrl+Z
+                eprintln!("\n{}", job);
+                JOBS.lock().unwrap().restore(job);
+                Ok(Value::success())
+            }
+        }
+    }
+
+    fn background(&self, scope: &Arc<Scope>, args: &[String]) -> Result<Value, String> {
+        let id = Self::parse_job_id(scope, args)?;
+        let mut jobs = JOBS.lock().unwrap();
+
+        match jobs
+            .jobs()
+            .iter_mut()
+            .rev()
+            .find(|job| id.is_none_or(|id| job.id == id))
+        {
+            Some(job) if job.status() == JobStatus::Running => {
+                Err(format!("job {} already in background", job.id))
+            }
+            #[cfg(not(windows))]
+            Some(job) if job.status() == JobStatus::Stopped => {
+                job.resume().map_err(|e| e.to_string())?;
+                eprintln!("[{}] {} &", job.id, job.command);
+                Ok(Value::success())
+            }
+            Some(job) => Err(format!("job {} has terminated", job.id)),
+            None => Err("No such job".to_string()),
+        }
+    }
+}
+
+impl Exec for Jobs {
+    fn cli_flags(&self) -> Box<dyn Iterator<Item = &Flag> + '_> {
+        Box::new(self.flags.iter())
+    }
+
+    fn exec(&self, name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
+        let mut flags = self.flags.clone();
+        let args = flags.parse(scope, args)?;
+
+        if flags.is_present("help") {
+            match name {
+                "jobs" => {
+                    println!("Usage: jobs");
+                    println!("List background jobs.");
+                }
+                "fg" => {
+                    println!("Usage: fg [%JOB]");
+                    println!("Wait for JOB (default: the most recent job) in the foreground.");
+                }
+                "bg" => {
+                    println!("Usage: bg [%JOB]");
+                    println!("Resume JOB (default: the most recent job) in the background.");
+                }
+                _ => unreachable!(),
+            }
+            println!("\nOptions:");
+            print!("{}", flags.help());
+            return Ok(Value::success());
+        }
+
+        match name {
+            "jobs" => self.list(),
+            "fg" => self.foreground(scope, &args),
+            "bg" => self.background(scope, &args),
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[ctor::ctor]
+fn register() {
+    let jobs = Arc::new(Jobs::new());
+
+    for name in ["jobs", "fg", "bg"] {
+        register_command(ShellCommand {
+            name: name.to_string(),
+            inner: Arc::clone(&jobs) as Arc<dyn Exec>,
+        });
+    }
+}
@@ -13,18 +13,25 @@ use crossterm::{
 };
 use memmap2::Mmap;
 use std::borrow::Cow;
-use std::fs::File;
-use std::io::{self, BufRead, BufReader, Write};
-use std::path::Path;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
 enum FileAction {
     None,
-    NextFile,
-    PrevFile,
     Quit,
 }
 
+/// A file given on the command line, and the line it was last viewed at.
+struct FileEntry {
+    name: String,
+    path: PathBuf,
+    line: usize,
+}
+
 // Constant threshold for switching between strategies.
 // TODO: dynamically adapt based on available memory.
 const MEMORY_MAPPED_THRESHOLD: u64 = 10 * 1024 * 1024;
@@ -33,9 +40,15 @@ const MEMORY_MAPPED_THRESHOLD: u64 = 10 * 1024 * 1024;
 trait FileContent {
     fn len(&self) -> usize;
     fn get(&self, index: usize) -> Option<Cow<'_, str>>;
+
+    // Add bytes appended to the file, return false if not supported
+    fn append(&mut self, _data: &[u8]) -> bool {
+        false
+    }
 }
 
 // In-memory strategy
+#[derive(Default)]
 struct InMemoryContent {
     lines: Vec<String>,
 }
@@ -59,6 +72,67 @@ impl FileContent for InMemoryContent {
     }
 }
 
+// Strategy for following a growing file: the content loaded initially, plus the appended lines
+struct AppendedContent {
+    base: Box<dyn FileContent>,
+    base_len: usize, // Number of complete lines in the base content
+    lines: Vec<String>,
+    partial: Vec<u8>, // Bytes past the last newline
+}
+
+impl AppendedContent {
+    fn new(base: Box<dyn FileContent>, ends_with_newline: bool) -> Self {
+        let mut base_len = base.len();
+        let mut partial = Vec::new();
+
+        // The last line is incomplete, and the appended bytes continue it.
+        if !ends_with_newline && base_len > 0 {
+            base_len -= 1;
+            if let Some(line) = base.get(base_len) {
+                partial = line.into_owned().into_bytes();
+            }
+        }
+        Self {
+            base,
+            base_len,
+            lines: Vec::new(),
+            partial,
+        }
+    }
+}
+
+impl FileContent for AppendedContent {
+    fn len(&self) -> usize {
+        self.base_len + self.lines.len() + usize::from(!self.partial.is_empty())
+    }
+
+    fn get(&self, index: usize) -> Option<Cow<'_, str>> {
+        if index < self.base_len {
+            self.base.get(index)
+        } else if let Some(line) = self.lines.get(index - self.base_len) {
+            Some(Cow::Borrowed(line.as_str()))
+        } else if index == self.base_len + self.lines.len() && !self.partial.is_empty() {
+            Some(String::from_utf8_lossy(&self.partial))
+        } else {
+            None
+        }
+    }
+
+    fn append(&mut self, data: &[u8]) -> bool {
+        self.partial.extend_from_slice(data);
+
+        while let Some(pos) = self.partial.iter().position(|&b| b == b'\n') {
+            let mut line: Vec<u8> = self.partial.drain(..=pos).collect();
+            line.pop();
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            self.lines.push(String::from_utf8_lossy(&line).into_owned());
+        }
+        true
+    }
+}
+
 // Memory-mapped strategy
 struct MemoryMappedContent {
     mmap: Mmap,
@@ -104,6 +178,10 @@ impl MemoryMappedContent {
                 }
             }
         }
+        // Do not count an empty line after the final newline
+        if offsets.len() > 1 && offsets.last() == Some(&mmap.len()) {
+            offsets.pop();
+        }
         offsets
     }
 }
@@ -128,29 +206,130 @@ impl FileContent for MemoryMappedContent {
     }
 }
 
+// Load the file using the appropriate strategy for its size, return the content
+// and the number of bytes loaded
+fn load_file_content(path: &Path) -> io::Result<(Box<dyn FileContent>, u64)> {
+    let file = File::open(path)?;
+    let len = file.metadata()?.len();
+
+    if len > MEMORY_MAPPED_THRESHOLD {
+        let content = MemoryMappedContent::new(&file)?;
+        let len = content.mmap.len() as u64;
+        Ok((Box::new(content), len))
+    } else {
+        let reader = BufReader::new(file.take(len));
+        Ok((Box::new(InMemoryContent::new(reader)?), len))
+    }
+}
+
 // Factory function to create the appropriate FileContent instance
 fn create_file_content(
     scope: &Arc<Scope>,
     path: Option<&Path>,
-) -> io::Result<Box<dyn FileContent>> {
+) -> io::Result<(Box<dyn FileContent>, u64)> {
     if let Some(path) = path {
-        let file = File::open(path)?;
-        let metadata = file.metadata()?;
-
-        if metadata.len() > MEMORY_MAPPED_THRESHOLD {
-            Ok(Box::new(MemoryMappedContent::new(&file)?))
-        } else {
-            let reader = BufReader::new(file);
-            Ok(Box::new(InMemoryContent::new(reader)?))
-        }
+        load_file_content(path)
     } else {
         scope.show_eof_hint();
-        Ok(Box::new(InMemoryContent::new(io::stdin().lock())?))
+        Ok((Box::new(InMemoryContent::new(io::stdin().lock())?), 0))
+    }
+}
+
+/// Return the length in bytes of the ANSI escape sequence at the start of the string,
+/// if any: CSI (e.g. colors), OSC (e.g. hyperlinks), or two-character sequences.
+fn escape_len(s: &str) -> Option<usize> {
+    let bytes = s.as_bytes();
+    if bytes.first() != Some(&0x1b) {
+        return None;
+    }
+    match bytes.get(1) {
+        Some(b'[') => Some(
+            bytes[2..]
+                .iter()
+                .position(|b| (0x40..=0x7e).contains(b))
+                .map_or(bytes.len(), |i| i + 3),
+        ),
+        Some(b']') => Some(
+            (2..bytes.len())
+                .find_map(|i| match bytes[i] {
+                    0x07 => Some(i + 1),
+                    0x1b if bytes.get(i + 1) == Some(&b'\\') => Some(i + 2),
+                    _ => None,
+                })
+                .unwrap_or(bytes.len()),
+        ),
+        _ => Some(1 + s[1..].chars().next().map_or(0, char::len_utf8)),
     }
 }
 
+/// Remove ANSI escape sequences from the line.
+fn strip_ansi(line: &str) -> Cow<'_, str> {
+    if !line.contains('\x1b') {
+        return Cow::Borrowed(line);
+    }
+    let mut text = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(c) = rest.chars().next() {
+        let len = escape_len(rest).unwrap_or_else(|| {
+            text.push(c);
+            c.len_utf8()
+        });
+        rest = &rest[len..];
+    }
+    Cow::Owned(text)
+}
+
+/// Clip the line to `width` visible characters, starting at column `start`. All the escape
+/// sequences are kept, so that the colors carry over. Matches of the search are highlighted
+/// in reverse video, turned off with SGR 27 rather than a full reset, to preserve the colors.
+fn clip_ansi(line: &str, start: usize, width: usize, search: Option<&str>, buffer: &mut String) {
+    let text = strip_ansi(line);
+
+    // Mark the visible characters that are part of search matches
+    let mut highlight = vec![false; text.chars().count()];
+    if let Some(search) = search.filter(|s| !s.is_empty()) {
+        for (pos, _) in text.match_indices(search) {
+            let first = text[..pos].chars().count();
+            let count = search.chars().count();
+            highlight[first..first + count].fill(true);
+        }
+    }
+
+    let mut rest = line;
+    let mut column = 0;
+    let mut reverse = false;
+
+    while let Some(c) = rest.chars().next() {
+        if let Some(len) = escape_len(rest) {
+            buffer.push_str(&rest[..len]);
+            rest = &rest[len..];
+            continue;
+        }
+        if column >= start && column < start + width {
+            if highlight[column] != reverse {
+                reverse = highlight[column];
+                buffer.push_str(if reverse { "\x1b[7m" } else { "\x1b[27m" });
+            }
+            buffer.push(c);
+        }
+        column += 1;
+        rest = &rest[c.len_utf8()..];
+    }
+    buffer.push_str("\x1b[0m");
+}
+
+/// Return how many screen rows a line takes when wrapped at the given width.
+fn row_count(line: &str, width: usize) -> usize {
+    strip_ansi(line)
+        .chars()
+        .count()
+        .div_ceil(width.max(1))
+        .max(1)
+}
+
 #[derive(Clone, Debug, PartialEq)]
 struct ViewerState {
+    chop_long_lines: bool,
     current_line: usize,
     horizontal_scroll: usize,
     last_search: Option<String>,
@@ -164,6 +343,7 @@ struct ViewerState {
 impl ViewerState {
     fn new() -> Self {
         Self {
+            chop_long_lines: false,
             current_line: 0,
             horizontal_scroll: 0,
             redraw: false,
@@ -177,25 +357,37 @@ impl ViewerState {
 }
 
 struct Viewer {
-    file_info: Option<String>,
+    files: Vec<FileEntry>, // Empty when viewing stdin
+    index: usize,          // Index of the current file
+    follow: bool,          // Start in follow mode
     lines: Box<dyn FileContent>,
+    size: u64, // Bytes of the current file loaded into lines
     line_num_width: usize,
+    page_end: usize,                      // Index past the last line displayed
+    count: Option<usize>,                 // Numeric prefix for commands such as g and G
+    marks: HashMap<char, (usize, usize)>, // File index and line for each mark
     screen_width: usize,
     screen_height: usize,
     state: ViewerState,
 }
 
 impl Viewer {
-    fn new(scope: &Arc<Scope>, file_info: Option<String>, path: Option<&Path>) -> io::Result<Self> {
-        let content = create_file_content(scope, path)?;
+    fn new(scope: &Arc<Scope>, files: Vec<FileEntry>) -> io::Result<Self> {
+        let (content, size) = create_file_content(scope, files.first().map(|f| f.path.as_path()))?;
         let line_num_width = content.len().to_string().len() + 1;
 
         let (w, h) = crossterm::terminal::size().unwrap_or((80, 24));
 
         Ok(Self {
-            file_info,
+            files,
+            index: 0,
+            follow: false,
             lines: content,
+            size,
             line_num_width,
+            page_end: 0,
+            count: None,
+            marks: HashMap::new(),
             screen_width: w as usize,
             screen_height: h.saturating_sub(1) as usize,
             state: ViewerState::new(),
@@ -209,23 +401,48 @@ impl Viewer {
     fn display_page<W: Write>(&mut self, stdout: &mut W, buffer: &mut String) -> io::Result<()> {
         buffer.clear();
 
-        let end = (self.state.current_line + self.screen_height).min(self.lines.len());
+        let width = self.text_width();
+        let mut rows = 0;
+        let mut index = self.state.current_line;
+
+        while rows < self.screen_height && index < self.lines.len() {
+            let Some(line) = self.lines.get(index) else {
+                break;
+            };
+            let line = line.trim_end();
+            let count = if self.state.chop_long_lines {
+                1
+            } else {
+                row_count(line, width)
+            };
 
-        for index in self.state.current_line..end {
-            buffer.push_str("\x1b[2K"); // Clear line
+            for row in 0..count.min(self.screen_height - rows) {
+                buffer.push_str("\x1b[2K"); // Clear line
 
-            if self.state.show_line_numbers {
-                let line_number = index + 1;
-                buffer.push_str(&format!("{:>w$}  ", line_number, w = self.line_num_width));
-            }
+                if self.state.show_line_numbers {
+                    if row == 0 {
+                        let line_number = index + 1;
+                        buffer.push_str(&format!("{:>w$}  ", line_number, w = self.line_num_width));
+                    } else {
+                        buffer.push_str(&" ".repeat(self.line_num_width + 2));
+                    }
+                }
 
-            if let Some(line) = self.lines.get(index) {
-                self.display_line(&line.trim_end(), buffer)?;
+                if self.state.chop_long_lines {
+                    self.display_line(line, buffer)?;
+                } else {
+                    let search = self.state.last_search.as_deref();
+                    clip_ansi(line, row * width, width, search, buffer);
+                    buffer.push_str("\r\n");
+                }
             }
+            rows += count;
+            index += 1;
         }
+        self.page_end = index;
 
         // Fill any remaining empty lines
-        for _ in end..self.state.current_line + self.screen_height {
+        for _ in rows..self.screen_height {
             buffer.push_str("\x1b[2K~\r\n");
         }
 
@@ -240,21 +457,100 @@ impl Viewer {
         )?;
 
         // Update the "status / hints" line
-        if let Some(ref message) = self.state.status_line {
+        if let Some(message) = self.state.status_line.take() {
             write!(stdout, "{}", message)?;
+        } else if let Some(status) = self.file_status() {
+            write!(stdout, "{}", self.strong(&status))?;
         } else {
             write!(stdout, ":")?;
         }
         stdout.flush()?;
 
-        self.state.status_line = self
-            .file_info
-            .as_ref()
-            .and_then(|info| Some(self.strong(&info)));
-
         Ok(())
     }
 
+    /// Return the first and last line numbers shown on the page (1-based), and the line count.
+    fn page_range(&self) -> (usize, usize, usize) {
+        let total = self.lines.len();
+        let first = (self.state.current_line + 1).min(total);
+        (first, self.page_end.min(total), total)
+    }
+
+    /// Describe the current file and position, e.g. "notes.txt (file 1 of 3) lines 1-23/120".
+    fn file_status(&self) -> Option<String> {
+        let file = self.files.get(self.index)?;
+        let (first, last, total) = self.page_range();
+        Some(format!(
+            "{} (file {} of {}) lines {}-{}/{}",
+            file.name,
+            self.index + 1,
+            self.files.len(),
+            first,
+            last,
+            total
+        ))
+    }
+
+    /// Show the file name, size and position in the file on the status line.
+    fn show_file_info(&mut self) {
+        let (_, last, total) = self.page_range();
+        let mut info = self
+            .file_status()
+            .unwrap_or_else(|| format!("Standard input lines {}/{}", last, total));
+
+        if let Some(Ok(metadata)) = self.path().map(fs::metadata) {
+            info.push_str(&format!(" {} bytes", metadata.len()));
+        }
+        if let Some(percent) = (last * 100).checked_div(total) {
+            info.push_str(&format!(" {}%", percent));
+        }
+        self.show_status(&info);
+    }
+
+    fn path(&self) -> Option<&Path> {
+        self.files.get(self.index).map(|f| f.path.as_path())
+    }
+
+    /// Switch to the next or previous file, remembering the position in the current one.
+    fn switch_file(&mut self, forward: bool) {
+        let index = if forward {
+            self.index + 1
+        } else {
+            self.index.wrapping_sub(1)
+        };
+        if index >= self.files.len() {
+            let which = if forward { "next" } else { "previous" };
+            self.show_status(&format!("No {} file", which));
+            return;
+        }
+        self.open_file(index);
+    }
+
+    /// Load the file at the given index in the list, and restore its last position.
+    fn open_file(&mut self, index: usize) -> bool {
+        let file = &self.files[index];
+
+        match load_file_content(&file.path) {
+            Ok((lines, size)) => {
+                let line = file.line;
+                self.files[self.index].line = self.state.current_line;
+                self.index = index;
+                self.lines = lines;
+                self.size = size;
+                self.line_num_width = self.lines.len().to_string().len() + 1;
+                self.state.current_line = line.min(self.lines.len().saturating_sub(1));
+                self.state.horizontal_scroll = 0;
+                self.state.search_start_index = 0;
+                true
+            }
+            Err(e) => {
+                let message = format!("{}: {}", file.name, e);
+                self.show_status(&message);
+                false
+            }
+        }
+    }
+
     fn display_line(&self, line: &str, buffer: &mut String) -> io::Result<()> {
         fn adjust_index_to_utf8_boundary(line: &str, index: usize) -> usize {
             if index >= line.len() {
@@ -267,12 +563,21 @@ impl Viewer {
                 .map_or(0, |(i, _)| i)
         }
 
-        // Determine the effective width of the line to be displayed
-        let effective_width = if self.state.show_line_numbers {
-            self.screen_width.saturating_sub(self.line_num_width + 2)
-        } else {
-            self.screen_width
-        };
+        let effective_width = self.text_width();
+
+        // Lines with escape sequences (e.g. colors) are clipped by visible characters.
+        if line.contains('\x1b') {
+            let search = self.state.last_search.as_deref();
+            clip_ansi(
+                line,
+                self.state.horizontal_scroll,
+                effective_width,
+                search,
+                buffer,
+            );
+            buffer.push_str("\r\n");
+            return Ok(());
+        }
 
         // Compute the starting point based on horizontal scroll
         let start_index = self.state.horizontal_scroll.min(line.len());
@@ -310,30 +615,156 @@ impl Viewer {
         Ok(())
     }
 
+    /// Read the bytes appended to the file since it was last read, and return true if
+    /// there were any. Reload the whole file if it got shorter (truncated or rotated).
+    fn read_appended(&mut self, path: &Path) -> io::Result<bool> {
+        let mut file = File::open(path)?;
+        let len = file.metadata()?.len();
+
+        if len == self.size {
+            return Ok(false);
+        }
+        if len < self.size {
+            (self.lines, self.size) = load_file_content(path)?;
+        } else {
+            // Read from the last byte already loaded, to tell if it ends a line.
+            let start = self.size.saturating_sub(1);
+            file.seek(SeekFrom::Start(start))?;
+            let mut data = Vec::new();
+            file.take(len - start).read_to_end(&mut data)?;
+
+            let (ends_with_newline, data) = if self.size == 0 {
+                (true, &data[..])
+            } else {
+                (
+                    data.first() == Some(&b'\n'),
+                    data.get(1..).unwrap_or_default(),
+                )
+            };
+            if !self.lines.append(data) {
+                let base = std::mem::replace(&mut self.lines, Box::new(InMemoryContent::default()));
+                let mut content = AppendedContent::new(base, ends_with_newline);
+                content.append(data);
+                self.lines = Box::new(content);
+            }
+            self.size += data.len() as u64;
+        }
+        self.line_num_width = self.lines.len().to_string().len() + 1;
+        Ok(true)
+    }
+
+    /// Keep reading the file as it grows, showing the last page, until a key is pressed.
+    fn follow<W: Write>(&mut self, stdout: &mut W) -> io::Result<()> {
+        let Some(path) = self.path().map(Path::to_path_buf) else {
+            self.show_status("Cannot follow the standard input");
+            return Ok(());
+        };
+        let name = self.files[self.index].name.clone();
+        let mut buffer = String::new();
+        let mut error = None;
+        let mut redraw = true;
+
+        loop {
+            match self.read_appended(&path) {
+                Ok(grown) => {
+                    redraw |= grown || error.is_some();
+                    error = None;
+                }
+                Err(e) => {
+                    let message = format!("{}: {}", name, e);
+                    redraw |= error.as_ref() != Some(&message);
+                    error = Some(message);
+                }
+            }
+            if redraw {
+                self.last_page();
+                match &error {
+                    Some(message) => self.show_status(message),
+                    None => self.show_status("Waiting for data... (press any key to stop)"),
+                }
+                self.display_page(stdout, &mut buffer)?;
+                redraw = false;
+            }
+
+            if event::poll(Duration::from_millis(250))? {
+                match event::read()? {
+                    Event::Key(key_event) if key_event.kind == KeyEventKind::Press => break,
+                    Event::Resize(w, h) => {
+                        self.screen_width = w.into();
+                        self.screen_height = h.saturating_sub(1).into();
+                        redraw = true;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn goto(&mut self, number: usize) {
+        if number < 1 || number > self.lines.len() {
+            self.show_status(&self.strong(&format!(
+                "{} is out of range: [1..{}]",
+                number,
+                self.lines.len()
+            )));
+        } else {
+            self.save_position();
+            self.state.current_line = number.saturating_sub(1);
+        }
+    }
+
     fn goto_line(&mut self, cmd: &str) {
         let num_str = cmd.trim();
 
         if let Ok(number) = num_str.parse::<usize>() {
-            if number < 1 || number > self.lines.len() {
-                self.show_status(&self.strong(&format!(
-                    "{} is out of range: [1..{}]",
-                    number,
-                    self.lines.len()
-                )));
-            } else {
-                self.state.current_line = number.saturating_sub(1);
-            }
+            self.goto(number);
         } else {
             self.show_status(&self.strong("Invalid line number"));
         }
     }
 
+    /// Jump to a position saved with `m`; `'` is the position before the last jump.
+    fn goto_mark(&mut self, mark: char) {
+        let Some(&(index, line)) = self.marks.get(&mark) else {
+            self.show_status(&format!("Mark not set: {}", mark));
+            return;
+        };
+        let previous = (self.index, self.state.current_line);
+
+        if index == self.index || self.open_file(index) {
+            self.state.current_line = line.min(self.lines.len().saturating_sub(1));
+            self.marks.insert('\'', previous);
+        }
+    }
+
+    /// Remember the current position, so that it can be returned to with ''.
+    fn save_position(&mut self) {
+        self.marks
+            .insert('\'', (self.index, self.state.current_line));
+    }
+
     fn last_page(&mut self) {
-        if self.lines.len() == 0 {
-            self.state.current_line = 0;
-        } else {
+        if self.state.chop_long_lines {
             self.state.current_line = self.lines.len().saturating_sub(self.screen_height);
+            return;
         }
+
+        // Walk back from the end until the wrapped lines fill the screen.
+        let width = self.text_width();
+        let mut rows = 0;
+        let mut line = self.lines.len();
+        while line > 0 {
+            rows += self
+                .lines
+                .get(line - 1)
+                .map_or(1, |s| row_count(s.trim_end(), width));
+            if rows > self.screen_height && line < self.lines.len() {
+                break;
+            }
+            line -= 1;
+        }
+        self.state.current_line = line;
     }
 
     fn next_line(&mut self) {
@@ -343,8 +774,12 @@ impl Viewer {
     }
 
     fn next_page(&mut self) {
-        let new_line =
-            (self.state.current_line + self.screen_height).min(self.lines.len().saturating_sub(1));
+        let end = if self.state.chop_long_lines {
+            self.state.current_line + self.screen_height
+        } else {
+            self.page_end
+        };
+        let new_line = end.min(self.lines.len().saturating_sub(1));
         if new_line > self.state.current_line {
             self.state.current_line = new_line;
         }
@@ -361,7 +796,10 @@ impl Viewer {
     }
 
     fn scroll_right(&mut self) {
-        self.state.horizontal_scroll += 1;
+        // Wrapped lines are shown in full, there is nothing to scroll.
+        if self.state.chop_long_lines {
+            self.state.horizontal_scroll += 1;
+        }
     }
 
     fn scroll_left(&mut self) {
@@ -401,7 +839,7 @@ impl Viewer {
                 break;
             }
 
-            if let Some(pos) = self.lines.get(i).and_then(|s| s.find(query)) {
+            if let Some(pos) = self.lines.get(i).and_then(|s| strip_ansi(&s).find(query)) {
                 found = true;
                 self.state.current_line = i;
 
@@ -434,7 +872,7 @@ impl Viewer {
         }
     }
 
-    fn run(&mut self) -> io::Result<FileAction> {
+    fn run(&mut self) -> io::Result<()> {
         let mut stdout = io::stdout();
         let _raw_mode = prompt::RawMode::new()?;
         execute!(stdout, EnterAlternateScreen, cursor::MoveTo(0, 0),)?;
@@ -444,6 +882,11 @@ impl Viewer {
 
         self.display_page(&mut stdout, &mut buffer)?;
 
+        if self.follow {
+            self.follow(&mut stdout)?;
+            self.display_page(&mut stdout, &mut buffer)?;
+        }
+
         while matches!(action, FileAction::None) {
             let mut state = self.state.clone();
 
@@ -463,7 +906,7 @@ impl Viewer {
             }
         }
         execute!(stdout, LeaveAlternateScreen)?;
-        Ok(action)
+        Ok(())
     }
 
     fn process_key_code(
@@ -473,6 +916,7 @@ impl Viewer {
         stdout: &mut io::Stdout,
     ) -> io::Result<FileAction> {
         let mut action = FileAction::None;
+        let count = self.count.take();
 
         match key_code {
             KeyCode::F(1) => self.show_help(),
@@ -480,9 +924,11 @@ impl Viewer {
             KeyCode::Char(':') => {
                 let cmd = self.prompt_for_command(":")?;
                 if cmd == "n" {
-                    action = FileAction::NextFile;
+                    self.switch_file(true);
+                    state.redraw = true;
                 } else if cmd == "p" {
-                    action = FileAction::PrevFile;
+                    self.switch_file(false);
+                    state.redraw = true;
                 } else if cmd == "q" {
                     action = FileAction::Quit;
                 } else if cmd.is_empty() {
@@ -497,7 +943,56 @@ impl Viewer {
             KeyCode::Char('b') => self.prev_page(),
             KeyCode::Char('f') => self.next_page(),
             KeyCode::Char(' ') => self.next_page(),
-            KeyCode::Char('G') => self.last_page(),
+            KeyCode::Char(c @ '0'..='9') => {
+                let digit = c.to_digit(10).unwrap_or_default() as usize;
+                let number = count.unwrap_or(0).saturating_mul(10).saturating_add(digit);
+                self.count = Some(number);
+                self.state.status_line = Some(format!(":{}", number));
+            }
+            KeyCode::Char('g') => match count {
+                Some(number) => self.goto(number),
+                None => {
+                    self.save_position();
+                    self.state.current_line = 0;
+                }
+            },
+            KeyCode::Char('G') => match count {
+                Some(number) => self.goto(number),
+                None => {
+                    self.save_position();
+                    self.last_page();
+                }
+            },
+            KeyCode::Char('m') => {
+                match self.prompt_for_char("mark: ")? {
+                    Some(c) if c.is_ascii_alphabetic() => {
+                        self.marks.insert(c, (self.index, self.state.current_line));
+                    }
+                    Some(_) => self.show_status("Invalid mark"),
+                    None => {}
+                }
+                state.redraw = true;
+            }
+            KeyCode::Char('\'') => {
+                if let Some(c) = self.prompt_for_char("goto mark: ")? {
+                    self.goto_mark(c);
+                }
+                state.redraw = true;
+            }
+            KeyCode::Char('S') => {
+                self.state.chop_long_lines = !self.state.chop_long_lines;
+                self.state.horizontal_scroll = 0;
+                if self.state.chop_long_lines {
+                    self.show_status("Chop long lines");
+                } else {
+                    self.show_status("Wrap long lines");
+                }
+            }
+            KeyCode::Char('=') => self.show_file_info(),
+            KeyCode::Char('F') => {
+                self.follow(stdout)?;
+                state.redraw = true;
+            }
             KeyCode::Esc => self.clear_search(),
             KeyCode::Enter => self.next_line(),
             KeyCode::Up => self.prev_line(),
@@ -564,6 +1059,27 @@ impl Viewer {
         Ok(cmd.trim().to_string())
     }
 
+    /// Read a single character, e.g. the name of a mark; None if another key is pressed.
+    fn prompt_for_char(&mut self, prompt: &str) -> io::Result<Option<char>> {
+        let mut stdout = io::stdout();
+        stdout
+            .queue(cursor::MoveTo(0, self.screen_height as u16))?
+            .queue(Clear(ClearType::CurrentLine))?
+            .queue(Print(prompt))?
+            .flush()?;
+
+        loop {
+            if let Event::Key(key_event) = event::read()? {
+                if key_event.kind == KeyEventKind::Press {
+                    if let KeyCode::Char(c) = key_event.code {
+                        return Ok(Some(c));
+                    }
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
     /// Print a message at the bottom of the screen on the "status" line
     fn show_status(&mut self, message: &str) {
         self.state.status_line = Some(self.strong(message))
@@ -578,7 +1094,9 @@ impl Viewer {
             ("?", "Search Backward"),
             (":n", "Next File"),
             (":p", "Prev File"),
+            ("=", "File Info"),
             (":q", "Quit"),
+            ("F", "Follow"),
         ];
 
         let help_text = help_items
@@ -590,6 +1108,15 @@ impl Viewer {
         self.show_status(&help_text)
     }
 
+    /// Return the number of columns available for text, excluding the line numbers.
+    fn text_width(&self) -> usize {
+        if self.state.show_line_numbers {
+            self.screen_width.saturating_sub(self.line_num_width + 2)
+        } else {
+            self.screen_width
+        }
+    }
+
     fn strong<'a>(&self, s: &'a str) -> String {
         format!("\x1b[7m{}\x1b[0m", s)
     }
@@ -603,6 +1130,16 @@ impl Less {
     fn new() -> Self {
         let mut flags = CommandFlags::with_help();
         flags.add_flag('n', "number", "Number output lines");
+        flags.add_flag(
+            'S',
+            "chop-long-lines",
+            "Chop long lines instead of wrapping them",
+        );
+        flags.add_flag(
+            'F',
+            "follow",
+            "Keep reading the end of the file as it grows, like tail -f",
+        );
         Self { flags }
     }
 }
@@ -620,85 +1157,94 @@ impl Exec for Less {
             println!("View FILE(s) or the standard input (stdin) in a pager.");
             println!("\nUser Interaction:");
             println!("  Navigation:");
-            println!("    {:<20} {}", "Up Arrow", "Move one line up.");
-            println!("    {:<20} {}", "Down Arrow", "Move one line down.");
-            println!("    {:<20} {}", "Left Arrow", "Scroll horizontally left.");
-            println!("    {:<20} {}", "Right Arrow", "Scroll horizontally right.");
-            println!("    {:<20} {}", "PageUp", "Go to the previous page.");
-            println!("    {:<20} {}", "b", "Go to the previous page.");
-            println!("    {:<20} {}", "PageDown", "Go to the next page.");
-            println!("    {:<20} {}", "f", "Go to the next page.");
-            println!("    {:<20} {}", "Space", "Go to the next page.");
-            println!("    {:<20} {}", "G", "Go to the last page.");
-            println!("    {:<20} {}", ":N", "Go to line number N (1-based).");
-            println!("    {:<20} {}", ":n", "Load the next file.");
-            println!("    {:<20} {}", ":p", "Load the previous file.");
-            println!("    {:<20} {}", ":q", "Quit the viewer.");
-            println!("    {:<20} {}", "q", "Quit the viewer.");
-            println!("\n  Search:");
-            println!("    {:<20} {}", "/", "Search forward.");
-            println!("    {:<20} {}", "?", "Search backward.");
+            println!("    {:<20} Move one line up.", "Up Arrow");
+            println!("    {:<20} Move one line down.", "Down Arrow");
+            println!("    {:<20} Scroll horizontally left.", "Left Arrow");
+            println!("    {:<20} Scroll horizontally right.", "Right Arrow");
+            println!("    {:<20} Go to the previous page.", "PageUp");
+            println!("    {:<20} Go to the previous page.", "b");
+            println!("    {:<20} Go to the next page.", "PageDown");
+            println!("    {:<20} Go to the next page.", "f");
+            println!("    {:<20} Go to the next page.", "Space");
             println!(
-                "    {:<20} {}",
-                "n", "Repeat the last search (preserving the direction)."
+                "    {:<20} Go to the first line, or to line N if preceded by a number N.",
+                "g"
             );
-            println!("    {:<20} {}", "Esc", "Clear the search.");
-            println!("\n  Miscellaneous:");
             println!(
-                "    {:<20} {}",
-                "l", "Toggle line numbering for the current file."
+                "    {:<20} Go to the last page, or to line N if preceded by a number N.",
+                "G"
+            );
+            println!(
+                "    {:<20} Mark the current position with the letter.",
+                "m<letter>"
+            );
+            println!(
+                "    {:<20} Go to the marked position ('' goes back to before the last jump).",
+                "'<letter>"
+            );
+            println!(
+                "    {:<20} Follow the end of the file as it grows, until a key is pressed.",
+                "F"
+            );
+            println!("    {:<20} Go to line number N (1-based).", ":N");
+            println!("    {:<20} Load the next file.", ":n");
+            println!("    {:<20} Load the previous file.", ":p");
+            println!(
+                "    {:<20} Show the file name, size, and position in the file.",
+                "="
+            );
+            println!("    {:<20} Quit the viewer.", ":q");
+            println!("    {:<20} Quit the viewer.", "q");
+            println!("\n  Search:");
+            println!("    {:<20} Search forward.", "/");
+            println!("    {:<20} Search backward.", "?");
+            println!(
+                "    {:<20} Repeat the last search (preserving the direction).",
+                "n"
             );
+            println!("    {:<20} Clear the search.", "Esc");
+            println!("\n  Miscellaneous:");
             println!(
-                "    {:<20} {}",
-                "h", "Show hints at the bottom of the screen."
+                "    {:<20} Toggle line numbering for the current file.",
+                "l"
             );
             println!(
-                "    {:<20} {}",
-                "F1", "Show hints at the bottom of the screen."
+                "    {:<20} Toggle between chopping and wrapping long lines.",
+                "S"
             );
+            println!("    {:<20} Show hints at the bottom of the screen.", "h");
+            println!("    {:<20} Show hints at the bottom of the screen.", "F1");
 
             return Ok(Value::success());
         }
 
-        if filenames.is_empty() {
-            run_viewer(scope, &flags, None, None).map_err(|e| e.to_string())?;
-        } else {
-            let mut i: usize = 0;
-            loop {
-                let filename = filenames.get(i).unwrap();
+        let files = filenames
+            .iter()
+            .map(|filename| {
                 let path = Path::new(filename)
                     .dereference()
                     .map_err(|e| format_error(&scope, filename, args, e))?;
+                Ok(FileEntry {
+                    name: filename.clone(),
+                    path: path.into_owned(),
+                    line: 0,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
 
-                match run_viewer(
-                    scope,
-                    &flags,
-                    Some(&path),
-                    Some(format!("{} ({} of {})", filename, i + 1, filenames.len())),
-                )
-                .map_err(|e| e.to_string())?
-                {
-                    FileAction::PrevFile => i = i.saturating_sub(1),
-                    FileAction::NextFile => i = std::cmp::min(i + 1, filenames.len() - 1),
-                    FileAction::Quit => break,
-                    FileAction::None => {}
-                }
-            }
-        };
+        // All files are viewed in one session, switching with :n and :p
+        run_viewer(scope, &flags, files).map_err(|e| e.to_string())?;
 
         Ok(Value::success())
     }
 }
 
-fn run_viewer(
-    scope: &Arc<Scope>,
-    flags: &CommandFlags,
-    path: Option<&Path>,
-    file_info: Option<String>,
-) -> io::Result<FileAction> {
-    let mut viewer = Viewer::new(scope, file_info, path)?;
+fn run_viewer(scope: &Arc<Scope>, flags: &CommandFlags, files: Vec<FileEntry>) -> io::Result<()> {
+    let mut viewer = Viewer::new(scope, files)?;
 
     viewer.state.show_line_numbers = flags.is_present("number");
+    viewer.state.chop_long_lines = flags.is_present("chop-long-lines");
+    viewer.follow = flags.is_present("follow");
     viewer.run()
 }
 
@@ -709,3 +1255,44 @@ fn register() {
         inner: Arc::new(Less::new()),
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_row_count() {
+        assert_eq!(row_count("", 10), 1);
+        assert_eq!(row_count("0123456789", 10), 1);
+        assert_eq!(row_count("0123456789a", 10), 2);
+        assert_eq!(row_count("\x1b[31m0123456789\x1b[0m", 10), 1);
+        assert_eq!(row_count("ééé", 2), 2);
+    }
+
+    #[test]
+    fn test_ansi() {
+        let line = "\x1b[31mred\x1b[0m plain \x1b]8;;file:///x\x1b\\link\x1b]8;;\x1b\\";
+        assert_eq!(strip_ansi(line), "red plain link");
+
+        let mut buffer = String::new();
+        clip_ansi("\x1b[31mred\x1b[0m plain", 1, 4, None, &mut buffer);
+        assert_eq!(buffer, "\x1b[31med\x1b[0m p\x1b[0m");
+
+        buffer.clear();
+        clip_ansi("\x1b[32mabc\x1b[0m", 0, 10, Some("b"), &mut buffer);
+        assert_eq!(buffer, "\x1b[32ma\x1b[7mb\x1b[27mc\x1b[0m\x1b[0m");
+    }
+
+    #[test]
+    fn test_appended_content() {
+        let base = InMemoryContent::new("one\ntw".as_bytes()).unwrap();
+        let mut content = AppendedContent::new(Box::new(base), false);
+        assert_eq!(content.len(), 2);
+
+        assert!(content.append(b"o\r\nthr"));
+        assert!(content.append(b"ee\n"));
+        let lines: Vec<_> = (0..content.len()).filter_map(|i| content.get(i)).collect();
+        assert_eq!(lines, ["one", "two", "three"]);
+        assert!(content.get(3).is_none());
+    }
+}
@@ -10,6 +10,8 @@ struct Link {
 
 struct Options {
     symbolic: bool,
+    #[cfg(windows)]
+    junction: bool,
     force: bool,
     target: Option<String>,
     link_name: Option<String>,
@@ -20,6 +22,12 @@ impl Link {
         let mut flags = CommandFlags::with_help();
         flags.add_flag('s', "symbolic", "Make symbolic links instead of hard links");
         flags.add_flag('f', "force", "Remove existing destination files");
+        #[cfg(windows)]
+        flags.add_flag(
+            'j',
+            "junction",
+            "Make a directory junction (does not require elevation)",
+        );
 
         Self { flags }
     }
@@ -31,6 +39,8 @@ impl Link {
         if flags.is_present("help") {
             return Ok(Options {
                 symbolic: false,
+                #[cfg(windows)]
+                junction: false,
                 force: false,
                 target: None,
                 link_name: None,
@@ -43,6 +53,8 @@ impl Link {
 
         Ok(Options {
             symbolic: flags.is_present("symbolic"),
+            #[cfg(windows)]
+            junction: flags.is_present("junction"),
             force: flags.is_present("force"),
             target: Some(parsed_args[0].clone()),
             link_name: Some(parsed_args[1].clone()),
@@ -54,6 +66,11 @@ impl Link {
         println!("Create a link to TARGET with the name LINK_NAME.");
         println!("\nOptions:");
         print!("{}", self.flags.help());
+        println!();
+        println!("Examples:");
+        println!("    ln -s ../config/settings.yaml settings.yaml");
+        #[cfg(windows)]
+        println!("    ln --junction C:\\Projects\\shmy shmy");
     }
 }
 
@@ -88,8 +105,17 @@ fn create_link(
     let target_path = Path::new(target);
     let link_path = Path::new(link_name);
 
-    if opts.force && link_path.exists() {
-        fs::remove_file(link_path).map_err(|error| {
+    if opts.force && fs::symlink_metadata(link_path).is_ok() {
+        #[cfg(windows)]
+        let result = if link_path.is_symlink() {
+            crate::utils::win::remove_link(link_path)
+        } else {
+            fs::remove_file(link_path)
+        };
+        #[cfg(not(windows))]
+        let result = fs::remove_file(link_path);
+
+        result.map_err(|error| {
             format!(
                 "Failed to remove existing {}: {}",
                 scope.err_path(link_path),
@@ -99,7 +125,9 @@ fn create_link(
     }
 
     #[cfg(windows)]
-    let result = if opts.symbolic {
+    let result = if opts.junction {
+        crate::utils::win::create_junction(target_path, link_path)
+    } else if opts.symbolic {
         use std::os::windows::fs as windows_fs;
         if target_path.is_dir() {
             windows_fs::symlink_dir(target_path, link_path)
@@ -118,7 +146,18 @@ fn create_link(
         fs::hard_link(target_path, link_path)
     };
 
-    result.map_err(|e| format!("Failed to create link: {}", e))?;
+    result.map_err(|e| {
+        // ERROR_PRIVILEGE_NOT_HELD
+        if cfg!(windows) && opts.symbolic && e.raw_os_error() == Some(1314) {
+            format!(
+                "{}: Creating symbolic links requires Administrator privileges or Developer Mode \
+                (use --junction to link directories without elevation)",
+                scope.err_path(link_path)
+            )
+        } else {
+            format!("Failed to create link {}: {}", scope.err_path(link_path), e)
+        }
+    })?;
 
     Ok(Value::success())
 }
@@ -44,6 +44,8 @@ impl ColorScheme {
                 return file_name.blue().bold();
             } else if metadata.is_symlink() {
                 return file_name.cyan().bold();
+            } else if is_executable(file_name, metadata) {
+                return file_name.green().bold();
             }
         }
         return file_name.normal();
@@ -98,10 +100,20 @@ struct Dir {
     flags: CommandFlags,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SortBy {
+    Name,
+    Size,
+    Time,
+}
+
 struct Options {
     all_files: bool,
     show_details: bool,
     human_readable: bool,
+    recursive: bool,
+    sort_by: SortBy,
+    reverse: bool,
     help: bool,
     paths: Vec<String>,
     colors: ColorScheme,
@@ -120,6 +132,16 @@ impl Dir {
             "Print sizes in human readable format (e.g., 1K, 234M, 2G)",
         );
         flags.add_flag('u', "utc", "Show file times in UTC");
+        flags.add_flag('R', "recursive", "List subdirectories recursively");
+        flags.add_flag('S', "sort-size", "Sort by file size, largest first");
+        flags.add_flag('t', "sort-time", "Sort by modification time, newest first");
+        flags.add(
+            None,
+            "sort",
+            Some("WORD".to_string()),
+            "Sort by WORD: name (default), size or time",
+        );
+        flags.add_flag('r', "reverse", "Reverse the sort order");
 
         Self { flags }
     }
@@ -128,10 +150,32 @@ impl Dir {
         let mut flags = self.flags.clone();
         let parsed_args = flags.parse(scope, args)?;
 
+        let sort_by = match flags.value("sort") {
+            None | Some("name") => SortBy::Name,
+            Some("size") => SortBy::Size,
+            Some("time") => SortBy::Time,
+            Some(word) => {
+                return Err(format!(
+                    "Invalid sort: {} (expecting name, size or time)",
+                    scope.err_str(word)
+                ))
+            }
+        };
+        let sort_by = if flags.is_present("sort-size") {
+            SortBy::Size
+        } else if flags.is_present("sort-time") {
+            SortBy::Time
+        } else {
+            sort_by
+        };
+
         let cmd_args = Options {
             all_files: flags.is_present("all"),
             show_details: flags.is_present("long"),
             human_readable: flags.is_present("human-readable"),
+            recursive: flags.is_present("recursive"),
+            sort_by,
+            reverse: flags.is_present("reverse"),
             help: flags.is_present("help"),
             paths: if parsed_args.is_empty() {
                 vec![".".to_string()]
@@ -151,6 +195,11 @@ impl Dir {
         println!("List information about the FILEs (the current directory by default).");
         println!("\nOptions:");
         print!("{}", self.flags.help());
+        println!();
+        println!("Examples:");
+        println!("    ls -lh");
+        println!("    ls -ltr src");
+        println!("    ls -R --sort size");
     }
 }
 
@@ -337,6 +386,46 @@ fn is_hidden(_: &Metadata) -> bool {
     false
 }
 
+#[cfg(unix)]
+fn is_executable(_: &str, metadata: &Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(file_name: &str, metadata: &Metadata) -> bool {
+    metadata.is_file()
+        && Path::new(file_name)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| {
+                ["exe", "bat", "cmd", "com", "ps1"]
+                    .iter()
+                    .any(|e| ext.eq_ignore_ascii_case(e))
+            })
+}
+
+/// Sort the entries by name, size (largest first) or modification time (newest first).
+fn sort_entries(entries: &mut [DirEntry], opts: &Options) {
+    match opts.sort_by {
+        SortBy::Name => entries.sort_by_key(|e| e.file_name()),
+        SortBy::Size => entries.sort_by_cached_key(|e| {
+            let size = e.metadata().map(|m| m.len()).unwrap_or_default();
+            (std::cmp::Reverse(size), e.file_name())
+        }),
+        SortBy::Time => entries.sort_by_cached_key(|e| {
+            let time = e
+                .metadata()
+                .and_then(|m| m.modified())
+                .unwrap_or(UNIX_EPOCH);
+            (std::cmp::Reverse(time), e.file_name())
+        }),
+    }
+    if opts.reverse {
+        entries.reverse();
+    }
+}
+
 fn list_entries(
     scope: &Arc<Scope>,
     opts: &mut Options,
@@ -374,9 +463,9 @@ fn print_dir(scope: &Arc<Scope>, path: &Path, args: &Options) -> Result<(), Stri
         .collect::<Result<_, _>>()
         .map_err(|e| format!("Error reading entries: {}", e))?;
 
-    entries.sort_by_key(|e| e.file_name());
+    sort_entries(&mut entries, args);
 
-    if args.paths.len() > 1 {
+    if args.paths.len() > 1 || args.recursive {
         my_println!("\n{}:", path.display())?;
     }
 
@@ -385,6 +474,26 @@ fn print_dir(scope: &Arc<Scope>, path: &Path, args: &Options) -> Result<(), Stri
     } else {
         print_simple_entries(&entries, &args, 4)?;
     }
+
+    if args.recursive {
+        // Symbolic links to directories are not followed, to avoid cycles
+        for entry in &entries {
+            let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+            let is_visible = args.all_files
+                || (!entry.file_name().to_string_lossy().starts_with('.')
+                    && !entry.metadata().is_ok_and(|m| is_hidden(&m)));
+
+            if Scope::is_interrupted() {
+                break;
+            }
+            if is_dir && is_visible {
+                if let Err(e) = print_dir(scope, &entry.path(), args) {
+                    // Show warning and keep going.
+                    my_warning!(scope, "{}", e);
+                }
+            }
+        }
+    }
     Ok(())
 }
 
@@ -590,3 +699,41 @@ fn register() {
         inner: Arc::clone(&dir) as Arc<dyn Exec>,
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_sort_entries() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("a"), "12345").unwrap();
+        fs::write(temp_dir.path().join("b"), "1").unwrap();
+        fs::write(temp_dir.path().join("c"), "123").unwrap();
+
+        let ls = Dir::new();
+        let scope = Scope::new();
+        let sorted = |args: &[&str]| {
+            let args = args.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+            let opts = ls.parse_args(&scope, &args).unwrap();
+            let mut entries = fs::read_dir(temp_dir.path())
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            sort_entries(&mut entries, &opts);
+            entries
+                .iter()
+                .map(|e| e.file_name().to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(sorted(&[]), vec!["a", "b", "c"]);
+        assert_eq!(sorted(&["-r"]), vec!["c", "b", "a"]);
+        assert_eq!(sorted(&["-S"]), vec!["a", "c", "b"]);
+        assert_eq!(sorted(&["--sort", "size", "-r"]), vec!["b", "c", "a"]);
+        assert!(ls
+            .parse_args(&scope, &["--sort".to_string(), "x".to_string()])
+            .is_err());
+    }
+}
@@ -11,7 +11,8 @@ struct Open {
 impl Open {
     fn new() -> Self {
         let mut flags = CommandFlags::with_help();
-        flags.add_value('a', "application", "name", "Application to open with");
+        flags.add_value('w', "with", "APP", "Application to open with");
+        flags.add_value('a', "application", "APP", "Same as --with");
         flags.add_flag('d', "detached", "Open using a detached process");
 
         Self { flags }
@@ -32,6 +33,9 @@ impl Exec for Open {
             println!("Open one or more files or URLs with the default or specified application.");
             println!("\nOptions:");
             print!("{}", flags.help());
+            println!("\nExamples:");
+            println!("    open report.pdf https://github.com/cristivlas/shmy");
+            println!("    open --with code src/main.rs");
             return Ok(Value::success());
         }
 
@@ -39,7 +43,7 @@ impl Exec for Open {
             return Err("open: no file or URL specified".to_string());
         }
 
-        let application = flags.value("application");
+        let application = flags.value("with").or(flags.value("application"));
         let detached = flags.is_present("detached");
 
         for arg in &args {
@@ -0,0 +1,373 @@
+/// Commands provided by plugins in ~/.shmy/plugins: executables, registered under their
+/// file name (without extension), and YAML manifests that describe the command:
+///
+/// ```yaml
+/// name: weather
+/// command: weather-plugin     # relative to the plugins directory, or found in $PATH
+/// description: Show the weather forecast
+/// flags:
+///   - long: city
+///     short: c
+///     value: CITY             # the flag takes a value
+///     help: Name of the city
+/// ```
+///
+/// The flags of the manifest are used for completions and hints. When the command runs,
+/// the shell writes a JSON request to the standard input of the plugin:
+/// `{"command": "weather", "args": ["-c", "Paris"], "cwd": "/home/me"}`
+/// and reads a JSON response from its standard output:
+/// `{"status": 0, "output": "Sunny\n", "error": ""}`
+///
+/// WebAssembly modules (.wasm) are not run in-process: the shell launches an external WASI
+/// runtime (wasmtime, unless the __wasm_runtime variable names another one, e.g. wasmer),
+/// which must be installed separately. The runtime preopens the current directory only, and
+/// passes the exported variables as the environment of the module; the module uses the same
+/// protocol over its standard input and output.
+use super::{
+    flags::CommandFlags, get_command, is_executable, register_command, Exec, Flag, ShellCommand,
+};
+use crate::{eval::Value, scope::Scope, utils::copy_exported_vars_to_command_env};
+use serde_json::{json, Value as Json};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use yaml_rust::{Yaml, YamlLoader};
+
+const WASM_RUNTIME: &str = "wasmtime";
+
+struct Plugin {
+    path: PathBuf,
+    flags: CommandFlags,
+}
+
+fn is_wasm(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "wasm")
+}
+
+impl Plugin {
+    fn runtime(scope: &Scope) -> String {
+        scope
+            .lookup_value("__wasm_runtime")
+            .map(|v| v.to_string())
+            .unwrap_or(WASM_RUNTIME.to_string())
+    }
+
+    /// The command that runs the plugin, with the exported variables as its environment.
+    /// The WASI runtime is told the names of the variables that the module inherits, so
+    /// that the values are not visible in the command line of the runtime.
+    fn command(&self, scope: &Arc<Scope>) -> Command {
+        let mut command = if is_wasm(&self.path) {
+            let mut command = Command::new(Self::runtime(scope));
+            command.args(["run", "--dir", "."]);
+            for (key, variable) in scope.visible_vars() {
+                if variable.is_exported() && !key.is_special_var() {
+                    command.arg("--env").arg(key.view());
+                }
+            }
+            command.arg(&self.path);
+            command
+        } else {
+            Command::new(&self.path)
+        };
+        copy_exported_vars_to_command_env(&mut command, scope);
+        command
+    }
+}
+
+fn request(name: &str, args: &[String], cwd: &Path) -> String {
+    let request = json!({
+        "command": name,
+        "args": args,
+        "cwd": cwd.to_string_lossy(),
+    });
+    format!("{}\n", request)
+}
+
+#[derive(Debug, PartialEq)]
+struct Response {
+    status: i64,
+    output: String,
+    error: String,
+}
+
+fn parse_response(text: &str) -> Result<Response, String> {
+    let response =
+        serde_json::from_str::<Json>(text).map_err(|e| format!("invalid response: {}", e))?;
+    if !response.is_object() {
+        return Err("invalid response: expecting JSON object".to_string());
+    }
+
+    Ok(Response {
+        status: response["status"].as_i64().unwrap_or_default(),
+        output: response["output"].as_str().unwrap_or_default().to_string(),
+        error: response["error"].as_str().unwrap_or_default().to_string(),
+    })
+}
+
+impl Exec for Plugin {
+    fn cli_flags(&self) -> Box<dyn Iterator<Item = &Flag> + '_> {
+        Box::new(self.flags.iter())
+    }
+
+    fn exec(&self, name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
+        let err = |e: io::Error| format!("{}: {}", scope.err_path(&self.path), e);
+        let cwd = std::env::current_dir().map_err(|e| e.to_string())?;
+
+        let mut command = self.command(scope);
+
+        let mut child = match command.stdin(Stdio::piped()).stdout(Stdio::piped()).spawn() {
+            Ok(child) => child,
+            Err(e) if e.kind() == io::ErrorKind::NotFound && is_wasm(&self.path) => {
+                return Err(format!(
+                    "{}: WebAssembly plugins need an external WASI runtime, {} not found \
+                    (install it, or set __wasm_runtime)",
+                    scope.err_path(&self.path),
+                    scope.err_str(&Self::runtime(scope))
+                ));
+            }
+            Err(e) => return Err(err(e)),
+        };
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(request(name, args, &cwd).as_bytes())
+                .map_err(err)?;
+        }
+        let output = child.wait_with_output().map_err(err)?;
+
+        let text = String::from_utf8_lossy(&output.stdout);
+        let response = parse_response(&text).map_err(|e| format!("{}: {}", name, e))?;
+
+        my_print!("{}", response.output)?;
+
+        if response.status != 0 || !output.status.success() {
+            let status = if response.status != 0 {
+                response.status
+            } else {
+                output.status.code().unwrap_or(-1) as i64
+            };
+            scope.set_exit_status(status);
+            return Err(if response.error.is_empty() {
+                format!("{}: exited with status {}", name, status)
+            } else {
+                response.error
+            });
+        }
+        Ok(Value::success())
+    }
+}
+
+/// Create the command flags from the manifest.
+fn manifest_flags(manifest: &Yaml) -> Result<CommandFlags, String> {
+    let mut flags = CommandFlags::new();
+
+    for flag in manifest["flags"].as_vec().into_iter().flatten() {
+        let long = flag["long"]
+            .as_str()
+            .ok_or_else(|| "flag without long name".to_string())?;
+        let short = flag["short"].as_str().and_then(|s| s.chars().next());
+
+        if flags
+            .iter()
+            .any(|f| f.long == long || (short.is_some() && f.short == short))
+        {
+            return Err(format!("duplicate flag: {}", long));
+        }
+        let value = flag["value"].as_str().map(String::from);
+        flags.add(
+            short,
+            long,
+            value,
+            flag["help"].as_str().unwrap_or_default(),
+        );
+    }
+    Ok(flags)
+}
+
+fn load_manifest(dir: &Path, path: &Path) -> Result<(String, Plugin), String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let docs = YamlLoader::load_from_str(&text).map_err(|e| e.to_string())?;
+    let manifest = docs.first().ok_or_else(|| "empty manifest".to_string())?;
+
+    let name = manifest["name"]
+        .as_str()
+        .ok_or_else(|| "name not specified".to_string())?;
+    let command = manifest["command"]
+        .as_str()
+        .ok_or_else(|| "command not specified".to_string())?;
+
+    let local = dir.join(command);
+    let path = if local.exists() {
+        local
+    } else {
+        PathBuf::from(command)
+    };
+    let flags = manifest_flags(manifest)?;
+
+    Ok((name.to_string(), Plugin { path, flags }))
+}
+
+/// Register the commands of the plugins in the directory. Plugins do not replace
+/// commands that exist already. Return the names of the registered commands.
+pub fn load_plugins(dir: &Path) -> Result<Vec<String>, String> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Could not read {}: {}", dir.display(), e)),
+    };
+    let mut paths = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    let (manifests, others): (Vec<_>, Vec<_>) = paths.into_iter().partition(|path| {
+        path.extension()
+            .is_some_and(|ext| ext == "yaml" || ext == "yml")
+    });
+
+    let mut plugins = Vec::new();
+    for path in &manifests {
+        let plugin = load_manifest(dir, path)
+            .map_err(|e| format!("Failed to load {}: {}", path.display(), e))?;
+        plugins.push(plugin);
+    }
+    for path in others {
+        // Executables described by manifests are not registered twice
+        if !path.is_file()
+            || !(is_executable(&path) || is_wasm(&path))
+            || plugins.iter().any(|(_, plugin)| plugin.path == path)
+        {
+            continue;
+        }
+        if let Some(name) = path.file_stem().map(|s| s.to_string_lossy().to_string()) {
+            let flags = CommandFlags::new();
+            plugins.push((name, Plugin { path, flags }));
+        }
+    }
+
+    let mut names = Vec::new();
+    for (name, plugin) in plugins {
+        if get_command(&name).is_some() || names.contains(&name) {
+            continue;
+        }
+        register_command(ShellCommand {
+            name: name.clone(),
+            inner: Arc::new(plugin),
+        });
+        names.push(name);
+    }
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_protocol() {
+        assert_eq!(
+            request(
+                "p",
+                &["a \"b\"".to_string(), "c\\d".to_string()],
+                Path::new("/x")
+            ),
+            "{\"command\":\"p\",\"args\":[\"a \\\"b\\\"\",\"c\\\\d\"],\"cwd\":\"/x\"}\n"
+        );
+        assert_eq!(
+            parse_response("{\"status\": 2, \"output\": \"a\\nb\", \"error\": \"failed\"}"),
+            Ok(Response {
+                status: 2,
+                output: "a\nb".to_string(),
+                error: "failed".to_string()
+            })
+        );
+        assert!(parse_response("hello").is_err());
+        assert!(parse_response("[1, 2]").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_load_plugins() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let script = temp_dir.path().join("plugin_test_echo.sh");
+        fs::write(
+            &script,
+            "#!/bin/sh\nread request\necho '{\"status\": 0, \"output\": \"\"}'\n",
+        )
+        .unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+        fs::write(
+            temp_dir.path().join("plugin_test.yaml"),
+            "name: plugin_test_cmd\ncommand: plugin_test_echo.sh\nflags:\n  - long: city\n    short: c\n    value: CITY\n    help: City\n",
+        )
+        .unwrap();
+
+        let names = load_plugins(temp_dir.path()).unwrap();
+        assert_eq!(names, vec!["plugin_test_cmd"]);
+
+        let cmd = get_command("plugin_test_cmd").unwrap();
+        assert_eq!(
+            cmd.cli_flags().map(|f| f.long.clone()).collect::<Vec<_>>(),
+            vec!["city"]
+        );
+        assert!(cmd
+            .exec("plugin_test_cmd", &vec!["-c".to_string()], &Scope::new())
+            .is_ok());
+    }
+
+    #[test]
+    fn test_wasm_plugin() {
+        let temp_dir = TempDir::new().unwrap();
+        let module = temp_dir.path().join("plugin_test_wasm.wasm");
+        fs::write(&module, b"\0asm\x01\0\0\0").unwrap();
+
+        let names = load_plugins(temp_dir.path()).unwrap();
+        assert_eq!(names, vec!["plugin_test_wasm"]);
+
+        let plugin = Plugin {
+            path: module.clone(),
+            flags: CommandFlags::new(),
+        };
+        let scope = Scope::new();
+        scope.insert("PLUGIN_TEST".to_string(), Value::from("1"));
+        scope.lookup("PLUGIN_TEST").unwrap().set_exported(true);
+        scope.insert("PLUGIN_TEST_LOCAL".to_string(), Value::from("2"));
+
+        let command = plugin.command(&scope);
+        assert_eq!(command.get_program(), WASM_RUNTIME);
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            vec![
+                "run".as_ref(),
+                "--dir".as_ref(),
+                ".".as_ref(),
+                "--env".as_ref(),
+                "PLUGIN_TEST".as_ref(),
+                module.as_os_str()
+            ]
+        );
+        assert!(command
+            .get_envs()
+            .any(|(key, value)| key == "PLUGIN_TEST" && value == Some("1".as_ref())));
+
+        // Missing runtime
+        scope.insert(
+            "__wasm_runtime".to_string(),
+            Value::Str(Arc::new("plugin-test-no-such-runtime".to_string())),
+        );
+        assert_eq!(
+            plugin.command(&scope).get_program(),
+            "plugin-test-no-such-runtime"
+        );
+        let err = plugin
+            .exec("plugin_test_wasm", &vec![], &scope)
+            .unwrap_err();
+        assert!(err.contains("external WASI runtime"), "{}", err);
+    }
+}
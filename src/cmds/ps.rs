@@ -291,12 +291,12 @@ impl Field for RunTime {
 /// Convert Uid to User.name and format for printing
 ///
 #[cfg(windows)]
-fn uid_to_name(uid: &Uid) -> String {
+pub(super) fn uid_to_name(uid: &Uid) -> String {
     crate::utils::win::name_from_sid(Some(uid.to_string()))
 }
 
 #[cfg(not(windows))]
-fn uid_to_name(uid: &Uid) -> String {
+pub(super) fn uid_to_name(uid: &Uid) -> String {
     use std::sync::OnceLock;
     use sysinfo::Users;
     static USERS: OnceLock<Users> = OnceLock::new();
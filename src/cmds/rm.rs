@@ -1,3 +1,4 @@
+use super::trash::move_to_trash;
 use super::{flags::CommandFlags, register_command, Exec, Flag, ShellCommand};
 use crate::prompt::{confirm, Answer};
 use crate::{eval::Value, scope::Scope, symlnk::SymLink, utils::format_error};
@@ -10,6 +11,7 @@ use std::sync::Arc;
 struct Context {
     interactive: bool,
     recursive: bool,
+    trash: bool,
     many: bool,
     quit: bool,
     scope: Arc<Scope>,
@@ -50,6 +52,17 @@ impl Remove {
             "recursive",
             "Remove directories and their contents recursively",
         );
+        flags.add_flag(
+            'I',
+            "interactive-once",
+            "Prompt once before removing more than three files, or when removing recursively",
+        );
+        flags.add(
+            None,
+            "trash",
+            None,
+            "Move the files to the trash instead of deleting them",
+        );
         Self { flags }
     }
 
@@ -61,8 +74,36 @@ impl Remove {
         }
     }
 
+    /// Remove the directory and its contents, stop if interrupted.
+    fn remove_tree(&self, path: &Path, ctx: &mut Context) -> io::Result<()> {
+        for entry in fs::read_dir(path)? {
+            if Scope::is_interrupted() {
+                ctx.quit = true;
+            }
+            if ctx.quit {
+                return Ok(());
+            }
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                self.remove_tree(&entry.path(), ctx)?;
+            } else {
+                self.remove(&entry.path(), ctx)?;
+            }
+        }
+        if ctx.quit {
+            return Ok(());
+        }
+        fs::remove_dir(path)
+    }
+
     fn remove(&self, path: &Path, ctx: &mut Context) -> io::Result<()> {
-        if path.is_symlink() {
+        if ctx.trash {
+            let prompt = format!("Move {} to the trash", path.display());
+            if ctx.confirm(path, prompt)? == Answer::Yes {
+                move_to_trash(path)?;
+            }
+            Ok(())
+        } else if path.is_symlink() {
             #[cfg(windows)]
             {
                 use crate::utils::win::remove_link;
@@ -80,7 +121,7 @@ impl Remove {
         } else if path.is_dir() {
             if ctx.recursive && !ctx.interactive {
                 // Nuke it, no questions asked
-                fs::remove_dir_all(path)
+                self.remove_tree(path, ctx)
             } else {
                 let prompt = format!(
                     "{} is a directory. Delete all of its content recursively",
@@ -96,7 +137,7 @@ impl Remove {
                         ctx.interactive = false;
                         ctx.recursive = true;
 
-                        fs::remove_dir_all(path)?;
+                        self.remove_tree(path, ctx)?;
 
                         // Restore context
                         ctx.interactive = interactive;
@@ -106,7 +147,7 @@ impl Remove {
                         ctx.interactive = false;
                         ctx.recursive = true;
 
-                        fs::remove_dir_all(path)?;
+                        self.remove_tree(path, ctx)?;
                     }
                     Answer::Quit => {
                         ctx.quit = true;
@@ -121,6 +162,24 @@ impl Remove {
     }
 }
 
+/// Refuse to remove the root directory, or the tree that contains the current directory.
+fn check_protected(path: &Path) -> io::Result<()> {
+    if path.is_symlink() || !path.is_dir() {
+        return Ok(());
+    }
+    let path = path.canonicalize()?;
+    if path.parent().is_none() {
+        return Err(io::Error::other("Refusing to remove the root directory"));
+    }
+    let cwd = std::env::current_dir().and_then(|dir| dir.canonicalize())?;
+    if cwd.starts_with(&path) {
+        return Err(io::Error::other(
+            "Refusing to remove a directory that contains the current directory",
+        ));
+    }
+    Ok(())
+}
+
 impl Exec for Remove {
     fn cli_flags(&self) -> Box<dyn Iterator<Item = &Flag> + '_> {
         Box::new(self.flags.iter())
@@ -135,6 +194,10 @@ impl Exec for Remove {
             println!("Remove (delete) the specified FILE(s).");
             println!("\nOptions:");
             print!("{}", flags.help());
+            println!();
+            println!("Examples:");
+            println!("    rm -f *.tmp");
+            println!("    rm -rI build");
             return Ok(Value::success());
         }
 
@@ -145,6 +208,7 @@ impl Exec for Remove {
         let mut ctx = Context {
             interactive: flags.is_present("interactive"),
             recursive: flags.is_present("recursive"),
+            trash: flags.is_present("trash"),
             many: paths.len() > 1,
             quit: false,
             scope: Arc::clone(&scope),
@@ -155,10 +219,30 @@ impl Exec for Remove {
         // Use a set to dedupe inputs, e.g. avoid ```rm *.rs *.rs``` resulting in error.
         let to_remove: HashSet<&String> = HashSet::from_iter(&paths);
 
+        // With -I, ask once instead of for each file (unless -f is also given)
+        if ctx.interactive && flags.is_present("interactive-once") {
+            ctx.interactive = false;
+
+            if to_remove.len() > 3 || ctx.recursive {
+                let prompt = format!(
+                    "Remove {} argument{}{}",
+                    to_remove.len(),
+                    if to_remove.len() == 1 { "" } else { "s" },
+                    if ctx.recursive { " recursively" } else { "" }
+                );
+                if confirm(prompt, scope, false).map_err(|e| e.to_string())? != Answer::Yes {
+                    return Ok(Value::success());
+                }
+            }
+        }
+
         for &path in to_remove.iter() {
             Path::new(path)
                 .resolve(follow_links)
-                .and_then(|path| self.remove(&path, &mut ctx))
+                .and_then(|path| {
+                    check_protected(&path)?;
+                    self.remove(&path, &mut ctx)
+                })
                 .map_err(|e| format_error(scope, path, args, e))?;
 
             if ctx.quit {
@@ -207,6 +291,7 @@ mod tests {
         let mut ctx = Context {
             interactive: true,
             recursive: false,
+            trash: false,
             many: false,
             quit: false,
             scope: Arc::clone(&scope),
@@ -233,6 +318,7 @@ mod tests {
         let mut ctx = Context {
             interactive: true,
             recursive: true,
+            trash: false,
             many: false,
             quit: false,
             scope: Arc::clone(&scope),
@@ -245,4 +331,27 @@ mod tests {
         // Clean up
         fs::remove_dir_all(temp_dir).unwrap();
     }
+
+    #[test]
+    fn test_remove_tree() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir_path = temp_dir.path().join("a");
+        fs::create_dir_all(dir_path.join("b").join("c")).unwrap();
+        File::create(dir_path.join("b").join("file.txt")).unwrap();
+
+        let scope = create_test_scope();
+        let remove_cmd = Remove::new();
+        let args = vec!["-rI".to_string(), dir_path.display().to_string()];
+
+        assert!(remove_cmd.exec("rm", &args, &scope).is_ok());
+        assert!(!dir_path.exists());
+    }
+
+    #[test]
+    fn test_check_protected() {
+        assert!(check_protected(Path::new("/")).is_err());
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        assert!(check_protected(temp_dir.path()).is_ok());
+    }
 }
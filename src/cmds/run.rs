@@ -1,5 +1,5 @@
 use super::{flags::CommandFlags, get_command, register_command, Exec, Flag, ShellCommand};
-use crate::{eval::Value, scope::Scope, utils::executable};
+use crate::{eval::Value, scope::Scope};
 use std::sync::Arc;
 
 struct Run {
@@ -49,11 +49,7 @@ impl Exec for Run {
         }
 
         if command_args.is_empty() {
-            if name == "exec" {
-                command_args.push(executable()?);
-            } else {
-                return Err("No command specified".to_string());
-            }
+            return Err("No command specified".to_string());
         }
 
         let cmd_name = command_args.iter().next().cloned().unwrap();
@@ -91,15 +87,8 @@ impl Exec for Run {
 
 #[ctor::ctor]
 fn register() {
-    let exec = Arc::new(Run::new());
-
     register_command(ShellCommand {
         name: "run".to_string(),
-        inner: exec.clone() as Arc<dyn Exec>,
-    });
-
-    register_command(ShellCommand {
-        name: "exec".to_string(),
-        inner: exec.clone() as Arc<dyn Exec>,
+        inner: Arc::new(Run::new()),
     });
 }
@@ -0,0 +1,110 @@
+use super::{flags::CommandFlags, register_command, Exec, Flag, ShellCommand};
+use crate::{eval::Value, scope::Scope};
+use std::cell::Cell;
+use std::sync::Arc;
+
+struct Set {
+    flags: CommandFlags,
+}
+
+impl Set {
+    fn new() -> Self {
+        let mut flags = CommandFlags::with_help();
+        flags.add_flag(
+            'e',
+            "errexit",
+            "Abort on the first failed command (default)",
+        );
+        flags.add_flag(
+            'u',
+            "nounset",
+            "Treat the expansion of undefined variables as an error",
+        );
+        flags.add_flag(
+            'x',
+            "xtrace",
+            "Print commands and their arguments before executing them",
+        );
+
+        Self { flags }
+    }
+
+    fn options(scope: &Arc<Scope>) -> [(&'static str, char, &Cell<bool>); 3] {
+        let options = scope.options();
+        [
+            ("errexit", 'e', &options.errexit),
+            ("nounset", 'u', &options.nounset),
+            ("xtrace", 'x', &options.xtrace),
+        ]
+    }
+
+    fn list(scope: &Arc<Scope>) -> Result<Value, String> {
+        for (name, _, option) in Self::options(scope) {
+            my_println!("{:<10}{}", name, if option.get() { "on" } else { "off" })?;
+        }
+        Ok(Value::success())
+    }
+}
+
+impl Exec for Set {
+    fn cli_flags(&self) -> Box<dyn Iterator<Item = &Flag> + '_> {
+        Box::new(self.flags.iter())
+    }
+
+    fn exec(&self, _name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
+        let mut flags = self.flags.clone();
+        let args = flags.parse(scope, args)?;
+
+        if flags.is_present("help") {
+            println!("Usage: set [-eux] [+eux]");
+            println!("Set (-) or unset (+) interpreter options.");
+            println!("Without arguments, show the current options.");
+            println!("\nOptions:");
+            print!("{}", flags.help());
+            println!();
+            println!("Examples:");
+            println!("    set -ux");
+            println!("    set +e");
+            return Ok(Value::success());
+        }
+
+        let mut changed = false;
+
+        for (name, _, option) in Self::options(scope) {
+            if flags.is_present(name) {
+                option.set(true);
+                changed = true;
+            }
+        }
+
+        for arg in &args {
+            let letters = arg
+                .strip_prefix('+')
+                .filter(|s| !s.is_empty())
+                .ok_or_else(|| format!("Invalid argument: {}", scope.err_str(arg)))?;
+
+            for c in letters.chars() {
+                let (_, _, option) = Self::options(scope)
+                    .into_iter()
+                    .find(|(_, short, _)| *short == c)
+                    .ok_or_else(|| format!("Invalid option: +{}", c))?;
+                option.set(false);
+            }
+            changed = true;
+        }
+
+        if !changed {
+            return Self::list(scope);
+        }
+
+        Ok(Value::success())
+    }
+}
+
+#[ctor::ctor]
+fn register() {
+    register_command(ShellCommand {
+        name: "set".to_string(),
+        inner: Arc::new(Set::new()),
+    });
+}
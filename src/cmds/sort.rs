@@ -1,11 +1,287 @@
 use super::{flags::CommandFlags, register_command, Exec, Flag, ShellCommand};
 use crate::{eval::Value, scope::Scope, symlnk::SymLink, utils::format_error};
-use std::collections::HashSet;
-use std::fs::File;
-use std::io::{self, BufRead, BufReader};
-use std::path::Path;
+use std::cmp::Ordering;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
 use std::sync::Arc;
 
+/// Sort in memory up to this many bytes of input, then merge sorted runs from temporary files.
+const BUFFER_SIZE: usize = 256 * 1024 * 1024;
+
+/// Sort key: the fields from start to end (1-based, inclusive), to the end of line by default.
+#[derive(Debug, PartialEq)]
+struct Key {
+    start: usize,
+    end: Option<usize>,
+}
+
+fn parse_key(def: &str) -> Result<Key, String> {
+    let field = |s: &str| match s.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!("Invalid field: {}", s)),
+    };
+    match def.split_once(',') {
+        Some((start, end)) => {
+            let key = Key {
+                start: field(start)?,
+                end: Some(field(end)?),
+            };
+            if key.end < Some(key.start) {
+                return Err(format!("Invalid key: {}", def));
+            }
+            Ok(key)
+        }
+        None => Ok(Key {
+            start: field(def)?,
+            end: None,
+        }),
+    }
+}
+
+/// Parse numbers with optional K, M, G, T, P, E suffixes (powers of 1024), as output by du -h.
+fn parse_human(s: &str) -> Option<f64> {
+    let s = s.trim_start();
+    let end = s
+        .char_indices()
+        .find(|&(i, c)| !(c.is_ascii_digit() || c == '.' || (i == 0 && c == '-')))
+        .map_or(s.len(), |(i, _)| i);
+    let number = s[..end].parse::<f64>().ok()?;
+
+    let exponent = match s[end..].trim_start().chars().next() {
+        Some('K' | 'k') => 1,
+        Some('M') => 2,
+        Some('G') => 3,
+        Some('T') => 4,
+        Some('P') => 5,
+        Some('E') => 6,
+        _ => 0,
+    };
+    Some(number * 1024f64.powi(exponent))
+}
+
+struct Options {
+    key: Option<Key>,
+    separator: Option<String>,
+    numeric: bool,
+    human: bool,
+    reverse: bool,
+    unique: bool,
+}
+
+impl Options {
+    /// Extract the key from the line. Fields are separated by the separator, or else by
+    /// runs of whitespace.
+    fn key<'a>(&self, line: &'a str) -> &'a str {
+        let Some(key) = &self.key else {
+            return line;
+        };
+
+        let mut fields = Vec::new(); // Byte ranges of the fields
+        match &self.separator {
+            Some(sep) => {
+                let mut start = 0;
+                for (i, _) in line.match_indices(sep.as_str()) {
+                    fields.push((start, i));
+                    start = i + sep.len();
+                }
+                fields.push((start, line.len()));
+            }
+            None => {
+                let mut start = None;
+                for (i, c) in line.char_indices() {
+                    match (c.is_whitespace(), start) {
+                        (false, None) => start = Some(i),
+                        (true, Some(s)) => {
+                            fields.push((s, i));
+                            start = None;
+                        }
+                        _ => {}
+                    }
+                }
+                if let Some(s) = start {
+                    fields.push((s, line.len()));
+                }
+            }
+        }
+
+        if key.start > fields.len() {
+            return "";
+        }
+        let end = key.end.unwrap_or(fields.len()).min(fields.len());
+        &line[fields[key.start - 1].0..fields[end - 1].1]
+    }
+
+    fn compare_keys(&self, a: &str, b: &str) -> Ordering {
+        if self.numeric || self.human {
+            let value = |s: &str| {
+                if self.human {
+                    parse_human(s)
+                } else {
+                    s.trim().parse::<f64>().ok()
+                }
+                .unwrap_or(f64::MAX)
+            };
+            value(a).partial_cmp(&value(b)).unwrap_or(Ordering::Equal)
+        } else {
+            a.cmp(b)
+        }
+    }
+
+    /// Compare the keys; when not looking for unique lines, compare equal keys by the
+    /// whole lines, so that the order does not depend on how the input was split into runs.
+    fn compare(&self, a: &str, b: &str) -> Ordering {
+        let mut ordering = self.compare_keys(self.key(a), self.key(b));
+        if ordering == Ordering::Equal && !self.unique {
+            ordering = a.cmp(b);
+        }
+        if self.reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+/// Distinguishes the temporary files of sorts running at the same time.
+static RUN_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Sorted runs saved to temporary files, removed when dropped.
+struct Runs {
+    paths: Vec<PathBuf>,
+}
+
+impl Runs {
+    fn save(&mut self, lines: &[String]) -> io::Result<()> {
+        let path = std::env::temp_dir().join(format!(
+            "shmy-sort-{}-{}.tmp",
+            std::process::id(),
+            RUN_COUNTER.fetch_add(1, Relaxed)
+        ));
+        let mut writer = BufWriter::new(File::create(&path)?);
+        self.paths.push(path);
+
+        for line in lines {
+            writer.write_all(line.as_bytes())?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()
+    }
+}
+
+impl Drop for Runs {
+    fn drop(&mut self) {
+        for path in &self.paths {
+            _ = fs::remove_file(path);
+        }
+    }
+}
+
+struct Sorter<'a> {
+    opts: &'a Options,
+    buffer_size: usize,
+    lines: Vec<String>,
+    size: usize,
+    runs: Runs,
+}
+
+impl<'a> Sorter<'a> {
+    fn new(opts: &'a Options, buffer_size: usize) -> Self {
+        Self {
+            opts,
+            buffer_size,
+            lines: Vec::new(),
+            size: 0,
+            runs: Runs { paths: Vec::new() },
+        }
+    }
+
+    fn sort(&mut self) {
+        let opts = self.opts;
+        self.lines.sort_by(|a, b| opts.compare(a, b));
+        if opts.unique {
+            self.lines
+                .dedup_by(|a, b| opts.compare(a, b) == Ordering::Equal);
+        }
+    }
+
+    fn push(&mut self, line: String) -> io::Result<()> {
+        self.size += line.len() + std::mem::size_of::<String>();
+        self.lines.push(line);
+
+        if self.size >= self.buffer_size {
+            self.sort();
+            self.runs.save(&self.lines)?;
+            self.lines.clear();
+            self.size = 0;
+        }
+        Ok(())
+    }
+
+    /// Output the sorted lines, merging the runs saved to temporary files, if any.
+    fn output<F: FnMut(&str) -> Result<(), String>>(mut self, mut print: F) -> Result<(), String> {
+        self.sort();
+
+        if self.runs.paths.is_empty() {
+            for line in &self.lines {
+                if Scope::is_interrupted() {
+                    break;
+                }
+                print(line)?;
+            }
+            return Ok(());
+        }
+
+        let err = |e: io::Error| format!("Could not read sorted run: {}", e);
+
+        // The lines still in memory are one more run
+        let mut readers: Vec<Box<dyn Iterator<Item = io::Result<String>>>> = Vec::new();
+        for path in &self.runs.paths {
+            let file = File::open(path).map_err(err)?;
+            readers.push(Box::new(BufReader::new(file).lines()));
+        }
+        let lines = std::mem::take(&mut self.lines);
+        readers.push(Box::new(lines.into_iter().map(Ok)));
+
+        let mut heads = Vec::new();
+        for reader in &mut readers {
+            heads.push(reader.next().transpose().map_err(err)?);
+        }
+
+        let mut last: Option<String> = None;
+        while !Scope::is_interrupted() {
+            // Few runs are expected, find the smallest head by scanning
+            let mut min: Option<usize> = None;
+            for (i, head) in heads.iter().enumerate() {
+                if let Some(line) = head {
+                    if min.is_none_or(|m| {
+                        self.opts.compare(line, heads[m].as_ref().unwrap()) == Ordering::Less
+                    }) {
+                        min = Some(i);
+                    }
+                }
+            }
+            let Some(i) = min else {
+                break;
+            };
+            let line = heads[i].take().unwrap();
+            heads[i] = readers[i].next().transpose().map_err(err)?;
+
+            if self.opts.unique
+                && last
+                    .as_ref()
+                    .is_some_and(|l| self.opts.compare(l, &line) == Ordering::Equal)
+            {
+                continue;
+            }
+            print(&line)?;
+            last = Some(line);
+        }
+        Ok(())
+    }
+}
+
 struct Sort {
     flags: CommandFlags,
 }
@@ -20,42 +296,31 @@ impl Sort {
             "numeric-sort",
             "Compare according to string numerical value",
         );
+        flags.add_flag(
+            'h',
+            "human-numeric-sort",
+            "Compare human readable numbers (e.g. 2K, 1.5 M)",
+        );
+        flags.add_value(
+            'k',
+            "key",
+            "START[,END]",
+            "Sort by the fields from START to END (default: end of line)",
+        );
+        flags.add_value(
+            't',
+            "field-separator",
+            "SEP",
+            "Use SEP instead of whitespace to separate fields",
+        );
+        flags.add_value(
+            'S',
+            "buffer-size",
+            "SIZE",
+            "Sort up to SIZE bytes in memory, then merge using temporary files (default: 256M)",
+        );
         Self { flags }
     }
-
-    fn sort_lines(
-        &self,
-        lines: Vec<String>,
-        unique: bool,
-        reverse: bool,
-        numeric: bool,
-    ) -> Vec<String> {
-        let mut sorted_lines: Vec<String> = if unique {
-            lines
-                .into_iter()
-                .collect::<HashSet<_>>()
-                .into_iter()
-                .collect()
-        } else {
-            lines
-        };
-
-        if numeric {
-            sorted_lines.sort_by(|a, b| {
-                let a_num = a.parse::<f64>().unwrap_or(f64::MAX);
-                let b_num = b.parse::<f64>().unwrap_or(f64::MAX);
-                a_num.partial_cmp(&b_num).unwrap()
-            });
-        } else {
-            sorted_lines.sort();
-        }
-
-        if reverse {
-            sorted_lines.reverse();
-        }
-
-        sorted_lines
-    }
 }
 
 impl Exec for Sort {
@@ -65,23 +330,50 @@ impl Exec for Sort {
 
     fn exec(&self, _name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
         let mut flags = self.flags.clone();
-        let args = flags.parse(scope, args)?;
+        let files = flags.parse(scope, args)?;
 
         if flags.is_present("help") {
             println!("Usage: sort [OPTIONS] [FILE]...");
             println!("Sort lines of text (from FILES or standard input).");
             println!("\nOptions:");
             print!("{}", flags.help());
+            println!("\nExamples:");
+            println!("    sort -t, -k2 -n data.csv");
+            println!("    du -h | sort -hr");
             return Ok(Value::success());
         }
 
-        let unique = flags.is_present("unique");
-        let reverse = flags.is_present("reverse");
-        let numeric = flags.is_present("numeric-sort");
+        let key = flags
+            .value("key")
+            .map(|v| parse_key(v).map_err(|e| format_error(scope, v, args, e)))
+            .transpose()?;
+
+        let separator = match flags.value("field-separator") {
+            Some("") => return Err("Empty field separator".to_string()),
+            sep => sep.map(String::from),
+        };
+
+        let buffer_size = match flags.value("buffer-size") {
+            Some(v) => match parse_human(v) {
+                Some(size) if size >= 1.0 => size as usize,
+                _ => return Err(format_error(scope, v, args, "Invalid buffer size")),
+            },
+            None => BUFFER_SIZE,
+        };
+
+        let opts = Options {
+            key,
+            separator,
+            numeric: flags.is_present("numeric-sort"),
+            human: flags.is_present("human-numeric-sort"),
+            reverse: flags.is_present("reverse"),
+            unique: flags.is_present("unique"),
+        };
 
-        let mut lines = Vec::new();
+        let mut sorter = Sorter::new(&opts, buffer_size);
+        let save_err = |e: io::Error| format!("Could not save sorted run: {}", e);
 
-        if args.is_empty() {
+        if files.is_empty() {
             // Read from stdin if no files are provided
             scope.show_eof_hint();
             let reader = io::stdin().lock();
@@ -90,13 +382,13 @@ impl Exec for Sort {
                     break;
                 }
                 let line = line.map_err(|e| e.to_string())?;
-                lines.push(line);
+                sorter.push(line).map_err(save_err)?;
             }
         } else {
-            for file_path in &args {
+            for file_path in &files {
                 let path = Path::new(file_path)
                     .dereference()
-                    .map_err(|e| format_error(scope, file_path, &args, e))?;
+                    .map_err(|e| format_error(scope, file_path, args, e))?;
 
                 if path.is_file() {
                     match File::open(&path) {
@@ -107,7 +399,7 @@ impl Exec for Sort {
                                     break;
                                 }
                                 match line {
-                                    Ok(line) => lines.push(line),
+                                    Ok(line) => sorter.push(line).map_err(save_err)?,
                                     Err(e) => {
                                         my_warning!(scope, "{}: {}", scope.err_path(&path), e);
                                         break; // The file may not contain valid UTF-8, bail
@@ -127,15 +419,7 @@ impl Exec for Sort {
             }
         }
 
-        let sorted_lines = self.sort_lines(lines, unique, reverse, numeric);
-
-        for line in sorted_lines {
-            if Scope::is_interrupted() {
-                break;
-            }
-
-            my_println!("{line}")?;
-        }
+        sorter.output(|line| my_println!("{line}"))?;
 
         Ok(Value::success())
     }
@@ -148,3 +432,84 @@ fn register() {
         inner: Arc::new(Sort::new()),
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(key: Option<&str>, separator: Option<&str>) -> Options {
+        Options {
+            key: key.map(|k| parse_key(k).unwrap()),
+            separator: separator.map(String::from),
+            numeric: false,
+            human: false,
+            reverse: false,
+            unique: false,
+        }
+    }
+
+    fn sort(opts: &Options, buffer_size: usize, lines: &[&str]) -> Vec<String> {
+        let mut sorter = Sorter::new(opts, buffer_size);
+        for line in lines {
+            sorter.push(line.to_string()).unwrap();
+        }
+        let mut result = Vec::new();
+        sorter
+            .output(|line| {
+                result.push(line.to_string());
+                Ok(())
+            })
+            .unwrap();
+        result
+    }
+
+    #[test]
+    fn test_key() {
+        let opts = options(Some("2"), None);
+        assert_eq!(opts.key("  a  b c"), "b c");
+        assert_eq!(opts.key("a"), "");
+
+        let opts = options(Some("2,3"), Some(","));
+        assert_eq!(opts.key("a,b,,d"), "b,");
+        assert_eq!(opts.key("a,b"), "b");
+
+        assert!(parse_key("0").is_err());
+        assert!(parse_key("3,2").is_err());
+        assert_eq!(parse_human("1.5 K"), Some(1536.0));
+        assert_eq!(parse_human("2M"), Some(2097152.0));
+        assert_eq!(parse_human("x"), None);
+    }
+
+    #[test]
+    fn test_sort_and_merge() {
+        let lines = ["b 10", "a 9", "c 2K", "d 1.5 K", "e 2", "a 9"];
+
+        let mut opts = options(Some("2"), None);
+        opts.human = true;
+        let expected = vec!["e 2", "a 9", "a 9", "b 10", "d 1.5 K", "c 2K"];
+        assert_eq!(sort(&opts, BUFFER_SIZE, &lines), expected);
+        // Merge runs of about two lines each
+        assert_eq!(sort(&opts, 60, &lines), expected);
+
+        opts.reverse = true;
+        opts.unique = true;
+        let expected = vec!["c 2K", "d 1.5 K", "b 10", "a 9", "e 2"];
+        assert_eq!(sort(&opts, BUFFER_SIZE, &lines), expected);
+        assert_eq!(sort(&opts, 60, &lines), expected);
+    }
+
+    #[test]
+    fn test_merge_blank_and_duplicate_lines() {
+        let lines = ["x", "y", "", "z", "y", "a", ""];
+
+        let mut opts = options(None, None);
+        opts.reverse = true;
+        let expected = vec!["z", "y", "y", "x", "a", "", ""];
+        assert_eq!(sort(&opts, BUFFER_SIZE, &lines), expected);
+        // The last run, kept in memory, ends with a blank line
+        assert_eq!(sort(&opts, 60, &lines), expected);
+
+        opts.unique = true;
+        assert_eq!(sort(&opts, 60, &lines), vec!["z", "y", "x", "a", ""]);
+    }
+}
@@ -0,0 +1,103 @@
+use super::{flags::CommandFlags, register_command, Exec, Flag, ShellCommand};
+use crate::{
+    eval::{Interp, Value},
+    scope::Scope,
+    symlnk::SymLink,
+    utils::format_error,
+};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+struct Source {
+    flags: CommandFlags,
+}
+
+impl Source {
+    fn new() -> Self {
+        let flags = CommandFlags::with_help();
+        Self { flags }
+    }
+}
+
+impl Exec for Source {
+    fn cli_flags(&self) -> Box<dyn Iterator<Item = &Flag> + '_> {
+        Box::new(self.flags.iter())
+    }
+
+    fn exec(&self, _name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
+        let mut flags = self.flags.clone();
+        let script_args = flags.parse_relaxed(scope, args);
+
+        if flags.is_present("help") {
+            println!("Usage: source FILE [ARG]...");
+            println!("Execute the script in FILE in the current scope, with the positional");
+            println!("arguments $1, $2... set to ARGs; the arguments are restored afterwards.");
+            println!("\nOptions:");
+            print!("{}", flags.help());
+            return Ok(Value::success());
+        }
+
+        let Some(file) = script_args.first() else {
+            return Err("Missing script file name".to_string());
+        };
+
+        let path = Path::new(file)
+            .dereference()
+            .map_err(|e| format_error(scope, file, args, e))?;
+
+        let script = fs::read_to_string(&path).map_err(|e| format_error(scope, file, args, e))?;
+
+        // The positional arguments live in their own scope, which shadows the arguments of the caller
+        // and goes away when the script is done; the script is evaluated in a child of this scope.
+        let args_scope = Scope::with_parent(Some(Arc::clone(scope)));
+        for (i, arg) in script_args.iter().enumerate() {
+            args_scope.insert(i.to_string(), Value::from(arg.as_str()));
+        }
+        // Hide the caller's extra arguments
+        let count = scope
+            .lookup_value("#")
+            .and_then(|n| n.to_string().parse::<usize>().ok())
+            .unwrap_or(0);
+        for i in script_args.len()..=count {
+            args_scope.insert(i.to_string(), Value::from(""));
+        }
+        args_scope.insert("#".to_string(), Value::Int(script_args.len() as i64 - 1));
+        args_scope.insert(
+            "@".to_string(),
+            Value::from(script_args[1..].join(" ").as_str()),
+        );
+
+        let eval_scope = Scope::with_parent(Some(Arc::clone(&args_scope)));
+        let mut interp = Interp::new(Arc::clone(scope));
+        interp.set_file(Some(Arc::new(path.to_string_lossy().to_string())));
+
+        let result = interp.eval(&script, Some(Arc::clone(&eval_scope)));
+
+        // Variables assigned by the script (other than locals) are visible to the caller.
+        for (key, var) in eval_scope.vars().iter() {
+            if !key.is_special_var() && !var.is_local() {
+                scope.vars_mut().insert(key.clone(), var.clone());
+            }
+        }
+
+        match result {
+            Err(e) => {
+                e.show(scope, &script);
+                Err(format!("Error evaluating {}", scope.err_str(file)))
+            }
+            Ok(Value::Stat(mut status)) if status.is_err() => {
+                Err(status.err().unwrap().to_string())
+            }
+            Ok(_) => Ok(Value::success()),
+        }
+    }
+}
+
+#[ctor::ctor]
+fn register() {
+    register_command(ShellCommand {
+        name: "source".to_string(),
+        inner: Arc::new(Source::new()),
+    });
+}
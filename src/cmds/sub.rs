@@ -0,0 +1,367 @@
+use super::{register_command, Exec, Flag, ShellCommand};
+use crate::{
+    cmds::flags::CommandFlags, eval::Value, scope::Scope, symlnk::SymLink, utils::format_error,
+};
+use regex::bytes::{Captures, Regex, RegexBuilder};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Parsed s/PATTERN/REPLACEMENT/FLAGS expression.
+#[derive(Debug)]
+struct Substitution {
+    regex: Regex,
+    replacement: Vec<u8>, // In the syntax of Captures::expand
+    global: bool,
+    nth: usize, // Replace starting with the nth match
+}
+
+/// Split the expression into its parts, on the delimiters that are not escaped.
+fn split_expression(expr: &str) -> Result<(char, Vec<String>), String> {
+    let mut chars = expr.chars();
+    if chars.next() != Some('s') {
+        return Err(format!("Expecting s/PATTERN/REPLACEMENT/, found: {}", expr));
+    }
+    let delim = match chars.next() {
+        Some(c) if !c.is_alphanumeric() && c != '\\' && c != '\n' => c,
+        _ => return Err(format!("Invalid delimiter in expression: {}", expr)),
+    };
+
+    let mut parts = vec![String::new()];
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                // Keep the escape, so that the pattern can tell escaped delimiters apart
+                Some(next) => {
+                    parts.last_mut().unwrap().push('\\');
+                    parts.last_mut().unwrap().push(next);
+                }
+                None => return Err("Trailing backslash in expression".to_string()),
+            }
+        } else if c == delim {
+            parts.push(String::new());
+        } else {
+            parts.last_mut().unwrap().push(c);
+        }
+    }
+    if parts.len() != 3 {
+        return Err(format!("Unterminated expression: {}", expr));
+    }
+    Ok((delim, parts))
+}
+
+/// Translate the sed-style replacement: & is the whole match, \1 to \9 are the groups.
+fn translate_replacement(text: &str, delim: char) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '&' => result.push_str("${0}"),
+            '$' => result.push_str("$$"),
+            '\\' => match chars.next() {
+                Some(d @ '0'..='9') => result.push_str(&format!("${{{}}}", d)),
+                Some('n') => result.push('\n'),
+                Some('t') => result.push('\t'),
+                Some('$') => result.push_str("$$"),
+                Some(c) if c == delim || c == '&' || c == '\\' => result.push(c),
+                Some(c) => {
+                    result.push('\\');
+                    result.push(c);
+                }
+                None => result.push('\\'),
+            },
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+fn parse_expression(expr: &str, ignore_case: bool) -> Result<Substitution, String> {
+    let (delim, parts) = split_expression(expr)?;
+
+    let escaped_delim = format!("\\{}", delim);
+    let pattern = parts[0].replace(&escaped_delim, &regex::escape(&delim.to_string()));
+
+    let mut global = false;
+    let mut ignore_case = ignore_case;
+    let mut nth = String::new();
+
+    for c in parts[2].chars() {
+        match c {
+            'g' => global = true,
+            'i' | 'I' => ignore_case = true,
+            '0'..='9' => nth.push(c),
+            _ => return Err(format!("Unknown flag in expression: {}", c)),
+        }
+    }
+    let nth = if nth.is_empty() {
+        1
+    } else {
+        match nth.parse::<usize>() {
+            Ok(n) if n > 0 => n,
+            _ => return Err(format!("Invalid occurrence number: {}", nth)),
+        }
+    };
+
+    let regex = RegexBuilder::new(&pattern)
+        .case_insensitive(ignore_case)
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    Ok(Substitution {
+        regex,
+        replacement: translate_replacement(&parts[1], delim).into_bytes(),
+        global,
+        nth,
+    })
+}
+
+impl Substitution {
+    /// Apply the substitution to the line, return the number of replacements.
+    fn apply(&self, line: &[u8], out: &mut Vec<u8>) -> usize {
+        let mut last = 0;
+        let mut count = 0;
+
+        for (i, caps) in self.regex.captures_iter(line).enumerate() {
+            let index = i + 1;
+            if index < self.nth {
+                continue;
+            }
+            if index > self.nth && !self.global {
+                break;
+            }
+            let m = caps.get(0).unwrap();
+            out.extend_from_slice(&line[last..m.start()]);
+            Captures::expand(&caps, &self.replacement, out);
+            last = m.end();
+            count += 1;
+        }
+        out.extend_from_slice(&line[last..]);
+        count
+    }
+
+    /// Substitute in each line read from the input, write the result.
+    fn process<R: BufRead, W: Write>(&self, reader: &mut R, writer: &mut W) -> io::Result<usize> {
+        let mut count = 0;
+        let mut line = Vec::new();
+        let mut out = Vec::new();
+
+        loop {
+            if Scope::is_interrupted() {
+                return Err(io::Error::new(ErrorKind::Interrupted, "interrupted"));
+            }
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            // Do not match the line ending
+            let end = line
+                .iter()
+                .rposition(|&b| b != b'\n' && b != b'\r')
+                .map_or(0, |i| i + 1);
+
+            out.clear();
+            count += self.apply(&line[..end], &mut out);
+            out.extend_from_slice(&line[end..]);
+
+            writer.write_all(&out)?;
+        }
+        Ok(count)
+    }
+}
+
+/// Append the suffix to the file name, for making the backup.
+fn backup_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Edit the file in place: write the result to a temporary file in the same directory,
+/// and rename it over the original, optionally backing up the original first.
+fn edit_in_place(subst: &Substitution, path: &Path, suffix: Option<&str>) -> io::Result<()> {
+    let metadata = fs::metadata(path)?;
+
+    let mut temp_name = path.file_name().unwrap_or_default().to_owned();
+    temp_name.push(".sub~");
+    let temp_path = path.with_file_name(temp_name);
+
+    let result = (|| {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut writer = io::BufWriter::new(File::create(&temp_path)?);
+        subst.process(&mut reader, &mut writer)?;
+        writer.flush()?;
+        drop(writer);
+
+        fs::set_permissions(&temp_path, metadata.permissions())?;
+        if let Some(suffix) = suffix {
+            fs::copy(path, backup_path(path, suffix))?;
+        }
+        fs::rename(&temp_path, path)
+    })();
+
+    if result.is_err() {
+        _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+struct Sub {
+    flags: CommandFlags,
+}
+
+impl Sub {
+    fn new() -> Self {
+        let mut flags = CommandFlags::with_help();
+        flags.add_flag('i', "in-place", "Edit the files in place");
+        flags.add(
+            None,
+            "suffix",
+            Some("SUFFIX".to_string()),
+            "Back up files edited in place, appending SUFFIX to their names (implies -i)",
+        );
+        flags.add_flag(
+            'I',
+            "ignore-case",
+            "Ignore case distinctions in the pattern",
+        );
+        Self { flags }
+    }
+}
+
+impl Exec for Sub {
+    fn cli_flags(&self) -> Box<dyn Iterator<Item = &Flag> + '_> {
+        Box::new(self.flags.iter())
+    }
+
+    fn exec(&self, name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
+        let mut flags = self.flags.clone();
+        let sub_args = flags.parse(scope, args)?;
+
+        if flags.is_present("help") {
+            println!(
+                "Usage: {} [OPTIONS] s/PATTERN/REPLACEMENT/[FLAGS] [FILE]...",
+                name
+            );
+            println!("Replace the matches of the regular expression PATTERN in each line");
+            println!("of the FILEs (or stdin), and write the result to stdout.");
+            println!("\nIn REPLACEMENT, & stands for the whole match and \\1 to \\9 for the");
+            println!(
+                "groups of the match. FLAGS: g replaces all the matches (not only the first),"
+            );
+            println!("N replaces the Nth match (with g: the Nth and all the following ones),");
+            println!("i ignores case. Any character can be used instead of the / delimiter.");
+            println!("\nOptions:");
+            print!("{}", flags.help());
+            println!("\nExamples:");
+            println!("    sub s/foo/bar/g notes.txt");
+            println!("    sub -i --suffix .bak r\"(s|(\\w+)@(\\w+)|\\2 at \\1|g)\" contacts.txt");
+            println!("    ls | sub s/.txt$/.md/");
+            return Ok(Value::success());
+        }
+
+        let Some(expr) = sub_args.first() else {
+            return Err("Missing expression".to_string());
+        };
+        let subst = parse_expression(expr, flags.is_present("ignore-case"))
+            .map_err(|e| format_error(scope, expr, args, e))?;
+
+        let suffix = flags.value("suffix");
+        let in_place = flags.is_present("in-place") || suffix.is_some();
+        let files = &sub_args[1..];
+
+        if files.is_empty() {
+            if in_place {
+                return Err("In-place editing requires files".to_string());
+            }
+            scope.show_eof_hint();
+            let mut reader = BufReader::new(io::stdin());
+            match subst.process(&mut reader, &mut io::stdout().lock()) {
+                Err(e) if e.kind() != ErrorKind::BrokenPipe => return Err(e.to_string()),
+                _ => {}
+            }
+            return Ok(Value::success());
+        }
+
+        for filename in files {
+            let path = Path::new(filename)
+                .dereference()
+                .map_err(|e| format_error(scope, filename, args, e))?;
+
+            let result = if in_place {
+                edit_in_place(&subst, &path, suffix)
+            } else {
+                File::open(&path).and_then(|file| {
+                    subst
+                        .process(&mut BufReader::new(file), &mut io::stdout().lock())
+                        .map(|_| ())
+                })
+            };
+            match result {
+                Err(e) if e.kind() == ErrorKind::BrokenPipe => break,
+                Err(e) => return Err(format_error(scope, filename, args, e)),
+                Ok(_) => {}
+            }
+        }
+
+        Ok(Value::success())
+    }
+}
+
+#[ctor::ctor]
+fn register() {
+    register_command(ShellCommand {
+        name: "sub".to_string(),
+        inner: Arc::new(Sub::new()),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn sub(expr: &str, text: &str) -> String {
+        let subst = parse_expression(expr, false).unwrap();
+        let mut out = Vec::new();
+        subst.process(&mut text.as_bytes(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_substitute() {
+        assert_eq!(sub("s/o/0/", "foo\nboo"), "f0o\nb0o");
+        assert_eq!(sub("s/o/0/g", "foo\r\nboo\n"), "f00\r\nb00\n");
+        assert_eq!(sub("s/o/0/2", "fooo"), "fo0o");
+        assert_eq!(sub("s/o/0/2g", "fooo"), "fo00");
+        assert_eq!(sub("s/(\\w+) (\\w+)/\\2 \\1 [&]/", "a b"), "b a [a b]");
+        assert_eq!(sub("s|/|\\||g", "/usr/bin"), "|usr|bin");
+        assert_eq!(sub("s/\\//$/g", "a/b"), "a$b");
+        assert_eq!(sub("s/A/x/gi", "aA"), "xx");
+        assert_eq!(sub("s/$/;/", "a\nb\n"), "a;\nb;\n");
+
+        assert!(parse_expression("s/a/b", false).is_err());
+        assert!(parse_expression("s/a/b/q", false).is_err());
+        assert!(parse_expression("x/a/b/", false).is_err());
+        assert!(parse_expression("s/(/b/", false).is_err());
+    }
+
+    #[test]
+    fn test_edit_in_place() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("file.txt");
+        fs::write(&path, "hello world\n").unwrap();
+
+        let subst = parse_expression("s/world/there/", false).unwrap();
+        edit_in_place(&subst, &path, Some(".bak")).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "hello there\n");
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("file.txt.bak")).unwrap(),
+            "hello world\n"
+        );
+        assert!(!temp_dir.path().join("file.txt.sub~").exists());
+    }
+}
@@ -0,0 +1,508 @@
+use super::{flags::CommandFlags, ps::uid_to_name, register_command, Exec, Flag, ShellCommand};
+use crate::{
+    eval::Value,
+    prompt,
+    scope::Scope,
+    utils::{format_duration, format_error, format_size, DurationStyle},
+};
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    execute,
+    style::Print,
+    terminal::{EnterAlternateScreen, LeaveAlternateScreen},
+};
+use std::cmp::Ordering;
+use std::io::{self, IsTerminal, Write};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use sysinfo::{Pid, ProcessesToUpdate, Signal, System, Uid, MINIMUM_CPU_UPDATE_INTERVAL};
+
+/// Lines above the process list: summary (2), blank line and column headers.
+const HEADER_LINES: usize = 4;
+
+#[derive(Clone, Copy, PartialEq)]
+enum SortKey {
+    Pid,
+    User,
+    Cpu,
+    Memory,
+    Time,
+    Name,
+}
+
+impl SortKey {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "pid" => Some(Self::Pid),
+            "user" => Some(Self::User),
+            "cpu" => Some(Self::Cpu),
+            "mem" | "memory" => Some(Self::Memory),
+            "time" => Some(Self::Time),
+            "name" => Some(Self::Name),
+            _ => None,
+        }
+    }
+
+    fn from_key(c: char) -> Option<Self> {
+        match c {
+            'p' => Some(Self::Pid),
+            'u' => Some(Self::User),
+            'c' => Some(Self::Cpu),
+            'm' => Some(Self::Memory),
+            't' => Some(Self::Time),
+            'n' => Some(Self::Name),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Pid => "PID",
+            Self::User => "USER",
+            Self::Cpu => "%CPU",
+            Self::Memory => "MEM",
+            Self::Time => "TIME",
+            Self::Name => "NAME",
+        }
+    }
+}
+
+/// Process information, collected at each refresh.
+struct Row {
+    pid: Pid,
+    user: String,
+    cpu: f32,
+    memory: u64,
+    time: u64,
+    name: String,
+}
+
+impl Row {
+    /// Numeric columns sort in descending order, the others in ascending order.
+    fn cmp(&self, other: &Self, key: SortKey) -> Ordering {
+        match key {
+            SortKey::Pid => self.pid.cmp(&other.pid),
+            SortKey::User => self.user.cmp(&other.user),
+            SortKey::Cpu => other.cpu.total_cmp(&self.cpu),
+            SortKey::Memory => other.memory.cmp(&self.memory),
+            SortKey::Time => other.time.cmp(&self.time),
+            SortKey::Name => self.name.to_lowercase().cmp(&other.name.to_lowercase()),
+        }
+        .then(self.pid.cmp(&other.pid))
+    }
+}
+
+/// Truncate the line to the width of the screen.
+fn fit(line: &str, width: usize) -> String {
+    let line = line.chars().take(width).collect::<String>();
+    format!("{:<width$}", line, width = width)
+}
+
+fn strong(s: &str) -> String {
+    format!("\x1b[7m{}\x1b[0m", s)
+}
+
+struct Viewer {
+    system: System,
+    rows: Vec<Row>,
+    user: Option<Uid>, // Show only the processes of this user
+    sort: SortKey,
+    reverse: bool,
+    selected: usize,
+    offset: usize, // Index of the first visible row
+    screen_width: usize,
+    screen_height: usize,
+    status_line: Option<String>,
+}
+
+impl Viewer {
+    fn new(sort: SortKey, user: Option<Uid>) -> Self {
+        let mut system = System::new_all();
+        // CPU usage is computed from the difference between two refreshes
+        std::thread::sleep(MINIMUM_CPU_UPDATE_INTERVAL);
+
+        let (w, h) = crossterm::terminal::size().unwrap_or((80, 24));
+        system.refresh_cpu_usage();
+
+        let mut viewer = Self {
+            system,
+            rows: Vec::new(),
+            user,
+            sort,
+            reverse: false,
+            selected: 0,
+            offset: 0,
+            screen_width: w as usize,
+            screen_height: h as usize,
+            status_line: None,
+        };
+        viewer.refresh();
+        viewer
+    }
+
+    fn page_size(&self) -> usize {
+        self.screen_height.saturating_sub(HEADER_LINES + 1).max(1)
+    }
+
+    fn selected_pid(&self) -> Option<Pid> {
+        self.rows.get(self.selected).map(|row| row.pid)
+    }
+
+    fn refresh(&mut self) {
+        let selected_pid = self.selected_pid();
+
+        self.system.refresh_cpu_usage();
+        self.system.refresh_memory();
+        self.system.refresh_processes(ProcessesToUpdate::All);
+
+        self.rows = self
+            .system
+            .processes()
+            .values()
+            .filter(|p| p.thread_kind().is_none())
+            .filter(|p| self.user.is_none() || p.user_id() == self.user.as_ref())
+            .map(|p| Row {
+                pid: p.pid(),
+                user: p.user_id().map(uid_to_name).unwrap_or_default(),
+                cpu: p.cpu_usage(),
+                memory: p.memory(),
+                time: p.run_time(),
+                name: p.name().to_string_lossy().to_string(),
+            })
+            .collect();
+        self.sort_rows(selected_pid);
+    }
+
+    /// Sort the rows, and keep the selection on the same process, if still running.
+    fn sort_rows(&mut self, selected_pid: Option<Pid>) {
+        let (key, reverse) = (self.sort, self.reverse);
+        self.rows.sort_by(|a, b| {
+            let ord = a.cmp(b, key);
+            if reverse {
+                ord.reverse()
+            } else {
+                ord
+            }
+        });
+        if let Some(index) =
+            selected_pid.and_then(|pid| self.rows.iter().position(|r| r.pid == pid))
+        {
+            self.selected = index;
+        }
+        self.select(self.selected as isize);
+    }
+
+    /// Move the selection to the given row, scrolling as needed.
+    fn select(&mut self, index: isize) {
+        let last = self.rows.len().saturating_sub(1) as isize;
+        self.selected = index.clamp(0, last) as usize;
+
+        let page = self.page_size();
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset + page {
+            self.offset = self.selected + 1 - page;
+        }
+    }
+
+    fn summary(&self) -> [String; 2] {
+        let system = &self.system;
+        let mut tasks = format!(
+            "{} processes, CPU: {:.1}%, up {}",
+            self.rows.len(),
+            system.global_cpu_usage(),
+            format_duration(Duration::from_secs(System::uptime()), DurationStyle::Clock),
+        );
+        if cfg!(unix) {
+            let load = System::load_average();
+            tasks.push_str(&format!(
+                ", load average: {:.2} {:.2} {:.2}",
+                load.one, load.five, load.fifteen
+            ));
+        }
+        let memory = format!(
+            "Mem: {} used of {}, {} available  Swap: {} used of {}",
+            format_size(system.used_memory(), 1, true),
+            format_size(system.total_memory(), 1, true),
+            format_size(system.available_memory(), 1, true),
+            format_size(system.used_swap(), 1, true),
+            format_size(system.total_swap(), 1, true),
+        );
+        [tasks, memory]
+    }
+
+    fn display<W: Write>(&self, stdout: &mut W) -> io::Result<()> {
+        let width = self.screen_width;
+        let mut buffer = String::new();
+        for line in self.summary() {
+            buffer.push_str(&fit(&line, width));
+            buffer.push_str("\r\n");
+        }
+        buffer.push_str(&fit("", width));
+        buffer.push_str("\r\n");
+
+        let header = format!(
+            "{:>8} {:<12} {:>6} {:>10} {:>5} {:>11}  {}",
+            "PID", "USER", "%CPU", "MEM", "%MEM", "TIME", "NAME"
+        );
+        buffer.push_str(&strong(&fit(&header, width)));
+        buffer.push_str("\r\n");
+
+        let total_memory = self.system.total_memory().max(1) as f64;
+        let page = self.page_size();
+        for index in self.offset..self.offset + page {
+            let line = match self.rows.get(index) {
+                Some(row) => fit(
+                    &format!(
+                        "{:>8} {:<12} {:>6.1} {:>10} {:>5.1} {:>11}  {}",
+                        row.pid.as_u32(),
+                        row.user.chars().take(12).collect::<String>(),
+                        row.cpu,
+                        format_size(row.memory, 1, true),
+                        row.memory as f64 * 100.0 / total_memory,
+                        format_duration(Duration::from_secs(row.time), DurationStyle::Clock),
+                        row.name
+                    ),
+                    width,
+                ),
+                None => fit("", width),
+            };
+            if index == self.selected && index < self.rows.len() {
+                buffer.push_str(&strong(&line));
+            } else {
+                buffer.push_str(&line);
+            }
+            buffer.push_str("\r\n");
+        }
+
+        let status = match &self.status_line {
+            Some(message) => message.clone(),
+            None => format!(
+                "Sorted by {}{} | h Help | q Quit",
+                self.sort.name(),
+                if self.reverse { " (reversed)" } else { "" }
+            ),
+        };
+        buffer.push_str(&strong(&fit(&status, width.saturating_sub(1))));
+
+        execute!(stdout, cursor::MoveTo(0, 0), Print(buffer))?;
+        stdout.flush()
+    }
+
+    /// Ask for confirmation on the status line, and terminate the selected process.
+    fn kill_selected<W: Write>(&mut self, stdout: &mut W) -> io::Result<()> {
+        let Some(row) = self.rows.get(self.selected) else {
+            return Ok(());
+        };
+        let (pid, name) = (row.pid, row.name.clone());
+        self.status_line = Some(format!("Kill process {} ({})? [y/N]", pid, name));
+        self.display(stdout)?;
+
+        let confirmed = loop {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    break matches!(key.code, KeyCode::Char('y') | KeyCode::Char('Y'));
+                }
+            }
+        };
+        self.status_line = if !confirmed {
+            None
+        } else {
+            // Ask the process to terminate, where supported, then kill it forcibly.
+            let killed = self
+                .system
+                .process(pid)
+                .map(|proc| proc.kill_with(Signal::Term).unwrap_or_else(|| proc.kill()));
+            match killed {
+                Some(true) => Some(format!("Terminated {} ({})", pid, name)),
+                Some(false) => Some(format!("Failed to kill {} ({})", pid, name)),
+                None => Some(format!("No such process: {}", pid)),
+            }
+        };
+        Ok(())
+    }
+
+    fn show_help(&mut self) {
+        let help_items = [
+            ("Up/Down", "Select"),
+            ("k", "Kill"),
+            ("c m p t n u", "Sort by CPU, MEM, PID, TIME, NAME, USER"),
+            ("r", "Reverse"),
+            ("q", "Quit"),
+        ];
+        self.status_line = Some(
+            help_items
+                .iter()
+                .map(|(key, description)| format!("{} {}", key, description))
+                .collect::<Vec<_>>()
+                .join(" | "),
+        );
+    }
+
+    fn run(&mut self, delay: Duration) -> io::Result<()> {
+        let mut stdout = io::stdout();
+        let _raw_mode = prompt::RawMode::new()?;
+        execute!(stdout, EnterAlternateScreen, cursor::Hide)?;
+
+        let result = self.event_loop(&mut stdout, delay);
+
+        execute!(stdout, cursor::Show, LeaveAlternateScreen)?;
+        result
+    }
+
+    fn event_loop<W: Write>(&mut self, stdout: &mut W, delay: Duration) -> io::Result<()> {
+        let mut next_refresh = Instant::now() + delay;
+        loop {
+            self.display(stdout)?;
+
+            let timeout = next_refresh.saturating_duration_since(Instant::now());
+            if !event::poll(timeout)? {
+                self.refresh();
+                next_refresh = Instant::now() + delay;
+                continue;
+            }
+            let key = match event::read()? {
+                Event::Resize(w, h) => {
+                    self.screen_width = w as usize;
+                    self.screen_height = h as usize;
+                    self.select(self.selected as isize);
+                    continue;
+                }
+                Event::Key(key) if key.kind == KeyEventKind::Press => key,
+                _ => continue,
+            };
+            let selected = self.selected as isize;
+            let page = self.page_size() as isize;
+            self.status_line = None;
+
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => break,
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
+                KeyCode::Up => self.select(selected - 1),
+                KeyCode::Down => self.select(selected + 1),
+                KeyCode::PageUp => self.select(selected - page),
+                KeyCode::PageDown => self.select(selected + page),
+                KeyCode::Home => self.select(0),
+                KeyCode::End => self.select(isize::MAX),
+                KeyCode::Char('k') | KeyCode::Delete => {
+                    self.kill_selected(stdout)?;
+                    self.refresh();
+                }
+                KeyCode::Char('r') => {
+                    self.reverse = !self.reverse;
+                    self.sort_rows(self.selected_pid());
+                }
+                KeyCode::Char('h') | KeyCode::F(1) => self.show_help(),
+                KeyCode::Char(c) => {
+                    if let Some(sort) = SortKey::from_key(c) {
+                        self.sort = sort;
+                        self.reverse = false;
+                        self.sort_rows(self.selected_pid());
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+const KEYS: [(&str, &str); 8] = [
+    ("Up / Down", "Select the previous / next process."),
+    ("PageUp / PageDown", "Move the selection by one page."),
+    ("Home / End", "Select the first / last process."),
+    ("k", "Kill the selected process (asks for confirmation)."),
+    (
+        "c m p t n u",
+        "Sort by CPU, memory, PID, time, name or user.",
+    ),
+    ("r", "Reverse the sort order."),
+    ("h", "Show hints at the bottom of the screen."),
+    ("q", "Quit."),
+];
+
+struct Top {
+    flags: CommandFlags,
+}
+
+impl Top {
+    fn new() -> Self {
+        let mut flags = CommandFlags::with_help();
+        flags.add_value('d', "delay", "SECONDS", "Time between updates (default: 2)");
+        flags.add_value(
+            's',
+            "sort",
+            "COLUMN",
+            "Sort by cpu (default), mem, pid, time, name or user",
+        );
+        flags.add_flag('u', "user", "Show only the processes of the current user");
+        Self { flags }
+    }
+}
+
+impl Exec for Top {
+    fn cli_flags(&self) -> Box<dyn Iterator<Item = &Flag> + '_> {
+        Box::new(self.flags.iter())
+    }
+
+    fn exec(&self, name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
+        let mut flags = self.flags.clone();
+        flags.parse(scope, args)?;
+
+        if flags.is_present("help") {
+            println!("Usage: {} [OPTIONS]", name);
+            println!(
+                "Display a live view of the running processes, and their CPU and memory usage."
+            );
+            println!("\nOptions:");
+            print!("{}", flags.help());
+            println!("\nUser Interaction:");
+            for (keys, description) in KEYS {
+                println!("    {:<20} {}", keys, description);
+            }
+            return Ok(Value::success());
+        }
+        if !io::stdout().is_terminal() {
+            return Err("Standard output is not a terminal".to_string());
+        }
+
+        let delay = match flags.value("delay") {
+            Some(value) => value
+                .parse::<f64>()
+                .ok()
+                .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                .filter(|d| *d >= MINIMUM_CPU_UPDATE_INTERVAL)
+                .ok_or_else(|| format_error(scope, value, args, "Invalid delay"))?,
+            None => Duration::from_secs(2),
+        };
+        let sort = match flags.value("sort") {
+            Some(value) => SortKey::from_name(value)
+                .ok_or_else(|| format_error(scope, value, args, "Unknown column"))?,
+            None => SortKey::Cpu,
+        };
+        let user = if flags.is_present("user") {
+            let system = System::new_all();
+            sysinfo::get_current_pid()
+                .ok()
+                .and_then(|pid| system.process(pid))
+                .and_then(|proc| proc.user_id().cloned())
+        } else {
+            None
+        };
+
+        Viewer::new(sort, user)
+            .run(delay)
+            .map_err(|e| e.to_string())?;
+
+        Ok(Value::success())
+    }
+}
+
+#[ctor::ctor]
+fn register() {
+    register_command(ShellCommand {
+        name: "top".to_string(),
+        inner: Arc::new(Top::new()),
+    });
+}
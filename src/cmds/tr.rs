@@ -0,0 +1,336 @@
+use super::{register_command, Exec, Flag, ShellCommand};
+use crate::{cmds::flags::CommandFlags, eval::Value, scope::Scope, utils::format_error};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead, ErrorKind, Write};
+use std::sync::Arc;
+
+/// Character classes, expanded to their ASCII members in ascending order.
+fn class_chars(name: &str) -> Option<Vec<char>> {
+    let pred: fn(&char) -> bool = match name {
+        "alnum" => |c| c.is_ascii_alphanumeric(),
+        "alpha" => |c| c.is_ascii_alphabetic(),
+        "blank" => |c| *c == ' ' || *c == '\t',
+        "cntrl" => |c| c.is_ascii_control(),
+        "digit" => |c| c.is_ascii_digit(),
+        "graph" => |c| c.is_ascii_graphic(),
+        "lower" => |c| c.is_ascii_lowercase(),
+        "print" => |c| c.is_ascii_graphic() || *c == ' ',
+        "punct" => |c| c.is_ascii_punctuation(),
+        "space" => |c| c.is_ascii_whitespace() || *c == '\x0b',
+        "upper" => |c| c.is_ascii_uppercase(),
+        "xdigit" => |c| c.is_ascii_hexdigit(),
+        _ => return None,
+    };
+    Some((0..128u8).map(char::from).filter(pred).collect())
+}
+
+/// Take the next character from the string, resolving backslash escapes.
+fn next_char(rest: &mut &str) -> Option<char> {
+    let mut iter = rest.chars();
+    let c = iter.next()?;
+    let c = if c == '\\' {
+        match iter.next() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('0') => '\0',
+            Some(c) => c,
+            None => '\\',
+        }
+    } else {
+        c
+    };
+    *rest = iter.as_str();
+    Some(c)
+}
+
+/// Expand the set: backslash escapes, ranges like a-z, and classes like [:upper:].
+fn parse_set(set: &str) -> Result<Vec<char>, String> {
+    let mut chars = Vec::new();
+    let mut rest = set;
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("[:") {
+            if let Some(end) = after.find(":]") {
+                let name = &after[..end];
+                let members =
+                    class_chars(name).ok_or_else(|| format!("Invalid class: [:{}:]", name))?;
+                chars.extend(members);
+                rest = &after[end + 2..];
+                continue;
+            }
+        }
+        let Some(first) = next_char(&mut rest) else {
+            break;
+        };
+        if rest.len() > 1 && rest.starts_with('-') {
+            let mut after = &rest[1..];
+            let last = next_char(&mut after).unwrap();
+            if last < first {
+                return Err(format!("Invalid range: {}-{}", first, last));
+            }
+            chars.extend(first..=last);
+            rest = after;
+        } else {
+            chars.push(first);
+        }
+    }
+    Ok(chars)
+}
+
+/// Characters to squeeze: those of the last set given.
+enum Squeeze {
+    None,
+    Set1,
+    Set2(HashSet<char>),
+}
+
+struct Translator {
+    complement: bool,
+    delete: bool,
+    set1: HashSet<char>,
+    map: HashMap<char, char>,
+    default: Option<char>, // Replacement for the complement of SET1
+    squeeze: Squeeze,
+}
+
+impl Translator {
+    fn new(
+        set1: &[char],
+        set2: &[char],
+        complement: bool,
+        delete: bool,
+        squeeze: bool,
+    ) -> Result<Self, String> {
+        let translate = !delete && !set2.is_empty();
+        if translate && set1.is_empty() && !complement {
+            return Err("The first set is empty".to_string());
+        }
+
+        let mut map = HashMap::new();
+        let mut default = None;
+        if translate {
+            if complement {
+                default = set2.last().copied();
+            } else {
+                // Pad the second set with its last character
+                let last = *set2.last().unwrap();
+                for (i, &c) in set1.iter().enumerate() {
+                    map.insert(c, *set2.get(i).unwrap_or(&last));
+                }
+            }
+        }
+
+        let squeeze = if !squeeze {
+            Squeeze::None
+        } else if set2.is_empty() {
+            Squeeze::Set1
+        } else {
+            Squeeze::Set2(set2.iter().copied().collect())
+        };
+
+        Ok(Self {
+            complement,
+            delete,
+            set1: set1.iter().copied().collect(),
+            map,
+            default,
+            squeeze,
+        })
+    }
+
+    fn in_set1(&self, c: char) -> bool {
+        self.set1.contains(&c) != self.complement
+    }
+
+    /// Translate the text; last is the last character output, for squeezing across calls.
+    fn translate(&self, text: &str, last: &mut Option<char>, out: &mut String) {
+        for c in text.chars() {
+            let c = if self.in_set1(c) {
+                if self.delete {
+                    continue;
+                }
+                match self.default {
+                    Some(d) => d,
+                    None => *self.map.get(&c).unwrap_or(&c),
+                }
+            } else {
+                c
+            };
+            if *last == Some(c) && self.is_squeezed(c) {
+                continue;
+            }
+            out.push(c);
+            *last = Some(c);
+        }
+    }
+
+    fn is_squeezed(&self, c: char) -> bool {
+        match &self.squeeze {
+            Squeeze::None => false,
+            Squeeze::Set1 => self.in_set1(c),
+            Squeeze::Set2(set) => set.contains(&c),
+        }
+    }
+}
+
+struct Tr {
+    flags: CommandFlags,
+}
+
+impl Tr {
+    fn new() -> Self {
+        let mut flags = CommandFlags::with_help();
+        flags.add_flag('c', "complement", "Use the complement of SET1");
+        flags.add_flag('d', "delete", "Delete the characters in SET1");
+        flags.add_flag(
+            's',
+            "squeeze-repeats",
+            "Replace runs of a character in the last set given with one occurrence",
+        );
+        Self { flags }
+    }
+}
+
+impl Exec for Tr {
+    fn cli_flags(&self) -> Box<dyn Iterator<Item = &Flag> + '_> {
+        Box::new(self.flags.iter())
+    }
+
+    fn exec(&self, name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
+        let mut flags = self.flags.clone();
+        let sets = flags.parse(scope, args)?;
+
+        if flags.is_present("help") {
+            println!("Usage: {} [OPTIONS] SET1 [SET2]", name);
+            println!(
+                "Translate, squeeze or delete characters from stdin, and write them to stdout."
+            );
+            println!(
+                "\nSETs are strings of characters, with ranges such as a-z, escapes (\\n, \\t,"
+            );
+            println!(
+                "\\\\) and the classes [:alnum:], [:alpha:], [:blank:], [:cntrl:], [:digit:],"
+            );
+            println!(
+                "[:graph:], [:lower:], [:print:], [:punct:], [:space:], [:upper:], [:xdigit:]."
+            );
+            println!("\nOptions:");
+            print!("{}", flags.help());
+            println!("\nExamples:");
+            println!("    echo hello | tr a-z A-Z");
+            println!("    tr -d \"[:digit:]\" < notes.txt");
+            println!("    echo \"a   b    c\" | tr -s \" \"");
+            return Ok(Value::success());
+        }
+
+        let complement = flags.is_present("complement");
+        let delete = flags.is_present("delete");
+        let squeeze = flags.is_present("squeeze-repeats");
+
+        let max_sets = if delete && !squeeze { 1 } else { 2 };
+        if sets.is_empty() {
+            return Err("Missing SET1".to_string());
+        }
+        if sets.len() > max_sets {
+            return Err(format_error(scope, &sets[max_sets], args, "Extra operand"));
+        }
+        if sets.len() == 1 && (delete == squeeze) {
+            return Err("Missing SET2".to_string());
+        }
+
+        let parse = |set: &String| parse_set(set).map_err(|e| format_error(scope, set, args, e));
+        let set1 = parse(&sets[0])?;
+        let set2 = sets.get(1).map(parse).transpose()?.unwrap_or_default();
+
+        let translator = Translator::new(&set1, &set2, complement, delete, squeeze)
+            .map_err(|e| format_error(scope, &sets[0], args, e))?;
+
+        scope.show_eof_hint();
+        let mut stdin = io::stdin().lock();
+        let mut stdout = io::stdout().lock();
+        let mut line = Vec::new();
+        let mut out = String::new();
+        let mut last = None;
+
+        loop {
+            if Scope::is_interrupted() {
+                break;
+            }
+            line.clear();
+            if stdin
+                .read_until(b'\n', &mut line)
+                .map_err(|e| e.to_string())?
+                == 0
+            {
+                break;
+            }
+            out.clear();
+            translator.translate(&String::from_utf8_lossy(&line), &mut last, &mut out);
+
+            match stdout.write_all(out.as_bytes()) {
+                Err(e) if e.kind() == ErrorKind::BrokenPipe => break,
+                Err(e) => return Err(e.to_string()),
+                Ok(_) => {}
+            }
+        }
+        _ = stdout.flush();
+
+        Ok(Value::success())
+    }
+}
+
+#[ctor::ctor]
+fn register() {
+    register_command(ShellCommand {
+        name: "tr".to_string(),
+        inner: Arc::new(Tr::new()),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tr(
+        set1: &str,
+        set2: &str,
+        complement: bool,
+        delete: bool,
+        squeeze: bool,
+        text: &str,
+    ) -> String {
+        let set1 = parse_set(set1).unwrap();
+        let set2 = parse_set(set2).unwrap();
+        let translator = Translator::new(&set1, &set2, complement, delete, squeeze).unwrap();
+        let mut out = String::new();
+        translator.translate(text, &mut None, &mut out);
+        out
+    }
+
+    #[test]
+    fn test_parse_set() {
+        assert_eq!(parse_set("a-d").unwrap(), vec!['a', 'b', 'c', 'd']);
+        assert_eq!(parse_set("x\\n-").unwrap(), vec!['x', '\n', '-']);
+        assert_eq!(parse_set("[:digit:]").unwrap().len(), 10);
+        assert_eq!(parse_set("[:upper:]").unwrap()[25], 'Z');
+        assert!(parse_set("z-a").is_err());
+        assert!(parse_set("[:bogus:]").is_err());
+    }
+
+    #[test]
+    fn test_translate() {
+        assert_eq!(tr("a-z", "A-Z", false, false, false, "Hello!"), "HELLO!");
+        assert_eq!(
+            tr("[:lower:]", "[:upper:]", false, false, false, "ab"),
+            "AB"
+        );
+        assert_eq!(tr("abc", "x", false, false, false, "abcd"), "xxxd");
+        assert_eq!(tr("[:digit:]", "", false, true, false, "a1b22c"), "abc");
+        assert_eq!(tr("a-z", "", true, true, false, "a1 b\n"), "ab");
+        assert_eq!(tr(" ", "", false, false, true, "a   b  c"), "a b c");
+        assert_eq!(tr("a-z", "_", true, false, true, "a  b,,c"), "a_b_c");
+        assert_eq!(tr("0-9", " ", false, true, true, "1a  2b"), "a b");
+        assert_eq!(tr("é", "e", false, false, false, "café"), "cafe");
+    }
+}
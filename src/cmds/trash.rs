@@ -0,0 +1,545 @@
+/// Move files to the trash instead of deleting them, and restore them later.
+///
+/// On Windows the files go to the Recycle Bin. Elsewhere the trash follows the XDG
+/// (freedesktop.org) layout: the files are moved to $XDG_DATA_HOME/Trash/files, and for
+/// each file Trash/info holds a NAME.trashinfo file with the original path and deletion date.
+/// Files on another file system than the trash are copied to it, then removed.
+use super::{flags::CommandFlags, register_command, Exec, Flag, ShellCommand};
+use crate::prompt::{confirm, Answer};
+use crate::{eval::Value, scope::Scope};
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+#[cfg(not(windows))]
+mod xdg {
+    use chrono::Local;
+    use std::fs::{self, OpenOptions};
+    use std::io::{self, Write};
+    use std::path::{Path, PathBuf};
+
+    #[derive(Debug, PartialEq)]
+    pub struct TrashItem {
+        pub name: String,  // Name of the file in Trash/files
+        pub path: PathBuf, // Original path
+        pub deleted: String,
+    }
+
+    /// Percent-encode the path, as required for the Path key of trashinfo files.
+    pub fn encode(path: &str) -> String {
+        let mut result = String::new();
+        for b in path.bytes() {
+            if b.is_ascii_alphanumeric() || b"-_.~/".contains(&b) {
+                result.push(b as char);
+            } else {
+                result.push_str(&format!("%{:02X}", b));
+            }
+        }
+        result
+    }
+
+    pub fn decode(text: &str) -> String {
+        let bytes = text.as_bytes();
+        let mut result = Vec::new();
+        let mut i = 0;
+        while i < bytes.len() {
+            let hex = text.get(i + 1..i + 3);
+            match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                Some(b) if bytes[i] == b'%' => {
+                    result.push(b);
+                    i += 3;
+                }
+                _ => {
+                    result.push(bytes[i]);
+                    i += 1;
+                }
+            }
+        }
+        String::from_utf8_lossy(&result).to_string()
+    }
+
+    /// Make the path absolute without resolving the last component, so that
+    /// symbolic links are trashed rather than their targets.
+    pub fn absolute(path: &Path) -> io::Result<PathBuf> {
+        let name = path
+            .file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid file name"))?;
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.canonicalize()?,
+            _ => std::env::current_dir()?,
+        };
+        Ok(parent.join(name))
+    }
+
+    fn copy_all(from: &Path, to: &Path) -> io::Result<()> {
+        let meta = fs::symlink_metadata(from)?;
+        if meta.file_type().is_symlink() {
+            std::os::unix::fs::symlink(fs::read_link(from)?, to)
+        } else if meta.is_dir() {
+            fs::create_dir(to)?;
+            for entry in fs::read_dir(from)? {
+                let entry = entry?;
+                copy_all(&entry.path(), &to.join(entry.file_name()))?;
+            }
+            fs::set_permissions(to, meta.permissions())
+        } else {
+            fs::copy(from, to).map(|_| ())
+        }
+    }
+
+    fn remove_all(path: &Path) -> io::Result<()> {
+        if fs::symlink_metadata(path)?.is_dir() {
+            fs::remove_dir_all(path)
+        } else {
+            fs::remove_file(path)
+        }
+    }
+
+    /// Copy the file (or directory) and remove the original. A partial copy is removed.
+    pub fn copy_and_remove(from: &Path, to: &Path) -> io::Result<()> {
+        if fs::symlink_metadata(to).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("Could not copy {}: {} exists", from.display(), to.display()),
+            ));
+        }
+        if let Err(e) = copy_all(from, to) {
+            _ = remove_all(to);
+            return Err(io::Error::new(
+                e.kind(),
+                format!("Could not copy {}: {}", from.display(), e),
+            ));
+        }
+        remove_all(from).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!(
+                    "Copied to {}, but could not remove {}: {}",
+                    to.display(),
+                    from.display(),
+                    e
+                ),
+            )
+        })
+    }
+
+    /// Rename the file. The trash may be on another file system than the file,
+    /// where renaming fails: copy the file to the trash, and remove it instead.
+    fn move_path(from: &Path, to: &Path) -> io::Result<()> {
+        match fs::rename(from, to) {
+            Err(e) if e.kind() == io::ErrorKind::CrossesDevices => copy_and_remove(from, to),
+            result => result,
+        }
+    }
+
+    pub struct TrashDir {
+        dir: PathBuf,
+    }
+
+    impl TrashDir {
+        pub fn new(dir: PathBuf) -> Self {
+            Self { dir }
+        }
+
+        /// The trash of the user: $XDG_DATA_HOME/Trash, or ~/.local/share/Trash
+        pub fn home() -> io::Result<Self> {
+            directories::BaseDirs::new()
+                .map(|dirs| Self::new(dirs.data_dir().join("Trash")))
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Home directory not found"))
+        }
+
+        fn files(&self) -> PathBuf {
+            self.dir.join("files")
+        }
+
+        fn info(&self, name: &str) -> PathBuf {
+            self.dir.join("info").join(format!("{}.trashinfo", name))
+        }
+
+        /// Move the file into the trash, return the name of the trashed file.
+        pub fn put(&self, path: &Path) -> io::Result<String> {
+            let path = absolute(path)?;
+            fs::symlink_metadata(&path)?;
+            fs::create_dir_all(self.files())?;
+            fs::create_dir_all(self.dir.join("info"))?;
+
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+
+            // Reserve a unique name by creating the info file
+            let mut n = 1;
+            let (name, mut info) = loop {
+                let name = if n == 1 {
+                    file_name.to_string()
+                } else {
+                    format!("{}.{}", file_name, n)
+                };
+                n += 1;
+                if fs::symlink_metadata(self.files().join(&name)).is_ok() {
+                    continue;
+                }
+                match OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(self.info(&name))
+                {
+                    Ok(file) => break (name, file),
+                    Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                    Err(e) => return Err(e),
+                }
+            };
+
+            let result = write!(
+                info,
+                "[Trash Info]\nPath={}\nDeletionDate={}\n",
+                encode(&path.to_string_lossy()),
+                Local::now().format("%Y-%m-%dT%H:%M:%S")
+            )
+            .and_then(|_| move_path(&path, &self.files().join(&name)));
+
+            if let Err(e) = result {
+                _ = fs::remove_file(self.info(&name));
+                return Err(e);
+            }
+            Ok(name)
+        }
+
+        /// List the trashed files, in the order they were deleted.
+        pub fn list(&self) -> io::Result<Vec<TrashItem>> {
+            let entries = match fs::read_dir(self.dir.join("info")) {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+                Err(e) => return Err(e),
+            };
+
+            let mut items = Vec::new();
+            for entry in entries {
+                let path = entry?.path();
+                let Some(name) = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .and_then(|name| name.strip_suffix(".trashinfo"))
+                else {
+                    continue;
+                };
+                let text = fs::read_to_string(&path)?;
+                let value = |key: &str| {
+                    text.lines()
+                        .find_map(|line| line.strip_prefix(key))
+                        .unwrap_or_default()
+                        .to_string()
+                };
+                items.push(TrashItem {
+                    name: name.to_string(),
+                    path: PathBuf::from(decode(&value("Path="))),
+                    deleted: value("DeletionDate="),
+                });
+            }
+            items.sort_by(|a, b| (&a.deleted, &a.name).cmp(&(&b.deleted, &b.name)));
+            Ok(items)
+        }
+
+        /// Move the file back to its original path, unless another file exists there.
+        pub fn restore(&self, item: &TrashItem) -> io::Result<()> {
+            if fs::symlink_metadata(&item.path).is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} already exists", item.path.display()),
+                ));
+            }
+            if let Some(parent) = item.path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            move_path(&self.files().join(&item.name), &item.path)?;
+            fs::remove_file(self.info(&item.name))
+        }
+
+        /// Permanently delete the files in the trash, return how many were deleted.
+        pub fn empty(&self) -> io::Result<usize> {
+            let mut count = 0;
+            for dir in [self.files(), self.dir.join("info")] {
+                let entries = match fs::read_dir(&dir) {
+                    Ok(entries) => entries,
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                    Err(e) => return Err(e),
+                };
+                for entry in entries {
+                    let entry = entry?;
+                    if entry.file_type()?.is_dir() {
+                        fs::remove_dir_all(entry.path())?;
+                    } else {
+                        fs::remove_file(entry.path())?;
+                    }
+                    if dir == self.files() {
+                        count += 1;
+                    }
+                }
+            }
+            Ok(count)
+        }
+    }
+}
+
+#[cfg(windows)]
+mod win {
+    use std::io;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Path;
+    use windows::core::PCWSTR;
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::UI::Shell::{
+        SHEmptyRecycleBinW, SHFileOperationW, FOF_ALLOWUNDO, FOF_NOCONFIRMATION, FOF_NOERRORUI,
+        FOF_SILENT, FO_DELETE, SHERB_NOCONFIRMATION, SHERB_NOPROGRESSUI, SHERB_NOSOUND,
+        SHFILEOPSTRUCTW,
+    };
+
+    /// Move the file to the Recycle Bin.
+    pub fn recycle(path: &Path) -> io::Result<()> {
+        // The path must be absolute, and the list of paths double null-terminated.
+        let path = std::path::absolute(path)?;
+        let mut from: Vec<u16> = path.as_os_str().encode_wide().collect();
+        from.extend([0, 0]);
+
+        let mut op = SHFILEOPSTRUCTW {
+            wFunc: FO_DELETE,
+            pFrom: PCWSTR(from.as_ptr()),
+            fFlags: (FOF_ALLOWUNDO | FOF_NOCONFIRMATION | FOF_NOERRORUI | FOF_SILENT).0 as u16,
+            ..Default::default()
+        };
+        let result = unsafe { SHFileOperationW(&mut op) };
+        if result != 0 {
+            return Err(io::Error::other(format!(
+                "Could not move to the Recycle Bin (error {:#x})",
+                result
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn empty() -> io::Result<()> {
+        unsafe {
+            SHEmptyRecycleBinW(
+                HWND::default(),
+                PCWSTR::null(),
+                SHERB_NOCONFIRMATION | SHERB_NOPROGRESSUI | SHERB_NOSOUND,
+            )
+        }
+        .map_err(|e| io::Error::other(e.to_string()))
+    }
+}
+
+/// Move the file (or directory) to the trash, used by rm --trash.
+pub fn move_to_trash(path: &Path) -> io::Result<()> {
+    #[cfg(windows)]
+    {
+        win::recycle(path)
+    }
+    #[cfg(not(windows))]
+    {
+        xdg::TrashDir::home()?.put(path).map(|_| ())
+    }
+}
+
+struct Trash {
+    flags: CommandFlags,
+}
+
+impl Trash {
+    fn new() -> Self {
+        let mut flags = CommandFlags::with_help();
+        flags.add_flag(
+            'f',
+            "force",
+            "Do not ask for confirmation before emptying the trash",
+        );
+        Self { flags }
+    }
+
+    #[cfg(not(windows))]
+    fn list(&self) -> Result<(), String> {
+        let trash = xdg::TrashDir::home().map_err(|e| e.to_string())?;
+        for item in trash.list().map_err(|e| e.to_string())? {
+            my_println!(
+                "{}  {}",
+                item.deleted.replace('T', " "),
+                item.path.display()
+            )?;
+        }
+        Ok(())
+    }
+
+    #[cfg(not(windows))]
+    fn restore(&self, scope: &Arc<Scope>, args: &[String]) -> Result<(), String> {
+        let trash = xdg::TrashDir::home().map_err(|e| e.to_string())?;
+        let items = trash.list().map_err(|e| e.to_string())?;
+
+        for arg in args {
+            // Match the original path, or the name in the trash; the latest deletion wins
+            let path = xdg::absolute(Path::new(arg)).ok();
+            let item = items
+                .iter()
+                .rev()
+                .find(|item| Some(&item.path) == path.as_ref())
+                .or_else(|| items.iter().find(|item| &item.name == arg))
+                .ok_or_else(|| format!("{}: not found in the trash", scope.err_str(arg)))?;
+
+            trash
+                .restore(item)
+                .map_err(|e| format!("{}: {}", scope.err_str(arg), e))?;
+        }
+        Ok(())
+    }
+
+    #[cfg(not(windows))]
+    fn empty(&self) -> Result<(), String> {
+        let trash = xdg::TrashDir::home().map_err(|e| e.to_string())?;
+        trash.empty().map(|_| ()).map_err(|e| e.to_string())
+    }
+
+    #[cfg(windows)]
+    fn list(&self) -> Result<(), String> {
+        Err("Listing is not supported on Windows, use the Recycle Bin".to_string())
+    }
+
+    #[cfg(windows)]
+    fn restore(&self, _: &Arc<Scope>, _: &[String]) -> Result<(), String> {
+        Err("Restoring is not supported on Windows, use the Recycle Bin".to_string())
+    }
+
+    #[cfg(windows)]
+    fn empty(&self) -> Result<(), String> {
+        win::empty().map_err(|e| e.to_string())
+    }
+}
+
+impl Exec for Trash {
+    fn cli_flags(&self) -> Box<dyn Iterator<Item = &Flag> + '_> {
+        Box::new(self.flags.iter())
+    }
+
+    fn exec(&self, _name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
+        let mut flags = self.flags.clone();
+        let args = flags.parse(scope, args)?;
+
+        if flags.is_present("help") {
+            println!("Usage: trash list");
+            println!("       trash restore FILE...");
+            println!("       trash empty");
+            println!(
+                "List, restore, or permanently delete the files moved to the trash by rm --trash."
+            );
+            println!("Files are restored by their original path.");
+            println!("\nOptions:");
+            print!("{}", flags.help());
+            println!();
+            println!("Examples:");
+            println!("    rm --trash notes.txt");
+            println!("    trash restore notes.txt");
+            return Ok(Value::success());
+        }
+
+        match args.first().map(String::as_str) {
+            Some("list") => self.list()?,
+            Some("restore") if args.len() > 1 => self.restore(scope, &args[1..])?,
+            Some("restore") => return Err("FILE not specified".to_string()),
+            Some("empty") => {
+                if flags.is_present("force")
+                    || confirm(
+                        "Permanently delete the files in the trash".to_string(),
+                        scope,
+                        false,
+                    )
+                    .map_err(|e| e.to_string())?
+                        == Answer::Yes
+                {
+                    self.empty()?;
+                }
+            }
+            Some(command) => {
+                return Err(format!(
+                    "{}: expecting list, restore or empty",
+                    scope.err_str(command)
+                ))
+            }
+            None => return Err("Expecting list, restore or empty".to_string()),
+        }
+        Ok(Value::success())
+    }
+}
+
+#[ctor::ctor]
+fn register() {
+    register_command(ShellCommand {
+        name: "trash".to_string(),
+        inner: Arc::new(Trash::new()),
+    });
+}
+
+#[cfg(all(test, not(windows)))]
+mod tests {
+    use super::xdg::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode("/tmp/a b%.txt"), "/tmp/a%20b%25.txt");
+        assert_eq!(decode("/tmp/a%20b%25.txt"), "/tmp/a b%.txt");
+        assert_eq!(decode("100%"), "100%");
+    }
+
+    #[test]
+    fn test_trash_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let trash = TrashDir::new(temp_dir.path().join("Trash"));
+        let path = temp_dir
+            .path()
+            .canonicalize()
+            .unwrap()
+            .join("file name.txt");
+
+        fs::write(&path, "one").unwrap();
+        assert_eq!(trash.put(&path).unwrap(), "file name.txt");
+        fs::write(&path, "two").unwrap();
+        assert_eq!(trash.put(&path).unwrap(), "file name.txt.2");
+        assert!(!path.exists());
+
+        let items = trash.list().unwrap();
+        assert_eq!(items.len(), 2);
+        assert!(items.iter().all(|item| item.path == path));
+
+        let item = items.iter().find(|item| item.name.ends_with(".2")).unwrap();
+        trash.restore(item).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "two");
+        assert!(trash.restore(&items[0]).is_err()); // The file exists
+
+        assert_eq!(trash.list().unwrap().len(), 1);
+        assert_eq!(trash.empty().unwrap(), 1);
+        assert!(trash.list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_copy_and_remove() {
+        let temp_dir = TempDir::new().unwrap();
+        let from = temp_dir.path().join("from");
+        fs::create_dir_all(from.join("sub")).unwrap();
+        fs::write(from.join("sub").join("a.txt"), "a").unwrap();
+        std::os::unix::fs::symlink("sub/a.txt", from.join("link")).unwrap();
+
+        let to = temp_dir.path().join("to");
+        copy_and_remove(&from, &to).unwrap();
+        assert!(!from.exists());
+        assert_eq!(
+            fs::read_to_string(to.join("sub").join("a.txt")).unwrap(),
+            "a"
+        );
+        assert_eq!(
+            fs::read_link(to.join("link")).unwrap(),
+            std::path::Path::new("sub/a.txt")
+        );
+
+        // The destination exists: nothing is copied, and the original is kept
+        let err = copy_and_remove(&to.join("sub"), &to).unwrap_err();
+        assert!(err.to_string().starts_with("Could not copy"), "{}", err);
+        assert!(to.join("sub").join("a.txt").exists());
+    }
+}
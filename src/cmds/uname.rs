@@ -0,0 +1,167 @@
+use super::{flags::CommandFlags, register_command, Exec, Flag, ShellCommand};
+use crate::{eval::Value, scope::Scope, utils::format_size};
+use serde_json::{Map, Value as Json};
+use std::sync::Arc;
+use sysinfo::{MemoryRefreshKind, System};
+
+/// Fields in output order: flag name, label and whether the value is numeric (for JSON).
+const FIELDS: [(&str, &str, bool); 8] = [
+    ("kernel-name", "Kernel", false),
+    ("nodename", "Host", false),
+    ("kernel-release", "Kernel release", false),
+    ("os-version", "OS version", false),
+    ("machine", "Architecture", false),
+    ("operating-system", "Operating system", false),
+    ("cpus", "CPUs", true),
+    ("memory", "Memory", true),
+];
+
+fn kernel_name() -> String {
+    if cfg!(target_os = "linux") {
+        "Linux".to_string()
+    } else if cfg!(target_os = "macos") {
+        "Darwin".to_string()
+    } else if cfg!(windows) {
+        "Windows_NT".to_string()
+    } else {
+        std::env::consts::OS.to_string()
+    }
+}
+
+fn total_memory() -> u64 {
+    let mut system = System::new();
+    system.refresh_memory_specifics(MemoryRefreshKind::new().with_ram());
+    system.total_memory()
+}
+
+/// Return the value of the field; human_readable applies to the memory size.
+fn field_value(name: &str, human_readable: bool) -> String {
+    let unknown = || "unknown".to_string();
+    match name {
+        "kernel-name" => kernel_name(),
+        "nodename" => System::host_name().unwrap_or_else(unknown),
+        "kernel-release" => System::kernel_version().unwrap_or_else(unknown),
+        "os-version" => System::os_version().unwrap_or_else(unknown),
+        "machine" => System::cpu_arch().unwrap_or_else(|| std::env::consts::ARCH.to_string()),
+        "operating-system" => System::long_os_version().unwrap_or_else(unknown),
+        "cpus" => std::thread::available_parallelism()
+            .map_or(1, |n| n.get())
+            .to_string(),
+        "memory" if human_readable => format_size(total_memory(), 1, true),
+        "memory" => total_memory().to_string(),
+        _ => unreachable!(),
+    }
+}
+
+struct Uname {
+    flags: CommandFlags,
+    all_by_default: bool, // sysinfo shows all the fields, uname the kernel name
+}
+
+impl Uname {
+    fn new(all_by_default: bool) -> Self {
+        let mut flags = CommandFlags::with_help();
+        flags.add_flag('a', "all", "Print all the information");
+        flags.add_flag('s', "kernel-name", "Print the kernel name");
+        flags.add_flag('n', "nodename", "Print the network node (host) name");
+        flags.add_flag('r', "kernel-release", "Print the kernel release");
+        flags.add_flag('v', "os-version", "Print the operating system version");
+        flags.add_flag('m', "machine", "Print the machine (CPU architecture) name");
+        flags.add_flag('o', "operating-system", "Print the operating system name");
+        flags.add_flag('c', "cpus", "Print the number of CPUs");
+        flags.add_flag('M', "memory", "Print the total memory");
+        flags.add_flag('j', "json", "Print the fields as a JSON object");
+        Self {
+            flags,
+            all_by_default,
+        }
+    }
+}
+
+impl Exec for Uname {
+    fn cli_flags(&self) -> Box<dyn Iterator<Item = &Flag> + '_> {
+        Box::new(self.flags.iter())
+    }
+
+    fn exec(&self, name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
+        let mut flags = self.flags.clone();
+        let extra = flags.parse(scope, args)?;
+
+        if flags.is_present("help") {
+            println!("Usage: {} [OPTIONS]", name);
+            println!("Print information about the system.");
+            println!("\nOptions:");
+            print!("{}", flags.help());
+            println!("\nExamples:");
+            println!("    uname -sr");
+            println!("    sysinfo --json");
+            return Ok(Value::success());
+        }
+        if let Some(arg) = extra.first() {
+            return Err(format!("Extra operand: {}", scope.err_str(arg)));
+        }
+
+        let all = flags.is_present("all");
+        let json = flags.is_present("json");
+
+        let mut selected = FIELDS
+            .iter()
+            .filter(|(field, _, _)| all || flags.is_present(field))
+            .collect::<Vec<_>>();
+
+        if selected.is_empty() {
+            if self.all_by_default || json {
+                selected = FIELDS.iter().collect();
+            } else {
+                selected.push(&FIELDS[0]);
+            }
+        }
+
+        if json {
+            let members = selected
+                .iter()
+                .map(|(field, _, numeric)| {
+                    let value = field_value(field, false);
+                    let value = match value.parse::<u64>() {
+                        Ok(n) if *numeric => Json::from(n),
+                        _ => Json::from(value),
+                    };
+                    (field.replace('-', "_"), value)
+                })
+                .collect::<Map<_, _>>();
+            my_println!("{}", Json::Object(members))?;
+        } else if self.all_by_default {
+            let width = selected.iter().map(|(_, label, _)| label.len()).max();
+            for (field, label, _) in &selected {
+                let label = format!("{}:", label);
+                my_println!(
+                    "{:<width$} {}",
+                    label,
+                    field_value(field, true),
+                    width = width.unwrap_or_default() + 1
+                )?;
+            }
+        } else {
+            let values = selected
+                .iter()
+                .map(|(field, _, _)| field_value(field, true))
+                .collect::<Vec<_>>();
+            my_println!("{}", values.join(" "))?;
+        }
+
+        Ok(Value::success())
+    }
+}
+
+#[ctor::ctor]
+fn register() {
+    register_command(ShellCommand {
+        name: "uname".to_string(),
+        inner: Arc::new(Uname::new(false)),
+    });
+
+    register_command(ShellCommand {
+        name: "sysinfo".to_string(),
+        inner: Arc::new(Uname::new(true)),
+    });
+}
@@ -0,0 +1,139 @@
+use super::{flags::CommandFlags, register_command, Exec, Flag, ShellCommand};
+use crate::{eval::Value, scope::Scope};
+use chrono::{DateTime, Local};
+use std::sync::Arc;
+use sysinfo::System;
+
+/// Format the elapsed time like uptime does, e.g. "3 days,  2:15".
+fn format_uptime(secs: u64) -> String {
+    let (days, hours, minutes) = (secs / 86400, secs % 86400 / 3600, secs % 3600 / 60);
+    let time = if hours > 0 {
+        format!("{:2}:{:02}", hours, minutes)
+    } else {
+        format!("{} min", minutes)
+    };
+    match days {
+        0 => time,
+        1 => format!("1 day, {}", time),
+        _ => format!("{} days, {}", days, time),
+    }
+}
+
+/// Format the elapsed time in words, e.g. "3 days, 2 hours, 15 minutes".
+fn format_pretty(secs: u64) -> String {
+    let parts = [
+        (secs / 604800, "week"),
+        (secs % 604800 / 86400, "day"),
+        (secs % 86400 / 3600, "hour"),
+        (secs % 3600 / 60, "minute"),
+    ];
+    let words = parts
+        .iter()
+        .filter(|(n, _)| *n > 0)
+        .map(|(n, unit)| format!("{} {}{}", n, unit, if *n == 1 { "" } else { "s" }))
+        .collect::<Vec<_>>();
+
+    if words.is_empty() {
+        "0 minutes".to_string()
+    } else {
+        words.join(", ")
+    }
+}
+
+/// Load averages on Unix; Windows has no equivalent, show the current CPU usage instead.
+fn load() -> String {
+    if cfg!(windows) {
+        let mut system = System::new();
+        system.refresh_cpu_usage();
+        std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
+        system.refresh_cpu_usage();
+        format!("CPU usage: {:.1}%", system.global_cpu_usage())
+    } else {
+        let load = System::load_average();
+        format!(
+            "load average: {:.2}, {:.2}, {:.2}",
+            load.one, load.five, load.fifteen
+        )
+    }
+}
+
+struct Uptime {
+    flags: CommandFlags,
+}
+
+impl Uptime {
+    fn new() -> Self {
+        let mut flags = CommandFlags::with_help();
+        flags.add_flag('p', "pretty", "Show the uptime in a human readable format");
+        flags.add_flag('s', "since", "Show the boot time, as yyyy-mm-dd HH:MM:SS");
+        Self { flags }
+    }
+}
+
+impl Exec for Uptime {
+    fn cli_flags(&self) -> Box<dyn Iterator<Item = &Flag> + '_> {
+        Box::new(self.flags.iter())
+    }
+
+    fn exec(&self, name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
+        let mut flags = self.flags.clone();
+        flags.parse(scope, args)?;
+
+        if flags.is_present("help") {
+            println!("Usage: {} [OPTIONS]", name);
+            println!("Show how long the system has been running, and the load averages.");
+            println!("\nOptions:");
+            print!("{}", flags.help());
+            return Ok(Value::success());
+        }
+
+        let uptime = System::uptime();
+
+        if flags.is_present("since") {
+            let boot_time = DateTime::from_timestamp(System::boot_time() as i64, 0)
+                .ok_or("Invalid boot time")?
+                .with_timezone(&Local);
+            my_println!("{}", boot_time.format("%Y-%m-%d %H:%M:%S"))?;
+        } else if flags.is_present("pretty") {
+            my_println!("up {}", format_pretty(uptime))?;
+        } else {
+            my_println!(
+                " {} up {},  {}",
+                Local::now().format("%H:%M:%S"),
+                format_uptime(uptime),
+                load()
+            )?;
+        }
+
+        Ok(Value::success())
+    }
+}
+
+#[ctor::ctor]
+fn register() {
+    register_command(ShellCommand {
+        name: "uptime".to_string(),
+        inner: Arc::new(Uptime::new()),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_uptime() {
+        assert_eq!(format_uptime(59), "0 min");
+        assert_eq!(format_uptime(45 * 60), "45 min");
+        assert_eq!(format_uptime(2 * 3600 + 15 * 60), " 2:15");
+        assert_eq!(format_uptime(86400 + 3600), "1 day,  1:00");
+        assert_eq!(format_uptime(3 * 86400 + 5 * 60), "3 days, 5 min");
+
+        assert_eq!(format_pretty(30), "0 minutes");
+        assert_eq!(format_pretty(3600 + 60), "1 hour, 1 minute");
+        assert_eq!(
+            format_pretty(8 * 86400 + 2 * 3600 + 15 * 60),
+            "1 week, 1 day, 2 hours, 15 minutes"
+        );
+    }
+}
@@ -24,22 +24,15 @@ impl Vars {
     }
 
     fn collect_vars(scope: &Arc<Scope>, local_only: bool) -> BTreeMap<Ident, Variable> {
-        let mut all_vars = BTreeMap::new();
-        let mut current_scope = Some(Arc::clone(scope));
-
-        while let Some(scope) = current_scope {
-            for (key, value) in scope.vars().iter() {
-                if !all_vars.contains_key(key) {
-                    all_vars.insert(key.clone(), value.clone());
-                }
-            }
-            if local_only {
-                break;
-            }
-            current_scope = scope.parent.as_ref().map(Arc::clone);
+        if local_only {
+            scope
+                .vars()
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect()
+        } else {
+            scope.visible_vars()
         }
-
-        all_vars
     }
 }
 
@@ -0,0 +1,143 @@
+use crate::scope::Scope;
+use std::collections::VecDeque;
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+/// The indices of the directory entries on the way from a starting path to an entry,
+/// used for sorting results in the order of a sequential, depth-first walk.
+pub type Key = Vec<usize>;
+
+/// How often idle threads wake up to check for Ctrl+C.
+const IDLE_TIMEOUT: Duration = Duration::from_millis(50);
+
+#[derive(Default)]
+struct State {
+    pending: usize, // Items queued or being visited
+    stopped: bool,
+}
+
+/// Work-stealing queues shared by the threads of a multi-threaded directory walk.
+/// Each thread takes work from the back of its own queue, and steals from the front
+/// of the other queues when its own is empty. Idle threads wait until more work is
+/// queued, or until all the work is done.
+pub struct WorkQueue<T> {
+    queues: Vec<Mutex<VecDeque<T>>>,
+    state: Mutex<State>,
+    ready: Condvar,
+}
+
+impl<T> WorkQueue<T> {
+    pub fn new(threads: usize) -> Self {
+        Self {
+            queues: (0..threads).map(|_| Mutex::new(VecDeque::new())).collect(),
+            state: Mutex::new(State::default()),
+            ready: Condvar::new(),
+        }
+    }
+
+    /// The number of threads (and queues).
+    pub fn threads(&self) -> usize {
+        self.queues.len()
+    }
+
+    pub fn push(&self, worker: usize, item: T) {
+        self.state.lock().unwrap().pending += 1;
+        self.queues[worker].lock().unwrap().push_back(item);
+
+        // Take the lock, so that an idle thread is either waiting already, or sees the item.
+        let _state = self.state.lock().unwrap();
+        self.ready.notify_one();
+    }
+
+    /// Make all threads return from run, leaving the remaining work undone.
+    pub fn stop(&self) {
+        self.state.lock().unwrap().stopped = true;
+        self.ready.notify_all();
+    }
+
+    fn next(&self, worker: usize) -> Option<T> {
+        if let Some(item) = self.queues[worker].lock().unwrap().pop_back() {
+            return Some(item);
+        }
+        let count = self.queues.len();
+        (1..count).find_map(|i| {
+            self.queues[(worker + i) % count]
+                .lock()
+                .unwrap()
+                .pop_front()
+        })
+    }
+
+    /// Wait for an item to visit; return None when the work is done, stopped or interrupted.
+    fn wait(&self, worker: usize) -> Option<T> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if state.stopped || Scope::is_interrupted() {
+                return None;
+            }
+            if let Some(item) = self.next(worker) {
+                return Some(item);
+            }
+            if state.pending == 0 {
+                return None;
+            }
+            state = self.ready.wait_timeout(state, IDLE_TIMEOUT).unwrap().0;
+        }
+    }
+
+    /// Visit items on the calling thread until all the work is done. The visitor may push
+    /// more items, typically the entries of the directory being visited.
+    pub fn run<F: FnMut(T)>(&self, worker: usize, mut visit: F) {
+        while let Some(item) = self.next(worker).or_else(|| self.wait(worker)) {
+            visit(item);
+
+            let mut state = self.state.lock().unwrap();
+            state.pending -= 1;
+            if state.pending == 0 {
+                self.ready.notify_all();
+            }
+            if state.stopped || Scope::is_interrupted() {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+    use std::thread;
+
+    #[test]
+    fn test_run() {
+        // Count down from each item, on multiple threads
+        let work = WorkQueue::new(4);
+        let visited = AtomicUsize::new(0);
+        work.push(0, 10usize);
+        work.push(1, 5usize);
+
+        thread::scope(|s| {
+            for worker in 0..work.threads() {
+                let (work, visited) = (&work, &visited);
+                s.spawn(move || {
+                    work.run(worker, |n| {
+                        visited.fetch_add(1, SeqCst);
+                        if n > 0 {
+                            work.push(worker, n - 1);
+                        }
+                    })
+                });
+            }
+        });
+        assert_eq!(visited.load(SeqCst), 17);
+
+        // Stopping leaves the rest of the work undone
+        let work = WorkQueue::new(2);
+        work.push(0, ());
+        work.push(0, ());
+        work.run(0, |_| work.stop());
+        assert!(work.state.lock().unwrap().stopped);
+        assert_eq!(work.queues[0].lock().unwrap().len(), 1);
+    }
+}
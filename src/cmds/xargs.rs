@@ -0,0 +1,325 @@
+use super::{flags::CommandFlags, get_command, register_command, Exec, Flag, ShellCommand};
+use crate::{
+    eval::Value,
+    scope::Scope,
+    utils::{copy_exported_vars_to_command_env, format_error},
+};
+use std::collections::VecDeque;
+use std::io::{self, Read};
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Split the input into items: NUL-terminated, lines, or separated by whitespace.
+fn split_items(input: &str, null: bool, lines: bool) -> Vec<String> {
+    if null {
+        input
+            .split('\0')
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect()
+    } else if lines {
+        input
+            .lines()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect()
+    } else {
+        input.split_whitespace().map(String::from).collect()
+    }
+}
+
+/// Build the argument lists of the invocations.
+fn build_invocations(
+    args: &[String],
+    items: Vec<String>,
+    replace: Option<&str>,
+    max_args: Option<usize>,
+) -> Vec<Vec<String>> {
+    match replace {
+        Some(replace) => items
+            .iter()
+            .map(|item| args.iter().map(|arg| arg.replace(replace, item)).collect())
+            .collect(),
+        None => {
+            let size = max_args.unwrap_or(items.len()).max(1);
+            items
+                .chunks(size)
+                .map(|chunk| args.iter().chain(chunk).cloned().collect())
+                .collect()
+        }
+    }
+}
+
+struct Xargs {
+    flags: CommandFlags,
+}
+
+impl Xargs {
+    fn new() -> Self {
+        let mut flags = CommandFlags::with_help();
+        flags.add_flag(
+            '0',
+            "null",
+            "Items are terminated by NUL characters instead of whitespace",
+        );
+        flags.add_value(
+            'n',
+            "max-args",
+            "N",
+            "Pass at most N items to each invocation of the command",
+        );
+        flags.add_value(
+            'I',
+            "replace",
+            "STR",
+            "Run the command once per input line, replacing STR in the arguments with the line",
+        );
+        flags.add_value(
+            'P',
+            "max-procs",
+            "N",
+            "Run up to N external commands at a time (default: 1)",
+        );
+        flags.add_flag(
+            't',
+            "verbose",
+            "Print each command line to stderr before running it",
+        );
+        Self { flags }
+    }
+
+    /// Return the number of arguments that are options of xargs (and their values),
+    /// and the index where the command starts, after the optional -- separator.
+    fn command_start(&self, args: &[String]) -> (usize, usize) {
+        let takes_value = |f: &Flag| f.takes_value.is_some();
+        let mut i = 0;
+
+        while i < args.len() {
+            let arg = &args[i];
+            if arg == "--" {
+                return (i, i + 1);
+            } else if let Some(long) = arg.strip_prefix("--") {
+                if self.flags.iter().any(|f| f.long == long && takes_value(f)) {
+                    i += 1;
+                }
+            } else if arg.starts_with('-') && arg.len() > 1 {
+                // The value of the flag is either the rest of the argument, or the next one
+                let chars = arg[1..].chars().collect::<Vec<_>>();
+                if let Some(pos) = chars.iter().position(|&c| {
+                    self.flags
+                        .iter()
+                        .any(|f| f.short == Some(c) && takes_value(f))
+                }) {
+                    if pos == chars.len() - 1 {
+                        i += 1;
+                    }
+                }
+            } else {
+                return (i, i);
+            }
+            i += 1;
+        }
+        (args.len(), args.len())
+    }
+
+    /// Run the invocations of an external command, up to procs at a time.
+    /// Return the number of failed invocations.
+    fn run_external(
+        scope: &Arc<Scope>,
+        name: &str,
+        path: &Path,
+        invocations: Vec<Vec<String>>,
+        procs: usize,
+        verbose: bool,
+    ) -> usize {
+        let commands = invocations
+            .into_iter()
+            .map(|args| {
+                let mut command = Command::new(path);
+                command.args(&args);
+                copy_exported_vars_to_command_env(&mut command, scope);
+                (args, command)
+            })
+            .collect::<VecDeque<_>>();
+
+        let count = commands.len();
+        let queue = Mutex::new(commands);
+        let failed = AtomicUsize::new(0);
+
+        thread::scope(|s| {
+            for _ in 0..procs.min(count) {
+                s.spawn(|| loop {
+                    if Scope::is_interrupted() {
+                        break;
+                    }
+                    let Some((args, mut command)) = queue.lock().unwrap().pop_front() else {
+                        break;
+                    };
+                    if verbose {
+                        eprintln!("{} {}", name, args.join(" "));
+                    }
+                    match command.status() {
+                        Ok(status) if status.success() => {}
+                        Ok(_) => _ = failed.fetch_add(1, SeqCst),
+                        Err(e) => {
+                            eprintln!("{}: {}", name, e);
+                            failed.fetch_add(1, SeqCst);
+                        }
+                    }
+                });
+            }
+        });
+
+        // Invocations not started because of Ctrl+C count as failed
+        let not_started = queue.into_inner().unwrap().len();
+        failed.load(SeqCst) + not_started
+    }
+}
+
+impl Exec for Xargs {
+    fn cli_flags(&self) -> Box<dyn Iterator<Item = &Flag> + '_> {
+        Box::new(self.flags.iter())
+    }
+
+    fn exec(&self, name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
+        let (options_end, start) = self.command_start(args);
+        let mut flags = self.flags.clone();
+        flags.parse(scope, &args[..options_end])?;
+
+        if flags.is_present("help") {
+            println!("Usage: {} [OPTIONS] [COMMAND [ARGS]...]", name);
+            println!("Run COMMAND (echo by default) with ARGS followed by items read from stdin.");
+            println!("Built-in commands run in the shell, one at a time; -P applies to external");
+            println!("commands. The command does not run when there are no items.");
+            println!("\nOptions:");
+            print!("{}", flags.help());
+            println!("\nExamples:");
+            println!("    find . \".*\\.log\" | xargs rm");
+            println!("    ls | xargs -n 1 -P 4 gzip");
+            println!("    cat hosts.txt | xargs -I {{}} ping -c 1 {{}}");
+            return Ok(Value::success());
+        }
+
+        let parse_number = |flag: &str| -> Result<Option<usize>, String> {
+            flags
+                .value(flag)
+                .map(|v| match v.parse::<usize>() {
+                    Ok(n) if n > 0 => Ok(n),
+                    Ok(_) => Err(format_error(scope, v, args, "expecting at least 1")),
+                    Err(e) => Err(format_error(scope, v, args, e)),
+                })
+                .transpose()
+        };
+        let max_args = parse_number("max-args")?;
+        let procs = parse_number("max-procs")?.unwrap_or(1);
+        let replace = flags.value("replace");
+        let verbose = flags.is_present("verbose");
+
+        let default_command = vec!["echo".to_string()];
+        let command = if start < args.len() {
+            &args[start..]
+        } else {
+            &default_command
+        };
+        let cmd_name = &command[0];
+        let cmd = get_command(cmd_name)
+            .ok_or_else(|| format!("Command not found: {}", scope.err_str(cmd_name)))?;
+
+        scope.show_eof_hint();
+        let mut input = String::new();
+        io::stdin()
+            .lock()
+            .read_to_string(&mut input)
+            .map_err(|e| e.to_string())?;
+
+        let items = split_items(&input, flags.is_present("null"), replace.is_some());
+        let invocations = build_invocations(&command[1..], items, replace, max_args);
+        let count = invocations.len();
+
+        let failed = if cmd.is_external() {
+            Self::run_external(scope, cmd_name, &cmd.path(), invocations, procs, verbose)
+        } else {
+            let mut failed = 0;
+            for args in invocations {
+                if Scope::is_interrupted() {
+                    break;
+                }
+                if verbose {
+                    eprintln!("{} {}", cmd_name, args.join(" "));
+                }
+                if let Err(e) = cmd.exec(cmd_name, &args, scope) {
+                    my_warning!(scope, "{}: {}", scope.err_str(cmd_name), e);
+                    failed += 1;
+                }
+            }
+            failed
+        };
+
+        if failed > 0 {
+            scope.set_exit_status(123);
+            return Err(format!("{} of {} invocations failed", failed, count));
+        }
+        Ok(Value::success())
+    }
+}
+
+#[ctor::ctor]
+fn register() {
+    register_command(ShellCommand {
+        name: "xargs".to_string(),
+        inner: Arc::new(Xargs::new()),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_command_start() {
+        let xargs = Xargs::new();
+        assert_eq!(
+            xargs.command_start(&strings(&["-n", "2", "grep", "-i"])),
+            (2, 2)
+        );
+        assert_eq!(
+            xargs.command_start(&strings(&["-0tn2", "ls", "-l"])),
+            (1, 1)
+        );
+        assert_eq!(
+            xargs.command_start(&strings(&["-I", "{}", "--", "-x"])),
+            (2, 3)
+        );
+        assert_eq!(xargs.command_start(&strings(&["--max-procs", "4"])), (2, 2));
+    }
+
+    #[test]
+    fn test_invocations() {
+        let items = split_items(" a b\nc  d\n", false, false);
+        assert_eq!(items, strings(&["a", "b", "c", "d"]));
+
+        assert_eq!(
+            build_invocations(&strings(&["-l"]), items.clone(), None, Some(3)),
+            vec![strings(&["-l", "a", "b", "c"]), strings(&["-l", "d"])]
+        );
+        assert_eq!(
+            build_invocations(&strings(&["-l"]), items, None, None),
+            vec![strings(&["-l", "a", "b", "c", "d"])]
+        );
+
+        let items = split_items("x y\nz\n", false, true);
+        assert_eq!(
+            build_invocations(&strings(&["{}.bak", "{}"]), items, Some("{}"), None),
+            vec![strings(&["x y.bak", "x y"]), strings(&["z.bak", "z"])]
+        );
+        assert_eq!(split_items("a b\0c\0", true, false), strings(&["a b", "c"]));
+    }
+}
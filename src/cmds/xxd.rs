@@ -0,0 +1,377 @@
+use super::{flags::CommandFlags, register_command, Exec, Flag, ShellCommand};
+use crate::{eval::Value, scope::Scope, utils::format_error};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::sync::Arc;
+
+/// Parse decimal or 0x-prefixed hexadecimal numbers.
+fn parse_number(s: &str) -> Result<i64, String> {
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let n = match digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        Some(hex) => i64::from_str_radix(hex, 16),
+        None => digits.parse::<i64>(),
+    }
+    .map_err(|e| e.to_string())?;
+    Ok(if negative { -n } else { n })
+}
+
+struct Layout {
+    cols: usize,
+    group: usize, // Bytes per group, 0 for no grouping
+    upper: bool,
+    plain: bool,
+}
+
+impl Layout {
+    fn hex(&self, b: u8) -> String {
+        if self.upper {
+            format!("{:02X}", b)
+        } else {
+            format!("{:02x}", b)
+        }
+    }
+
+    /// Format a line of output: the offset, the bytes in hex, and the printable ASCII characters.
+    fn format_line(&self, offset: u64, bytes: &[u8]) -> String {
+        if self.plain {
+            return bytes.iter().map(|&b| self.hex(b)).collect();
+        }
+
+        let group = if self.group == 0 {
+            self.cols
+        } else {
+            self.group
+        };
+        let groups = self.cols.div_ceil(group);
+        let width = self.cols * 2 + groups - 1;
+
+        let hex = bytes
+            .chunks(group)
+            .map(|chunk| chunk.iter().map(|&b| self.hex(b)).collect::<String>())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let ascii = bytes
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect::<String>();
+
+        format!("{:08x}: {:<width$}  {}", offset, hex, ascii, width = width)
+    }
+}
+
+/// Parse a line of a hex dump, return the offset (if any) and the bytes.
+/// In plain mode, the line is just hex digits; otherwise the hex digits follow the offset,
+/// up to the ASCII column, which starts after two spaces.
+fn parse_line(line: &str, plain: bool) -> Result<(Option<u64>, Vec<u8>), String> {
+    let (offset, hex) = if plain {
+        (None, line)
+    } else {
+        match line.split_once(':') {
+            Some((offset, rest)) => {
+                let offset = u64::from_str_radix(offset.trim(), 16)
+                    .map_err(|_| format!("Invalid offset: {}", offset))?;
+                (Some(offset), rest.strip_prefix(' ').unwrap_or(rest))
+            }
+            None => return Ok((None, Vec::new())), // Not a hex dump line, skip it
+        }
+    };
+
+    let mut bytes = Vec::new();
+    let mut digits = String::new();
+    let mut spaces = 0;
+    for c in hex.chars() {
+        if c.is_ascii_whitespace() {
+            spaces += 1;
+            if spaces > 1 && !plain {
+                break;
+            }
+            continue;
+        }
+        spaces = 0;
+        if !c.is_ascii_hexdigit() {
+            if plain {
+                return Err(format!("Invalid hex digit: {}", c));
+            }
+            break;
+        }
+        digits.push(c);
+        if digits.len() == 2 {
+            bytes.push(u8::from_str_radix(&digits, 16).unwrap());
+            digits.clear();
+        }
+    }
+    Ok((offset, bytes))
+}
+
+/// Read until the buffer is full or the end of the input.
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match reader.read(&mut buf[n..]) {
+            Ok(0) => break,
+            Ok(count) => n += count,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(n)
+}
+
+struct Xxd {
+    flags: CommandFlags,
+}
+
+impl Xxd {
+    fn new() -> Self {
+        let mut flags = CommandFlags::with_help();
+        flags.add_value(
+            'c',
+            "cols",
+            "N",
+            "Output N bytes per line (default: 16, 30 with -p)",
+        );
+        flags.add_value(
+            'g',
+            "groupsize",
+            "N",
+            "Separate the output in groups of N bytes (default: 2, 0 for no grouping)",
+        );
+        flags.add_value(
+            's',
+            "seek",
+            "OFFSET",
+            "Start at OFFSET (from the end of the file if negative)",
+        );
+        flags.add_value('l', "len", "LEN", "Stop after LEN bytes");
+        flags.add_flag('p', "plain", "Output plain hex, without offsets and ASCII");
+        flags.add_flag('u', "upper", "Use uppercase hex letters");
+        flags.add_flag(
+            'r',
+            "reverse",
+            "Convert a hex dump (normal or plain) back to binary",
+        );
+        Self { flags }
+    }
+
+    fn dump<R: Read, W: Write>(
+        reader: &mut R,
+        writer: &mut W,
+        layout: &Layout,
+        mut offset: u64,
+        mut len: Option<u64>,
+    ) -> io::Result<()> {
+        let mut buf = vec![0; layout.cols];
+        while len != Some(0) && !Scope::is_interrupted() {
+            let want = len.map_or(buf.len(), |l| l.min(buf.len() as u64) as usize);
+            let n = read_full(reader, &mut buf[..want])?;
+            if n == 0 {
+                break;
+            }
+            writeln!(writer, "{}", layout.format_line(offset, &buf[..n]))?;
+            offset += n as u64;
+            len = len.map(|l| l - n as u64);
+        }
+        writer.flush()
+    }
+
+    fn reverse<R: BufRead, W: Write>(reader: R, writer: &mut W, plain: bool) -> Result<(), String> {
+        let mut pos = 0;
+        for (i, line) in reader.lines().enumerate() {
+            if Scope::is_interrupted() {
+                break;
+            }
+            let line = line.map_err(|e| e.to_string())?;
+            let (offset, bytes) =
+                parse_line(&line, plain).map_err(|e| format!("Line {}: {}", i + 1, e))?;
+
+            if let Some(offset) = offset {
+                if offset < pos {
+                    return Err(format!("Line {}: Offset goes backwards", i + 1));
+                }
+                // Fill gaps in the offsets with zeros
+                io::copy(&mut io::repeat(0).take(offset - pos), writer)
+                    .map_err(|e| e.to_string())?;
+                pos = offset;
+            }
+            writer.write_all(&bytes).map_err(|e| e.to_string())?;
+            pos += bytes.len() as u64;
+        }
+        writer.flush().map_err(|e| e.to_string())
+    }
+}
+
+impl Exec for Xxd {
+    fn cli_flags(&self) -> Box<dyn Iterator<Item = &Flag> + '_> {
+        Box::new(self.flags.iter())
+    }
+
+    fn exec(&self, name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
+        let mut flags = self.flags.clone();
+        let operands = flags.parse(scope, args)?;
+
+        if flags.is_present("help") {
+            println!("Usage: {} [OPTIONS] [INFILE [OUTFILE]]", name);
+            println!(
+                "Make a hex dump of INFILE (or of standard input), or convert it back with -r."
+            );
+            println!("\nOptions:");
+            print!("{}", flags.help());
+            println!("\nExamples:");
+            println!("    xxd -l 64 image.png");
+            println!("    xxd -s -32 -g 1 data.bin");
+            println!("    xxd -p key.bin | xxd -r -p > copy.bin");
+            return Ok(Value::success());
+        }
+        if operands.len() > 2 {
+            return Err(format_error(scope, &operands[2], args, "Extra operand"));
+        }
+
+        let number = |flag: &str| {
+            flags
+                .value(flag)
+                .map(|v| parse_number(v).map_err(|e| format_error(scope, v, args, e)))
+                .transpose()
+        };
+        let unsigned = |flag: &str| -> Result<Option<usize>, String> {
+            match number(flag)? {
+                Some(n) if n < 0 => {
+                    let v = flags.value(flag).unwrap();
+                    Err(format_error(scope, v, args, "Expecting a positive number"))
+                }
+                n => Ok(n.map(|n| n as usize)),
+            }
+        };
+
+        let plain = flags.is_present("plain");
+        let layout = Layout {
+            cols: unsigned("cols")?
+                .unwrap_or(if plain { 30 } else { 16 })
+                .max(1),
+            group: unsigned("groupsize")?.unwrap_or(2),
+            upper: flags.is_present("upper"),
+            plain,
+        };
+        let seek = number("seek")?.unwrap_or(0);
+        let len = unsigned("len")?.map(|n| n as u64);
+
+        let input = operands.first().filter(|f| *f != "-");
+        let output = operands.get(1);
+
+        let mut writer: Box<dyn Write> = match output {
+            Some(path) => Box::new(BufWriter::new(
+                File::create(path).map_err(|e| format_error(scope, path, args, e))?,
+            )),
+            None => Box::new(BufWriter::new(io::stdout().lock())),
+        };
+        let mut offset = seek.max(0) as u64;
+        let mut reader: Box<dyn BufRead> = match input {
+            Some(path) => {
+                let mut file = File::open(path).map_err(|e| format_error(scope, path, args, e))?;
+                if seek != 0 && !flags.is_present("reverse") {
+                    let from = if seek < 0 {
+                        SeekFrom::End(seek)
+                    } else {
+                        SeekFrom::Start(seek as u64)
+                    };
+                    offset = file
+                        .seek(from)
+                        .map_err(|e| format_error(scope, path, args, e))?;
+                }
+                Box::new(BufReader::new(file))
+            }
+            None => {
+                scope.show_eof_hint();
+                let mut stdin = io::stdin().lock();
+                if seek < 0 {
+                    return Err("Cannot seek from the end of standard input".to_string());
+                } else if seek > 0 && !flags.is_present("reverse") {
+                    io::copy(&mut (&mut stdin).take(seek as u64), &mut io::sink())
+                        .map_err(|e| e.to_string())?;
+                }
+                Box::new(stdin)
+            }
+        };
+
+        if flags.is_present("reverse") {
+            return Self::reverse(reader, &mut writer, plain).map(|_| Value::success());
+        }
+
+        match Self::dump(&mut reader, &mut writer, &layout, offset, len) {
+            Err(e) if e.kind() != ErrorKind::BrokenPipe => Err(e.to_string()),
+            _ => Ok(Value::success()),
+        }
+    }
+}
+
+#[ctor::ctor]
+fn register() {
+    register_command(ShellCommand {
+        name: "xxd".to_string(),
+        inner: Arc::new(Xxd::new()),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(cols: usize, group: usize, plain: bool) -> Layout {
+        Layout {
+            cols,
+            group,
+            upper: false,
+            plain,
+        }
+    }
+
+    #[test]
+    fn test_format_line() {
+        assert_eq!(
+            layout(16, 2, false).format_line(16, b"s is xxd\n\x00\x01\xff"),
+            "00000010: 7320 6973 2078 7864 0a00 01ff            s is xxd...."
+        );
+        assert_eq!(
+            layout(8, 1, false).format_line(0, b"hello wo"),
+            "00000000: 68 65 6c 6c 6f 20 77 6f  hello wo"
+        );
+        assert_eq!(
+            layout(4, 0, false).format_line(0, b"ab"),
+            "00000000: 6162      ab"
+        );
+        assert_eq!(layout(30, 2, true).format_line(0, b"\x00\xab"), "00ab");
+        assert_eq!(parse_number("0x10"), Ok(16));
+        assert_eq!(parse_number("-32"), Ok(-32));
+    }
+
+    #[test]
+    fn test_reverse() {
+        let line = "00000010: 7320 6973 2078 7864 0a00 01ff            s is xxd....";
+        assert_eq!(
+            parse_line(line, false),
+            Ok((Some(16), b"s is xxd\n\x00\x01\xff".to_vec()))
+        );
+        assert_eq!(
+            parse_line("00000000: 6162  ab 12", false),
+            Ok((Some(0), b"ab".to_vec()))
+        );
+        assert_eq!(parse_line("6162 63", true), Ok((None, b"abc".to_vec())));
+        assert!(parse_line("61zz", true).is_err());
+
+        let mut out = Vec::new();
+        let dump = "00000000: 6162  ab\n00000004: 63  c\n";
+        Xxd::reverse(dump.as_bytes(), &mut out, false).unwrap();
+        assert_eq!(out, b"ab\0\0c");
+    }
+}
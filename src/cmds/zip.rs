@@ -0,0 +1,564 @@
+use super::{flags::CommandFlags, register_command, Exec, Flag, ShellCommand};
+use crate::{
+    eval::Value,
+    prompt::{confirm, Answer},
+    scope::Scope,
+    utils::format_error,
+};
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use glob::Pattern;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+/// Parse comma-separated glob patterns.
+fn parse_patterns(
+    scope: &Scope,
+    spec: Option<&str>,
+    args: &[String],
+) -> Result<Vec<Pattern>, String> {
+    spec.map_or(Ok(Vec::new()), |spec| {
+        spec.split(',')
+            .filter(|p| !p.is_empty())
+            .map(|p| Pattern::new(p).map_err(|e| format_error(scope, spec, args, e)))
+            .collect()
+    })
+}
+
+/// Match the patterns against the name of the entry, and against its last component.
+fn matches_any(patterns: &[Pattern], name: &str) -> bool {
+    let name = name.trim_end_matches('/');
+    let base = name.rsplit('/').next().unwrap_or(name);
+    patterns.iter().any(|p| p.matches(name) || p.matches(base))
+}
+
+/// Convert the last modified time of a member to local time.
+fn modified_time(time: Option<zip::DateTime>) -> Option<DateTime<Local>> {
+    let naive = NaiveDateTime::try_from(time?).ok()?;
+    Local.from_local_datetime(&naive).earliest()
+}
+
+/// Make the name of an archive member from a path: relative, with forward slashes.
+fn entry_name(path: &Path) -> String {
+    path.components()
+        .filter_map(|c| match c {
+            Component::Normal(name) => Some(name.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(unix)]
+fn unix_mode(meta: &fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(meta.permissions().mode())
+}
+
+#[cfg(not(unix))]
+fn unix_mode(_: &fs::Metadata) -> Option<u32> {
+    None
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode & 0o7777))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_: &Path, _: u32) -> io::Result<()> {
+    Ok(())
+}
+
+struct Zip {
+    flags: CommandFlags,
+}
+
+/// The files to add to the archive, and the options.
+struct Archiver<'a> {
+    scope: &'a Arc<Scope>,
+    archive: PathBuf,
+    options: SimpleFileOptions,
+    recursive: bool,
+    quiet: bool,
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    writer: ZipWriter<BufWriter<File>>,
+}
+
+impl<'a> Archiver<'a> {
+    fn is_selected(&self, name: &str, is_dir: bool) -> bool {
+        !matches_any(&self.exclude, name)
+            && (is_dir || self.include.is_empty() || matches_any(&self.include, name))
+    }
+
+    /// Add the file, or the directory and (if recursive) its contents.
+    fn add(&mut self, path: &Path) -> Result<(), String> {
+        if Scope::is_interrupted() {
+            return Ok(());
+        }
+        let scope = self.scope;
+        let meta = fs::metadata(path).map_err(|e| format!("{}: {}", scope.err_path(path), e))?;
+        let mut name = entry_name(path);
+
+        if meta.is_dir() {
+            if !name.is_empty() {
+                name.push('/');
+                if self.is_selected(&name, true) {
+                    self.write_entry(path, &name, &meta)
+                        .map_err(|e| format!("{}: {}", scope.err_path(path), e))?;
+                } else {
+                    return Ok(());
+                }
+            }
+            if self.recursive {
+                let mut children = fs::read_dir(path)
+                    .and_then(|dir| {
+                        dir.map(|e| e.map(|e| e.path()))
+                            .collect::<io::Result<Vec<_>>>()
+                    })
+                    .map_err(|e| format!("{}: {}", scope.err_path(path), e))?;
+                children.sort();
+                for child in children {
+                    if child.is_symlink() && child.is_dir() {
+                        my_warning!(
+                            scope,
+                            "{}: Skipping link to directory",
+                            scope.err_path(&child)
+                        );
+                        continue;
+                    }
+                    self.add(&child)?;
+                }
+            }
+        } else if self.is_selected(&name, false) {
+            // Do not add the archive to itself
+            if fs::canonicalize(path).ok() == fs::canonicalize(&self.archive).ok() {
+                return Ok(());
+            }
+            self.write_entry(path, &name, &meta)
+                .map_err(|e| format!("{}: {}", scope.err_path(path), e))?;
+        }
+        Ok(())
+    }
+
+    fn write_entry(&mut self, path: &Path, name: &str, meta: &fs::Metadata) -> io::Result<()> {
+        let mut options = self.options.large_file(meta.len() >= u32::MAX as u64);
+        if let Some(time) = meta
+            .modified()
+            .ok()
+            .and_then(|t| zip::DateTime::try_from(DateTime::<Local>::from(t).naive_local()).ok())
+        {
+            options = options.last_modified_time(time);
+        }
+        if let Some(mode) = unix_mode(meta) {
+            options = options.unix_permissions(mode);
+        }
+
+        if meta.is_dir() {
+            self.writer.add_directory(name, options)?;
+        } else {
+            let mut file = File::open(path)?;
+            self.writer.start_file(name, options)?;
+            io::copy(&mut file, &mut self.writer)?;
+        }
+        if !self.quiet {
+            my_println!("  adding: {}", name).map_err(io::Error::other)?;
+        }
+        Ok(())
+    }
+}
+
+impl Zip {
+    fn new() -> Self {
+        let mut flags = CommandFlags::with_help();
+        flags.add_flag('r', "recursive", "Add the contents of directories");
+        flags.add_value(
+            'i',
+            "include",
+            "PATTERNS",
+            "Add only files matching the comma-separated glob patterns",
+        );
+        flags.add_value(
+            'x',
+            "exclude",
+            "PATTERNS",
+            "Do not add files matching the comma-separated glob patterns",
+        );
+        flags.add_value(
+            'l',
+            "level",
+            "N",
+            "Compression level, from 0 (store only) to 9 (best); default: 6",
+        );
+        flags.add_flag('f', "force", "Overwrite the archive without asking");
+        flags.add_flag('q', "quiet", "Do not list the files added");
+        Self { flags }
+    }
+}
+
+impl Exec for Zip {
+    fn cli_flags(&self) -> Box<dyn Iterator<Item = &Flag> + '_> {
+        Box::new(self.flags.iter())
+    }
+
+    fn exec(&self, name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
+        let mut flags = self.flags.clone();
+        let operands = flags.parse(scope, args)?;
+
+        if flags.is_present("help") {
+            println!("Usage: {} [OPTIONS] ARCHIVE FILE...", name);
+            println!("Create a .zip ARCHIVE from FILEs (and directories, with -r).");
+            println!("\nOptions:");
+            print!("{}", flags.help());
+            println!("\nExamples:");
+            println!("    zip -r project.zip src Cargo.toml");
+            println!("    zip -r -x \"*.o,target\" backup.zip .");
+            return Ok(Value::success());
+        }
+        if operands.len() < 2 {
+            return Err("Missing operand: ARCHIVE FILE...".to_string());
+        }
+
+        let level = match flags.value("level") {
+            Some(v) => match v.parse::<u32>() {
+                Ok(n) if n <= 9 => n,
+                _ => {
+                    return Err(format_error(
+                        scope,
+                        v,
+                        args,
+                        "Expecting a level from 0 to 9",
+                    ))
+                }
+            },
+            None => 6,
+        };
+
+        let archive = PathBuf::from(&operands[0]);
+        if archive.exists() && !flags.is_present("force") {
+            match confirm(format!("Overwrite {}", archive.display()), scope, false)
+                .map_err(|e| e.to_string())?
+            {
+                Answer::Yes | Answer::All => {}
+                _ => return Ok(Value::success()),
+            }
+        }
+
+        let method = if level == 0 {
+            CompressionMethod::Stored
+        } else {
+            CompressionMethod::Deflated
+        };
+        let file =
+            File::create(&archive).map_err(|e| format_error(scope, &operands[0], args, e))?;
+
+        let mut archiver = Archiver {
+            scope,
+            archive: archive.clone(),
+            options: SimpleFileOptions::default()
+                .compression_method(method)
+                .compression_level(Some(level as i64)),
+            recursive: flags.is_present("recursive"),
+            quiet: flags.is_present("quiet"),
+            include: parse_patterns(scope, flags.value("include"), args)?,
+            exclude: parse_patterns(scope, flags.value("exclude"), args)?,
+            writer: ZipWriter::new(BufWriter::new(file)),
+        };
+
+        for path in &operands[1..] {
+            if let Err(e) = archiver.add(Path::new(path)) {
+                _ = fs::remove_file(&archive);
+                return Err(e);
+            }
+        }
+        if Scope::is_interrupted() {
+            _ = fs::remove_file(&archive);
+            return Ok(Value::success());
+        }
+        archiver
+            .writer
+            .finish()
+            .and_then(|mut w| Ok(w.flush()?))
+            .map_err(|e| format_error(scope, &operands[0], args, e))?;
+
+        Ok(Value::success())
+    }
+}
+
+/// What to do with files that exist already.
+#[derive(Clone, Copy, PartialEq)]
+enum Overwrite {
+    Ask,
+    Always,
+    Never,
+}
+
+struct Unzip {
+    flags: CommandFlags,
+}
+
+impl Unzip {
+    fn new() -> Self {
+        let mut flags = CommandFlags::with_help();
+        flags.add_value('d', "dir", "DIR", "Extract the files into DIR");
+        flags.add_flag('l', "list", "List the contents of the archive");
+        flags.add_flag('t', "test", "Check the integrity of the archive");
+        flags.add_value(
+            'x',
+            "exclude",
+            "PATTERNS",
+            "Skip members matching the comma-separated glob patterns",
+        );
+        flags.add_flag('o', "overwrite", "Overwrite existing files without asking");
+        flags.add_flag('n', "never-overwrite", "Never overwrite existing files");
+        flags.add_flag('q', "quiet", "Do not list the files extracted");
+        Self { flags }
+    }
+
+    fn list(zip: &mut ZipArchive<BufReader<File>>, selected: &[usize]) -> Result<(), String> {
+        my_println!("  Length      Date    Time    Name")?;
+        my_println!("---------  ---------- -----   ----")?;
+        let mut total = 0u64;
+        for &index in selected {
+            let entry = zip.by_index_raw(index).map_err(|e| e.to_string())?;
+            let time = modified_time(entry.last_modified())
+                .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_else(|| "????-??-?? ??:??".to_string());
+            my_println!("{:>9}  {}   {}", entry.size(), time, entry.name())?;
+            total += entry.size();
+        }
+        my_println!("---------                     -------")?;
+        my_println!("{:>9}                     {} files", total, selected.len())?;
+        Ok(())
+    }
+}
+
+impl Exec for Unzip {
+    fn cli_flags(&self) -> Box<dyn Iterator<Item = &Flag> + '_> {
+        Box::new(self.flags.iter())
+    }
+
+    fn exec(&self, name: &str, args: &Vec<String>, scope: &Arc<Scope>) -> Result<Value, String> {
+        let mut flags = self.flags.clone();
+        let operands = flags.parse(scope, args)?;
+
+        if flags.is_present("help") {
+            println!("Usage: {} [OPTIONS] ARCHIVE [PATTERN]...", name);
+            println!("Extract the members of a .zip ARCHIVE (those matching PATTERNs, if given).");
+            println!("\nOptions:");
+            print!("{}", flags.help());
+            println!("\nExamples:");
+            println!("    unzip -l project.zip");
+            println!("    unzip -d out project.zip \"*.rs\"");
+            return Ok(Value::success());
+        }
+        let Some(archive) = operands.first() else {
+            return Err("Missing ARCHIVE operand".to_string());
+        };
+
+        let mut zip = File::open(archive)
+            .map_err(zip::result::ZipError::from)
+            .and_then(|f| ZipArchive::new(BufReader::new(f)))
+            .map_err(|e| format_error(scope, archive, args, e))?;
+
+        let include = operands[1..]
+            .iter()
+            .map(|p| Pattern::new(p).map_err(|e| format_error(scope, p, args, e)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let exclude = parse_patterns(scope, flags.value("exclude"), args)?;
+        let selected = (0..zip.len())
+            .filter(|&i| {
+                let name = zip.name_for_index(i).unwrap_or_default();
+                (include.is_empty() || matches_any(&include, name)) && !matches_any(&exclude, name)
+            })
+            .collect::<Vec<_>>();
+
+        if flags.is_present("list") {
+            return Self::list(&mut zip, &selected).map(|_| Value::success());
+        }
+
+        let test = flags.is_present("test");
+        let quiet = flags.is_present("quiet");
+        let dest_dir = PathBuf::from(flags.value("dir").unwrap_or("."));
+        let mut overwrite = if flags.is_present("overwrite") {
+            Overwrite::Always
+        } else if flags.is_present("never-overwrite") {
+            Overwrite::Never
+        } else {
+            Overwrite::Ask
+        };
+
+        let mut errors = 0;
+        for &index in &selected {
+            if Scope::is_interrupted() {
+                break;
+            }
+            let name = zip.name_for_index(index).unwrap_or_default().to_string();
+            let mut entry = match zip.by_index(index) {
+                Ok(entry) => entry,
+                Err(e) => {
+                    errors += 1;
+                    my_warning!(scope, "{}: {}", scope.err_str(&name), e);
+                    continue;
+                }
+            };
+
+            // The crate verifies the CRC when reaching the end of the data
+            if test {
+                match io::copy(&mut entry, &mut io::sink()) {
+                    Ok(_) if !quiet => my_println!("    testing: {:<40} OK", name)?,
+                    Ok(_) => {}
+                    Err(e) => {
+                        errors += 1;
+                        my_warning!(scope, "{}: {}", scope.err_str(&name), e);
+                    }
+                }
+                continue;
+            }
+
+            // Do not write outside of the destination
+            let Some(relative) = entry.enclosed_name() else {
+                my_warning!(scope, "{}: Skipping unsafe path", scope.err_str(&name));
+                errors += 1;
+                continue;
+            };
+            let dest = dest_dir.join(relative);
+
+            let result = if entry.is_dir() {
+                fs::create_dir_all(&dest)
+            } else {
+                if dest.exists() {
+                    let skip = match overwrite {
+                        Overwrite::Always => false,
+                        Overwrite::Never => true,
+                        Overwrite::Ask => {
+                            let prompt = format!("Overwrite {}", dest.display());
+                            match confirm(prompt, scope, selected.len() > 1)
+                                .map_err(|e| e.to_string())?
+                            {
+                                Answer::Yes => false,
+                                Answer::No => true,
+                                Answer::All => {
+                                    overwrite = Overwrite::Always;
+                                    false
+                                }
+                                Answer::Quit => break,
+                            }
+                        }
+                    };
+                    if skip {
+                        continue;
+                    }
+                }
+                if !quiet {
+                    my_println!("  inflating: {}", dest.display())?;
+                }
+                dest.parent()
+                    .map_or(Ok(()), fs::create_dir_all)
+                    .and_then(|_| File::create(&dest))
+                    .and_then(|f| {
+                        let mut writer = BufWriter::new(f);
+                        io::copy(&mut entry, &mut writer)?;
+                        writer.flush()
+                    })
+            };
+
+            let result = result.and_then(|_| {
+                if let Some(time) = modified_time(entry.last_modified()) {
+                    let mtime = filetime::FileTime::from_unix_time(time.timestamp(), 0);
+                    filetime::set_file_mtime(&dest, mtime)?;
+                }
+                match entry.unix_mode() {
+                    Some(mode) if !entry.is_dir() => set_mode(&dest, mode),
+                    _ => Ok(()),
+                }
+            });
+            if let Err(e) = result {
+                errors += 1;
+                my_warning!(scope, "{}: {}", scope.err_path(&dest), e);
+            }
+        }
+
+        if errors > 0 {
+            return Err(format!("{} errors in {}", errors, archive));
+        }
+        if test && !Scope::is_interrupted() {
+            my_println!("No errors detected in compressed data of {}.", archive)?;
+        }
+        Ok(Value::success())
+    }
+}
+
+#[ctor::ctor]
+fn register() {
+    register_command(ShellCommand {
+        name: "zip".to_string(),
+        inner: Arc::new(Zip::new()),
+    });
+
+    register_command(ShellCommand {
+        name: "unzip".to_string(),
+        inner: Arc::new(Unzip::new()),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_zip_unzip() {
+        let scope = Scope::new();
+        let tmp = TempDir::new().unwrap();
+        let src = tmp.path().join("src");
+        fs::create_dir_all(src.join("sub")).unwrap();
+        fs::write(src.join("a.txt"), "hello ".repeat(1000)).unwrap();
+        fs::write(src.join("sub").join("b.rs"), "fn main() {}\n").unwrap();
+        fs::write(src.join("sub").join("c.o"), [0u8, 1, 2]).unwrap();
+
+        let archive = tmp.path().join("test.zip");
+        let args = |items: &[&Path]| -> Vec<String> {
+            items.iter().map(|p| p.display().to_string()).collect()
+        };
+        let zip_args = [
+            vec!["-rqf".to_string(), "-x".to_string(), "*.o".to_string()],
+            args(&[&archive, &src]),
+        ]
+        .concat();
+        Zip::new().exec("zip", &zip_args, &scope).unwrap();
+
+        let mut zip = ZipArchive::new(File::open(&archive).unwrap()).unwrap();
+        let names = (0..zip.len())
+            .map(|i| zip.name_for_index(i).unwrap().to_string())
+            .collect::<Vec<_>>();
+        let prefix = entry_name(&src);
+        assert_eq!(
+            names,
+            vec![
+                format!("{}/", prefix),
+                format!("{}/a.txt", prefix),
+                format!("{}/sub/", prefix),
+                format!("{}/sub/b.rs", prefix),
+            ]
+        );
+        assert!(zip.by_index(1).unwrap().compressed_size() < 100);
+
+        let out = tmp.path().join("out");
+        let unzip_args = [
+            vec!["-q".to_string(), "-d".to_string()],
+            args(&[&out, &archive]),
+            vec!["*.rs".to_string()],
+        ]
+        .concat();
+        Unzip::new().exec("unzip", &unzip_args, &scope).unwrap();
+
+        let extracted = out.join(&prefix).join("sub").join("b.rs");
+        assert_eq!(fs::read_to_string(extracted).unwrap(), "fn main() {}\n");
+        assert!(!out.join(&prefix).join("a.txt").exists());
+    }
+}
@@ -1,14 +1,29 @@
 /// Custom (user-defined) completions.
 /// Used by CmdLineHelper.
+use crate::utils::executable;
+use regex::Regex;
+use std::collections::HashMap;
+use std::env;
 use std::fs;
+use std::hash::Hash;
 use std::io::{self, Read};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
 use yaml_rust::yaml::{Yaml, YamlLoader};
 
 /// Retrieves a list of suggestions based on the provided input and YAML configuration.
 ///
-/// This function analyzes the user's input and suggests possible commands, subcommands, or options
-/// based on a hierarchical configuration defined in YAML. The hierarchy is commands → subcommands → options.
+/// This function analyzes the user's input and suggests possible commands, subcommands, options,
+/// or arguments, based on a hierarchical configuration defined in YAML. Commands have subcommands,
+/// which can have subcommands of their own, to any depth; each command or subcommand can define
+/// options, and positional arguments.
+///
+/// Options are either strings, or maps with a name and (optionally) the arguments of the option.
+/// Arguments are either a list of strings, or a dynamic source: a command that is run (by a child
+/// instance of the interpreter) to produce the candidates, one per line of output, or one per match
+/// of an optional regular expression. The output of a source is cached for `cache` seconds.
 ///
 /// # Arguments
 ///
@@ -17,8 +32,9 @@ use yaml_rust::yaml::{Yaml, YamlLoader};
 ///
 /// # Returns
 ///
-/// Returns a `Vec<String>` containing suggestions based on the input. Suggestions are formatted as
-/// "command subcommand" or "command subcommand option" depending on the input's completeness.
+/// Returns a `Vec<Suggestion>` containing suggestions based on the input. Suggestions are formatted as
+/// "command subcommand" or "command subcommand option" depending on the input's completeness, and
+/// have the description of the last word, if the configuration provides one.
 /// # Example
 ///
 /// ```
@@ -27,91 +43,241 @@ use yaml_rust::yaml::{Yaml, YamlLoader};
 ///   - name: git
 ///     subcommands:
 ///       - name: commit
+///         description: Record changes to the repository
 ///         options:
-///           - amend
-///           - no-verify
-///       - name: clone
+///           - --amend
+///           - --no-verify
+///       - name: checkout
 ///         options:
-///           - depth
-///           - branch
+///           - name: -b
+///         args:
+///           command: git branch --format=%(refname:short)
+///           cache: 30
+///       - name: stash
+///         subcommands:
+///           - name: list
+///           - name: pop
 ///   - name: docker
 ///     subcommands:
 ///       - name: run
 ///         options:
-///           - detach
-///           - rm
-///       - name: build
-///         options:
-///           - tag
-///           - no-cache
+///           - --detach
+///           - name: --network
+///             args: [bridge, host, none]
 /// "#;
 /// let config = YamlLoader::load_from_str(config_str).unwrap()[0].clone();
 /// let suggestions = suggest(&config, "git c");
-/// assert_eq!(suggestions, vec!["git commit", "git clone"]);
+/// assert_eq!(suggestions, vec!["git commit", "git checkout"]);
 /// ```
-pub fn suggest(config: &Yaml, input: &str) -> Vec<String> {
-    const LEVELS: &[&str] = &["commands", "subcommands", "options"];
+pub fn suggest(config: &Yaml, input: &str) -> Vec<Suggestion> {
+    let words: Vec<&str> = input.split_whitespace().collect();
+    if words.is_empty() {
+        return Vec::new();
+    }
 
-    let parts: Vec<&str> = input.split_whitespace().collect();
+    // Walk down the tree of commands and subcommands.
+    let mut node = config;
+    let mut children = &config["commands"];
+    let mut i = 0;
 
-    let mut current = config;
-    let mut prefix = Vec::new();
-    let mut suggestions = Vec::new();
+    while let Some(child) = words.get(i).and_then(|word| find(children, word)) {
+        node = child;
+        children = &child["subcommands"];
+        i += 1;
+    }
 
-    fn elem_to_str(elem: &Yaml) -> &str {
-        if let Some(elem_name) = elem["name"].as_str() {
-            elem_name.trim()
-        } else {
-            elem.as_str().unwrap_or("")
+    let (prefix, partial, candidates) = if i == 0 {
+        if words.len() > 1 {
+            return Vec::new(); // Unknown command
         }
-    }
+        (String::new(), words[0], elems(children).collect())
+    } else if i == words.len() {
+        // The input ends with a complete command or subcommand, suggest what follows it.
+        (words.join(" "), "", candidates(node, &[]))
+    } else {
+        let (last, complete) = words.split_last().unwrap();
+        (complete.join(" "), *last, candidates(node, &complete[i..]))
+    };
 
-    for i in 0..LEVELS.len() {
-        if let Some(elems) = current[LEVELS[i]].as_vec() {
-            match parts.get(i) {
-                None => {
-                    if !prefix.is_empty() {
-                        let prefix = prefix.join(" ");
-                        for elem in elems {
-                            suggestions.push(format!("{} {}", prefix, elem_to_str(elem)));
-                        }
-                    }
-                    break;
-                }
-                Some(mut part) => {
-                    for j in i + 1.. {
-                        for elem in elems {
-                            let elem_name = elem_to_str(elem);
-                            if *part == elem_name {
-                                prefix.push(*part);
-                                current = elem;
-                                break;
-                            }
-
-                            if elem_name.starts_with(part) {
-                                if prefix.is_empty() {
-                                    suggestions.push(elem_name.to_string());
-                                } else {
-                                    suggestions.push(format!("{} {}", prefix.join(" "), elem_name));
-                                };
-                            }
-                        }
-
-                        // Match all remaining input parts against the last hierarchy level
-                        if j < LEVELS.len() {
-                            break; // Not last level
-                        }
-                        if let Some(next) = parts.get(j) {
-                            part = next;
-                        } else {
-                            break; // No more input parts
-                        }
-                    }
+    candidates
+        .into_iter()
+        .filter(|c| c.text.starts_with(partial))
+        .map(|c| {
+            if prefix.is_empty() {
+                c
+            } else {
+                Suggestion {
+                    text: format!("{} {}", prefix, c.text),
+                    ..c
                 }
             }
+        })
+        .collect()
+}
+
+/// Suggested completion, with the description from the configuration, if any.
+#[derive(Debug, PartialEq)]
+pub struct Suggestion {
+    pub text: String,
+    pub description: Option<String>,
+}
+
+impl PartialEq<&str> for Suggestion {
+    fn eq(&self, other: &&str) -> bool {
+        self.text == *other
+    }
+}
+
+/// Return the candidates for the word that follows the given words of the (sub)command node:
+/// the arguments of the preceding option, if it takes any; otherwise the options (excluding
+/// the ones already present) and the arguments of the node, and its subcommands if no other
+/// words follow the node.
+fn candidates(node: &Yaml, words: &[&str]) -> Vec<Suggestion> {
+    if let Some(option) = words.last().and_then(|word| find(&node["options"], word)) {
+        if !option["args"].is_badvalue() {
+            return args(&option["args"]);
+        }
+    }
+
+    let mut candidates = Vec::new();
+    if words.is_empty() {
+        candidates.extend(elems(&node["subcommands"]));
+    }
+    candidates.extend(elems(&node["options"]).filter(|opt| !words.contains(&opt.text.as_str())));
+    candidates.extend(args(&node["args"]));
+
+    candidates
+}
+
+fn elem_to_str(elem: &Yaml) -> &str {
+    if let Some(elem_name) = elem["name"].as_str() {
+        elem_name.trim()
+    } else {
+        elem.as_str().unwrap_or("")
+    }
+}
+
+/// Convert the elements of a list, which are either strings or maps with a name
+/// and an optional description, to suggestions.
+fn elems(list: &Yaml) -> impl Iterator<Item = Suggestion> + '_ {
+    list.as_vec()
+        .into_iter()
+        .flatten()
+        .filter(|elem| !elem_to_str(elem).is_empty())
+        .map(|elem| Suggestion {
+            text: elem_to_str(elem).to_string(),
+            description: elem["description"].as_str().map(String::from),
+        })
+}
+
+fn find<'a>(elems: &'a Yaml, name: &str) -> Option<&'a Yaml> {
+    elems
+        .as_vec()?
+        .iter()
+        .find(|elem| elem_to_str(elem) == name)
+}
+
+/// Get the argument candidates, from a list or from a dynamic source.
+fn args(source: &Yaml) -> Vec<Suggestion> {
+    match source {
+        Yaml::Array(_) => elems(source).collect(),
+        Yaml::Hash(_) => source["command"]
+            .as_str()
+            .map(|command| {
+                let ttl = source["cache"].as_i64().unwrap_or(DEFAULT_CACHE_SECS);
+                run_source(command, source["regex"].as_str(), ttl.max(0) as u64)
+                    .into_iter()
+                    .map(|text| Suggestion {
+                        text,
+                        description: None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+const DEFAULT_CACHE_SECS: i64 = 10;
+
+/// Time-limited cache for the results of expensive completions, such as directory scans
+/// on network drives and the output of dynamic sources, keyed by the completion context,
+/// e.g. the working directory and the input.
+pub struct Cache<K, V> {
+    entries: Mutex<HashMap<K, (Instant, V)>>,
+}
+
+impl<K: Eq + Hash, V: Clone> Cache<K, V> {
+    pub fn new() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
         }
     }
-    suggestions
+
+    /// Return the cached result for the context, unless expired.
+    pub fn get(&self, context: &K) -> Option<V> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(context)
+            .filter(|(expires, _)| *expires > Instant::now())
+            .map(|(_, value)| value.clone())
+    }
+
+    /// Cache the result for the context, for the ttl duration. Expired entries are dropped.
+    pub fn insert(&self, context: K, value: V, ttl: Duration) {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, (expires, _)| *expires > now);
+        entries.insert(context, (now + ttl, value));
+    }
+}
+
+/// Output of dynamic sources, keyed by working directory and command.
+static SOURCE_CACHE: LazyLock<Cache<(PathBuf, String), Vec<String>>> = LazyLock::new(Cache::new);
+
+/// Run the command of a dynamic source and parse its output into candidates:
+/// one per line, or one per match of the regex (the first capture group, if any).
+fn run_source(command: &str, regex: Option<&str>, ttl: u64) -> Vec<String> {
+    let context = (env::current_dir().unwrap_or_default(), command.to_string());
+    if let Some(candidates) = SOURCE_CACHE.get(&context) {
+        return candidates;
+    }
+
+    let Ok(program) = executable() else {
+        return Vec::new();
+    };
+    let Ok(output) = Command::new(program)
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+    else {
+        return Vec::new();
+    };
+    let output = String::from_utf8_lossy(&output.stdout);
+
+    let candidates = match regex.map(Regex::new) {
+        Some(Ok(re)) => output
+            .lines()
+            .flat_map(|line| {
+                re.captures_iter(line)
+                    .filter_map(|caps| caps.get(1).or_else(|| caps.get(0)))
+                    .map(|m| m.as_str().to_string())
+                    .collect::<Vec<_>>()
+            })
+            .collect(),
+        Some(Err(_)) => Vec::new(),
+        None => output
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(String::from)
+            .collect(),
+    };
+
+    SOURCE_CACHE.insert(context, candidates.clone(), Duration::from_secs(ttl));
+    candidates
 }
 
 /// Loads the YAML configuration from the specified file.
@@ -301,4 +467,80 @@ mod tests {
         let suggestions = suggest(config, "git clone --verbose --n");
         assert_eq!(suggestions, vec!["git clone --verbose --no-hard-links"]);
     }
+
+    #[test]
+    fn test_nested_subcommands_and_option_args() {
+        let config_str = r#"
+        commands:
+          - name: git
+            subcommands:
+              - name: stash
+                subcommands:
+                  - name: list
+                  - name: pop
+                    description: Apply and remove the latest stash
+                options:
+                  - --quiet
+          - name: docker
+            subcommands:
+              - name: run
+                options:
+                  - --detach
+                  - name: --network
+                    args: [bridge, host, none]
+        "#;
+        let config = &YamlLoader::load_from_str(config_str).unwrap()[0];
+
+        assert_eq!(
+            suggest(config, "git stash"),
+            vec!["git stash list", "git stash pop", "git stash --quiet"]
+        );
+        assert_eq!(
+            suggest(config, "git stash p"),
+            vec![Suggestion {
+                text: "git stash pop".to_string(),
+                description: Some("Apply and remove the latest stash".to_string()),
+            }]
+        );
+        assert_eq!(
+            suggest(config, "docker run --network h"),
+            vec!["docker run --network host"]
+        );
+        assert_eq!(
+            suggest(config, "docker run --detach --"),
+            vec!["docker run --detach --network"]
+        );
+    }
+
+    #[test]
+    fn test_dynamic_source() {
+        let config_str = r#"
+        commands:
+          - name: git
+            subcommands:
+              - name: checkout
+                args:
+                  command: echo main feature-x feature-y
+                  regex: "[\\w-]+"
+        "#;
+        let config = &YamlLoader::load_from_str(config_str).unwrap()[0];
+
+        assert_eq!(
+            suggest(config, "git checkout feat"),
+            vec!["git checkout feature-x", "git checkout feature-y"]
+        );
+        // Served from the cache
+        assert_eq!(suggest(config, "git checkout m"), vec!["git checkout main"]);
+    }
+
+    #[test]
+    fn test_cache() {
+        let cache = Cache::new();
+        cache.insert("ls sr", 1, Duration::from_secs(60));
+        cache.insert("ls ta", 2, Duration::ZERO);
+
+        assert_eq!(cache.get(&"ls sr"), Some(1));
+        assert_eq!(cache.get(&"ls ta"), None); // Expired
+        assert_eq!(cache.get(&"ls"), None);
+    }
 }
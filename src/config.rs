@@ -0,0 +1,186 @@
+/// Shell configuration, loaded at startup from ~/.shmy/config.toml.
+///
+/// # Example
+///
+/// ```toml
+/// [editor]
+/// mode = "vi"            # or "emacs"
+/// completion = "list"    # or "circular"
+///
+/// [history]
+/// size = 5000
+/// ignore_dups = true
+///
+/// [prompt]
+/// spec = '\u@\h:\w\$ '
+///
+/// [colors]
+/// command = "bright green"
+/// keyword = "magenta"
+///
+/// [commands]
+/// ls = ["-h"]
+/// grep = ["-i", "-n"]
+/// ```
+use rustyline::{CompletionType, EditMode};
+use std::fs;
+use std::path::Path;
+use toml::{Table, Value};
+
+/// Look up a dotted path, e.g. "editor.mode", in the table.
+fn get<'t>(table: &'t Table, path: &str) -> Option<&'t Value> {
+    let mut keys = path.split('.');
+    let first = table.get(keys.next()?)?;
+    keys.try_fold(first, |value, key| value.as_table()?.get(key))
+}
+
+#[derive(Debug, Default)]
+pub struct Config {
+    pub edit_mode: Option<EditMode>,
+    pub completion_type: Option<CompletionType>,
+    pub history_size: Option<usize>,
+    pub history_ignore_dups: Option<bool>,
+    pub correct: Option<String>, // Correction of mistyped commands: hint, ask or auto
+    pub prompt: Option<String>,
+    pub colors: Vec<(String, String)>, // Syntax highlighting colors
+    pub default_flags: Vec<(String, Vec<String>)>, // Flags passed to commands by default
+}
+
+impl Config {
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        let toml = text.parse::<Table>().map_err(|e| e.to_string())?;
+        let mut config = Config::default();
+
+        let str_list = |value: &Value| -> Option<Vec<String>> {
+            match value {
+                Value::Array(items) => items
+                    .iter()
+                    .map(|item| item.as_str().map(String::from))
+                    .collect(),
+                Value::String(s) => Some(s.split_whitespace().map(String::from).collect()),
+                _ => None,
+            }
+        };
+
+        if let Some(mode) = get(&toml, "editor.mode") {
+            config.edit_mode = Some(match mode.as_str() {
+                Some("emacs") => EditMode::Emacs,
+                Some("vi") => EditMode::Vi,
+                _ => return Err("editor.mode: expecting \"emacs\" or \"vi\"".to_string()),
+            });
+        }
+        if let Some(completion) = get(&toml, "editor.completion") {
+            config.completion_type = Some(match completion.as_str() {
+                Some("list") => CompletionType::List,
+                Some("circular") => CompletionType::Circular,
+                _ => {
+                    return Err("editor.completion: expecting \"list\" or \"circular\"".to_string())
+                }
+            });
+        }
+        if let Some(correct) = get(&toml, "editor.correct") {
+            config.correct = Some(match correct.as_str() {
+                Some(mode @ ("hint" | "ask" | "auto")) => mode.to_string(),
+                _ => {
+                    return Err(
+                        "editor.correct: expecting \"hint\", \"ask\" or \"auto\"".to_string()
+                    )
+                }
+            });
+        }
+        match get(&toml, "history.size") {
+            Some(Value::Integer(n)) if *n > 0 => config.history_size = Some(*n as usize),
+            Some(_) => return Err("history.size: expecting positive integer".to_string()),
+            None => {}
+        }
+        match get(&toml, "history.ignore_dups") {
+            Some(Value::Boolean(b)) => config.history_ignore_dups = Some(*b),
+            Some(_) => return Err("history.ignore_dups: expecting boolean".to_string()),
+            None => {}
+        }
+        match get(&toml, "prompt.spec") {
+            Some(Value::String(s)) => config.prompt = Some(s.clone()),
+            Some(_) => return Err("prompt.spec: expecting string".to_string()),
+            None => {}
+        }
+        for (name, color) in toml
+            .get("colors")
+            .and_then(Value::as_table)
+            .into_iter()
+            .flatten()
+        {
+            let color = color
+                .as_str()
+                .ok_or_else(|| format!("colors.{}: expecting string", name))?;
+            config.colors.push((name.clone(), color.to_string()));
+        }
+        for (name, flags) in toml
+            .get("commands")
+            .and_then(Value::as_table)
+            .into_iter()
+            .flatten()
+        {
+            let flags = str_list(flags)
+                .ok_or_else(|| format!("commands.{}: expecting list of strings", name))?;
+            config.default_flags.push((name.clone(), flags));
+        }
+
+        Ok(config)
+    }
+
+    /// Load the configuration file, if it exists.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+
+        Self::from_toml(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config() {
+        let config = Config::from_toml(
+            r#"
+            [editor]
+            mode = "vi"
+            correct = "auto"
+            [history]
+            size = 500
+            [prompt]
+            spec = '\w\$ '
+            [colors]
+            command = "bright green"
+            [commands]
+            ls = ["-h"]
+            grep = "-i -n"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.edit_mode, Some(EditMode::Vi));
+        assert_eq!(config.correct.as_deref(), Some("auto"));
+        assert_eq!(config.history_size, Some(500));
+        assert_eq!(config.prompt.as_deref(), Some("\\w\\$ "));
+        assert_eq!(
+            config.colors,
+            vec![("command".to_string(), "bright green".to_string())]
+        );
+        assert_eq!(
+            config.default_flags,
+            vec![
+                ("ls".to_string(), vec!["-h".to_string()]),
+                ("grep".to_string(), vec!["-i".to_string(), "-n".to_string()]),
+            ]
+        );
+        assert!(Config::from_toml("[editor]\nmode = \"ed\"").is_err());
+        assert!(Config::from_toml("[history]\nsize = 1\nsize = 2").is_err());
+        assert!(Config::from_toml("[prompt\nspec = 1").is_err());
+    }
+}
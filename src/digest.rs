@@ -0,0 +1,145 @@
+/// Message digests (MD5, SHA-1, SHA-256, BLAKE3) used by the checksum commands.
+/// Computed incrementally, so that large files do not have to fit in memory.
+use crate::scope::Scope;
+use sha2::Digest;
+use std::fmt::Write;
+use std::io::{self, ErrorKind, Read};
+
+pub enum Hasher {
+    Md5(md5::Md5),
+    Sha1(sha1::Sha1),
+    Sha256(sha2::Sha256),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Md5(h) => h.update(data),
+            Self::Sha1(h) => h.update(data),
+            Self::Sha256(h) => h.update(data),
+            Self::Blake3(h) => _ = h.update(data),
+        }
+    }
+
+    pub fn finalize(self) -> Vec<u8> {
+        match self {
+            Self::Md5(h) => h.finalize().to_vec(),
+            Self::Sha1(h) => h.finalize().to_vec(),
+            Self::Sha256(h) => h.finalize().to_vec(),
+            Self::Blake3(h) => h.finalize().as_bytes().to_vec(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Algorithm {
+    Md5,
+    Sha1,
+    Sha256,
+    Blake3,
+}
+
+impl Algorithm {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().replace('-', "").as_str() {
+            "md5" => Some(Self::Md5),
+            "sha1" => Some(Self::Sha1),
+            "sha256" => Some(Self::Sha256),
+            "blake3" | "b3" => Some(Self::Blake3),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Md5 => "MD5",
+            Self::Sha1 => "SHA1",
+            Self::Sha256 => "SHA256",
+            Self::Blake3 => "BLAKE3",
+        }
+    }
+
+    pub fn hasher(&self) -> Hasher {
+        match self {
+            Self::Md5 => Hasher::Md5(md5::Md5::new()),
+            Self::Sha1 => Hasher::Sha1(sha1::Sha1::new()),
+            Self::Sha256 => Hasher::Sha256(sha2::Sha256::new()),
+            Self::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+}
+
+/// Compute the digest of everything read from the reader.
+pub fn digest<R: Read>(algorithm: Algorithm, mut reader: R) -> io::Result<Vec<u8>> {
+    let mut hasher = algorithm.hasher();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        if Scope::is_interrupted() {
+            return Err(io::Error::from(ErrorKind::Interrupted));
+        }
+        match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => hasher.update(&buffer[..n]),
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(hasher.finalize())
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut s, b| {
+        _ = write!(s, "{:02x}", b);
+        s
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex_digest(algorithm: Algorithm, data: &[u8], split: usize) -> String {
+        let mut hasher = algorithm.hasher();
+        for chunk in data.chunks(split.max(1)) {
+            hasher.update(chunk);
+        }
+        to_hex(&hasher.finalize())
+    }
+
+    #[test]
+    fn test_digests() {
+        let cases = [
+            (Algorithm::Md5, "", "d41d8cd98f00b204e9800998ecf8427e"),
+            (Algorithm::Md5, "abc", "900150983cd24fb0d6963f7d28e17f72"),
+            (
+                Algorithm::Sha1,
+                "abc",
+                "a9993e364706816aba3e25717850c26c9cd0d89d",
+            ),
+            (
+                Algorithm::Sha256,
+                "",
+                "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            ),
+            (
+                Algorithm::Sha256,
+                "abc",
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            ),
+            (
+                Algorithm::Blake3,
+                "",
+                "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262",
+            ),
+            (
+                Algorithm::Blake3,
+                "abc",
+                "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85",
+            ),
+        ];
+        for (algorithm, input, expected) in cases {
+            assert_eq!(hex_digest(algorithm, input.as_bytes(), 64), expected);
+        }
+    }
+}
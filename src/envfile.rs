@@ -0,0 +1,273 @@
+/// Per-directory environment files, similar to direnv: when the current directory changes,
+/// the nearest .shmy.env file up the directory tree is loaded into the global scope, and the
+/// variables that it defined are restored when leaving the directory tree.
+///
+/// The file holds NAME=VALUE lines (optionally preceded by export); $NAME and ${NAME} in
+/// values are expanded, lines starting with # are comments. Files are loaded only after the
+/// user trusts them; the trusted files and hashes of their contents are saved, so that changed
+/// files have to be trusted again.
+use crate::digest::{to_hex, Algorithm};
+use crate::prompt::{confirm, Answer};
+use crate::scope::{Ident, Scope, Variable};
+use crate::utils::sync_env_vars;
+use regex::Regex;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+pub const ENV_FILE: &str = ".shmy.env";
+
+/// Find the environment file in dir, or in the nearest parent directory that has one.
+pub fn find(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(ENV_FILE))
+        .find(|path| path.is_file())
+}
+
+/// Parse the NAME=VALUE definitions, strip the quotes surrounding the values.
+pub fn parse(text: &str) -> Result<Vec<(String, String)>, String> {
+    let mut vars = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (name, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("line {}: expecting NAME=VALUE", i + 1))?;
+
+        let name = name.trim();
+        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return Err(format!("line {}: invalid variable name: {}", i + 1, name));
+        }
+        let value = value.trim();
+        let value = ['"', '\'']
+            .iter()
+            .find_map(|q| value.strip_prefix(*q).and_then(|v| v.strip_suffix(*q)))
+            .unwrap_or(value);
+
+        vars.push((name.to_string(), value.to_string()));
+    }
+    Ok(vars)
+}
+
+/// Expand $NAME and ${NAME} in the value; undefined variables expand to nothing.
+pub fn expand<F: Fn(&str) -> Option<String>>(value: &str, lookup: F) -> String {
+    let re = Regex::new(r"\$\{([^}]+)\}|\$([a-zA-Z_][a-zA-Z0-9_]*)").unwrap();
+
+    re.replace_all(value, |caps: &regex::Captures| {
+        let name = caps.get(1).or_else(|| caps.get(2)).unwrap().as_str();
+        lookup(name).unwrap_or_default()
+    })
+    .to_string()
+}
+
+/// SHA-256 of the path and contents of the file, as saved in the trusted files list.
+fn digest(path: &Path, text: &str) -> String {
+    let mut hasher = Algorithm::Sha256.hasher();
+    hasher.update(path.to_string_lossy().as_bytes());
+    hasher.update(b"\0");
+    hasher.update(text.as_bytes());
+    to_hex(&hasher.finalize())
+}
+
+struct Loaded {
+    path: PathBuf,
+    saved: Vec<(String, Option<Variable>)>, // Variables before loading the file
+}
+
+pub struct EnvFiles {
+    trusted_path: PathBuf, // File where trusted files are saved, as HASH<tab>PATH lines
+    cwd: Option<PathBuf>,
+    loaded: Option<Loaded>,
+    denied: HashSet<PathBuf>, // Files that the user did not trust during this session
+}
+
+impl EnvFiles {
+    pub fn new(trusted_path: PathBuf) -> Self {
+        Self {
+            trusted_path,
+            cwd: None,
+            loaded: None,
+            denied: HashSet::new(),
+        }
+    }
+
+    fn is_trusted(&self, path: &Path, text: &str) -> bool {
+        let digest = digest(path, text);
+
+        fs::read_to_string(&self.trusted_path).is_ok_and(|trusted| {
+            trusted
+                .lines()
+                .filter_map(|line| line.split_once('\t'))
+                .any(|(hash, file)| hash == digest && Path::new(file) == path)
+        })
+    }
+
+    fn trust(&self, path: &Path, text: &str) -> Result<(), String> {
+        let err = |e: io::Error| format!("Could not save {}: {}", self.trusted_path.display(), e);
+
+        let mut trusted = match fs::read_to_string(&self.trusted_path) {
+            Ok(trusted) => trusted,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(err(e)),
+        };
+        // Replace the hash of older contents of the file, if any
+        trusted = trusted
+            .lines()
+            .filter(|line| line.split_once('\t').map(|(_, file)| Path::new(file)) != Some(path))
+            .map(|line| format!("{}\n", line))
+            .collect();
+        trusted.push_str(&format!("{}\t{}\n", digest(path, text), path.display()));
+
+        fs::write(&self.trusted_path, trusted).map_err(err)
+    }
+
+    /// Restore the variables changed by the loaded file. The caller synchronizes the environment.
+    fn unload(&mut self, scope: &Arc<Scope>) {
+        if let Some(loaded) = self.loaded.take() {
+            let global = scope.global();
+            for (name, var) in loaded.saved.into_iter().rev() {
+                match var {
+                    Some(var) => _ = global.vars_mut().insert(Ident::from(name.as_str()), var),
+                    None => _ = global.erase(&name),
+                }
+            }
+            eprintln!("Unloaded {}", loaded.path.display());
+        }
+    }
+
+    fn load(&mut self, scope: &Arc<Scope>, path: PathBuf, text: &str) -> Result<(), String> {
+        let vars = parse(text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let global = scope.global();
+        let mut saved = Vec::new();
+
+        for (name, value) in vars {
+            let value = expand(&value, |name| {
+                global.lookup_value(name).map(|v| v.to_string())
+            });
+            saved.push((
+                name.clone(),
+                global.lookup_local(&name).map(|var| var.clone()),
+            ));
+
+            let var = Variable::new(value.parse().map_err(|e| format!("{}", e))?);
+            var.set_exported(true);
+            global.vars_mut().insert(Ident::from(name.as_str()), var);
+        }
+        eprintln!("Loaded {}", path.display());
+
+        self.loaded = Some(Loaded { path, saved });
+        Ok(())
+    }
+
+    /// Check if the current directory changed, and load (or unload) the environment file.
+    /// Ask the user before loading files that are not trusted.
+    pub fn update(&mut self, scope: &Arc<Scope>) -> Result<(), String> {
+        let cwd = std::env::current_dir().ok();
+        if cwd == self.cwd {
+            return Ok(());
+        }
+        self.cwd = cwd;
+
+        let path = self.cwd.as_deref().and_then(find);
+        if path.as_ref() == self.loaded.as_ref().map(|loaded| &loaded.path) {
+            return Ok(());
+        }
+        if self.loaded.is_some() {
+            self.unload(scope);
+            sync_env_vars(scope.global());
+        }
+
+        let Some(path) = path else {
+            return Ok(());
+        };
+        if self.denied.contains(&path) {
+            return Ok(());
+        }
+        let text = fs::read_to_string(&path)
+            .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+
+        if !self.is_trusted(&path, &text) {
+            let question = format!("{} is not trusted, load it", path.display());
+            match confirm(question, scope, false).map_err(|e| e.to_string())? {
+                Answer::Yes => self.trust(&path, &text)?,
+                _ => {
+                    self.denied.insert(path);
+                    return Ok(());
+                }
+            }
+        }
+        self.load(scope, path, &text)?;
+        sync_env_vars(scope.global());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::Value;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_and_expand() {
+        let vars = parse("# comment\nA=1\nexport B = \"x y\"\n\nC='$A:${B}:$D'\n").unwrap();
+        assert_eq!(
+            vars,
+            vec![
+                ("A".to_string(), "1".to_string()),
+                ("B".to_string(), "x y".to_string()),
+                ("C".to_string(), "$A:${B}:$D".to_string()),
+            ]
+        );
+        assert!(parse("A B").is_err());
+
+        let lookup = |name: &str| match name {
+            "A" => Some("1".to_string()),
+            "B" => Some("x y".to_string()),
+            _ => None,
+        };
+        assert_eq!(expand(&vars[2].1, lookup), "1:x y:");
+    }
+
+    #[test]
+    fn test_load_and_unload() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join(ENV_FILE);
+        let text = "ENVFILE_TEST_A=hello\nENVFILE_TEST_B=$ENVFILE_TEST_A/world\n";
+        fs::write(&path, text).unwrap();
+
+        let mut env_files = EnvFiles::new(temp_dir.path().join("trusted"));
+        assert!(!env_files.is_trusted(&path, text));
+        env_files.trust(&path, text).unwrap();
+        env_files.trust(&path, text).unwrap();
+        assert!(env_files.is_trusted(&path, text));
+        assert!(!env_files.is_trusted(&path, "ENVFILE_TEST_A=changed"));
+        assert_eq!(
+            digest(Path::new("/a/.shmy.env"), "A=1\n"),
+            "5929a8c0e3578741adaf4ee84cb7c14f5dc9c29356ae2f7ab83350a17bddffc7"
+        );
+
+        let scope = Scope::new();
+        scope.insert("ENVFILE_TEST_A".to_string(), Value::Int(42));
+
+        env_files.load(&scope, path, text).unwrap();
+        assert_eq!(
+            scope.lookup_value("ENVFILE_TEST_B").map(|v| v.to_string()),
+            Some("hello/world".to_string())
+        );
+
+        env_files.unload(&scope);
+        assert_eq!(
+            scope.lookup_value("ENVFILE_TEST_A").map(|v| v.to_string()),
+            Some("42".to_string())
+        );
+        assert!(scope.lookup("ENVFILE_TEST_B").is_none());
+    }
+}
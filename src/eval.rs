@@ -1,7 +1,12 @@
 use crate::cmds::{get_command, Exec, ShellCommand};
+use crate::job::{JobStatus, JOBS};
 use crate::prompt::{confirm, Answer};
-use crate::scope::Scope;
-use crate::utils::{self, copy_vars_to_command_env, executable};
+use crate::scope::{Scope, Variable};
+use crate::symlnk::SymLink;
+use crate::utils::{
+    self, child_cpu_times, copy_vars_to_command_env, executable, expand_tilde, format_duration,
+    DurationStyle,
+};
 use colored::*;
 use gag::{BufferRedirect, Gag, Redirect};
 use glob::glob;
@@ -9,6 +14,8 @@ use regex::Regex;
 use std::borrow::Cow;
 use std::cell::RefCell;
 use std::cmp::Ordering;
+use std::collections::{BTreeMap, VecDeque};
+use std::env;
 use std::fmt::{self, Debug};
 use std::fs::{File, OpenOptions};
 use std::io::{self, ErrorKind, IsTerminal, Read, Write};
@@ -17,7 +24,8 @@ use std::path::Path;
 use std::process::{Command as StdCommand, Stdio};
 use std::rc::Rc;
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 pub const KEYWORDS: [&str; 8] = [
     "BREAK", "CONTINUE", "ELSE", "FOR", "IF", "IN", "QUIT", "WHILE",
@@ -45,17 +53,31 @@ const ERR_SUB_STATUS: &str = "Cannot subtract from command status";
 const ERR_POW_STR_EXP: &str = "Exponent cannot be a string";
 const ERR_POW_STATUS_EXP: &str = "Exponent cannot be a command status";
 const ERR_POW_INVALID_BASE: &str = "Invalid base type";
+const ERR_MAP_ENTRY: &str = "Expecting KEY = VALUE in map literal";
+const ERR_COLLECTION_OP: &str = "Operation not supported for lists and maps";
+
+/// Redirection target for 2>&1 (standard error sent wherever standard output goes).
+const STDOUT_DUP: &str = "&1";
+
+/// Redirection target for >&- and 2>&- (close the stream, or undo exec redirections).
+const STREAM_CLOSE: &str = "&-";
 
 #[derive(Clone, Debug, PartialEq)]
 enum Op {
     And,
     Append,
+    AppendErr,
     Assign,
+    Background,
     Div,
     Equals,
     Gt,
     Gte,
+    HereDoc,
+    HereString,
     IntDiv,
+    Len,
+    Local,
     Minus,
     Mod,
     Mul,
@@ -67,7 +89,12 @@ enum Op {
     Pipe,
     Plus,
     Power,
+    Read,
+    Readonly,
+    Return,
+    Time,
     Write,
+    WriteErr,
 }
 
 impl fmt::Display for Op {
@@ -75,12 +102,18 @@ impl fmt::Display for Op {
         match self {
             Op::And => write!(f, "&&"),
             Op::Append => write!(f, "=>>"),
+            Op::AppendErr => write!(f, "2>>"),
             Op::Assign => write!(f, "="),
+            Op::Background => write!(f, "&"),
             Op::Div => write!(f, "/"),
             Op::Equals => write!(f, "=="),
             Op::Gt => write!(f, ">"),
             Op::Gte => write!(f, ">="),
+            Op::HereDoc => write!(f, "<<"),
+            Op::HereString => write!(f, "<<<"),
             Op::IntDiv => write!(f, "//"),
+            Op::Len => write!(f, "len"),
+            Op::Local => write!(f, "local"),
             Op::Minus => write!(f, "-"),
             Op::Mod => write!(f, "%"),
             Op::Mul => write!(f, "*"),
@@ -92,7 +125,12 @@ impl fmt::Display for Op {
             Op::Pipe => write!(f, "|"),
             Op::Plus => write!(f, "+"),
             Op::Power => write!(f, "^"),
+            Op::Read => write!(f, "<"),
+            Op::Readonly => write!(f, "readonly"),
+            Op::Return => write!(f, "return"),
+            Op::Time => write!(f, "time"),
             Op::Write => write!(f, "=>"),
+            Op::WriteErr => write!(f, "2>"),
         }
     }
 }
@@ -109,23 +147,50 @@ impl Op {
         match &self {
             // Give logical ops same (lowest) priority as assignment so that parentheses are not
             // needed in: ```a == b || b = c``` i.e. we don't need to write ```a == b || (b = c)```
-            Op::Assign | Op::Pipe | Op::Or | Op::And => Priority::VeryLow,
+            Op::Assign
+            | Op::Local
+            | Op::Pipe
+            | Op::Or
+            | Op::And
+            | Op::Readonly
+            | Op::Return
+            | Op::Time => Priority::VeryLow,
             Op::Append
+            | Op::AppendErr
             | Op::Gt
             | Op::Gte
+            | Op::HereDoc
+            | Op::HereString
             | Op::Lt
             | Op::Lte
             | Op::Not
             | Op::NotEquals
             | Op::Minus
             | Op::Plus
-            | Op::Write => Priority::Low,
+            | Op::Read
+            | Op::Write
+            | Op::WriteErr => Priority::Low,
             _ => Priority::High,
         }
     }
 
+    fn is_logical(&self) -> bool {
+        matches!(&self, Op::And | Op::Or)
+    }
+
+    fn is_redirect(&self) -> bool {
+        self.is_write() || matches!(&self, Op::HereDoc | Op::HereString | Op::Read)
+    }
+
+    fn is_write(&self) -> bool {
+        matches!(&self, Op::Append | Op::AppendErr | Op::Write | Op::WriteErr)
+    }
+
     fn is_unary_ok(&self) -> bool {
-        return matches!(&self, Op::Minus | Op::Not);
+        return matches!(
+            &self,
+            Op::Len | Op::Local | Op::Minus | Op::Not | Op::Readonly | Op::Return | Op::Time
+        );
     }
 }
 
@@ -168,6 +233,11 @@ enum Token {
     Operator(Op),
     LeftParen,
     RightParen,
+    LeftBrace,
+    RightBrace,
+    LeftBracket,
+    RightBracket,
+    Comma,
     Semicolon,
 }
 
@@ -348,6 +418,8 @@ pub enum Value {
     Real(f64),
     Str(Arc<String>),
     Stat(Box<Status>),
+    List(Arc<Vec<Value>>),
+    Map(Arc<BTreeMap<String, Value>>),
 }
 
 impl Default for Value {
@@ -371,6 +443,13 @@ impl fmt::Display for Value {
             Value::Stat(s) => {
                 write!(f, "{}", s)
             }
+            Value::List(items) => {
+                write!(f, "{}", join_values(items, " "))
+            }
+            Value::Map(map) => {
+                let entries: Vec<_> = map.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+                write!(f, "{}", entries.join(" "))
+            }
         }
     }
 }
@@ -426,7 +505,9 @@ impl TryFrom<Value> for f64 {
 impl Value {
     pub fn as_str(&self) -> Cow<'_, str> {
         match self {
-            Value::Int(_) | Value::Real(_) | Value::Stat(_) => Cow::Owned(self.to_string()),
+            Value::Int(_) | Value::Real(_) | Value::Stat(_) | Value::List(_) | Value::Map(_) => {
+                Cow::Owned(self.to_string())
+            }
             Value::Str(s) => Cow::Borrowed(s.as_str()),
         }
     }
@@ -441,16 +522,27 @@ impl Value {
 
     pub fn to_rc_string(&self) -> Arc<String> {
         match self {
-            Value::Int(_) | Value::Real(_) | Value::Stat(_) => Arc::new(self.to_string()),
+            Value::Int(_) | Value::Real(_) | Value::Stat(_) | Value::List(_) | Value::Map(_) => {
+                Arc::new(self.to_string())
+            }
             Value::Str(s) => Arc::clone(&s),
         }
     }
 }
 
+fn join_values(values: &[Value], separator: &str) -> String {
+    values
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
 #[derive(Clone, Debug, PartialEq)]
 enum Jump {
     Break(Value),
     Continue(Value),
+    Return(Value),
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -506,13 +598,63 @@ fn error<S: HasLocation, R>(source: &S, message: &str) -> EvalResult<R> {
     Err(EvalError::new(source.loc(), message))
 }
 
+/// Return the name of the variable in: $NAME, or $NAME[INDEX]
+fn base_name(var_name: &str) -> &str {
+    let name = var_name.trim_start_matches('$');
+    name.split_once('[').map_or(name, |(name, _)| name)
+}
+
+/// Check that the variable, if it exists, is not read-only.
+fn check_writable<S: HasLocation>(source: &S, scope: &Arc<Scope>, name: &str) -> EvalResult {
+    match scope.lookup(name) {
+        Some(var) if var.is_readonly() => error(
+            source,
+            &format!("Cannot assign to read-only variable: {}", name),
+        ),
+        _ => Ok(()),
+    }
+}
+
 /// Non-terminal AST node.
 trait ExprNode {
     fn add_child(&mut self, child: &Rc<Expression>) -> EvalResult;
 }
 
+/// Input characters, with the ability to put characters back. Needed for reading
+/// heredocs, which start on the line following the << operator.
+struct Chars<I: Iterator<Item = char>> {
+    iter: Peekable<I>,
+    put_back: VecDeque<char>,
+}
+
+impl<I: Iterator<Item = char>> Chars<I> {
+    fn new(iter: I) -> Self {
+        Self {
+            iter: iter.peekable(),
+            put_back: VecDeque::new(),
+        }
+    }
+
+    fn peek(&mut self) -> Option<&char> {
+        match self.put_back.front() {
+            Some(c) => Some(c),
+            None => self.iter.peek(),
+        }
+    }
+
+    fn next(&mut self) -> Option<char> {
+        self.put_back.pop_front().or_else(|| self.iter.next())
+    }
+
+    fn put_back(&mut self, chars: Vec<char>) {
+        for c in chars.into_iter().rev() {
+            self.put_back.push_front(c);
+        }
+    }
+}
+
 struct Parser<I: Iterator<Item = char>> {
-    chars: Peekable<I>,
+    chars: Chars<I>,
     loc: Location,
     prev_loc: Location,
     comment: bool,
@@ -526,7 +668,9 @@ struct Parser<I: Iterator<Item = char>> {
     scope_stack: Vec<Arc<Scope>>,
     group: Rc<Expression>,
     group_stack: Vec<Rc<Expression>>,
-    globbed_tokens: Vec<String>,
+    stack_base: Vec<usize>, // Size of the expr_stack at the start of each group
+    globbed_tokens: Vec<Token>,
+    heredoc_lines: u32, // Lines consumed ahead by heredocs
     text: String,
     quoted: bool,
     raw: bool,
@@ -588,6 +732,124 @@ fn globbed_token(value: String) -> Token {
     Token::Literal(Text::new(value, false, true))
 }
 
+/// Brace expansion: "file.{txt,bak}" -> ["file.txt", "file.bak"], "day{01..03}" -> ["day01",
+/// "day02", "day03"], "{a..e..2}" -> ["a", "c", "e"]. Variable references ${...} are skipped.
+fn expand_braces(word: &str) -> Vec<String> {
+    let mut start = 0;
+
+    while let Some(offset) = word[start..].find('{') {
+        let open = start + offset;
+        start = open + 1;
+
+        if word[..open].ends_with('$') {
+            continue;
+        }
+
+        // Find the matching closing brace, and the top-level commas.
+        let mut depth = 0;
+        let mut commas = Vec::new();
+        let mut close = None;
+
+        for (i, c) in word[open..].char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        close = Some(open + i);
+                        break;
+                    }
+                }
+                ',' if depth == 1 => commas.push(open + i),
+                _ => {}
+            }
+        }
+
+        let Some(close) = close else {
+            break;
+        };
+
+        let alternatives = if commas.is_empty() {
+            match expand_range(&word[open + 1..close]) {
+                Some(range) => range,
+                None => continue,
+            }
+        } else {
+            let mut from = open + 1;
+            let mut alternatives = Vec::new();
+            for &end in commas.iter().chain(std::iter::once(&close)) {
+                alternatives.push(word[from..end].to_string());
+                from = end + 1;
+            }
+            alternatives
+        };
+
+        let (prefix, suffix) = (&word[..open], &word[close + 1..]);
+
+        return alternatives
+            .iter()
+            .flat_map(|alt| expand_braces(&format!("{}{}{}", prefix, alt, suffix)))
+            .collect();
+    }
+
+    vec![word.to_string()]
+}
+
+/// Expand a numeric or character sequence FIRST..LAST[..STEP]. Numbers with leading
+/// zeros are padded to the same width.
+fn expand_range(seq: &str) -> Option<Vec<String>> {
+    let parts: Vec<&str> = seq.split("..").collect();
+    if parts.len() != 2 && parts.len() != 3 {
+        return None;
+    }
+    let step = match parts.get(2) {
+        Some(step) => step.parse::<i64>().ok()?.unsigned_abs().max(1) as usize,
+        None => 1,
+    };
+
+    let sequence = |first: i64, last: i64| -> Vec<i64> {
+        if first <= last {
+            (first..=last).step_by(step).collect()
+        } else {
+            (last..=first).rev().step_by(step).collect()
+        }
+    };
+
+    if let (Ok(first), Ok(last)) = (parts[0].parse::<i64>(), parts[1].parse::<i64>()) {
+        let padded = |s: &str| {
+            let digits = s.trim_start_matches('-');
+            digits.len() > 1 && digits.starts_with('0')
+        };
+        let width = if padded(parts[0]) || padded(parts[1]) {
+            parts[0].len().max(parts[1].len())
+        } else {
+            0
+        };
+        return Some(
+            sequence(first, last)
+                .iter()
+                .map(|n| format!("{:0width$}", n, width = width))
+                .collect(),
+        );
+    }
+
+    let as_char = |s: &str| {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) if c.is_ascii_alphabetic() => Some(c as i64),
+            _ => None,
+        }
+    };
+    let (first, last) = (as_char(parts[0])?, as_char(parts[1])?);
+
+    Some(
+        sequence(first, last)
+            .iter()
+            .map(|&c| (c as u8 as char).to_string())
+            .collect(),
+    )
+}
+
 impl<T> Parser<T>
 where
     T: Iterator<Item = char>,
@@ -597,7 +859,7 @@ where
         let loc = Location::with_file(file);
 
         Self {
-            chars: input.peekable(),
+            chars: Chars::new(input),
             loc: loc.clone(),
             prev_loc: loc.clone(),
             comment: false,
@@ -611,7 +873,9 @@ where
             scope_stack: Vec::new(),
             group: new_group(&loc, &scope),
             group_stack: Vec::new(),
+            stack_base: Vec::new(),
             globbed_tokens: Vec::new(),
+            heredoc_lines: 0,
             text: String::new(),
             quoted: false,
             raw: false,
@@ -640,17 +904,121 @@ where
             }
         } else if c == '#' && self.text == "$" {
             false // Special case for $# variable (holding number of command line arguments)
+        } else if c == ',' || c == ']' {
+            self.group.is_list() // Separators inside of list literals: [a, b, c]
+        } else if c == '=' && !tok.is_empty() && self.group.is_args() {
+            false // Arguments in the form NAME=VALUE, e.g.: export PATH=/usr/bin, make CC=gcc
         } else {
             const DELIMITERS: &str = " \t\n\r()+=;|&<>#^";
             DELIMITERS.contains(c)
         }
     }
 
+    /// Check if '[' starts a list literal: not in the arguments of a command, where
+    /// square brackets are part of glob patterns.
+    fn is_list_context(&self) -> bool {
+        !self.group.is_args()
+    }
+
+    /// Check if '>' starts an output redirection rather than a comparison, i.e. if
+    /// parsing the arguments of a command, or past the target of another redirection.
+    fn is_redirect_context(&self) -> bool {
+        if self.current_expr.is_brace() {
+            return true; // Redirect the output of the command group: { ...; } > file
+        }
+        match self.expr_stack.last() {
+            Some(top) if top.is_cmd() => self.group.is_args(),
+            Some(top) => top.is_redirect() && !self.current_expr.is_empty(),
+            None => false,
+        }
+    }
+
+    /// Read the body of a heredoc, i.e. the lines following the current line, up to the
+    /// line consisting of the delimiter. The remainder of the current line is put back
+    /// into the input, to be tokenized after the heredoc.
+    fn read_heredoc(&mut self) -> EvalResult<String> {
+        while matches!(self.chars.peek(), Some(' ' | '\t')) {
+            self.next();
+        }
+        let mut delim = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if !c.is_alphanumeric() && c != '_' {
+                break;
+            }
+            delim.push(c);
+            self.next();
+        }
+        if delim.is_empty() {
+            return error(self, "Expecting heredoc delimiter after <<");
+        }
+
+        let mut rest = Vec::new();
+        while let Some(c) = self.chars.next() {
+            rest.push(c);
+            if c == '\n' {
+                break;
+            }
+        }
+
+        let mut body = String::new();
+        loop {
+            let mut line = String::new();
+            let mut eol = false;
+
+            while let Some(c) = self.chars.next() {
+                if c == '\n' {
+                    eol = true;
+                    break;
+                }
+                line.push(c);
+            }
+            if line.trim_end_matches('\r') == delim {
+                break;
+            }
+            if !eol {
+                return error(self, &format!("Unterminated heredoc, expecting {}", delim));
+            }
+            body.push_str(&line);
+            body.push('\n');
+        }
+
+        // Account for the lines of the body, and the delimiter, at the end of the current line.
+        self.heredoc_lines += body.lines().count() as u32 + 1;
+        self.chars.put_back(rest);
+
+        Ok(body)
+    }
+
     fn next(&mut self) -> Option<char> {
         self.loc.col += 1;
         self.chars.next()
     }
 
+    /// Return the paths matching the pattern; glob errors are ignored (the text is
+    /// treated as a literal by the caller). Patterns that start with a tilde and have
+    /// no wildcards are left for tilde expansion at evaluation time (see Literal::eval).
+    fn glob_paths(&self, pattern: &str) -> Vec<String> {
+        let pattern = if pattern.starts_with('~') {
+            if !pattern.contains(['*', '?', '[']) {
+                return Vec::new();
+            }
+            match expand_tilde(pattern, &self.scope) {
+                Some(pattern) => pattern,
+                None => return Vec::new(),
+            }
+        } else {
+            pattern.to_string()
+        };
+
+        match glob(&pattern) {
+            Ok(paths) => paths
+                .filter_map(Result::ok)
+                .map(|p| p.to_string_lossy().into_owned())
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
     fn glob_literal(&mut self) -> EvalResult<Token> {
         // This function should not be called if globbed_tokens are not depleted.
         assert!(self.globbed_tokens.is_empty());
@@ -663,25 +1031,48 @@ where
                 }
             }
 
-            if self.text.starts_with("~") {
-                if let Some(v) = self.scope.lookup("HOME") {
-                    self.text = format!("{}{}", v.value().as_str(), &self.text[1..]);
+            if !self.group.is_args() {
+                match self.text.as_str() {
+                    "len" => return Ok(Token::Operator(Op::Len)),
+                    "local" => return Ok(Token::Operator(Op::Local)),
+                    "readonly" => return Ok(Token::Operator(Op::Readonly)),
+                    "return" => return Ok(Token::Operator(Op::Return)),
+                    "time" => return Ok(Token::Operator(Op::Time)),
+                    "{" => return Ok(Token::LeftBrace),
+                    "}" => return Ok(Token::RightBrace),
+                    _ => {}
                 }
             }
 
-            match glob(&self.text) {
-                Ok(paths) => {
-                    self.globbed_tokens = paths
-                        .filter_map(Result::ok)
-                        .map(|p| p.to_string_lossy().into_owned())
-                        .collect();
+            let words = expand_braces(&self.text);
 
-                    if !self.globbed_tokens.is_empty() {
-                        let value = self.globbed_tokens.remove(0);
-                        return Ok(globbed_token(value));
+            if words.len() > 1 {
+                for word in words {
+                    let paths = self.glob_paths(&word);
+                    if paths.is_empty() {
+                        // Keep words such as "01" verbatim, unless variables need expanding.
+                        self.globbed_tokens
+                            .push(if word.contains('$') || word.starts_with('~') {
+                                Token::from(word)
+                            } else {
+                                globbed_token(word)
+                            });
+                    } else {
+                        self.globbed_tokens
+                            .extend(paths.into_iter().map(globbed_token));
                     }
                 }
-                Err(_) => {} // Ignore glob errors and treat as literal
+                return Ok(self.globbed_tokens.remove(0));
+            }
+
+            self.globbed_tokens = self
+                .glob_paths(&self.text)
+                .into_iter()
+                .map(globbed_token)
+                .collect();
+
+            if !self.globbed_tokens.is_empty() {
+                return Ok(self.globbed_tokens.remove(0));
             }
         }
         Ok(Token::Literal(Text::new(
@@ -748,8 +1139,7 @@ where
     pub fn next_token(&mut self) -> EvalResult<Token> {
 
         if !self.globbed_tokens.is_empty() {
-            let value = self.globbed_tokens.remove(0);
-            return Ok(globbed_token(value));
+            return Ok(self.globbed_tokens.remove(0));
         }
 
         let mut tok = Token::End;
@@ -766,6 +1156,7 @@ where
 
             if c == '\n' {
                 self.loc.next_line();
+                self.loc.line += std::mem::take(&mut self.heredoc_lines);
                 self.comment = false;
                 self.next();
                 continue;
@@ -779,10 +1170,22 @@ where
                 '%' => token!(self, tok, Token::Operator(Op::Mod)),
                 '(' => token!(self, tok, Token::LeftParen),
                 ')' => token!(self, tok, Token::RightParen),
+                '[' if self.is_list_context() => token!(self, tok, Token::LeftBracket),
+                ']' if self.group.is_list() => token!(self, tok, Token::RightBracket),
+                ',' if self.group.is_list() => token!(self, tok, Token::Comma),
                 ';' => token!(self, tok, Token::Semicolon),
-                '+' => token!(self, tok, Token::Operator(Op::Plus)),
+                '+' => {
+                    // Leading plus in arguments, e.g.: chmod +x, set +e
+                    if !self.is_delimiter(&self.text, c) {
+                        self.text.push(c);
+                    } else {
+                        check_text!(self, tok);
+                        tok = Token::Operator(Op::Plus);
+                    }
+                    self.next();
+                }
                 '^' => token!(self, tok, Token::Operator(Op::Power)),
-                '&' => token!(self, tok, '&', Token::Operator(Op::And)),
+                '&' => token!(self, tok, '&', Token::Operator(Op::Background), Token::Operator(Op::And)),
                 '|' => token!(self, tok, '|', Token::Operator(Op::Pipe), Token::Operator(Op::Or)),
                 '!' => token!(self, tok, '=', Token::Operator(Op::Not), Token::Operator(Op::NotEquals)),
                 '*' => {
@@ -794,8 +1197,63 @@ where
                     }
                     self.next();
                 }
-                '<' => token!(self, tok, '=', Token::Operator(Op::Lt), Token::Operator(Op::Lte)),
-                '>' => token!(self, tok, '=', Token::Operator(Op::Gt), Token::Operator(Op::Gte)),
+                '<' => {
+                    if self.is_redirect_context() {
+                        // Input redirection: < file, heredoc: <<EOF, or here-string: <<< "text"
+                        check_text!(self, tok);
+                        self.next();
+                        if self.chars.peek() == Some(&'<') {
+                            self.next();
+                            if self.chars.peek() == Some(&'<') {
+                                self.next();
+                                tok = Token::Operator(Op::HereString);
+                                continue;
+                            }
+                            let body = self.read_heredoc()?;
+                            self.globbed_tokens.push(globbed_token(body));
+                            tok = Token::Operator(Op::HereDoc);
+                        } else {
+                            tok = Token::Operator(Op::Read);
+                        }
+                    } else {
+                        token!(self, tok, '=', Token::Operator(Op::Lt), Token::Operator(Op::Lte))
+                    }
+                }
+                '>' => {
+                    if self.is_redirect_context() {
+                        // Output redirection: >, >>, 2>, 2>>, 2>&1, >&-, 2>&-
+                        let stderr = self.text == "2";
+                        if stderr {
+                            self.text.clear();
+                        } else {
+                            check_text!(self, tok);
+                        }
+                        self.next();
+                        let append = self.chars.peek() == Some(&'>');
+                        if append {
+                            self.next();
+                        }
+                        if !append && self.chars.peek() == Some(&'&') {
+                            self.next();
+                            let target = match self.chars.peek() {
+                                Some('-') => STREAM_CLOSE,
+                                Some('1') if stderr => STDOUT_DUP,
+                                _ if stderr => return error(self, "Expecting 2>&1 or 2>&-"),
+                                _ => return error(self, "Expecting >&-"),
+                            };
+                            self.next();
+                            self.globbed_tokens.push(globbed_token(target.to_string()));
+                        }
+                        tok = Token::Operator(match (stderr, append) {
+                            (false, false) => Op::Write,
+                            (false, true) => Op::Append,
+                            (true, false) => Op::WriteErr,
+                            (true, true) => Op::AppendErr,
+                        });
+                    } else {
+                        token!(self, tok, '=', Token::Operator(Op::Gt), Token::Operator(Op::Gte))
+                    }
+                }
                 '=' => {
                     check_text!(self, tok);
                     self.next();
@@ -913,6 +1371,14 @@ where
                         }
                     }
 
+                    if self.text == "2"
+                        && !self.quoted
+                        && self.chars.peek() == Some(&'>')
+                        && self.is_redirect_context()
+                    {
+                        continue; // Standard error redirection, handled above
+                    }
+
                     if !self.text.is_empty() || self.quoted {
                         assert!(self.text != "-" && self.text != "/");
 
@@ -995,8 +1461,16 @@ where
         }
     }
 
+    /// Return the top of the expr_stack, within the current group. Operators saved on
+    /// the stack before the start of the group (e.g. the multiplication in: 2 * (3 + 1))
+    /// must not be popped inside of the group.
+    fn stack_top(&self) -> Option<&Rc<Expression>> {
+        let base = self.stack_base.last().copied().unwrap_or(0);
+        self.expr_stack.get(base..).and_then(|stack| stack.last())
+    }
+
     fn pop_binary_ops(&mut self, end_statement: bool) -> EvalResult {
-        while let Some(stack_top) = self.expr_stack.last() {
+        while let Some(stack_top) = self.stack_top() {
             // If the expression on the top of the expression stack is a binary
             // expression, pop it; add current expression to it; then make it the
             // new current expression.
@@ -1017,6 +1491,25 @@ where
         Ok(())
     }
 
+    /// Logical operators are left-associative, same as in other shells, so that
+    /// ```make && ./run || echo failed``` evaluates as ```(make && ./run) || echo failed```
+    fn pop_logical_ops(&mut self) -> EvalResult {
+        while let Some(stack_top) = self.stack_top() {
+            if stack_top.is_logical() {
+                let expr = Rc::clone(&self.current_expr);
+                self.current_expr = self.expr_stack.pop().unwrap();
+
+                if !expr.is_empty() {
+                    self.add_expr(&expr)?;
+                }
+                self.pop_binary_ops(false)?;
+            } else {
+                break;
+            }
+        }
+        Ok(())
+    }
+
     fn add_current_expr_to_group(&mut self) -> EvalResult {
         if self.current_expr.is_for() {
             if !self.current_expr.is_complete() {
@@ -1024,10 +1517,10 @@ where
             }
         }
 
-        // Handle the use case of erasing variables, e.g. $VAR = ;
+        // Handle the use cases of erasing variables, e.g. $VAR = ; and of RETURN without a value.
         if self.current_expr.is_empty() {
             if let Some(top) = self.expr_stack.last() {
-                if top.is_assignment() {
+                if top.is_assignment() || top.is_return() {
                     self.current_expr = self.expr_stack.pop().unwrap();
                 }
             }
@@ -1075,25 +1568,59 @@ where
             // Save the current scope
             let current_scope = Arc::clone(&self.scope);
             self.scope_stack.push(current_scope.clone());
-            // Create new scope and make it current
-            self.scope = Scope::with_parent(Some(current_scope));
+            // Create new scope and make it current; brace groups share the current scope
+            if group != Group::Brace {
+                self.scope = Scope::with_parent(Some(current_scope));
+            }
             // Start a new group
             self.group_stack.push(Rc::clone(&self.group));
 
             if group == Group::Args {
                 self.group = new_args(&self.prev_loc, &self.scope);
                 self.prev_loc = self.loc();
+            } else if group == Group::List {
+                self.group = new_list(&self.prev_loc, &self.scope);
+                self.prev_loc = self.loc();
             } else {
-                self.group = new_group(&self.prev_loc, &self.scope);
+                let subshell = group == Group::Block && self.is_statement_start();
+                self.group = Rc::new(Expression::Group(RefCell::new(GroupExpr::new_block(
+                    group,
+                    subshell,
+                    &self.prev_loc,
+                    &self.scope,
+                ))));
                 self.prev_loc = self.loc();
             }
         }
         self.expr_stack.push(Rc::clone(&self.current_expr));
         self.clear_current();
 
+        if group != Group::None {
+            self.stack_base.push(self.expr_stack.len());
+        }
         Ok(())
     }
 
+    /// Check if a group that starts here is a statement by itself (or is chained with other statements
+    /// by logical operators or pipes), as opposed to a condition or body of IF, WHILE, FOR, or an operand.
+    fn is_statement_start(&self) -> bool {
+        if self.expect_else_expr {
+            return false;
+        }
+        if !self.current_expr.is_empty() {
+            return false;
+        }
+        // Binary operators waiting for their right hand-side operand are on the stack.
+        match self.stack_top().map(|top| &**top) {
+            None => true,
+            Some(Expression::Bin(b)) => {
+                let b = b.borrow();
+                b.rhs.is_empty() && (b.op.is_logical() || b.op == Op::Pipe)
+            }
+            Some(_) => false,
+        }
+    }
+
     fn pop(&mut self) -> EvalResult {
         self.finalize_groups()?;
         self.pop_group()
@@ -1106,6 +1633,7 @@ where
 
         Self::close_group(&self.group);
         let group = Rc::clone(&self.group);
+        self.stack_base.pop();
 
         self.group = self.group_stack.pop().unwrap(); // Restore group
         self.scope = self.scope_stack.pop().unwrap(); // Restore scope
@@ -1130,11 +1658,32 @@ where
                     self.push(Group::Block)?;
                 }
                 Token::RightParen => {
-                    if self.group_stack.is_empty() {
+                    if self.group_stack.is_empty() || self.group.is_list() || self.group.is_brace()
+                    {
                         return error(self, "Unmatched right parenthesis");
                     }
                     self.pop()?;
                 }
+                Token::LeftBrace => {
+                    self.push(Group::Brace)?;
+                }
+                Token::RightBrace => {
+                    if !self.group.is_brace() {
+                        return error(self, "Unmatched right brace, missing semicolon?");
+                    }
+                    self.pop()?;
+                }
+                Token::LeftBracket => {
+                    self.push(Group::List)?;
+                }
+                Token::RightBracket => {
+                    self.pop()?;
+                }
+                Token::Comma => {
+                    // Commas separate the items of list literals.
+                    self.finalize_groups()?;
+                    self.clear_current();
+                }
                 Token::Semicolon => {
                     self.finalize_groups()?;
 
@@ -1213,7 +1762,8 @@ where
                 }
                 Token::Literal(text) => {
                     if !text.quoted && !self.group.is_args() {
-                        if let Some(cmd) = get_command(&text.value) {
+                        let name = expand_tilde(&text.value, &self.scope);
+                        if let Some(cmd) = get_command(name.as_deref().unwrap_or(&text.value)) {
                             let expr = Rc::new(Expression::Cmd(RefCell::new(Command {
                                 cmd,
                                 args: self.empty(),
@@ -1238,6 +1788,26 @@ where
                         self.add_expr(&expr)?;
                     }
                 }
+                Token::Operator(Op::Background) => {
+                    // Run the statement in the background: finish it, same as for semicolon.
+                    if self.group.is_args() {
+                        self.add_current_expr_to_group()?;
+                    }
+                    self.pop_binary_ops(true)?;
+
+                    if self.current_expr.is_empty() {
+                        return error(self, "Expecting expression before &");
+                    }
+                    self.current_expr = Rc::new(Expression::Bin(RefCell::new(BinExpr {
+                        op: Op::Background,
+                        lhs: Rc::clone(&self.current_expr),
+                        rhs: self.empty(),
+                        loc: self.prev_loc.clone(),
+                        scope: Arc::clone(&self.scope),
+                    })));
+                    self.finalize_groups()?;
+                    self.clear_current();
+                }
                 Token::Operator(op) => {
                     let is_low_priority = op.priority() <= Priority::Low;
 
@@ -1247,6 +1817,10 @@ where
                             self.add_current_expr_to_group()?;
                         }
                         self.pop_binary_ops(false)?;
+
+                        if op.is_logical() {
+                            self.pop_logical_ops()?;
+                        }
                     }
 
                     let expr = Rc::new(Expression::Bin(RefCell::new(BinExpr {
@@ -1361,11 +1935,45 @@ where
 /// "${NAME/(\\w+) (\\w+)/\\2, \\1}"   -> "Doe, John"
 /// "${GREETING/(Hello), (World)!/\\2 says \\1}" -> "World says Hello"
 /// ```
+///
+/// Indexing and slicing of lists, map lookup:
+/// ```
+/// "$LIST[0]"      -> first item
+/// "$LIST[-1]"     -> last item
+/// "$LIST[1:3]"    -> list of the items at positions 1 and 2
+/// "$MAP[key]"     -> value associated with key
+/// ```
 fn parse_value(s: &str, loc: &Location, scope: &Arc<Scope>) -> EvalResult<Value> {
-    let re = Regex::new(r"\$\{([^}]+)\}|\$([a-zA-Z0-9_$@#][a-zA-Z0-9_]*)")
+    let re = Regex::new(r"\$\{([^}]+)\}|\$([a-zA-Z0-9_$@#?][a-zA-Z0-9_]*)(?:\[([^\]]*)\])?")
         .map_err(|e| EvalError::new(loc.clone(), e.to_string()))?;
 
+    // A list or map variable (or an item, or a slice of it) by itself is not converted to string.
+    if let Some(caps) = re.captures(s) {
+        if caps[0].len() == s.len() {
+            if let Some(value) = lookup_collection(&caps, loc, scope)? {
+                return Ok(value);
+            }
+        }
+    }
+
+    let mut list_error = None;
+
     let result = re.replace_all(s, |caps: &regex::Captures| {
+        match lookup_collection(caps, loc, scope) {
+            Ok(Some(value)) => return value.to_string(),
+            Ok(None) => {}
+            Err(e) => {
+                list_error.get_or_insert(e);
+                return String::new();
+            }
+        }
+
+        // Indexing applies to lists and maps only, keep the square brackets otherwise.
+        let index = caps
+            .get(3)
+            .map(|m| format!("[{}]", m.as_str()))
+            .unwrap_or_default();
+
         let var_expr = caps
             .get(1)
             .or_else(|| caps.get(2))
@@ -1402,17 +2010,120 @@ fn parse_value(s: &str, loc: &Location, scope: &Arc<Scope>) -> EvalResult<Value>
                     }
                 }
 
-                value
+                value + &index
+            }
+            None => {
+                if scope.options().nounset.get() {
+                    list_error.get_or_insert(EvalError::new(
+                        loc.clone(),
+                        format!("Undefined variable: {}", var_name),
+                    ));
+                }
+                format!("${}{}", var_name, index)
             }
-            None => format!("${}", var_name),
         }
     });
 
+    if let Some(e) = list_error {
+        return Err(e);
+    }
+
     result
         .parse::<Value>()
         .map_err(|e| EvalError::new(loc.clone(), e.to_string()))
 }
 
+/// Look up a list or map variable, and index it if followed by square brackets.
+/// Return None if the captured variable is not a list or map.
+fn lookup_collection(
+    caps: &regex::Captures,
+    loc: &Location,
+    scope: &Arc<Scope>,
+) -> EvalResult<Option<Value>> {
+    let Some(var) = caps.get(2).and_then(|name| scope.lookup(name.as_str())) else {
+        return Ok(None);
+    };
+    let value = var.value().clone();
+
+    if !matches!(value, Value::List(_) | Value::Map(_)) {
+        return Ok(None);
+    }
+    match caps.get(3) {
+        None => Ok(Some(value)),
+        Some(index) => index_value(&value, index.as_str(), loc, scope).map(Some),
+    }
+}
+
+/// Parse list index; negative indices count from the end.
+fn list_index(index: &str, len: usize, loc: &Location, scope: &Arc<Scope>) -> EvalResult<i64> {
+    match parse_value(index.trim(), loc, scope)? {
+        Value::Int(i) if i < 0 => Ok(i + len as i64),
+        Value::Int(i) => Ok(i),
+        _ => Err(EvalError::new(
+            loc.clone(),
+            format!("Invalid list index: {}", index),
+        )),
+    }
+}
+
+fn checked_list_index(
+    index: &str,
+    len: usize,
+    loc: &Location,
+    scope: &Arc<Scope>,
+) -> EvalResult<usize> {
+    match list_index(index, len, loc, scope)? {
+        i if (0..len as i64).contains(&i) => Ok(i as usize),
+        _ => Err(EvalError::new(
+            loc.clone(),
+            format!("List index out of range: {}", index),
+        )),
+    }
+}
+
+fn map_key(index: &str, loc: &Location, scope: &Arc<Scope>) -> EvalResult<String> {
+    Ok(parse_value(index, loc, scope)?.to_string())
+}
+
+fn key_not_found(key: &str, loc: &Location) -> EvalError {
+    EvalError::new(loc.clone(), format!("Key not found: {}", key))
+}
+
+/// Look up key in map, or index or slice a list, as in Python.
+fn index_value(
+    value: &Value,
+    index: &str,
+    loc: &Location,
+    scope: &Arc<Scope>,
+) -> EvalResult<Value> {
+    match value {
+        Value::Map(map) => {
+            let key = map_key(index, loc, scope)?;
+            map.get(&key)
+                .cloned()
+                .ok_or_else(|| key_not_found(&key, loc))
+        }
+        Value::List(items) => {
+            let len = items.len();
+
+            if let Some((start, end)) = index.split_once(':') {
+                let bound = |s: &str, default: usize| -> EvalResult<usize> {
+                    if s.trim().is_empty() {
+                        return Ok(default);
+                    }
+                    Ok(list_index(s, len, loc, scope)?.clamp(0, len as i64) as usize)
+                };
+                let (start, end) = (bound(start, 0)?, bound(end, len)?);
+
+                Ok(Value::List(Arc::new(items[start..end.max(start)].to_vec())))
+            } else {
+                Ok(items[checked_list_index(index, len, loc, scope)?].clone())
+            }
+        }
+        _ => Err(EvalError::new(loc.clone(), ERR_COLLECTION_OP)),
+    }
+}
+
 #[derive(Debug)]
 enum Expression {
     Empty,
@@ -1445,6 +2156,10 @@ impl Expression {
         false
     }
 
+    fn is_return(&self) -> bool {
+        matches!(self, Expression::Bin(b) if b.borrow().op == Op::Return)
+    }
+
     fn is_bin(&self) -> bool {
         matches!(self, Expression::Bin(_))
     }
@@ -1453,8 +2168,32 @@ impl Expression {
         matches!(self, Expression::Cmd(_))
     }
 
-    fn is_for(&self) -> bool {
-        matches!(self, Expression::For(_))
+    fn is_bare_exec(&self) -> bool {
+        match self {
+            Expression::Cmd(cmd) => {
+                let cmd = cmd.borrow();
+                cmd.cmd.name() == "exec" && (cmd.args.is_empty() || cmd.args.is_no_args())
+            }
+            _ => false,
+        }
+    }
+
+    fn is_logical(&self) -> bool {
+        if let Expression::Bin(bin_expr) = &self {
+            return bin_expr.borrow().op.is_logical();
+        }
+        false
+    }
+
+    fn is_redirect(&self) -> bool {
+        if let Expression::Bin(bin_expr) = &self {
+            return bin_expr.borrow().op.is_redirect();
+        }
+        false
+    }
+
+    fn is_for(&self) -> bool {
+        matches!(self, Expression::For(_))
     }
 
     fn is_empty(&self) -> bool {
@@ -1462,7 +2201,15 @@ impl Expression {
     }
 
     fn is_group(&self) -> bool {
-        matches!(self, Expression::Group(_))
+        matches!(self, Expression::Group(g) if g.borrow().kind != Group::List)
+    }
+
+    fn is_list(&self) -> bool {
+        matches!(self, Expression::Group(g) if g.borrow().kind == Group::List)
+    }
+
+    fn is_brace(&self) -> bool {
+        matches!(self, Expression::Group(g) if g.borrow().kind == Group::Brace)
     }
 
     fn is_number(&self) -> bool {
@@ -1521,6 +2268,14 @@ impl Expression {
                             tokens.push(lit.text.value());
                             continue;
                         }
+                        // Flags that look like negative numbers, e.g. xargs -0, are passed as written
+                        if !lit.text.quoted
+                            && lit.text.value.starts_with('-')
+                            && lit.text.value.parse::<f64>().is_ok()
+                        {
+                            tokens.push(lit.text.value());
+                            continue;
+                        }
                         lit.text.quoted
                     } else {
                         false
@@ -1529,12 +2284,7 @@ impl Expression {
                     // Evaluate the argument expression
                     let val = Status::check_result(expr.eval(), true)?;
 
-                    if quoted {
-                        tokens.push(val.to_string());
-                    } else {
-                        // If not quoted, split at ASCII whitespace
-                        tokens.extend(val.to_string().split_ascii_whitespace().map(String::from));
-                    }
+                    value_to_args(&val, quoted, &mut tokens);
                 }
 
                 // Read from stdin if args consist of one single dash, allowing arguments to be piped
@@ -1627,7 +2377,21 @@ impl ExprNode for BinExpr {
 
 impl fmt::Display for BinExpr {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{} {} {}", self.lhs, self.op, self.rhs)
+        if self.op == Op::HereDoc {
+            // Pick a delimiter that does not occur in the body
+            let body = self.rhs.to_string();
+            let mut delim = String::from("EOF");
+            while body.lines().any(|line| line == delim) {
+                delim.push('_');
+            }
+            write!(f, "{} <<{}\n{}{}\n", self.lhs, delim, body, delim)
+        } else if matches!(self.op, Op::Write | Op::WriteErr)
+            && [STDOUT_DUP, STREAM_CLOSE].contains(&self.rhs.to_string().as_str())
+        {
+            write!(f, "{} {}{}", self.lhs, self.op, self.rhs)
+        } else {
+            write!(f, "{} {} {}", self.lhs, self.op, self.rhs)
+        }
     }
 }
 
@@ -1651,6 +2415,7 @@ macro_rules! div_match {
             }
             Value::Str(s) => Ok(Value::new_str(format!("{}/{}", $i, s.as_str()))),
             Value::Stat(_) => error($self, "Cannot divide by command status"),
+            Value::List(_) | Value::Map(_) => error($self, ERR_COLLECTION_OP),
         }
     };
 }
@@ -1759,9 +2524,14 @@ impl BinExpr {
             }
             let var_name = &lit.text.value;
 
-            if var_name.starts_with('$') {
+            check_writable(self, &lit.scope, base_name(var_name))?;
+
+            if let Some(name) = var_name.strip_prefix('$') {
+                if let Some(value) = self.assign_item(&lit.scope, name, Some(rhs.clone()))? {
+                    return Ok(value);
+                }
                 // Assigning to an already-defined variable, as in: $i = $i + 1?
-                if let Some(var) = lit.scope.lookup(&var_name[1..]) {
+                if let Some(var) = lit.scope.lookup(name) {
                     return Ok(var.assign(rhs).clone());
                 } else {
                     return error(self, &format!("Variable not found: {}", var_name));
@@ -1775,6 +2545,70 @@ impl BinExpr {
         error(self, "Identifier expected on left hand-side of assignment")
     }
 
+    /// Declare local or read-only variable, as in: local x = 1; readonly x = 1; or: local x
+    fn eval_declare(&self) -> EvalResult<Value> {
+        if !self.lhs.is_empty() {
+            return error(self, &format!("Unexpected expression before {}", self.op));
+        }
+        match &*self.rhs {
+            Expression::Leaf(lit) if self.op == Op::Readonly => {
+                // Make an existing variable read-only
+                let name = lit.text.value.trim_start_matches('$');
+                match lit.scope.lookup(name) {
+                    Some(var) => {
+                        var.set_readonly();
+                        Ok(var.value().clone())
+                    }
+                    None => error(&**lit, &format!("Variable not found: {}", name)),
+                }
+            }
+            Expression::Leaf(lit) => self.declare(lit, Value::new_str(String::default())),
+            Expression::Bin(bin) if bin.borrow().op == Op::Assign => {
+                let bin = bin.borrow();
+                let Expression::Leaf(lit) = &*bin.lhs else {
+                    return error(&*bin, &self.err_ident());
+                };
+                if bin.rhs.is_empty() {
+                    return error(
+                        &*bin,
+                        &format!("Expecting value in {} declaration", self.op),
+                    );
+                }
+                // Evaluate first, so that the value may refer to an outer variable with the same name.
+                let value = bin.rhs.eval()?;
+                if let Value::Stat(stat) = &value {
+                    let msg = format!("{} {} | {};", ASSIGN_STATUS_ERROR, stat.cmd, bin.lhs);
+                    return error(&*bin, &msg);
+                }
+                self.declare(lit, value)
+            }
+            _ => error(self, &self.err_ident()),
+        }
+    }
+
+    fn declare(&self, lit: &Rc<Literal>, value: Value) -> EvalResult<Value> {
+        let name = &lit.text.value;
+        if name.starts_with('$') || starts_with_special(name) {
+            return error(self, &self.err_ident());
+        }
+        check_writable(&**lit, &self.scope, name)?;
+
+        let var = if self.op == Op::Local {
+            Variable::new_local(value.clone())
+        } else {
+            Variable::new(value.clone())
+        };
+        if self.op == Op::Readonly {
+            var.set_readonly();
+        }
+        self.scope.insert_var(name, var);
+        Ok(value)
+    }
+
+    fn err_ident(&self) -> String {
+        format!("Identifier expected in {} variable declaration", self.op)
+    }
+
     fn eval_cmp_status(&self) -> EvalResult<Value> {
         let message = if self.op == Op::Gt {
             "Command status does not support '>', did you mean redirect '=>' ?"
@@ -1803,6 +2637,15 @@ impl BinExpr {
             (Int(_) | Real(_), Str(_)) => error(self, ERR_CMP_NUM_STR),
             (Str(_), Int(_) | Real(_)) => error(self, ERR_CMP_STR_NUM),
             (Stat(_), _) | (_, Stat(_)) => self.eval_cmp_status(),
+            (List(l1), List(l2)) if matches!(self.op, Op::Equals | Op::NotEquals) => {
+                Ok(Int((l1 != l2) as _))
+            }
+            (Map(m1), Map(m2)) if matches!(self.op, Op::Equals | Op::NotEquals) => {
+                Ok(Int((m1 != m2) as _))
+            }
+            (List(_) | Map(_), _) | (_, List(_) | Map(_)) => {
+                error(self, "Lists and maps can only be compared for equality")
+            }
         }
     }
 
@@ -1823,8 +2666,10 @@ impl BinExpr {
                 }
                 Value::Str(s2) => Ok(Value::new_str(format!("{}/{}", s1.as_str(), s2.as_str()))),
                 Value::Stat(_) => error(self, "Cannot divide by command status"),
+                Value::List(_) | Value::Map(_) => error(self, ERR_COLLECTION_OP),
             },
             Value::Stat(_) => error(self, "Cannot divide command status"),
+            Value::List(_) | Value::Map(_) => error(self, ERR_COLLECTION_OP),
         }
     }
 
@@ -1850,6 +2695,7 @@ impl BinExpr {
             (Str(_), Str(_)) => error(self, ERR_SUB_STR_STR),
             (Str(_), Stat(_)) => error(self, ERR_SUB_STR_STATUS),
             (Stat(_), _) => error(self, ERR_SUB_STATUS),
+            (List(_) | Map(_), _) | (_, List(_) | Map(_)) => error(self, ERR_COLLECTION_OP),
         }
     }
     fn eval_mod(&self, lhs: Value, rhs: Value) -> EvalResult<Value> {
@@ -1872,6 +2718,7 @@ impl BinExpr {
             (Str(_), Int(_) | Real(_)) => error(self, ERR_MUL_STR_NUM),
             (Str(_), Str(_)) => error(self, ERR_MUL_STR_STR),
             (Stat(_), _) | (_, Stat(_)) => error(self, ERR_MUL_STATUS),
+            (List(_) | Map(_), _) | (_, List(_) | Map(_)) => error(self, ERR_COLLECTION_OP),
         }
     }
 
@@ -1887,28 +2734,16 @@ impl BinExpr {
             (Int(_) | Real(_), Str(_)) => error(self, ERR_POW_STR_EXP),
             (Int(_) | Real(_), Stat(_)) => error(self, ERR_POW_STATUS_EXP),
             (Str(_), _) | (Stat(_), _) => error(self, ERR_POW_INVALID_BASE),
+            (_, List(_) | Map(_)) | (List(_) | Map(_), _) => error(self, ERR_COLLECTION_OP),
         }
     }
 
-    /// Evaluate expr and redirect output into a String
-    fn eval_redirect(&self, expr: &Rc<Expression>) -> EvalResult<String> {
-        let mut redirect =
-            BufferRedirect::stdout().map_err(|e| EvalError::new(self.loc(), e.to_string()))?;
-
-        Status::check_result(expr.eval(), false)?;
-
-        let mut str_buf = String::new();
-        redirect
-            .read_to_string(&mut str_buf)
-            .map_err(|e| EvalError::new(self.loc(), e.to_string()))?;
-
-        Ok(str_buf.to_string())
-    }
-
     fn eval_exit_code(&self, cmd: String, status: &std::process::ExitStatus) -> EvalResult<Value> {
         let exit_code = status.code().unwrap_or_else(|| -1);
         my_dbg!(exit_code);
 
+        self.scope.set_exit_status(exit_code as _);
+
         let result = if exit_code == 0 {
             Ok(Value::success())
         } else {
@@ -1984,8 +2819,10 @@ impl BinExpr {
                 })?
             } else {
                 // Base use case, left hand-side is not a pipe expression
-                self.eval_redirect(lhs)?
+                eval_redirect(lhs, &self.loc)?
             };
+            check_writable(&**lit, &self.scope, &lit.text.value)?;
+
             let value = Value::from_str(output.trim())?;
             self.scope.insert_value(&lit.text.value, value.clone());
 
@@ -2078,25 +2915,100 @@ impl BinExpr {
                 Value::Real(j) => Ok(Value::Real(i as f64 + j)),
                 Value::Str(ref s) => Ok(Value::new_str(format!("{}{}", i, s.as_str()))),
                 Value::Stat(_) => error(self, ERR_ADD_NUM_STATUS),
+                Value::List(_) | Value::Map(_) => error(self, ERR_COLLECTION_OP),
             },
             Value::Real(i) => match rhs {
                 Value::Int(j) => Ok(Value::Real(i + j as f64)),
                 Value::Real(j) => Ok(Value::Real(i + j)),
                 Value::Str(ref s) => Ok(Value::new_str(format!("{}{}", i, s.as_str()))),
                 Value::Stat(_) => error(self, ERR_ADD_NUM_STATUS),
+                Value::List(_) | Value::Map(_) => error(self, ERR_COLLECTION_OP),
             },
             Value::Str(s) => Ok(Value::new_str(format!("{}{}", s.as_str(), rhs.as_str()))),
             Value::Stat(_) => error(self, ERR_ADD_STATUS),
+            Value::List(items) => {
+                // Concatenate lists, or append item to list.
+                let mut items = items.as_ref().clone();
+                match rhs {
+                    Value::List(other) => items.extend(other.iter().cloned()),
+                    Value::Stat(_) => return error(self, ERR_ADD_STATUS),
+                    _ => items.push(rhs),
+                }
+                Ok(Value::List(Arc::new(items)))
+            }
+            Value::Map(map) => match rhs {
+                // Merge maps, entries on the right hand-side take precedence.
+                Value::Map(other) => {
+                    let mut map = map.as_ref().clone();
+                    map.extend(other.iter().map(|(k, v)| (k.clone(), v.clone())));
+                    Ok(Value::Map(Arc::new(map)))
+                }
+                _ => error(self, "Only maps can be added to maps"),
+            },
         }
     }
 
+    /// Assign to (or erase, if value is None) an item of a list or map: $VAR[INDEX] = VALUE
+    /// Return None if var_name is not of the form VAR[INDEX] where VAR is a list or a map.
+    fn assign_item(
+        &self,
+        scope: &Arc<Scope>,
+        var_name: &str,
+        value: Option<Value>,
+    ) -> EvalResult<Option<Value>> {
+        let Some((name, index)) = var_name.strip_suffix(']').and_then(|s| s.split_once('[')) else {
+            return Ok(None);
+        };
+        let Some(var) = scope.lookup(name) else {
+            return Ok(None);
+        };
+
+        let mut collection = var.value().clone();
+
+        let result = match &mut collection {
+            Value::Map(map) => {
+                let key = map_key(index, &self.loc, scope)?;
+                let map = Arc::make_mut(map);
+                match value {
+                    Some(value) => {
+                        map.insert(key, value.clone());
+                        value
+                    }
+                    None => map
+                        .remove(&key)
+                        .ok_or_else(|| key_not_found(&key, &self.loc))?,
+                }
+            }
+            Value::List(items) => {
+                let i = checked_list_index(index, items.len(), &self.loc, scope)?;
+                let items = Arc::make_mut(items);
+                match value {
+                    Some(value) => {
+                        items[i] = value.clone();
+                        value
+                    }
+                    None => items.remove(i),
+                }
+            }
+            _ => return Ok(None),
+        };
+
+        var.assign(collection);
+        Ok(Some(result))
+    }
+
     /// Lookup and erase the variable named by the left hand-side expression
     fn eval_erase(&self) -> EvalResult<Value> {
         if let Expression::Leaf(lit) = &*self.lhs {
             let var_name = &lit.text.value;
 
-            if var_name.starts_with('$') {
-                if let Some(var) = lit.scope.erase(&var_name[1..]) {
+            if let Some(name) = var_name.strip_prefix('$') {
+                check_writable(self, &lit.scope, base_name(var_name))?;
+
+                if let Some(value) = self.assign_item(&lit.scope, name, None)? {
+                    return Ok(value); // Return the erased value
+                }
+                if let Some(var) = lit.scope.erase(name) {
                     return Ok(var.value().clone()); // Return the erased value
                 } else {
                     return error(self, &format!("Variable not found: {}", var_name));
@@ -2106,47 +3018,277 @@ impl BinExpr {
         error(self, "Variable expected on left hand-side of assignment")
     }
 
-    /// Redirect standard output to file, and evaluate the left hand-side expression.
-    fn eval_write(&self, append: bool) -> EvalResult<Value> {
-        let filename = self.rhs.eval()?.to_string();
-        let operation = if append { "append" } else { "overwrite" };
+    /// Redirect standard output (or error) as indicated by the operator. Return None
+    /// if the target file exists and the user declines to overwrite it.
+    fn redirect(&self) -> EvalResult<Option<Redirection>> {
+        self.redirect_to(self.rhs.eval()?.to_string())
+    }
+
+    fn redirect_to(&self, target: String) -> EvalResult<Option<Redirection>> {
+        let is_stderr = matches!(self.op, Op::AppendErr | Op::WriteErr);
+
+        if is_stderr && target == STDOUT_DUP {
+            let redir = Redirect::stderr(io::stdout()).map_err(|e| {
+                EvalError::new(self.loc(), format!("Failed to redirect stderr: {}", e))
+            })?;
+            return Ok(Some(Redirection::Stdout(Some(redir))));
+        }
+        if target == STREAM_CLOSE {
+            let gag = if is_stderr {
+                Gag::stderr()
+            } else {
+                Gag::stdout()
+            };
+            return gag
+                .map(|gag| Some(Redirection::Null(gag)))
+                .map_err(|e| EvalError::new(self.loc(), format!("Failed to close stream: {}", e)));
+        }
+
+        let append = matches!(self.op, Op::Append | Op::AppendErr);
 
-        if Path::new(&filename).exists()
+        if !append
+            && Path::new(&target).exists()
             && confirm(
-                format!("{} exists, confirm {}", filename, operation),
+                format!("{} exists, confirm overwrite", target),
                 &self.scope,
                 false,
             )
             .map_err(|e| EvalError::new(self.loc(), e.to_string()))?
                 != Answer::Yes
         {
-            Ok(Value::success())
-        } else {
-            // Open destination file
-            let file = OpenOptions::new()
-                .write(true)
-                .create(true)
-                .append(append)
-                .truncate(!append)
-                .open(&filename)
-                .map_err(|e| {
-                    EvalError::new(
-                        self.loc(),
-                        format!(
-                            "Failed to open {}: {}",
-                            self.scope.err_str(&filename),
-                            e.to_string()
-                        ),
-                    )
-                })?;
+            return Ok(None);
+        }
+
+        let path = Path::new(&target)
+            .dereference()
+            .map_err(|e| EvalError::new(self.loc(), format!("{}: {}", target, e)))?;
 
-            // Redirect stdout to the file
-            let _redirect = Redirect::stdout(file).map_err(|e| {
-                EvalError::new(self.loc(), format!("Failed to redirect stdout: {}", e))
+        // Open destination file
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .append(append)
+            .truncate(!append)
+            .open(&path)
+            .map_err(|e| {
+                EvalError::new(
+                    self.loc(),
+                    format!("Failed to open {}: {}", self.scope.err_str(&target), e),
+                )
             })?;
 
-            // Evaluate left hand-side expression
-            self.lhs.eval()
+        let stream = if is_stderr { "stderr" } else { "stdout" };
+
+        if is_stderr {
+            Redirect::stderr(file)
+        } else {
+            Redirect::stdout(file)
+        }
+        .map(|redir| Some(Redirection::File(redir)))
+        .map_err(|e| EvalError::new(self.loc(), format!("Failed to redirect {}: {}", stream, e)))
+    }
+
+    /// Evaluate the right hand-side expression, and report the elapsed time, and the
+    /// user and system CPU times of the external commands that it ran, if available.
+    fn eval_time(&self) -> EvalResult<Value> {
+        if !self.lhs.is_empty() {
+            return error(self, "Unexpected expression before time");
+        }
+        if self.rhs.is_empty() {
+            return error(self, "Expecting expression after time");
+        }
+
+        let start = Instant::now();
+        let cpu_times = child_cpu_times();
+
+        let result = self.rhs.eval();
+
+        let real = start.elapsed();
+        eprintln!("\nreal\t{}", format_duration(real, DurationStyle::Minutes));
+
+        if let (Some((user, sys)), Some((user_end, sys_end))) = (cpu_times, child_cpu_times()) {
+            eprintln!(
+                "user\t{}",
+                format_duration(user_end.saturating_sub(user), DurationStyle::Minutes)
+            );
+            eprintln!(
+                "sys\t{}",
+                format_duration(sys_end.saturating_sub(sys), DurationStyle::Minutes)
+            );
+        }
+        result
+    }
+
+    /// Evaluate the left hand-side expression in the background, in a child instance of
+    /// the interpreter, and add it to the table of jobs.
+    fn eval_background(&self) -> EvalResult<Value> {
+        let program = executable().map_err(|e| EvalError::new(self.loc(), e))?;
+        let lhs_str = self.lhs.to_string();
+
+        let mut command = StdCommand::new(&program);
+        copy_vars_to_command_env(&mut command, &self.scope);
+        command.arg("-c").arg(&lhs_str).stdin(Stdio::null());
+
+        // Start the job in its own process group, so that Ctrl+C does not reach it.
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            command.process_group(0);
+        }
+        #[cfg(windows)]
+        {
+            use std::os::windows::process::CommandExt;
+            use windows::Win32::System::Threading::CREATE_NEW_PROCESS_GROUP;
+            command.creation_flags(CREATE_NEW_PROCESS_GROUP.0);
+        }
+
+        let child = command.spawn().map_err(|e| {
+            EvalError::new(self.loc(), format!("Failed to spawn child process: {}", e))
+        })?;
+
+        let pid = child.id();
+        let id = JOBS
+            .lock()
+            .unwrap()
+            .add(child, lhs_str.clone(), JobStatus::Running);
+
+        if io::stdin().is_terminal() {
+            eprintln!("[{}] {}", id, pid);
+        }
+        self.scope.set_exit_status(0);
+
+        Ok(Value::Stat(Status::new(
+            lhs_str,
+            Ok(Value::success()),
+            &self.loc,
+        )))
+    }
+
+    /// Evaluate the left hand-side expression in a child instance of the interpreter (same as
+    /// the right hand-side of pipes), with the standard input read from a file, or from a heredoc.
+    fn eval_input(&self) -> EvalResult<Value> {
+        let mut input = self.rhs.eval()?.to_string();
+
+        if self.op == Op::HereString {
+            input.push('\n'); // Same as in bash
+        }
+
+        let program = executable().map_err(|e| EvalError::new(self.loc(), e))?;
+        let lhs_str = self.lhs.to_string();
+
+        let mut command = StdCommand::new(&program);
+        copy_vars_to_command_env(&mut command, &self.scope);
+        command.arg("-c").arg(&lhs_str);
+
+        if self.op == Op::Read {
+            let path = Path::new(&input)
+                .dereference()
+                .map_err(|e| EvalError::new(self.rhs.loc(), format!("{}: {}", input, e)))?;
+
+            match File::open(&path) {
+                Ok(file) => command.stdin(Stdio::from(file)),
+                Err(e) => {
+                    // Failing to open the input fails the command, same as in other shells.
+                    let err = EvalError::new(
+                        self.rhs.loc(),
+                        format!("Failed to open {}: {}", self.scope.err_str(&input), e),
+                    );
+                    return Ok(Value::Stat(Status::new(lhs_str, Err(err), &self.loc)));
+                }
+            };
+        } else {
+            command.stdin(Stdio::piped());
+        }
+
+        let mut child = command.spawn().map_err(|e| {
+            EvalError::new(self.loc(), format!("Failed to spawn child process: {}", e))
+        })?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            // Ignore errors, the child process may exit without reading all input.
+            _ = stdin.write_all(input.as_bytes());
+        }
+
+        let exit_status = child.wait().map_err(|e| {
+            EvalError::new(
+                self.loc(),
+                format!("Failed to wait for child process: {}", e),
+            )
+        })?;
+
+        self.eval_exit_code(lhs_str, &exit_status)
+    }
+
+    /// Apply redirections left to right, like: ```cmd > out.txt 2>&1```
+    /// then evaluate the expression on the left of the first one.
+    fn eval_write(&self) -> EvalResult<Value> {
+        let mut chain = vec![];
+        let mut lhs = Rc::clone(&self.lhs);
+
+        loop {
+            let next = match &*lhs {
+                Expression::Bin(b) if b.borrow().op.is_write() => Rc::clone(&b.borrow().lhs),
+                _ => break,
+            };
+            chain.push(lhs);
+            lhs = next;
+        }
+
+        // exec without a command, e.g. exec > log.txt: keep the redirections in effect
+        if lhs.is_bare_exec() {
+            for expr in chain.iter().rev() {
+                if let Expression::Bin(b) = &**expr {
+                    if !b.borrow().redirect_session()? {
+                        return Ok(Value::success());
+                    }
+                }
+            }
+            if !self.redirect_session()? {
+                return Ok(Value::success());
+            }
+            return lhs.eval();
+        }
+
+        let mut redirections = vec![];
+
+        for expr in chain.iter().rev() {
+            if let Expression::Bin(b) = &**expr {
+                match b.borrow().redirect()? {
+                    Some(redir) => redirections.push(redir),
+                    None => return Ok(Value::success()),
+                }
+            }
+        }
+        match self.redirect()? {
+            Some(redir) => redirections.push(redir),
+            None => return Ok(Value::success()),
+        }
+
+        // Evaluate left hand-side expression
+        lhs.eval()
+    }
+
+    /// Apply a redirection of exec without a command to the rest of the session, replacing
+    /// the previous one of the same stream. Redirecting to &- or /dev/tty only undoes the
+    /// previous redirection. Return false if the user declines to overwrite the target.
+    fn redirect_session(&self) -> EvalResult<bool> {
+        let is_stderr = matches!(self.op, Op::AppendErr | Op::WriteErr);
+        let target = self.rhs.eval()?.to_string();
+        let mut session = SESSION_REDIRECTIONS.lock().unwrap();
+
+        // Write out what is buffered before the stream goes back to where it was.
+        _ = io::stdout().flush();
+        session.retain(|(stderr, _)| *stderr != is_stderr);
+
+        if target == STREAM_CLOSE || target == "/dev/tty" {
+            return Ok(true);
+        }
+        match self.redirect_to(target)? {
+            Some(redir) => {
+                session.push((is_stderr, redir));
+                Ok(true)
+            }
+            None => Ok(false),
         }
     }
 }
@@ -2159,9 +3301,19 @@ macro_rules! eval_bin {
 
 impl Eval for BinExpr {
     fn eval(&self) -> EvalResult<Value> {
-        if self.rhs.is_empty() {
+        if self.op == Op::Background {
+            self.eval_background()
+        } else if self.op == Op::Time {
+            self.eval_time()
+        } else if matches!(self.op, Op::Local | Op::Readonly) {
+            self.eval_declare()
+        } else if self.rhs.is_empty() {
             if self.op == Op::Assign {
                 return self.eval_erase(); // Assign empty, erase variable
+            } else if self.op == Op::Return && self.lhs.is_empty() {
+                // RETURN without a value is a successful status, same as a command exiting with 0.
+                let status = Status::new(self.op.to_string(), Ok(Value::success()), &self.loc);
+                return eval_unary(self, &self.op, Value::Stat(status), &self.scope);
             }
             error(self, "Expecting right hand-side operand")
         } else if self.lhs.is_empty() {
@@ -2173,12 +3325,18 @@ impl Eval for BinExpr {
         } else {
             match self.op {
                 Op::And => self.eval_and(),
-                Op::Append => self.eval_write(true),
+                Op::Append | Op::AppendErr => self.eval_write(),
                 Op::Assign => self.eval_assign(),
+                Op::Background => unreachable!(),
                 Op::Div => eval_bin!(self, eval_div),
                 Op::Gt => eval_bin!(self, eval_gt),
                 Op::Gte => eval_bin!(self, eval_gte),
+                Op::HereDoc | Op::HereString | Op::Read => self.eval_input(),
                 Op::IntDiv => eval_bin!(self, eval_int_div),
+                Op::Len => error(self, "Unexpected expression before len"),
+                Op::Local | Op::Readonly => unreachable!(),
+                Op::Return => error(self, "Unexpected expression before return"),
+                Op::Time => unreachable!(),
                 Op::Equals => eval_bin!(self, eval_equals),
                 Op::Lt => eval_bin!(self, eval_lt),
                 Op::Lte => eval_bin!(self, eval_lte),
@@ -2191,23 +3349,26 @@ impl Eval for BinExpr {
                 Op::Pipe => self.eval_pipe(&self.lhs, &self.rhs),
                 Op::Plus => eval_bin!(self, eval_plus),
                 Op::Power => eval_bin!(self, eval_power),
-                Op::Write => self.eval_write(false),
+                Op::Write | Op::WriteErr => self.eval_write(),
             }
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum Group {
     None,
     Args,
     Block,
+    Brace,
+    List,
 }
 
 #[derive(Debug)]
 struct GroupExpr {
     kind: Group,
     closed: bool,
+    subshell: bool, // Restore the working directory and the variables after evaluation
     scope: Arc<Scope>,
     content: Vec<Rc<Expression>>,
     loc: Location,
@@ -2221,17 +3382,74 @@ impl GroupExpr {
             content: Vec::new(),
             loc: loc.clone(),
             closed: false,
+            subshell: false,
         }
     }
 
     fn new_group(loc: &Location, scope: &Arc<Scope>) -> Self {
+        Self::new_block(Group::Block, false, loc, scope)
+    }
+
+    fn new_block(kind: Group, subshell: bool, loc: &Location, scope: &Arc<Scope>) -> Self {
         Self {
-            kind: Group::Block,
+            kind,
             content: Vec::new(),
             loc: loc.clone(),
             scope: Arc::clone(&scope),
             closed: false,
+            subshell,
+        }
+    }
+
+    fn new_list(loc: &Location, scope: &Arc<Scope>) -> Self {
+        Self {
+            kind: Group::List,
+            content: Vec::new(),
+            loc: loc.clone(),
+            scope: Arc::clone(scope),
+            closed: false,
+            subshell: false,
+        }
+    }
+
+    /// Evaluate the items of a list literal.
+    fn eval_list(&self) -> EvalResult<Value> {
+        if self.content.iter().any(|e| e.is_assignment()) {
+            return self.eval_map();
+        }
+        let mut items = Vec::with_capacity(self.content.len());
+
+        for e in &self.content {
+            items.push(Status::check_result(e.eval(), true)?);
         }
+        Ok(Value::List(Arc::new(items)))
+    }
+
+    /// Evaluate a map literal: [KEY = VALUE, ...], where [=] is the empty map.
+    fn eval_map(&self) -> EvalResult<Value> {
+        let mut map = BTreeMap::new();
+
+        for e in &self.content {
+            let Expression::Bin(bin) = &**e else {
+                return error(&**e, ERR_MAP_ENTRY);
+            };
+            let bin = bin.borrow();
+
+            if bin.op != Op::Assign {
+                return error(&*bin, ERR_MAP_ENTRY);
+            }
+            if bin.lhs.is_empty() && bin.rhs.is_empty() && self.content.len() == 1 {
+                break; // Empty map
+            }
+            if bin.lhs.is_empty() || bin.rhs.is_empty() {
+                return error(&*bin, ERR_MAP_ENTRY);
+            }
+            let key = Status::check_result(bin.lhs.eval(), true)?.to_string();
+            let value = Status::check_result(bin.rhs.eval(), true)?;
+
+            map.insert(key, value);
+        }
+        Ok(Value::Map(Arc::new(map)))
     }
 }
 
@@ -2239,11 +3457,59 @@ derive_has_location!(GroupExpr);
 
 impl Eval for GroupExpr {
     fn eval(&self) -> EvalResult<Value> {
-        self.scope.clear();
+        // Brace groups share the scope of the enclosing group
+        if self.kind != Group::Brace {
+            self.scope.clear();
+        }
+
+        if self.kind == Group::List {
+            return self.eval_list();
+        }
+        if !self.subshell {
+            return self.eval_block();
+        }
+
+        // Changes to the variables of the enclosing scopes (including exports) do not
+        // outlive the subshell; neither does changing the working directory.
+        let cwd = env::current_dir().map_err(|e| EvalError::new(self.loc(), e.to_string()))?;
+        let vars = self.scope.save_vars();
+        let result = self.eval_block();
+
+        self.scope.restore_vars(vars);
+
+        env::set_current_dir(&cwd).map_err(|e| {
+            EvalError::new(
+                self.loc(),
+                format!(
+                    "Failed to restore working directory {}: {}",
+                    cwd.display(),
+                    e
+                ),
+            )
+        })?;
+        result
+    }
+}
 
+impl GroupExpr {
+    /// Evaluate the expressions in the group, in order, and return the result of the last one.
+    fn eval_block(&self) -> EvalResult<Value> {
         let mut result = Ok(Value::success());
 
         for e in &self.content {
+            // Unless errexit is set, report the failed command and keep going.
+            if !self.scope.options().errexit.get() {
+                if let Ok(Value::Stat(status)) = &result {
+                    if let Some(err) = &status.err {
+                        eprintln!(
+                            "{}",
+                            err.loc.error(&self.scope, &err.message, &io::stderr())
+                        );
+                        result = Ok(Value::success());
+                    }
+                }
+            }
+
             // Check the previous result for unhandled command errors
             result = Status::check_result(result, false);
 
@@ -2292,7 +3558,7 @@ impl Eval for GroupExpr {
                             });
                             break;
                         }
-                        None => {
+                        Some(Jump::Return(_)) | None => {
                             result = Err(err);
                             break;
                         }
@@ -2326,6 +3592,10 @@ impl fmt::Display for GroupExpr {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if self.kind == Group::Args {
             write!(f, "{}", join_expr(&self.content, " "))
+        } else if self.kind == Group::List {
+            write!(f, "[{}]", join_expr(&self.content, ", "))
+        } else if self.kind == Group::Brace {
+            write!(f, "{{ {}; }}", join_expr(&self.content, "; "))
         } else {
             write!(f, "( {} )", join_expr(&self.content, "; "))
         }
@@ -2359,6 +3629,10 @@ macro_rules! handle_redir_error {
 /// __stdout = some/path/file.txt ls -al;
 /// __stdout = output.txt; __stderr = 1; ls -al c:\
 /// ```
+/// Redirections applied by exec without a command, kept in effect until replaced or undone
+/// by a later exec; the flag tells if standard error is redirected.
+static SESSION_REDIRECTIONS: Mutex<Vec<(bool, Redirection)>> = Mutex::new(Vec::new());
+
 enum Redirection {
     #[allow(dead_code)]
     File(Redirect<File>),
@@ -2503,12 +3777,26 @@ impl Eval for Command {
 
         let args = self.args.tokenize_args(&self.scope, false)?;
 
+        if self.scope.options().xtrace.get() {
+            eprintln!("+ {} {}", self.cmd.name(), args.join(" "));
+        }
+
+        // Clear $? so that the exit code of an external command (possibly
+        // nested, e.g. inside of an alias) is not overwritten below.
+        self.scope.global().erase("?");
+
         // Execute command
         let result = self
             .cmd
             .exec(&self.cmd.name(), &args, &self.scope)
             .map_err(|e| EvalError::new(self.err_loc(), e));
 
+        if result.is_ok() {
+            self.scope.set_exit_status(0);
+        } else if self.scope.lookup("?").is_none() {
+            self.scope.set_exit_status(1);
+        }
+
         // if Scope::is_interrupted() {
         //     eprintln!("^C");
         // }
@@ -2584,6 +3872,12 @@ fn value_as_bool<L: HasLocation>(loc: &L, val: &Value, scope: &Arc<Scope>) -> Ev
             ));
         }
         Value::Stat(stat) => stat.as_bool(&scope),
+        Value::List(_) | Value::Map(_) => {
+            return Err(EvalError::new(
+                loc.loc(),
+                "Cannot evaluate list or map as boolean",
+            ));
+        }
     };
 
     hoist(scope, "__errors");
@@ -2662,6 +3956,12 @@ derive_has_location!(Literal);
 
 impl Eval for Literal {
     fn eval(&self) -> EvalResult<Value> {
+        if !self.text.quoted {
+            // Expand ~ and ~user using $HOME from the scope active at evaluation time.
+            if let Some(path) = expand_tilde(&self.text.value, &self.scope) {
+                return parse_value(&path, &self.loc, &self.scope);
+            }
+        }
         parse_value(&self.text.value, &self.loc, &self.scope)
     }
 }
@@ -2710,7 +4010,7 @@ macro_rules! eval_iteration {
                 Some(Jump::Continue(v)) => {
                     $result = Ok(v.clone());
                 }
-                None => {
+                Some(Jump::Return(_)) | None => {
                     break;
                 }
             }
@@ -2758,6 +4058,39 @@ impl fmt::Display for LoopExpr {
     }
 }
 
+/// Convert value to command arguments.
+fn value_to_args(val: &Value, quoted: bool, args: &mut Vec<String>) {
+    match val {
+        // Each item in a list is one argument
+        Value::List(items) => args.extend(items.iter().map(|item| item.to_string())),
+        // Iterate over the keys of maps
+        Value::Map(map) => args.extend(map.keys().cloned()),
+        _ if quoted => args.push(val.to_string()),
+        // If not quoted, split at ASCII whitespace
+        _ => args.extend(val.to_string().split_ascii_whitespace().map(String::from)),
+    }
+}
+
+/// Evaluate expr and redirect output into a String
+fn eval_redirect(expr: &Rc<Expression>, loc: &Location) -> EvalResult<String> {
+    Ok(eval_capture(expr, loc)?.1)
+}
+
+/// Evaluate expr with the output redirected, return the value and the captured output.
+fn eval_capture(expr: &Rc<Expression>, loc: &Location) -> EvalResult<(Value, String)> {
+    let mut redirect =
+        BufferRedirect::stdout().map_err(|e| EvalError::new(loc.clone(), e.to_string()))?;
+
+    let value = Status::check_result(expr.eval(), false)?;
+
+    let mut str_buf = String::new();
+    redirect
+        .read_to_string(&mut str_buf)
+        .map_err(|e| EvalError::new(loc.clone(), e.to_string()))?;
+
+    Ok((value, str_buf))
+}
+
 #[derive(Debug)]
 struct ForExpr {
     var: String,
@@ -2781,9 +4114,14 @@ impl Eval for ForExpr {
             return error(self, "Expecting FOR body");
         }
 
+        check_writable(self, &self.scope, &self.var)?;
+
         let mut result = Ok(Value::success());
 
-        let args = self.args.tokenize_args(&self.scope, true)?;
+        let args = match self.command_arg() {
+            Some(expr) => self.eval_command_arg(&expr)?,
+            None => self.args.tokenize_args(&self.scope, true)?,
+        };
         for arg in &args {
             // Bind variable to arg. TODO: experiment with binding multiple vars for i, j in $args
             self.scope.insert(self.var.clone(), arg.parse::<Value>()?);
@@ -2795,6 +4133,39 @@ impl Eval for ForExpr {
     }
 }
 
+impl ForExpr {
+    /// Return the expression if the arguments consist of one single parenthesized expression.
+    fn command_arg(&self) -> Option<Rc<Expression>> {
+        match &*self.args {
+            Expression::Args(args) => match &args.borrow().content[..] {
+                [expr] if expr.is_group() => Some(Rc::clone(expr)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Iterate over the lines of output if expr is a command, as in:
+    /// for line in (cat file.txt); (echo $line)
+    fn eval_command_arg(&self, expr: &Rc<Expression>) -> EvalResult<Vec<String>> {
+        let (value, output) = eval_capture(expr, &self.loc)?;
+
+        if let Value::Stat(_) = value {
+            return Ok(output
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(String::from)
+                .collect());
+        }
+        // Not a command: pass the output through and iterate over the value
+        print!("{}", output);
+
+        let mut args = Vec::new();
+        value_to_args(&value, false, &mut args);
+        Ok(args)
+    }
+}
+
 impl ExprNode for ForExpr {
     fn add_child(&mut self, child: &Rc<Expression>) -> EvalResult {
         if self.var.is_empty() {
@@ -2839,7 +4210,22 @@ fn eval_unary<T: HasLocation>(
             Value::Real(r) => Ok(Value::Real(-r)),
             Value::Str(s) => Ok(Value::new_str(format!("-{}", s))),
             Value::Stat(_) => error(loc, "Unary minus not supported for command status"),
+            Value::List(_) | Value::Map(_) => {
+                error(loc, "Unary minus not supported for lists and maps")
+            }
+        },
+        Op::Len => match val {
+            Value::List(items) => Ok(Value::Int(items.len() as _)),
+            Value::Map(map) => Ok(Value::Int(map.len() as _)),
+            Value::Stat(_) => error(loc, "len not supported for command status"),
+            _ => Ok(Value::Int(val.as_str().chars().count() as _)),
         },
+        // Unwind to the evaluation of the enclosing script, alias, or command line, see Interp::eval
+        Op::Return => Err(EvalError {
+            loc: loc.loc(),
+            message: "RETURN outside script".to_string(),
+            jump: Some(Jump::Return(val)),
+        }),
         Op::Not => {
             if let Value::Stat(mut s) = val {
                 hoist(&scope, "__errors");
@@ -2883,6 +4269,12 @@ fn new_args(loc: &Location, scope: &Arc<Scope>) -> Rc<Expression> {
     ))))
 }
 
+fn new_list(loc: &Location, scope: &Arc<Scope>) -> Rc<Expression> {
+    Rc::new(Expression::Group(RefCell::new(GroupExpr::new_list(
+        loc, scope,
+    ))))
+}
+
 fn new_group(loc: &Location, scope: &Arc<Scope>) -> Rc<Expression> {
     Rc::new(Expression::Group(RefCell::new(GroupExpr::new_group(
         loc, &scope,
@@ -2918,10 +4310,42 @@ impl Interp {
         if self.scope.lookup("__dump_ast").is_some() {
             dbg!(&ast);
         }
-        ast.eval()
+        match ast.eval() {
+            Err(EvalError {
+                loc,
+                jump: Some(Jump::Return(value)),
+                ..
+            }) => Ok(self.return_value(value, &loc)),
+            result => result,
+        }
+    }
+
+    /// Set $? to the exit status corresponding to the value of a RETURN statement.
+    /// Integers are returned as exit codes, non-zero codes result in a failed command status.
+    fn return_value(&self, value: Value, loc: &Location) -> Value {
+        let code = match &value {
+            Value::Int(i) => *i,
+            Value::Stat(status) if status.is_err() => match self.scope.lookup("?") {
+                Some(var) => match *var.value() {
+                    Value::Int(code) if code != 0 => code,
+                    _ => 1,
+                },
+                None => 1,
+            },
+            _ => 0,
+        };
+        self.scope.set_exit_status(code);
+
+        match &value {
+            Value::Int(i) if *i != 0 => {
+                let err = EvalError::new(loc.clone(), format!("return code: {}", code));
+                Value::Stat(Status::new("return".to_string(), Err(err), loc))
+            }
+            _ => value,
+        }
     }
 
-    #[cfg(test)]
+    /// Evaluate the input, and turn a failed command status into an error.
     pub fn eval_status(&mut self, input: &str, scope: Option<Arc<Scope>>) -> EvalResult<Value> {
         let result = self.eval(input, scope);
         Status::check_result(result, false)
@@ -0,0 +1,196 @@
+/// Git status of the current directory, shown in the prompt by the \g escape.
+///
+/// The branch is read directly from the .git directory, which is fast. The dirty and
+/// ahead / behind markers come from running git status; if git does not finish within
+/// STATUS_TIMEOUT it is killed and the markers are left out, so that the prompt never
+/// blocks on slow repositories.
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+const STATUS_TIMEOUT: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Status {
+    pub branch: String,
+    pub dirty: bool,
+    pub ahead: u32,
+    pub behind: u32,
+}
+
+impl Status {
+    /// Format as branch name followed by markers, e.g. main*↑1↓2
+    pub fn to_prompt(&self) -> String {
+        let mut result = self.branch.clone();
+        if self.dirty {
+            result.push('*');
+        }
+        if self.ahead > 0 {
+            result.push_str(&format!("↑{}", self.ahead));
+        }
+        if self.behind > 0 {
+            result.push_str(&format!("↓{}", self.behind));
+        }
+        result
+    }
+}
+
+/// Find the git directory of the repository that contains dir. In worktrees and
+/// submodules .git is a file that points to the actual git directory.
+fn find_git_dir(dir: &Path) -> Option<PathBuf> {
+    for dir in dir.ancestors() {
+        let git = dir.join(".git");
+        if git.is_dir() {
+            return Some(git);
+        } else if git.is_file() {
+            let text = fs::read_to_string(&git).ok()?;
+            let path = text.trim().strip_prefix("gitdir:")?.trim();
+            return Some(dir.join(path));
+        }
+    }
+    None
+}
+
+/// Parse the contents of the HEAD file: the branch name, or the abbreviated
+/// commit hash if the HEAD is detached.
+fn parse_head(head: &str) -> String {
+    let head = head.trim();
+    match head.strip_prefix("ref:") {
+        Some(r) => {
+            let r = r.trim();
+            r.strip_prefix("refs/heads/").unwrap_or(r).to_string()
+        }
+        None => head.chars().take(7).collect(),
+    }
+}
+
+/// Parse the output of git status --porcelain=v2 --branch into the dirty flag
+/// and the ahead / behind counts.
+fn parse_porcelain(output: &str, status: &mut Status) {
+    for line in output.lines() {
+        if let Some(ab) = line.strip_prefix("# branch.ab ") {
+            for count in ab.split_whitespace() {
+                if let Some(n) = count.strip_prefix('+') {
+                    status.ahead = n.parse().unwrap_or_default();
+                } else if let Some(n) = count.strip_prefix('-') {
+                    status.behind = n.parse().unwrap_or_default();
+                }
+            }
+        } else if !line.starts_with('#') && !line.is_empty() {
+            status.dirty = true;
+        }
+    }
+}
+
+/// Run git status, reading its output in a background thread, and wait for it at most
+/// for the timeout. If git does not finish in time, it is killed.
+fn run_git_status(dir: &Path, timeout: Duration) -> Option<String> {
+    let mut child = Command::new("git")
+        .args([
+            "status",
+            "--porcelain=v2",
+            "--branch",
+            "--untracked-files=no",
+        ])
+        .current_dir(dir)
+        .env("GIT_OPTIONAL_LOCKS", "0")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+
+    let mut stdout = child.stdout.take()?;
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        let mut output = Vec::new();
+        if stdout.read_to_end(&mut output).is_ok() {
+            _ = sender.send(output);
+        }
+    });
+
+    match receiver.recv_timeout(timeout) {
+        Ok(output) => child
+            .wait()
+            .is_ok_and(|status| status.success())
+            .then(|| String::from_utf8_lossy(&output).to_string()),
+        Err(_) => {
+            // The reader thread exits when the pipe is closed
+            _ = child.kill();
+            _ = child.wait();
+            None
+        }
+    }
+}
+
+/// Return the git status of the repository that contains dir, if any.
+pub fn status(dir: &Path) -> Option<Status> {
+    let git_dir = find_git_dir(dir)?;
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+
+    let mut status = Status {
+        branch: parse_head(&head),
+        ..Default::default()
+    };
+    if let Some(output) = run_git_status(dir, STATUS_TIMEOUT) {
+        parse_porcelain(&output, &mut status);
+    }
+    Some(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_head() {
+        assert_eq!(parse_head("ref: refs/heads/main\n"), "main");
+        assert_eq!(parse_head("ref: refs/heads/feature/x"), "feature/x");
+        assert_eq!(
+            parse_head("0123456789abcdef0123456789abcdef01234567\n"),
+            "0123456"
+        );
+    }
+
+    #[test]
+    fn test_parse_porcelain() {
+        let mut status = Status::default();
+        parse_porcelain(
+            "# branch.oid 0123\n# branch.head main\n# branch.ab +1 -2\n",
+            &mut status,
+        );
+        assert_eq!((status.dirty, status.ahead, status.behind), (false, 1, 2));
+
+        parse_porcelain(
+            "1 .M N... 100644 100644 100644 0 0 src/main.rs\n",
+            &mut status,
+        );
+        assert!(status.dirty);
+        status.branch = "main".to_string();
+        assert_eq!(status.to_prompt(), "main*↑1↓2");
+    }
+
+    #[test]
+    fn test_find_git_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = temp_dir.path().join("repo");
+        let sub = repo.join("src").join("sub");
+        fs::create_dir_all(&sub).unwrap();
+        fs::create_dir(repo.join(".git")).unwrap();
+        fs::write(repo.join(".git").join("HEAD"), "ref: refs/heads/dev\n").unwrap();
+
+        assert_eq!(find_git_dir(&sub), Some(repo.join(".git")));
+
+        let worktree = temp_dir.path().join("worktree");
+        fs::create_dir(&worktree).unwrap();
+        fs::write(worktree.join(".git"), "gitdir: ../repo/.git\n").unwrap();
+        assert_eq!(find_git_dir(&worktree), Some(worktree.join("../repo/.git")));
+        assert_eq!(status(&sub).map(|s| s.branch), Some("dev".to_string()));
+    }
+}
@@ -0,0 +1,285 @@
+/// Syntax highlighting of the command line, used by CmdLineHelper.
+use crate::eval::KEYWORDS;
+use colored::Color;
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum Kind {
+    Plain,
+    Command, // Built-in, alias, or external command
+    Unknown, // Word in command position that is not a command
+    Keyword,
+    Str,
+    Var,
+    Flag,
+    Bracket, // Matches the bracket at the cursor
+}
+
+/// Colors configured by the user, which replace the default ones.
+static THEME: LazyLock<Mutex<HashMap<Kind, Color>>> = LazyLock::new(Mutex::default);
+
+/// Set the color of an element: command, unknown, keyword, string, variable, flag or bracket.
+pub fn set_color(name: &str, color: &str) -> Result<(), String> {
+    let kind = match name {
+        "command" => Kind::Command,
+        "unknown" => Kind::Unknown,
+        "keyword" => Kind::Keyword,
+        "string" => Kind::Str,
+        "variable" => Kind::Var,
+        "flag" => Kind::Flag,
+        "bracket" => Kind::Bracket,
+        _ => return Err(format!("{}: unknown syntax element", name)),
+    };
+    let color = color
+        .replace('_', " ")
+        .parse::<Color>()
+        .map_err(|_| format!("{}: unknown color", color))?;
+
+    THEME.lock().unwrap().insert(kind, color);
+    Ok(())
+}
+
+impl Kind {
+    fn color(self) -> Option<Color> {
+        if let Some(color) = THEME.lock().unwrap().get(&self) {
+            return Some(*color);
+        }
+        match self {
+            Kind::Plain => None,
+            Kind::Command => Some(Color::Green),
+            Kind::Unknown => Some(Color::Red),
+            Kind::Keyword => Some(Color::Magenta),
+            Kind::Str => Some(Color::Yellow),
+            Kind::Var => Some(Color::Cyan),
+            Kind::Flag => Some(Color::BrightBlue),
+            Kind::Bracket => Some(Color::Blue),
+        }
+    }
+
+    /// Wrap the text in ANSI escape codes. This does not go through the `colored` crate's
+    /// global override, so that the caller decides whether colors are used.
+    fn paint(self, text: &str) -> String {
+        match self.color() {
+            None => text.to_string(),
+            Some(color) if self == Kind::Bracket => {
+                format!("\x1b[1;{}m{}\x1b[0m", color.to_fg_str(), text)
+            }
+            Some(color) => format!("\x1b[{}m{}\x1b[0m", color.to_fg_str(), text),
+        }
+    }
+}
+
+/// Characters that end a word.
+fn is_delim(c: char) -> bool {
+    c.is_whitespace() || matches!(c, ';' | '|' | '&' | '(' | ')' | '{' | '}' | '"' | '<' | '>')
+}
+
+/// Characters that start an expression rather than a command argument, e.g. x = 1, i < 5
+fn is_operator(c: char) -> bool {
+    matches!(c, '=' | '<' | '>' | '+' | '-' | '*' | '/' | '%' | '^' | '!')
+}
+
+/// Colorize the line: commands are green, words in command position that are not known
+/// commands are red, and keywords, strings, variables and flags each have their own color.
+/// The bracket that matches the one at (or before) the cursor position is highlighted.
+/// The line is returned unchanged when colors are off.
+pub fn highlight<F: Fn(&str) -> bool>(
+    line: &str,
+    pos: usize,
+    colors: bool,
+    is_command: F,
+) -> String {
+    if !colors {
+        return line.to_string();
+    }
+    let chars = line.chars().collect::<Vec<_>>();
+    let mut kinds = vec![Kind::Plain; chars.len()];
+    let mut command_pos = true; // Expecting a command
+    let mut i = 0;
+
+    while i < chars.len() {
+        let start = i;
+        let c = chars[i];
+
+        if c == '"'
+            || (c == 'r' && chars.get(i + 1) == Some(&'"') && chars.get(i + 2) == Some(&'('))
+        {
+            // Strings and raw strings
+            let raw = c == 'r';
+            i += if raw { 3 } else { 1 };
+            while i < chars.len() {
+                if raw && chars[i] == ')' && chars.get(i + 1) == Some(&'"') {
+                    i += 2;
+                    break;
+                } else if !raw && chars[i] == '\\' {
+                    i += 1;
+                } else if !raw && chars[i] == '"' {
+                    i += 1;
+                    break;
+                }
+                i += 1;
+            }
+            i = i.min(chars.len());
+            kinds[start..i].fill(Kind::Str);
+            command_pos = false;
+        } else if c == '$' {
+            i += 1;
+            if chars.get(i) == Some(&'{') {
+                while i < chars.len() && chars[i] != '}' {
+                    i += 1;
+                }
+                i = (i + 1).min(chars.len());
+            } else if chars
+                .get(i)
+                .is_some_and(|c| matches!(c, '?' | '#' | '@' | '$'))
+            {
+                i += 1;
+            } else {
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+            }
+            kinds[start..i].fill(Kind::Var);
+            command_pos = false;
+        } else if matches!(c, ';' | '|' | '&' | '(' | '{' | '\n') {
+            command_pos = true;
+            i += 1;
+        } else if is_delim(c) {
+            if matches!(c, ')' | '}' | '<' | '>') {
+                command_pos = false;
+            }
+            i += 1;
+        } else {
+            while i < chars.len() && !is_delim(chars[i]) && chars[i] != '$' {
+                i += 1;
+            }
+            let word = chars[start..i].iter().collect::<String>();
+
+            let kind = if KEYWORDS.contains(&word.to_uppercase().as_str()) {
+                Kind::Keyword
+            } else if command_pos {
+                let next = chars[i..].iter().find(|c| !c.is_whitespace());
+                if is_command(&word) {
+                    Kind::Command
+                } else if word.parse::<f64>().is_ok()
+                    || word.chars().any(is_operator)
+                    || next.is_some_and(|c| is_operator(*c))
+                {
+                    Kind::Plain
+                } else {
+                    Kind::Unknown
+                }
+            } else if word.len() > 1
+                && word.starts_with('-')
+                && !word.chars().nth(1).is_some_and(|c| c.is_ascii_digit())
+            {
+                Kind::Flag
+            } else {
+                Kind::Plain
+            };
+            kinds[start..i].fill(kind);
+
+            // The statement after ELSE starts with a command
+            command_pos = kind == Kind::Keyword && word.eq_ignore_ascii_case("else");
+        }
+    }
+
+    let cursor = line[..pos.min(line.len())].chars().count();
+    if let Some(idx) = matching_bracket(&chars, cursor) {
+        kinds[idx] = Kind::Bracket;
+    }
+
+    let mut result = String::new();
+    let mut run_start = 0;
+    for i in 1..=chars.len() {
+        if i == chars.len() || kinds[i] != kinds[run_start] {
+            let run = chars[run_start..i].iter().collect::<String>();
+            result.push_str(&kinds[run_start].paint(&run));
+            run_start = i;
+        }
+    }
+    result
+}
+
+/// Find the bracket that matches the one under the cursor, or before it.
+fn matching_bracket(chars: &[char], cursor: usize) -> Option<usize> {
+    let pair = |c: char| match c {
+        '(' => Some((')', true)),
+        '[' => Some((']', true)),
+        '{' => Some(('}', true)),
+        ')' => Some(('(', false)),
+        ']' => Some(('[', false)),
+        '}' => Some(('{', false)),
+        _ => None,
+    };
+
+    let (pos, (matching, forward)) = [Some(cursor), cursor.checked_sub(1)]
+        .into_iter()
+        .flatten()
+        .filter(|&i| i < chars.len())
+        .find_map(|i| pair(chars[i]).map(|p| (i, p)))?;
+
+    let bracket = chars[pos];
+    let mut unmatched = 0;
+    let mut check = |i: usize| {
+        if chars[i] == bracket {
+            unmatched += 1;
+        } else if chars[i] == matching {
+            unmatched -= 1;
+        }
+        unmatched == 0
+    };
+
+    if forward {
+        (pos..chars.len()).find(|&i| check(i))
+    } else {
+        (0..=pos).rev().find(|&i| check(i))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn colorize(line: &str) -> String {
+        highlight(line, line.len(), true, |word| {
+            word == "ls" || word == "echo"
+        })
+    }
+
+    fn painted(text: &str, color: Color) -> String {
+        format!("\x1b[{}m{}\x1b[0m", color.to_fg_str(), text)
+    }
+
+    #[test]
+    fn test_highlight() {
+        let line = colorize("ls -al $HOME \"x y\"; bogus; x = 1");
+        assert!(line.starts_with(&painted("ls", Color::Green)));
+        assert!(line.contains(&painted("-al", Color::BrightBlue)));
+        assert!(line.contains(&painted("$HOME", Color::Cyan)));
+        assert!(line.contains(&painted("\"x y\"", Color::Yellow)));
+        assert!(line.contains(&painted("bogus", Color::Red)));
+        assert!(!line.contains(&painted("x", Color::Red)));
+
+        let line = colorize("if (1) (echo) else echo");
+        assert!(line.starts_with(&painted("if", Color::Magenta)));
+        assert!(line.ends_with(&painted("echo", Color::Green)));
+    }
+
+    #[test]
+    fn test_highlight_no_colors() {
+        let line = "ls -al $HOME";
+        assert_eq!(highlight(line, 0, false, |_| true), line);
+    }
+
+    #[test]
+    fn test_matching_bracket() {
+        let chars = "(a (b) c)".chars().collect::<Vec<_>>();
+        assert_eq!(matching_bracket(&chars, 0), Some(8));
+        assert_eq!(matching_bracket(&chars, 9), Some(0));
+        assert_eq!(matching_bracket(&chars, 6), Some(3));
+        assert_eq!(matching_bracket(&chars, 2), None);
+        assert_eq!(matching_bracket(&chars[..5], 0), None);
+    }
+}
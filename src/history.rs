@@ -0,0 +1,251 @@
+/// Command line history file, shared by concurrent shell sessions.
+///
+/// Each line in the file holds one entry: the time when the command was entered (seconds
+/// since the epoch), the working directory, and the command, separated by tabs; backslashes,
+/// tabs and newlines are escaped. Files in the older rustyline format (a plain list of
+/// commands, optionally preceded by a #V2 header) are read as entries without time and directory.
+use chrono::{Local, TimeZone};
+use glob::Pattern;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+const HEADER: &str = "#shmy-history-v1";
+const HEADER_V2: &str = "#V2";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub time: i64,
+    pub dir: String,
+    pub command: String,
+}
+
+impl Entry {
+    /// Create entry for command entered now, in the current directory.
+    pub fn new(command: &str) -> Self {
+        Self {
+            time: Local::now().timestamp(),
+            dir: std::env::current_dir()
+                .map(|dir| dir.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            command: command.to_string(),
+        }
+    }
+
+    /// Format the time of the entry, or return an empty string if the time is not known.
+    pub fn time_str(&self) -> String {
+        if self.time == 0 {
+            return String::new();
+        }
+        Local
+            .timestamp_opt(self.time, 0)
+            .single()
+            .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default()
+    }
+
+    fn parse(line: &str, extended: bool, v2: bool) -> Option<Self> {
+        if line.is_empty() {
+            return None;
+        }
+        if !extended {
+            return Some(Self {
+                time: 0,
+                dir: String::new(),
+                command: if v2 { unescape(line) } else { line.to_string() },
+            });
+        }
+        let mut fields = line.splitn(3, '\t');
+        let time = fields.next()?.parse::<i64>().ok()?;
+        let dir = unescape(fields.next()?);
+        let command = unescape(fields.next()?);
+
+        Some(Self { time, dir, command })
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t")
+}
+
+fn unescape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some(c) => result.push(c),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+/// Load the entries from the history file. A missing file is an empty history.
+pub fn load(path: &Path) -> Result<Vec<Entry>, String> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Could not load {}: {}", path.display(), e)),
+    };
+
+    let mut lines = text.lines().peekable();
+    let extended = lines.next_if_eq(&HEADER).is_some();
+    let v2 = !extended && lines.next_if_eq(&HEADER_V2).is_some();
+
+    Ok(lines
+        .filter_map(|line| Entry::parse(line, extended, v2))
+        .collect())
+}
+
+/// Replace the contents of the history file with the entries. The file is written
+/// to a temporary file first, then renamed, so that other sessions never read a partial file.
+pub fn save(path: &Path, entries: &[Entry]) -> Result<(), String> {
+    let err = |e: io::Error| format!("Could not save {}: {}", path.display(), e);
+
+    let mut text = String::from(HEADER);
+    text.push('\n');
+    for entry in entries {
+        text.push_str(&format!(
+            "{}\t{}\t{}\n",
+            entry.time,
+            escape(&entry.dir),
+            escape(&entry.command)
+        ));
+    }
+
+    let temp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+    fs::File::create(&temp_path)
+        .and_then(|mut file| file.write_all(text.as_bytes()))
+        .map_err(err)?;
+    fs::rename(&temp_path, path).map_err(|e| {
+        _ = fs::remove_file(&temp_path);
+        err(e)
+    })
+}
+
+/// Lock the history file against other sessions, until the returned file is dropped.
+/// The lock is held on a separate file, because save replaces the history file.
+fn lock(path: &Path) -> Result<File, String> {
+    let lock_path = path.with_extension("lock");
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .and_then(|file| file.lock().map(|_| file))
+        .map_err(|e| format!("Could not lock {}: {}", lock_path.display(), e))?;
+    Ok(file)
+}
+
+/// Add the entry to the history file, merging it with the entries saved by other sessions
+/// rather than overwriting them. Earlier duplicates of the command are removed, and the
+/// oldest entries are dropped when the file holds more than max_len entries.
+/// Return the merged history.
+pub fn append(path: &Path, entry: Entry, max_len: usize) -> Result<Vec<Entry>, String> {
+    let _lock = lock(path)?;
+    let mut entries = load(path)?;
+
+    entries.retain(|e| e.command != entry.command);
+    // Entries from concurrent sessions are ordered by time (stable, legacy entries first)
+    let pos = entries.partition_point(|e| e.time <= entry.time);
+    entries.insert(pos, entry);
+
+    if entries.len() > max_len {
+        entries.drain(..entries.len() - max_len);
+    }
+    save(path, &entries)?;
+
+    Ok(entries)
+}
+
+/// Check the command against HISTIGNORE, a colon-separated list of glob patterns
+/// that match the whole command line, e.g. "ls:cd *:history*".
+pub fn is_ignored(command: &str, histignore: &str) -> bool {
+    histignore
+        .split(':')
+        .filter(|p| !p.is_empty())
+        .filter_map(|p| Pattern::new(p).ok())
+        .any(|p| p.matches(command.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn entry(time: i64, command: &str) -> Entry {
+        Entry {
+            time,
+            dir: "/tmp".to_string(),
+            command: command.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_load_legacy() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("history.txt");
+        fs::write(&path, "#V2\nls -al\necho a\\nb\n").unwrap();
+
+        let commands = load(&path)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.command)
+            .collect::<Vec<_>>();
+        assert_eq!(commands, vec!["ls -al", "echo a\nb"]);
+    }
+
+    #[test]
+    fn test_save_and_append() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("history.txt");
+
+        let mut saved = entry(10, "echo \"a\tb\"\nls");
+        saved.dir = "C:\\Users".to_string();
+        save(&path, &[saved.clone(), entry(30, "pwd")]).unwrap();
+        assert_eq!(load(&path).unwrap(), vec![saved.clone(), entry(30, "pwd")]);
+
+        // Entries of concurrent sessions are merged by time, and duplicates removed.
+        append(&path, entry(20, "cargo build"), 10).unwrap();
+        let entries = append(&path, entry(40, "cargo build"), 10).unwrap();
+        assert_eq!(
+            entries,
+            vec![saved, entry(30, "pwd"), entry(40, "cargo build")]
+        );
+
+        let entries = append(&path, entry(50, "ls"), 2).unwrap();
+        assert_eq!(entries, vec![entry(40, "cargo build"), entry(50, "ls")]);
+        assert_eq!(load(&path).unwrap(), entries);
+    }
+
+    #[test]
+    fn test_concurrent_append() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("history.txt");
+
+        std::thread::scope(|s| {
+            for i in 0..8 {
+                let path = &path;
+                s.spawn(move || append(path, entry(i, &format!("echo {}", i)), 100).unwrap());
+            }
+        });
+        assert_eq!(load(&path).unwrap().len(), 8);
+    }
+
+    #[test]
+    fn test_is_ignored() {
+        assert!(is_ignored("ls", "ls:cd *"));
+        assert!(is_ignored("cd /tmp", "ls:cd *"));
+        assert!(!is_ignored("ls -al", "ls:cd *"));
+        assert!(!is_ignored("ls", ""));
+    }
+}
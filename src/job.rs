@@ -1,7 +1,9 @@
 use crate::scope::Scope;
+use std::fmt;
 use std::io;
 use std::path::Path;
-use std::process::Command;
+use std::process::{Child, Command};
+use std::sync::{LazyLock, Mutex};
 
 /// Execute commands as part of a Job. Experimental.
 /// Just a simple std::process::Command wrapper for non-Windows targets.
@@ -26,28 +28,61 @@ impl<'a> Job<'a> {
     }
 }
 
+/// Non-zero exit code of a process, wrapped in the io::Error returned by Job::run.
+#[derive(Debug)]
+struct ExitCode(i64);
+
+impl fmt::Display for ExitCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "exit code: {} (0x{:X})", self.0, self.0)
+    }
+}
+
+impl std::error::Error for ExitCode {}
+
 fn check_exit_code(code: i64) -> io::Result<()> {
     if code != 0 {
-        return Err(io::Error::new(
-            io::ErrorKind::Other,
-            format!("exit code: {} (0x{:X})", code, code),
-        ));
+        return Err(io::Error::new(io::ErrorKind::Other, ExitCode(code)));
     }
     Ok(())
 }
 
+/// Retrieve the exit code of the process from an error returned by Job::run, if any.
+pub fn exit_code(error: &io::Error) -> Option<i64> {
+    error
+        .get_ref()
+        .and_then(|e| e.downcast_ref::<ExitCode>())
+        .map(|code| code.0)
+}
+
 #[cfg(not(windows))]
 mod imp {
     use super::*;
+    use nix::sys::wait::WaitStatus;
+
+    use std::os::unix::process::ExitStatusExt;
 
     fn check_exit_status(status: std::process::ExitStatus) -> io::Result<()> {
         if let Some(code) = status.code() {
             check_exit_code(code as _)
+        } else if let Some(sig) = status.signal() {
+            check_exit_code(128 + sig as i64)
         } else {
             Ok(())
         }
     }
 
+    /// Killed or stopped processes fail with 128 + the signal number, like in POSIX shells.
+    fn check_wait_status(status: WaitStatus) -> io::Result<()> {
+        match status {
+            WaitStatus::Exited(_, code) => check_exit_code(code as _),
+            WaitStatus::Signaled(_, sig, _) | WaitStatus::Stopped(_, sig) => {
+                check_exit_code(128 + sig as i64)
+            }
+            _ => Ok(()),
+        }
+    }
+
     pub struct Job<'a> {
         cmd: Command,
         _marker: std::marker::PhantomData<&'a ()>,
@@ -64,6 +99,18 @@ mod imp {
         }
 
         pub fn run(&mut self) -> io::Result<()> {
+            if control::is_enabled() {
+                control::prepare_command(&mut self.cmd);
+                let child = self.cmd.spawn()?;
+
+                let command = std::iter::once(self.cmd.get_program())
+                    .chain(self.cmd.get_args())
+                    .map(|a| a.to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+
+                return check_wait_status(control::run_in_foreground(child, command)?);
+            }
             let mut child = self.cmd.spawn()?;
             check_exit_status(child.wait()?)
         }
@@ -636,3 +683,307 @@ mod imp {
         }
     }
 }
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JobStatus {
+    Running,
+    Stopped,
+    Done(i32),
+}
+
+impl fmt::Display for JobStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobStatus::Running => write!(f, "Running"),
+            JobStatus::Stopped => write!(f, "Stopped"),
+            JobStatus::Done(0) => write!(f, "Done"),
+            JobStatus::Done(code) => write!(f, "Exit {}", code),
+        }
+    }
+}
+
+/// Background job: an expression evaluated by a child instance of the interpreter,
+/// or (with job control) an external command stopped with Ctrl+Z.
+pub struct BackgroundJob {
+    pub id: usize,
+    pub command: String,
+    child: Child,
+    status: JobStatus,
+}
+
+impl BackgroundJob {
+    pub fn status(&self) -> JobStatus {
+        self.status
+    }
+
+    /// Poll the child process, without blocking.
+    #[cfg(not(windows))]
+    fn update(&mut self) {
+        use nix::sys::wait::{waitpid, WaitPidFlag};
+
+        if self.status != JobStatus::Running && self.status != JobStatus::Stopped {
+            return;
+        }
+        let flags = WaitPidFlag::WNOHANG | WaitPidFlag::WUNTRACED | WaitPidFlag::WCONTINUED;
+        if let Ok(status) = waitpid(control::pid(&self.child), Some(flags)) {
+            self.status = control::job_status(status).unwrap_or(self.status);
+        }
+    }
+
+    /// Poll the child process, without blocking.
+    #[cfg(windows)]
+    fn update(&mut self) {
+        if self.status == JobStatus::Running {
+            if let Ok(Some(exit_status)) = self.child.try_wait() {
+                self.status = JobStatus::Done(exit_status.code().unwrap_or(-1));
+            }
+        }
+    }
+
+    /// Wait for the job to complete. Kill the child process if interrupted by Ctrl+C
+    /// (background jobs do not receive the interrupt from the terminal).
+    fn poll_until_done(&mut self) -> io::Result<JobStatus> {
+        loop {
+            self.update();
+            if self.status != JobStatus::Running {
+                return Ok(self.status);
+            }
+            if Scope::is_interrupted() {
+                _ = self.child.kill();
+            }
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+    }
+
+    /// Bring the job to the foreground, and wait for it to complete or to be stopped.
+    #[cfg(not(windows))]
+    pub fn wait(&mut self) -> io::Result<JobStatus> {
+        if control::is_enabled() {
+            self.status = control::resume(&self.child, true)?;
+            Ok(self.status)
+        } else {
+            self.poll_until_done()
+        }
+    }
+
+    #[cfg(windows)]
+    pub fn wait(&mut self) -> io::Result<JobStatus> {
+        self.poll_until_done()
+    }
+
+    /// Continue a stopped job in the background.
+    #[cfg(not(windows))]
+    pub fn resume(&mut self) -> io::Result<()> {
+        self.status = control::resume(&self.child, false)?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for BackgroundJob {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{}] {:<10} {}",
+            self.id,
+            self.status.to_string(),
+            self.command
+        )
+    }
+}
+
+#[derive(Default)]
+pub struct JobTable {
+    jobs: Vec<BackgroundJob>,
+}
+
+impl JobTable {
+    /// Add a new job and return its id.
+    pub fn add(&mut self, child: Child, command: String, status: JobStatus) -> usize {
+        let id = self.jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1;
+        self.jobs.push(BackgroundJob {
+            id,
+            command,
+            child,
+            status,
+        });
+        id
+    }
+
+    /// Put back a job previously removed from the table.
+    pub fn restore(&mut self, job: BackgroundJob) {
+        let index = self.jobs.partition_point(|j| j.id < job.id);
+        self.jobs.insert(index, job);
+    }
+
+    /// Return the jobs, with up-to-date status.
+    pub fn jobs(&mut self) -> &mut [BackgroundJob] {
+        self.jobs.iter_mut().for_each(BackgroundJob::update);
+        &mut self.jobs
+    }
+
+    /// Remove the job with the given id, or the most recent job if id is None.
+    pub fn remove(&mut self, id: Option<usize>) -> Option<BackgroundJob> {
+        let index = match id {
+            Some(id) => self.jobs.iter().position(|job| job.id == id)?,
+            None => self.jobs.len().checked_sub(1)?,
+        };
+        Some(self.jobs.remove(index))
+    }
+
+    /// Remove and return the jobs that have completed.
+    pub fn remove_done(&mut self) -> Vec<BackgroundJob> {
+        self.jobs.iter_mut().for_each(BackgroundJob::update);
+
+        let (done, active) = self
+            .jobs
+            .drain(..)
+            .partition(|job| matches!(job.status, JobStatus::Done(_)));
+        self.jobs = active;
+        done
+    }
+}
+
+pub static JOBS: LazyLock<Mutex<JobTable>> = LazyLock::new(|| Mutex::new(JobTable::default()));
+
+/// Report the background jobs that completed since the last call.
+/// Called by the interactive loop before showing the prompt.
+pub fn report_done_jobs() {
+    if let Ok(mut jobs) = JOBS.lock() {
+        for job in jobs.remove_done() {
+            eprintln!("{}", job);
+        }
+    }
+}
+
+/// Job control: external commands run in their own process group, which owns the terminal while
+/// in the foreground. Ctrl+Z stops the foreground process and adds it to the table of jobs.
+#[cfg(not(windows))]
+pub mod control {
+    use super::*;
+    use nix::errno::Errno;
+    use nix::sys::signal::{self, killpg, SigHandler, SigSet, SigmaskHow, Signal};
+    use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+    use nix::unistd::{getpgrp, getpid, setpgid, tcsetpgrp, Pid};
+    use std::io::IsTerminal;
+    use std::os::unix::process::CommandExt;
+    use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+
+    static ENABLED: AtomicBool = AtomicBool::new(false);
+
+    extern "C" fn ignore_signal(_: i32) {}
+
+    /// Enable job control, if running interactively in a terminal.
+    pub fn enable() {
+        if io::stdin().is_terminal() {
+            // Install handlers rather than ignoring the signals, so that
+            // child processes start with the default behavior.
+            unsafe {
+                _ = signal::signal(Signal::SIGTSTP, SigHandler::Handler(ignore_signal));
+                _ = signal::signal(Signal::SIGTTIN, SigHandler::Handler(ignore_signal));
+            }
+            ENABLED.store(true, SeqCst);
+        }
+    }
+
+    pub fn is_enabled() -> bool {
+        ENABLED.load(SeqCst)
+    }
+
+    pub fn pid(child: &Child) -> Pid {
+        Pid::from_raw(child.id() as i32)
+    }
+
+    /// Make the process group the foreground group of the terminal.
+    fn set_foreground(pgid: Pid) {
+        // Block SIGTTOU, which is sent when tcsetpgrp is called from a background group.
+        let mut mask = SigSet::empty();
+        mask.add(Signal::SIGTTOU);
+        let mut old_mask = SigSet::empty();
+
+        _ = signal::pthread_sigmask(SigmaskHow::SIG_BLOCK, Some(&mask), Some(&mut old_mask));
+        _ = tcsetpgrp(io::stdin(), pgid);
+        _ = signal::pthread_sigmask(SigmaskHow::SIG_SETMASK, Some(&old_mask), None);
+    }
+
+    /// Map wait status to job status; None if the status is not a change of state.
+    pub fn job_status(status: WaitStatus) -> Option<JobStatus> {
+        match status {
+            WaitStatus::Exited(_, code) => Some(JobStatus::Done(code)),
+            WaitStatus::Signaled(_, sig, _) => Some(JobStatus::Done(128 + sig as i32)),
+            WaitStatus::Stopped(..) => Some(JobStatus::Stopped),
+            WaitStatus::Continued(_) => Some(JobStatus::Running),
+            _ => None,
+        }
+    }
+
+    /// Wait for the process to exit, or to be stopped.
+    fn wait_foreground(child: &Child) -> io::Result<WaitStatus> {
+        set_foreground(pid(child));
+
+        let result = loop {
+            match waitpid(pid(child), Some(WaitPidFlag::WUNTRACED)) {
+                Err(Errno::EINTR) => continue,
+                Err(e) => break Err(io::Error::from(e)),
+                Ok(status) if job_status(status).is_some() => break Ok(status),
+                Ok(_) => continue,
+            }
+        };
+
+        set_foreground(getpgrp());
+
+        if let Ok(WaitStatus::Signaled(_, Signal::SIGINT, _)) = result {
+            // The shell did not receive the interrupt, forward it.
+            _ = crate::INTERRUPT_EVENT
+                .try_lock()
+                .map(|mut event| event.set());
+        }
+        result
+    }
+
+    /// Start the command in its own process group, in the foreground.
+    pub fn prepare_command(cmd: &mut Command) {
+        unsafe {
+            cmd.pre_exec(|| {
+                let pid = getpid();
+                setpgid(pid, pid)?;
+                // Also set the foreground group from the parent, to avoid a race.
+                set_foreground(pid);
+                Ok(())
+            });
+        }
+    }
+
+    /// Run the child process in the foreground. Add it to the table of jobs if stopped.
+    pub fn run_in_foreground(child: Child, command: String) -> io::Result<WaitStatus> {
+        let status = wait_foreground(&child)?;
+
+        if let WaitStatus::Stopped(..) = status {
+            let id = JOBS
+                .lock()
+                .unwrap()
+                .add(child, command.clone(), JobStatus::Stopped);
+            eprintln!(
+                "\n[{}] {:<10} {}",
+                id,
+                JobStatus::Stopped.to_string(),
+                command
+            );
+        }
+        Ok(status)
+    }
+
+    /// Send SIGCONT to the process group of the job, either in the foreground or background.
+    pub fn resume(child: &Child, foreground: bool) -> io::Result<JobStatus> {
+        if foreground {
+            set_foreground(pid(child));
+        }
+        killpg(pid(child), Signal::SIGCONT)?;
+
+        if foreground {
+            let status = wait_foreground(child)?;
+            Ok(job_status(status).unwrap_or(JobStatus::Running))
+        } else {
+            Ok(JobStatus::Running)
+        }
+    }
+}
@@ -0,0 +1,249 @@
+/// User-defined key bindings, loaded from ~/.shmy/keybindings.yaml.
+/// Keys map to line editor actions, or to snippets of text inserted at the cursor.
+///
+/// # Example
+///
+/// ```yaml
+/// bindings:
+///   - key: Ctrl-G
+///     insert: "| grep "
+///   - key: Alt-L
+///     insert: "ls -al"
+///   - key: Ctrl-B
+///     action: backward-word
+/// ```
+use crate::cmds::{clear_screen, expand_abbreviation};
+use rustyline::{
+    Anchor, At, Cmd, ConditionalEventHandler, Event, EventContext, EventHandler, KeyCode, KeyEvent,
+    Modifiers, Movement, RepeatCount, Word,
+};
+use std::fs;
+use std::path::Path;
+use yaml_rust::{Yaml, YamlLoader};
+
+/// Insert a snippet of text at the cursor position.
+struct Snippet {
+    text: String,
+}
+
+impl ConditionalEventHandler for Snippet {
+    fn handle(&self, _: &Event, n: RepeatCount, _: bool, _: &EventContext) -> Option<Cmd> {
+        Some(Cmd::Insert(n, self.text.clone()))
+    }
+}
+
+/// Expand the abbreviation (defined with the abbr command) before the cursor when Space is pressed.
+struct AbbreviationExpander;
+
+impl ConditionalEventHandler for AbbreviationExpander {
+    fn handle(&self, _: &Event, _: RepeatCount, _: bool, ctx: &EventContext) -> Option<Cmd> {
+        let (start, expansion) = expand_abbreviation(&ctx.line()[..ctx.pos()])?;
+        let len = ctx.line()[start..ctx.pos()].chars().count();
+
+        Some(Cmd::Replace(
+            Movement::BackwardChar(len),
+            Some(format!("{} ", expansion)),
+        ))
+    }
+}
+
+/// Bind Space to the expansion of abbreviations.
+pub fn abbreviation_binding() -> (KeyEvent, EventHandler) {
+    (
+        KeyEvent::from(' '),
+        EventHandler::Conditional(Box::new(AbbreviationExpander)),
+    )
+}
+
+/// Clear the screen and the scrollback buffer, then let the editor redraw the prompt and the line.
+struct ScreenClearer;
+
+impl ConditionalEventHandler for ScreenClearer {
+    fn handle(&self, _: &Event, _: RepeatCount, _: bool, _: &EventContext) -> Option<Cmd> {
+        _ = clear_screen(false);
+        Some(Cmd::ClearScreen)
+    }
+}
+
+/// Bind Ctrl+L to clearing the screen, including the scrollback buffer.
+pub fn clear_screen_binding() -> (KeyEvent, EventHandler) {
+    (
+        KeyEvent::ctrl('L'),
+        EventHandler::Conditional(Box::new(ScreenClearer)),
+    )
+}
+
+/// Parse key specifications such as "Ctrl-G", "Alt-Left", "Ctrl-Alt-x" or "F5".
+pub fn parse_key(spec: &str) -> Result<KeyEvent, String> {
+    let (prefix, name) = match spec.strip_suffix("--") {
+        Some(prefix) => (prefix, "-"),
+        None => spec.rsplit_once('-').unwrap_or(("", spec)),
+    };
+
+    let mut mods = Modifiers::NONE;
+    for m in prefix.split('-').filter(|m| !m.is_empty()) {
+        mods |= match m.to_lowercase().as_str() {
+            "c" | "ctrl" | "control" => Modifiers::CTRL,
+            "m" | "alt" | "meta" => Modifiers::ALT,
+            "s" | "shift" => Modifiers::SHIFT,
+            _ => return Err(format!("{}: unknown key modifier {}", spec, m)),
+        };
+    }
+
+    let mut chars = name.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) => return Ok(KeyEvent::normalize(KeyEvent::new(c, mods))),
+        _ => match name.to_lowercase().as_str() {
+            "backspace" => KeyCode::Backspace,
+            "backtab" => KeyCode::BackTab,
+            "delete" | "del" => KeyCode::Delete,
+            "down" => KeyCode::Down,
+            "end" => KeyCode::End,
+            "enter" | "return" => KeyCode::Enter,
+            "esc" | "escape" => KeyCode::Esc,
+            "home" => KeyCode::Home,
+            "insert" | "ins" => KeyCode::Insert,
+            "left" => KeyCode::Left,
+            "pagedown" => KeyCode::PageDown,
+            "pageup" => KeyCode::PageUp,
+            "right" => KeyCode::Right,
+            "space" => KeyCode::Char(' '),
+            "tab" => KeyCode::Tab,
+            "up" => KeyCode::Up,
+            key => match key.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                Some(n) if (1..=24).contains(&n) => KeyCode::F(n),
+                _ => return Err(format!("{}: unknown key", spec)),
+            },
+        },
+    };
+
+    Ok(KeyEvent::normalize(KeyEvent(code, mods)))
+}
+
+/// Map a readline-style action name to a line editor command.
+pub fn parse_action(name: &str) -> Result<Cmd, String> {
+    let word = Word::Emacs;
+
+    Ok(match name {
+        "abort" => Cmd::Abort,
+        "accept-line" => Cmd::AcceptLine,
+        "backward-char" => Cmd::Move(Movement::BackwardChar(1)),
+        "backward-delete-char" => Cmd::Kill(Movement::BackwardChar(1)),
+        "backward-kill-line" | "unix-line-discard" => Cmd::Kill(Movement::BeginningOfLine),
+        "backward-kill-word" => Cmd::Kill(Movement::BackwardWord(1, word)),
+        "backward-word" => Cmd::Move(Movement::BackwardWord(1, word)),
+        "beginning-of-history" => Cmd::BeginningOfHistory,
+        "beginning-of-line" => Cmd::Move(Movement::BeginningOfLine),
+        "capitalize-word" => Cmd::CapitalizeWord,
+        "clear-screen" => Cmd::ClearScreen,
+        "complete" => Cmd::Complete,
+        "delete-char" => Cmd::Kill(Movement::ForwardChar(1)),
+        "downcase-word" => Cmd::DowncaseWord,
+        "end-of-file" => Cmd::EndOfFile,
+        "end-of-history" => Cmd::EndOfHistory,
+        "end-of-line" => Cmd::Move(Movement::EndOfLine),
+        "forward-char" => Cmd::Move(Movement::ForwardChar(1)),
+        "forward-search-history" => Cmd::ForwardSearchHistory,
+        "forward-word" => Cmd::Move(Movement::ForwardWord(1, At::AfterEnd, word)),
+        "history-search-backward" => Cmd::HistorySearchBackward,
+        "history-search-forward" => Cmd::HistorySearchForward,
+        "interrupt" => Cmd::Interrupt,
+        "kill-line" => Cmd::Kill(Movement::EndOfLine),
+        "kill-whole-line" => Cmd::Kill(Movement::WholeLine),
+        "kill-word" => Cmd::Kill(Movement::ForwardWord(1, At::AfterEnd, word)),
+        "newline" => Cmd::Newline,
+        "next-history" => Cmd::NextHistory,
+        "noop" => Cmd::Noop,
+        "previous-history" => Cmd::PreviousHistory,
+        "quoted-insert" => Cmd::QuotedInsert,
+        "reverse-search-history" => Cmd::ReverseSearchHistory,
+        "transpose-chars" => Cmd::TransposeChars,
+        "transpose-words" => Cmd::TransposeWords(1),
+        "undo" => Cmd::Undo(1),
+        "upcase-word" => Cmd::UpcaseWord,
+        "yank" => Cmd::Yank(1, Anchor::Before),
+        "yank-pop" => Cmd::YankPop,
+        _ => return Err(format!("{}: unknown action", name)),
+    })
+}
+
+/// Parse the bindings from the YAML configuration.
+pub fn parse_config(config: &Yaml) -> Result<Vec<(KeyEvent, EventHandler)>, String> {
+    let mut bindings = Vec::new();
+
+    for binding in config["bindings"].as_vec().into_iter().flatten() {
+        let key = binding["key"]
+            .as_str()
+            .ok_or_else(|| "Key binding without key".to_string())?;
+        let key_event = parse_key(key)?;
+
+        let handler = if let Some(text) = binding["insert"].as_str() {
+            EventHandler::Conditional(Box::new(Snippet {
+                text: text.to_string(),
+            }))
+        } else if let Some(action) = binding["action"].as_str() {
+            EventHandler::Simple(parse_action(action)?)
+        } else {
+            return Err(format!("{}: expecting action or insert", key));
+        };
+
+        bindings.push((key_event, handler));
+    }
+    Ok(bindings)
+}
+
+/// Load the key bindings from the specified file.
+pub fn load_from_file(path: &Path) -> Result<Vec<(KeyEvent, EventHandler)>, String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let docs = YamlLoader::load_from_str(&text).map_err(|e| e.to_string())?;
+
+    match docs.first() {
+        Some(config) => parse_config(config),
+        None => Ok(Vec::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(parse_key("Ctrl-G"), Ok(KeyEvent::ctrl('G')));
+        assert_eq!(parse_key("ctrl-g"), Ok(KeyEvent::ctrl('G')));
+        assert_eq!(parse_key("Alt-x"), Ok(KeyEvent::alt('x')));
+        assert_eq!(
+            parse_key("Ctrl-Alt-Left"),
+            Ok(KeyEvent(KeyCode::Left, Modifiers::CTRL_ALT))
+        );
+        assert_eq!(
+            parse_key("F5"),
+            Ok(KeyEvent(KeyCode::F(5), Modifiers::NONE))
+        );
+        assert_eq!(parse_key("Alt--"), Ok(KeyEvent::alt('-')));
+        assert!(parse_key("Hyper-X").is_err());
+        assert!(parse_key("Ctrl-Foo").is_err());
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config = YamlLoader::load_from_str(
+            r#"
+            bindings:
+              - key: Ctrl-G
+                insert: "| grep "
+              - key: Ctrl-B
+                action: backward-word
+            "#,
+        )
+        .unwrap();
+
+        let bindings = parse_config(&config[0]).unwrap();
+        let keys = bindings.iter().map(|(key, _)| *key).collect::<Vec<_>>();
+        assert_eq!(keys, vec![KeyEvent::ctrl('G'), KeyEvent::ctrl('B')]);
+
+        let config =
+            YamlLoader::load_from_str("bindings:\n  - key: Ctrl-G\n    action: bogus").unwrap();
+        assert!(parse_config(&config[0]).is_err());
+    }
+}
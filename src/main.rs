@@ -4,20 +4,22 @@ use directories::UserDirs;
 use eval::{Interp, Value, KEYWORDS};
 use prompt::PromptBuilder;
 use rustyline::completion::{self, FilenameCompleter};
+use rustyline::config::Configurer;
 use rustyline::error::ReadlineError;
-use rustyline::highlight::MatchingBracketHighlighter;
 use rustyline::history::{DefaultHistory, History, SearchDirection};
-use rustyline::{highlight::Highlighter, Context, Editor, Helper, Hinter, Validator};
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{highlight::Highlighter, Context, Editor, Helper, Hinter};
 use scope::Scope;
 use std::borrow::Cow;
 use std::collections::HashSet;
 use std::fs::{self, File};
-use std::io::{self, BufRead, BufReader, Cursor};
-use std::path::PathBuf;
+use std::io::{self, BufRead, BufReader, Cursor, Read};
+use std::path::{Path, PathBuf};
 use std::sync::{
     atomic::{AtomicBool, Ordering::SeqCst},
     Arc, LazyLock, Mutex,
 };
+use std::time::{Duration, Instant, SystemTime};
 use std::{env, usize};
 use yaml_rust::Yaml;
 
@@ -26,8 +28,16 @@ mod macros;
 
 mod cmds;
 mod completions;
+mod config;
+mod digest;
+mod envfile;
 mod eval;
+mod git;
+mod highlight;
+mod history;
 mod job;
+mod keybindings;
+mod multiline;
 mod prompt;
 mod scope;
 mod symlnk;
@@ -35,17 +45,24 @@ mod testcmds;
 mod testeval;
 mod utils;
 
-#[derive(Helper, Hinter, Validator)]
+#[derive(Helper, Hinter)]
 struct CmdLineHelper {
     #[rustyline(Completer)]
     completer: FilenameCompleter,
-    #[rustyline(Highlighter)]
-    highlighter: MatchingBracketHighlighter,
     interp: Interp, // Interpreter instance for tab completion
     completions: Option<Yaml>,
+    path_cache: completions::Cache<(PathBuf, String), (usize, Vec<completion::Pair>)>,
+    executables: completions::Cache<String, bool>, // PATH lookups made by the highlighter
     prompt: String,
 }
 
+/// Path completions that take longer are cached for PATH_COMPLETION_TTL.
+const SLOW_COMPLETION: Duration = Duration::from_millis(100);
+const PATH_COMPLETION_TTL: Duration = Duration::from_secs(10);
+
+/// How long the highlighter remembers whether a word is an executable in $PATH.
+const EXECUTABLE_LOOKUP_TTL: Duration = Duration::from_secs(10);
+
 impl Highlighter for CmdLineHelper {
     fn highlight_prompt<'b, 's: 'b, 'p: 'b>(
         &'s self,
@@ -60,21 +77,55 @@ impl Highlighter for CmdLineHelper {
     }
 
     fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
-        self.highlighter.highlight(line, pos)
+        Cow::Owned(highlight::highlight(line, pos, self.use_colors(), |word| {
+            self.is_command(word)
+        }))
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize, _forced: bool) -> bool {
+        // Highlight the whole line as it is edited
+        self.use_colors()
     }
+}
 
-    fn highlight_char(&self, line: &str, pos: usize, forced: bool) -> bool {
-        self.highlighter.highlight_char(line, pos, forced)
+impl Validator for CmdLineHelper {
+    /// Keep reading lines while the input has unclosed quotes or brackets, or ends with a backslash.
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        if multiline::is_complete(ctx.input()) {
+            Ok(ValidationResult::Valid(None))
+        } else {
+            Ok(ValidationResult::Incomplete)
+        }
     }
 }
 
 impl CmdLineHelper {
+    fn use_colors(&self) -> bool {
+        self.interp.global_scope().use_colors(&io::stdout())
+    }
+
+    /// Check if the word names a command, for highlighting. Unlike get_command,
+    /// this does not register external commands, and caches failed lookups.
+    fn is_command(&self, word: &str) -> bool {
+        if cmds::is_registered(word) {
+            return true;
+        }
+        let key = word.to_string();
+        if let Some(found) = self.executables.get(&key) {
+            return found;
+        }
+        let found = cmds::lookup_executable(word).is_some();
+        self.executables.insert(key, found, EXECUTABLE_LOOKUP_TTL);
+        found
+    }
+
     fn new(scope: Arc<Scope>, completions: Option<Yaml>) -> Self {
         Self {
             completer: FilenameCompleter::new(),
-            highlighter: MatchingBracketHighlighter::new(),
             interp: Interp::new(scope),
             completions,
+            path_cache: completions::Cache::new(),
+            executables: completions::Cache::new(),
             prompt: String::default(),
         }
     }
@@ -90,10 +141,11 @@ impl CmdLineHelper {
         // to include cached, previously used external commands
         for name in &registered_commands(false) {
             if name.starts_with(input) {
-                candidates.push(completion::Pair {
-                    display: name.clone(),
-                    replacement: name.clone(),
-                })
+                let expansion = get_command(name).and_then(|cmd| cmd.alias_expansion());
+                candidates.push(describe(
+                    name.clone(),
+                    expansion.map(|e| format!("alias for {}", e)).as_deref(),
+                ));
             } else if input.starts_with(name) {
                 if let Some(delim_pos) = input.rfind(&['\t', ' '][..]) {
                     // Complete command line flags and options for internal cmds.
@@ -106,18 +158,12 @@ impl CmdLineHelper {
                         if let Some(short) = f.short {
                             let flag = format!("-{}", short);
                             if flag.starts_with(arg) {
-                                candidates.push(completion::Pair {
-                                    display: flag.clone(),
-                                    replacement: flag,
-                                })
+                                candidates.push(describe(flag, Some(&f.help)));
                             }
                         }
                         let flag = format!("--{}", f.long);
                         if flag.starts_with(arg) {
-                            candidates.push(completion::Pair {
-                                display: flag.clone(),
-                                replacement: flag,
-                            })
+                            candidates.push(describe(flag, Some(&f.help)));
                         }
                         if !f.takes_value.is_some()
                             && arg.starts_with("--no-")
@@ -125,10 +171,7 @@ impl CmdLineHelper {
                         {
                             if f.long.starts_with(&arg[5..]) {
                                 let flag = format!("--no-{}", f.long);
-                                candidates.push(completion::Pair {
-                                    display: flag.clone(),
-                                    replacement: flag,
-                                })
+                                candidates.push(describe(flag, Some(&f.help)));
                             }
                         }
                     }
@@ -156,6 +199,65 @@ impl CmdLineHelper {
         candidates
     }
 
+    /// Complete the path that follows a variable, e.g. $HOME/Doc or ${HOME}/Doc, as if
+    /// the variable was replaced by its value. The variable is kept in the replacements,
+    /// unless the completer needs to escape (or quote) its value.
+    fn complete_var_path(&self, input: &str, candidates: &mut Vec<completion::Pair>) {
+        let Some(sep) = input.find(['/', '\\']) else {
+            return;
+        };
+        let name = &input[1..sep];
+        let name = name
+            .strip_prefix('{')
+            .and_then(|n| n.strip_suffix('}'))
+            .unwrap_or(name);
+
+        let Some(value) = self.interp.global_scope().lookup_value(name) else {
+            return;
+        };
+        let value = value.to_string();
+        let expanded = format!("{}{}", value, &input[sep..]);
+
+        if let Ok((start, pairs)) = self.completer.complete_path(&expanded, expanded.len()) {
+            for pair in pairs {
+                let replacement = format!("{}{}", &expanded[..start], pair.replacement);
+                let replacement = match replacement.strip_prefix(&value) {
+                    Some(path) => format!("{}{}", &input[..sep], path),
+                    None => replacement,
+                };
+                candidates.push(completion::Pair {
+                    display: pair.display,
+                    replacement,
+                });
+            }
+        }
+    }
+
+    /// Complete file names using the rustyline completer. Slow results (e.g. directory scans
+    /// on network drives) are cached for a short while, so that repeated TABs stay responsive.
+    fn complete_path(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> Result<(usize, Vec<completion::Pair>), ReadlineError> {
+        let context = (
+            env::current_dir().unwrap_or_default(),
+            line[..pos].to_string(),
+        );
+        if let Some(result) = self.path_cache.get(&context) {
+            return Ok(result);
+        }
+
+        let start = Instant::now();
+        let result = completion::Completer::complete(&self.completer, line, pos, ctx)?;
+        if start.elapsed() >= SLOW_COMPLETION {
+            self.path_cache
+                .insert(context, result.clone(), PATH_COMPLETION_TTL);
+        }
+        Ok(result)
+    }
+
     fn set_prompt(&mut self, prompt: &str) {
         self.prompt = prompt.into()
     }
@@ -258,6 +360,39 @@ fn match_symlinks(input: &str, pos: &mut usize, candidates: &mut Vec<completion:
 #[cfg(not(windows))]
 fn match_symlinks(_: &str, _: &mut usize, _: &mut Vec<completion::Pair>) {}
 
+/// Separates the candidate from its description in the display text, until aligned.
+const DESCRIPTION_SEP: char = '\t';
+
+/// Make a completion candidate, with an optional description.
+fn describe(replacement: String, description: Option<&str>) -> completion::Pair {
+    let display = match description.filter(|d| !d.is_empty()) {
+        Some(description) => format!("{}{}{}", replacement, DESCRIPTION_SEP, description),
+        None => replacement.clone(),
+    };
+    completion::Pair {
+        display,
+        replacement,
+    }
+}
+
+/// Lay out the candidates that have descriptions in two columns,
+/// so that the completion menu shows the descriptions lined up.
+fn align_descriptions(candidates: &mut [completion::Pair]) {
+    let width = candidates
+        .iter()
+        .filter_map(|c| c.display.split_once(DESCRIPTION_SEP))
+        .map(|(text, _)| text.chars().count())
+        .max();
+
+    if let Some(width) = width {
+        for c in candidates.iter_mut() {
+            if let Some((text, description)) = c.display.split_once(DESCRIPTION_SEP) {
+                c.display = format!("{:<width$}  {}", text, description, width = width);
+            }
+        }
+    }
+}
+
 impl completion::Completer for CmdLineHelper {
     type Candidate = completion::Pair;
 
@@ -300,6 +435,12 @@ impl completion::Completer for CmdLineHelper {
                 });
             }
         } else if let Some(var_pos) = tail.rfind("$") {
+            // Complete file names in the directory that the variable refers to, e.g. $HOME/Doc
+            self.complete_var_path(&tail[var_pos..], &mut completions);
+            if !completions.is_empty() {
+                return Ok((tail_pos + var_pos, completions));
+            }
+
             // Expand variables. NOTE: No variable substitution, just name expansion.
             completions.extend(
                 self.interp
@@ -330,11 +471,9 @@ impl completion::Completer for CmdLineHelper {
             if completions.is_empty() {
                 // Custom (user-defined) command completions
                 if let Some(config) = &self.completions {
-                    for completion in completions::suggest(config, tail) {
-                        completions.push(completion::Pair {
-                            display: completion.clone(),
-                            replacement: completion,
-                        });
+                    for suggestion in completions::suggest(config, tail) {
+                        completions
+                            .push(describe(suggestion.text, suggestion.description.as_deref()));
                     }
                 }
             }
@@ -345,8 +484,9 @@ impl completion::Completer for CmdLineHelper {
             match_symlinks(&tail, &mut tail_pos, &mut completions);
         }
         if completions.is_empty() {
-            self.completer.complete(line, pos, ctx) // Rustyline path completion
+            self.complete_path(line, pos, ctx) // Rustyline path completion
         } else {
+            align_descriptions(&mut completions);
             Ok((tail_pos, completions))
         }
     }
@@ -361,20 +501,126 @@ struct Shell {
     interp: Interp,
     home_dir: Option<PathBuf>,
     history_path: Option<PathBuf>,
+    history_modified: Option<SystemTime>,
+    history_size: usize,
     profile: Option<PathBuf>,
     edit_config: rustyline::config::Config,
     prompt_builder: prompt::PromptBuilder,
     user_dirs: UserDirs,
+    env_files: Option<envfile::EnvFiles>,
 }
 
-/// Search history in reverse for entry that starts with &line[1..]
-fn search_history<H: Helper>(rl: &Editor<H, DefaultHistory>, line: &str) -> Option<String> {
-    let search = &line[1..];
-    rl.history()
-        .iter()
-        .rev()
-        .find(|entry| entry.starts_with(search))
-        .cloned()
+/// Expand history references in the line:
+/// `!!` (the last command), `!N` (entry N), `!-N` (the Nth most recent entry),
+/// `!$` (the last word of the last command), `!*` (the arguments of the last command),
+/// `!PREFIX` at the start of the line (the most recent command that starts with PREFIX),
+/// and `^OLD^NEW` (the last command, with the first occurrence of OLD replaced by NEW).
+/// Return None if there is nothing to expand.
+fn expand_history(line: &str, history: &[&String]) -> Result<Option<String>, String> {
+    let last = || {
+        history
+            .last()
+            .map(|entry| entry.as_str())
+            .ok_or_else(|| "!!: event not found".to_string())
+    };
+
+    if let Some(rest) = line.strip_prefix('^') {
+        let mut parts = rest.splitn(3, '^');
+        let old = parts.next().unwrap_or_default();
+        let new = parts.next().unwrap_or_default();
+        let last = last()?;
+        if old.is_empty() || !last.contains(old) {
+            return Err(format!("^{}: substitution failed", old));
+        }
+        return Ok(Some(format!(
+            "{}{}",
+            last.replacen(old, new, 1),
+            parts.next().unwrap_or_default()
+        )));
+    }
+
+    let chars = line.chars().collect::<Vec<_>>();
+    let mut result = String::new();
+    let mut expanded = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        i += 1;
+
+        if c == '\\' && chars.get(i) == Some(&'!') {
+            result.push_str("\\!");
+            i += 1;
+            continue;
+        }
+        if c != '!' {
+            result.push(c);
+            continue;
+        }
+
+        let digits = |start: usize| {
+            chars[start..]
+                .iter()
+                .take_while(|c| c.is_ascii_digit())
+                .collect::<String>()
+        };
+        let is_word_char = |c: Option<&char>| c.is_some_and(|c| c.is_alphanumeric() || *c == '_');
+
+        match chars.get(i) {
+            Some('!') => {
+                result.push_str(last()?);
+                i += 1;
+            }
+            // Do not expand !$x, which negates the variable x
+            Some('$') if !is_word_char(chars.get(i + 1)) => {
+                let word = last()?.split_whitespace().last().unwrap_or_default();
+                result.push_str(word);
+                i += 1;
+            }
+            Some('*') => {
+                let args = last()?.split_whitespace().skip(1).collect::<Vec<_>>();
+                result.push_str(&args.join(" "));
+                i += 1;
+            }
+            Some(c) if c.is_ascii_digit() || (*c == '-' && !digits(i + 1).is_empty()) => {
+                let negative = *c == '-';
+                let n = digits(if negative { i + 1 } else { i });
+                i += n.len() + negative as usize;
+
+                let event = format!("!{}{}", if negative { "-" } else { "" }, n);
+                let n = n.parse::<usize>().unwrap_or(0);
+                let index = match negative {
+                    true => history.len().checked_sub(n),
+                    false => n.checked_sub(1),
+                };
+                let entry = index
+                    .and_then(|index| history.get(index))
+                    .ok_or_else(|| format!("{}: event not found", event))?;
+                result.push_str(entry);
+            }
+            Some(c) if i == 1 && !c.is_whitespace() && !matches!(c, '=' | '(' | '$') => {
+                let prefix = &line[1..];
+                let entry = history
+                    .iter()
+                    .rev()
+                    .find(|entry| entry.starts_with(prefix))
+                    .ok_or_else(|| format!("!{}: event not found", prefix))?;
+                result.push_str(entry);
+                i = chars.len();
+            }
+            _ => {
+                result.push(c);
+                continue;
+            }
+        }
+        expanded = true;
+    }
+
+    Ok(expanded.then_some(result))
+}
+
+fn history_modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
 }
 
 impl Shell {
@@ -399,6 +645,8 @@ impl Shell {
             interp,
             home_dir: None,
             history_path: None,
+            history_modified: None,
+            history_size: 1024,
             profile: None,
             edit_config: rustyline::Config::builder()
                 .edit_mode(rustyline::EditMode::Emacs)
@@ -412,6 +660,7 @@ impl Shell {
             prompt_builder: PromptBuilder::with_scope(&scope),
             user_dirs: UserDirs::new()
                 .ok_or_else(|| "Failed to get user directories".to_string())?,
+            env_files: None,
         };
         shell.set_home_dir(shell.user_dirs.home_dir().to_path_buf());
 
@@ -429,6 +678,8 @@ impl Shell {
             .map_err(|e| format!("Failed to create .shmy directory: {}", e))?;
 
         self.profile = Some(path.join("profile"));
+        cmds::load_abbreviations(&path.join("abbreviations"))?;
+        self.env_files = Some(envfile::EnvFiles::new(path.join("trusted_env")));
 
         // Load custom completion file if present
         let compl_config_path = path.join("completions.yaml");
@@ -474,6 +725,8 @@ impl Shell {
         );
         // Interpreter process id
         scope.insert("$".to_string(), Value::Int(std::process::id() as _));
+        // Exit status of the last command
+        scope.set_exit_status(0);
         // $0, $1, ...
         for (i, arg) in env::args().enumerate() {
             scope.insert(format!("{}", i), Value::Str(Arc::new(arg)));
@@ -491,20 +744,29 @@ impl Shell {
                 .map_err(|e| format!("Failed to create editor: {}", e))?;
 
             let scope = self.interp.global_scope();
-            let (history_path, completion_config) = self.init_interactive_mode()?;
+            let (_, completion_config) = self.init_interactive_mode()?;
 
             rl.set_helper(Some(CmdLineHelper::new(scope, completion_config)));
-            rl.load_history(history_path).unwrap();
-
-            self.source_profile()?; // source ~/.shmy/profile if found
+            self.load_key_bindings(&mut rl)?;
+            self.load_config(&mut rl)?;
 
             // Read the max history file size (in entries) from the environment.
-            let hist_size = env::var("HISTFILESIZE")
+            if let Some(hist_size) = env::var("HISTFILESIZE")
                 .ok()
                 .and_then(|v| v.parse::<usize>().ok())
-                .unwrap_or(1024);
+            {
+                self.history_size = hist_size;
+            }
             // Adjust history file size
-            _ = rl.history_mut().set_max_len(hist_size);
+            _ = rl.history_mut().set_max_len(self.history_size);
+            self.load_history(&mut rl)?;
+
+            self.load_plugins(); // register the commands in ~/.shmy/plugins
+            self.source_profile()?; // source ~/.shmy/profile if found
+            self.load_aliases()?; // load ~/.shmy/aliases, after the profile
+
+            #[cfg(not(windows))]
+            job::control::enable();
 
             if !Term::stdout().features().colors_supported() {
                 self.interp
@@ -527,7 +789,17 @@ impl Shell {
 
             // Run interactive read-evaluate loop
             while !self.interp.quit {
-                let prompt = self.prompt_builder.prompt();
+                job::report_done_jobs();
+
+                self.prompt_builder
+                    .set_history_number(rl.history().len() + 1);
+                self.update_env_file();
+                self.run_hook("__precmd", None);
+
+                let prompt = match self.run_prompt_command() {
+                    Some(output) => self.prompt_builder.set_prompt(&output),
+                    None => self.prompt_builder.prompt(),
+                };
 
                 // Hack around peculiarity in Rustyline, where a prompt that contains color ANSI codes
                 // needs to go through the highlighter trait in the helper. The prompt passed to readline
@@ -539,24 +811,43 @@ impl Shell {
 
                 match readline {
                     Ok(line) => {
-                        if line.starts_with("!") {
-                            if let Some(history_entry) = search_history(&rl, &line) {
-                                eprintln!("{}", &history_entry);
-                                // Make the entry found in history the most recent
-                                rl.add_history_entry(&history_entry)
-                                    .map_err(|e| e.to_string())?;
-                                // Evaluate the line from history
-                                self.eval(&history_entry);
-                            } else {
-                                eprintln!("No match.");
+                        let line = multiline::join_lines(&line);
+                        // Expand the abbreviation at the end of the line, if any
+                        let line = match cmds::expand_abbreviation(&line) {
+                            Some((start, expansion)) => {
+                                let expanded = format!("{}{}", &line[..start], expansion);
+                                eprintln!("{}", &expanded);
+                                expanded
+                            }
+                            None => line,
+                        };
+                        let history = rl.history().iter().collect::<Vec<_>>();
+                        let line = match expand_history(&line, &history) {
+                            Ok(Some(expanded)) => {
+                                // Show the expanded line before running it
+                                eprintln!("{}", &expanded);
+                                expanded
+                            }
+                            Ok(None) => line,
+                            Err(e) => {
+                                eprintln!("{}", e);
+                                continue;
                             }
-                        } else {
+                        };
+
+                        if !self.is_history_ignored(&line) {
                             rl.add_history_entry(line.as_str())
                                 .map_err(|e| e.to_string())?;
 
-                            self.save_history(&mut rl)?;
-                            self.eval(&line);
+                            self.save_history(&line)?;
                         }
+                        self.run_hook("__preexec", Some(&line));
+
+                        let start = Instant::now();
+                        let status = self.eval(&line);
+                        self.prompt_builder.set_duration(start.elapsed());
+                        self.prompt_builder.set_exit_status(status);
+                        self.reload_history(&mut rl)?;
                     }
                     Err(ReadlineError::Interrupted) => {
                         eprintln!("^C");
@@ -579,10 +870,114 @@ impl Shell {
         Ok(())
     }
 
-    fn save_history(&mut self, rl: &mut CmdLineEditor) -> Result<(), String> {
+    /// Add the line to the history file, merging with the entries saved by other sessions.
+    fn save_history(&mut self, line: &str) -> Result<(), String> {
         let hist_path = self.history_path.as_ref().unwrap();
-        rl.save_history(&hist_path)
-            .map_err(|e| format!("Could not save {}: {}", hist_path.to_string_lossy(), e))
+        history::append(hist_path, history::Entry::new(line), self.history_size)?;
+        self.history_modified = history_modified(hist_path);
+        Ok(())
+    }
+
+    fn load_history(&mut self, rl: &mut CmdLineEditor) -> Result<(), String> {
+        let hist_path = self.history_path.as_ref().unwrap();
+        let entries = history::load(hist_path)?;
+
+        rl.clear_history().map_err(|e| e.to_string())?;
+        for entry in entries {
+            rl.add_history_entry(entry.command)
+                .map_err(|e| e.to_string())?;
+        }
+        self.history_modified = history_modified(hist_path);
+        Ok(())
+    }
+
+    /// Reload the history if the file was modified by a command (e.g. `history -d`),
+    /// or by another session.
+    fn reload_history(&mut self, rl: &mut CmdLineEditor) -> Result<(), String> {
+        let hist_path = self.history_path.as_ref().unwrap();
+        if history_modified(hist_path) != self.history_modified {
+            self.load_history(rl)?;
+        }
+        Ok(())
+    }
+
+    /// Bind Space to the expansion of abbreviations, Ctrl+L to clearing the screen,
+    /// and the keys configured in ~/.shmy/keybindings.yaml, if present.
+    fn load_key_bindings(&self, rl: &mut CmdLineEditor) -> Result<(), String> {
+        let path = self
+            .home_dir
+            .as_ref()
+            .expect("home dir not set")
+            .join(".shmy")
+            .join("keybindings.yaml");
+
+        let (key, handler) = keybindings::abbreviation_binding();
+        rl.bind_sequence(key, handler);
+
+        let (key, handler) = keybindings::clear_screen_binding();
+        rl.bind_sequence(key, handler);
+
+        if path.exists() {
+            let bindings = keybindings::load_from_file(&path)
+                .map_err(|e| format!("Failed to load {}: {}", path.display(), e))?;
+
+            for (key, handler) in bindings {
+                rl.bind_sequence(key, handler);
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply the settings in ~/.shmy/config.toml, if present.
+    fn load_config(&mut self, rl: &mut CmdLineEditor) -> Result<(), String> {
+        let path = self
+            .home_dir
+            .as_ref()
+            .expect("home dir not set")
+            .join(".shmy")
+            .join("config.toml");
+
+        let config = config::Config::load(&path)?;
+        let err = |e: ReadlineError| format!("{}: {}", path.display(), e);
+
+        if let Some(edit_mode) = config.edit_mode {
+            rl.set_edit_mode(edit_mode);
+        }
+        if let Some(completion_type) = config.completion_type {
+            rl.set_completion_type(completion_type);
+        }
+        if let Some(ignore_dups) = config.history_ignore_dups {
+            rl.set_history_ignore_dups(ignore_dups).map_err(err)?;
+        }
+        if let Some(size) = config.history_size {
+            rl.set_max_history_size(size).map_err(err)?;
+            self.history_size = size;
+        }
+        if let Some(spec) = config.prompt {
+            self.interp
+                .global_scope()
+                .insert("__prompt".to_string(), Value::new_str(spec));
+        }
+        if let Some(correct) = config.correct {
+            self.interp
+                .global_scope()
+                .insert("__correct".to_string(), Value::new_str(correct));
+        }
+        for (name, color) in &config.colors {
+            highlight::set_color(name, color).map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+        for (name, flags) in config.default_flags {
+            cmds::set_default_flags(&name, flags);
+        }
+        Ok(())
+    }
+
+    /// Check the line against the $HISTIGNORE patterns.
+    fn is_history_ignored(&self, line: &str) -> bool {
+        self.interp
+            .global_scope()
+            .lookup_value("HISTIGNORE")
+            .is_some_and(|patterns| history::is_ignored(line, &patterns.to_string()))
     }
 
     fn set_home_dir(&mut self, path: PathBuf) {
@@ -593,11 +988,12 @@ impl Shell {
 
     /// Display evaluation result in user-friendly form.
     /// Provide hints if string evaluation could be result of typo (user wanted to execute command).
-    fn show_result(&self, scope: &Arc<Scope>, input: &str, value: &eval::Value) {
+    /// Return the corrected command, if the user wants to run it.
+    fn show_result(&self, scope: &Arc<Scope>, input: &str, value: &eval::Value) -> Option<String> {
         use strsim::levenshtein;
 
         if input.is_empty() {
-            return;
+            return None;
         }
         match value {
             Value::Str(s) => {
@@ -611,17 +1007,77 @@ impl Shell {
                         .min_by_key(|&(_, distance)| distance)
                     {
                         if distance < std::cmp::min(near.len(), input.len()) {
-                            eprintln!(
-                                "{} was evaluated as a string. Did you mean '{}'?",
-                                scope.err_str(input),
-                                scope.err_str(near),
-                            );
+                            return self.correct(scope, input, near, distance);
                         }
                     }
                 }
             }
             _ => println!("{}", value),
         }
+        None
+    }
+
+    /// Offer to run the command nearest to the mistyped input, depending on $__correct:
+    /// "hint" only shows the suggestion, "ask" (the default) asks the user, and "auto" runs
+    /// the command without asking if it is one edit away from the input.
+    fn correct(
+        &self,
+        scope: &Arc<Scope>,
+        input: &str,
+        near: &str,
+        distance: usize,
+    ) -> Option<String> {
+        let mode = scope
+            .lookup_value("__correct")
+            .map(|v| v.to_string())
+            .unwrap_or("ask".to_string());
+
+        let message = format!("{} was evaluated as a string", scope.err_str(input));
+        match mode.as_str() {
+            "auto" if distance == 1 => {
+                eprintln!("{}, running '{}'", message, scope.err_str(near));
+                Some(near.to_string())
+            }
+            "ask" | "auto" => {
+                let question = format!("{}. Run '{}' instead", message, scope.err_str(near));
+                match prompt::confirm_default_yes(question, scope) {
+                    Ok(prompt::Answer::Yes) => Some(near.to_string()),
+                    _ => None,
+                }
+            }
+            _ => {
+                eprintln!("{}. Did you mean '{}'?", message, scope.err_str(near));
+                None
+            }
+        }
+    }
+
+    /// Register the aliases saved in ~/.shmy/aliases. Aliases that are defined already
+    /// (by the profile) take precedence.
+    fn load_aliases(&self) -> Result<(), String> {
+        let path = self
+            .home_dir
+            .as_ref()
+            .expect("home dir not set")
+            .join(".shmy")
+            .join("aliases");
+
+        cmds::load_aliases(&path)
+    }
+
+    /// Register the commands provided by the plugins in ~/.shmy/plugins. Plugins that
+    /// fail to load are reported, and do not keep the shell from starting.
+    fn load_plugins(&self) {
+        let path = self
+            .home_dir
+            .as_ref()
+            .expect("home dir not set")
+            .join(".shmy")
+            .join("plugins");
+
+        if let Err(e) = cmds::load_plugins(&path) {
+            my_warning!(self.interp.global_scope(), "{}", e);
+        }
     }
 
     fn source_profile(&self) -> Result<(), String> {
@@ -640,7 +1096,88 @@ impl Shell {
         Ok(())
     }
 
-    fn eval(&mut self, input: &String) {
+    /// Run $__prompt_command, if defined, and return its output, to be used as the prompt.
+    /// The exit status of the last command ($?) is preserved.
+    fn run_prompt_command(&mut self) -> Option<String> {
+        let global_scope = self.interp.global_scope();
+        let command = global_scope.lookup("__prompt_command")?.value().to_string();
+        if command.trim().is_empty() {
+            return None;
+        }
+
+        let status = global_scope.lookup("?").map(|var| var.value().clone());
+
+        let mut output = String::new();
+        let result = gag::BufferRedirect::stdout()
+            .map_err(|e| e.to_string())
+            .and_then(|mut redirect| {
+                let result = self.interp.eval_status(&command, None);
+                redirect
+                    .read_to_string(&mut output)
+                    .map_err(|e| e.to_string())?;
+                Ok(result)
+            });
+
+        if let Some(status) = status {
+            global_scope.insert("?".to_string(), status);
+        }
+
+        match result {
+            Ok(Ok(_)) => Some(output.trim_end_matches(['\r', '\n']).to_string()),
+            Ok(Err(e)) => {
+                e.show(&global_scope, &command);
+                None
+            }
+            Err(e) => {
+                eprintln!("__prompt_command: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Load the .shmy.env file of the current directory, if changed.
+    fn update_env_file(&mut self) {
+        let scope = self.interp.global_scope();
+        if let Some(env_files) = &mut self.env_files {
+            if let Err(e) = env_files.update(&scope) {
+                eprintln!("{}", e);
+            }
+        }
+    }
+
+    /// Run the hook held by the variable (__precmd or __preexec), if defined. The command
+    /// line about to be executed, if any, is passed in $__command. $? is preserved.
+    fn run_hook(&mut self, name: &str, command_line: Option<&str>) {
+        let global_scope = self.interp.global_scope();
+        let Some(hook) = global_scope.lookup(name).map(|var| var.value().to_string()) else {
+            return;
+        };
+        if hook.trim().is_empty() {
+            return;
+        }
+
+        let status = global_scope.lookup("?").map(|var| var.value().clone());
+
+        // The top scope of the evaluation is cleared, insert $__command in its parent.
+        let hook_scope = Scope::with_parent(Some(Arc::clone(&global_scope)));
+        if let Some(command_line) = command_line {
+            hook_scope.insert(
+                "__command".to_string(),
+                Value::new_str(command_line.to_string()),
+            );
+        }
+        let scope = Scope::with_parent(Some(hook_scope));
+        if let Err(e) = self.interp.eval_status(&hook, Some(Arc::clone(&scope))) {
+            e.show(&scope, &hook);
+        }
+
+        if let Some(status) = status {
+            global_scope.insert("?".to_string(), status);
+        }
+    }
+
+    /// Evaluate the input and return the exit status.
+    fn eval(&mut self, input: &String) -> i64 {
         INTERRUPT_EVENT
             .try_lock()
             .and_then(|mut event| Ok(event.clear()))
@@ -654,20 +1191,38 @@ impl Shell {
                 if let Value::Stat(mut status) = value {
                     if let Some(e) = status.err() {
                         e.show(&scope, input);
+                        return self.error_status();
                     }
                 } else if self.interactive {
-                    self.show_result(&scope, &input.trim(), &value);
+                    if let Some(command) = self.show_result(&scope, &input.trim(), &value) {
+                        return self.eval(&command);
+                    }
                 }
+                0
             }
             Err(e) => {
                 e.show(&scope, input);
                 if !self.interactive && !self.wait {
                     std::process::exit(500);
                 }
+                self.error_status()
             }
         }
     }
 
+    /// Exit status of the last command that failed: $? if non-zero, otherwise 1.
+    fn error_status(&self) -> i64 {
+        match self
+            .interp
+            .global_scope()
+            .lookup("?")
+            .map(|var| var.value().clone())
+        {
+            Some(Value::Int(code)) if code != 0 => code,
+            _ => 1,
+        }
+    }
+
     fn eval_input(&mut self) -> Result<(), String> {
         if let Some(reader) = self.source.take() {
             self.read_lines(reader)
@@ -853,6 +1408,35 @@ mod tests {
         assert_eq!(actual_completions, expected_completions);
     }
 
+    #[test]
+    fn test_expand_history() {
+        let entries = ["ls -al", "git commit -m msg", "cargo build --release"].map(String::from);
+        let history = entries.iter().collect::<Vec<_>>();
+        let expand = |line: &str| expand_history(line, &history);
+
+        assert_eq!(
+            expand("sudo !!"),
+            Ok(Some("sudo cargo build --release".into()))
+        );
+        assert_eq!(expand("!1 | wc"), Ok(Some("ls -al | wc".into())));
+        assert_eq!(expand("!-2"), Ok(Some("git commit -m msg".into())));
+        assert_eq!(
+            expand("echo !$ !*"),
+            Ok(Some("echo --release build --release".into()))
+        );
+        assert_eq!(expand("!git"), Ok(Some("git commit -m msg".into())));
+        assert_eq!(
+            expand("^build^test"),
+            Ok(Some("cargo test --release".into()))
+        );
+        assert_eq!(expand("!9"), Err("!9: event not found".into()));
+        assert_eq!(expand("^x^y"), Err("^x: substitution failed".into()));
+
+        // Negation and comparison operators
+        assert_eq!(expand("!$x && $x != 1"), Ok(None));
+        assert_eq!(expand("echo \\!! (!(1))"), Ok(None));
+    }
+
     #[test]
     fn test_complete_history() {
         let helper = CmdLineHelper::new(Scope::new(), None);
@@ -885,13 +1469,46 @@ mod tests {
         assert_eq!(actual_completions, expected_completions);
     }
 
+    #[test]
+    fn test_complete_var_path() {
+        let scope = Scope::new();
+        scope.insert("SRC".into(), Value::from("src"));
+        let helper = CmdLineHelper::new(scope, None);
+
+        let separator = if cfg!(windows) { "\\" } else { "/" };
+        for input in ["ls $SRC/mai", "ls ${SRC}/mai"] {
+            let input = input.replace('/', separator);
+            let var = &input[3..input.len() - 4];
+            let actual_completions = get_completions(&helper, &input, &MemHistory::new());
+            assert_eq!(actual_completions.len(), 1);
+            assert_eq!(
+                actual_completions[0].1,
+                format!("{}{}main.rs", var, separator)
+            );
+        }
+    }
+
     #[test]
     fn test_complete_negated_flags() {
         let helper = CmdLineHelper::new(Scope::new(), None);
         let actual_completions = get_completions(&helper, "cat  abc --no-", &MemHistory::new());
         let expected_completions = vec![
-            ("--no-help".to_string(), "--no-help".to_string()),
-            ("--no-number".to_string(), "--no-number".to_string()),
+            (
+                "--no-help             Display this help and exit".to_string(),
+                "--no-help".to_string(),
+            ),
+            (
+                "--no-number           Number output lines".to_string(),
+                "--no-number".to_string(),
+            ),
+            (
+                "--no-number-nonblank  Number non-empty output lines, overrides -n".to_string(),
+                "--no-number-nonblank".to_string(),
+            ),
+            (
+                "--no-show-all         Show non-printing characters, TAB as ^I, and $ at the end of each line".to_string(),
+                "--no-show-all".to_string(),
+            ),
         ];
         assert_eq!(actual_completions, expected_completions);
     }
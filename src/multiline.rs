@@ -0,0 +1,162 @@
+//! Multi-line input in interactive mode: the line editor keeps reading lines while
+//! the input is incomplete, then the lines are joined into statements.
+
+fn is_word_start(chars: &[char], i: usize) -> bool {
+    i == 0 || chars[i - 1].is_whitespace() || chars[i - 1] == ';'
+}
+
+fn is_raw_string(chars: &[char], i: usize) -> bool {
+    chars[i] == 'r'
+        && is_word_start(chars, i)
+        && chars.get(i + 1) == Some(&'"')
+        && chars.get(i + 2) == Some(&'(')
+}
+
+/// Return the end of the string or raw string that starts at i (one past the closing
+/// quote), or None if the string is not closed.
+fn string_end(chars: &[char], mut i: usize) -> Option<usize> {
+    if chars[i] == 'r' {
+        i += 3;
+        while i < chars.len() && !(chars[i] == ')' && chars.get(i + 1) == Some(&'"')) {
+            i += 1;
+        }
+        (i < chars.len()).then_some(i + 2)
+    } else {
+        i += 1;
+        while i < chars.len() && chars[i] != '"' {
+            if chars[i] == '\\' {
+                i += 1;
+            }
+            i += 1;
+        }
+        (i < chars.len()).then_some(i + 1)
+    }
+}
+
+/// Return the end of the comment that starts at i, i.e. the position of the newline.
+fn comment_end(chars: &[char], i: usize) -> usize {
+    (i..chars.len())
+        .find(|&j| chars[j] == '\n')
+        .unwrap_or(chars.len())
+}
+
+/// A trailing backslash continues the line only if preceded by whitespace,
+/// so that Windows paths such as C:\ are not mistaken for continuations.
+fn ends_with_backslash(text: &str) -> bool {
+    text.strip_suffix('\\')
+        .is_some_and(|rest| rest.chars().last().is_none_or(char::is_whitespace))
+}
+
+/// Check the input for unclosed quotes, raw strings, parentheses, brackets and braces,
+/// and for a trailing backslash.
+pub fn is_complete(input: &str) -> bool {
+    let chars = input.chars().collect::<Vec<_>>();
+    let mut depth = 0;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '"' || is_raw_string(&chars, i) {
+            match string_end(&chars, i) {
+                Some(end) => i = end,
+                None => return false,
+            }
+            continue;
+        } else if c == '#' && is_word_start(&chars, i) {
+            i = comment_end(&chars, i);
+            continue;
+        } else if matches!(c, '(' | '[' | '{') {
+            depth += 1;
+        } else if matches!(c, ')' | ']' | '}') {
+            depth -= 1;
+        }
+        i += 1;
+    }
+
+    depth <= 0 && !ends_with_backslash(input)
+}
+
+/// Join the lines of (complete) multi-line input into one line. Statements must end with
+/// a semicolon, so line breaks between statements become semicolons; line breaks after
+/// an opening bracket, a separator or an operator, or before a closing bracket or ELSE,
+/// become spaces. Line breaks inside strings are kept, and comments are dropped.
+pub fn join_lines(input: &str) -> String {
+    let chars = input.chars().collect::<Vec<_>>();
+    let mut result = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '"' || is_raw_string(&chars, i) {
+            let end = string_end(&chars, i).unwrap_or(chars.len());
+            result.extend(&chars[i..end]);
+            i = end;
+            continue;
+        } else if c == '#' && is_word_start(&chars, i) {
+            i = comment_end(&chars, i);
+            continue;
+        } else if c == '\n' {
+            if ends_with_backslash(&result) {
+                result.pop();
+            } else {
+                result.truncate(result.trim_end().len());
+                let prev = result.chars().last();
+                let next = chars[i + 1..].iter().collect::<String>();
+                let next = next.trim_start();
+                let separate = prev.is_some_and(|c| !"([{;,|&=".contains(c))
+                    && next.chars().next().is_some_and(|c| !")]}".contains(c))
+                    && !is_else(next);
+                result.push_str(if separate { "; " } else { " " });
+            }
+        } else {
+            result.push(c);
+        }
+        i += 1;
+    }
+    result
+}
+
+fn is_else(text: &str) -> bool {
+    text.get(..4)
+        .is_some_and(|word| word.eq_ignore_ascii_case("else"))
+        && !text[4..].starts_with(|c: char| c.is_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_complete() {
+        assert!(is_complete("ls -al"));
+        assert!(!is_complete("for i in 1 2 3; ("));
+        assert!(is_complete("for i in 1 2 3; (\necho $i)"));
+        assert!(!is_complete("x = [1, 2,"));
+        assert!(!is_complete("{ echo a;"));
+        assert!(!is_complete("echo \"hello"));
+        assert!(is_complete("echo \"(\\\"\""));
+        assert!(!is_complete("echo r\"(a \" b"));
+        assert!(is_complete("echo r\"(a \" b)\""));
+        assert!(is_complete("echo $# # (comment"));
+        assert!(!is_complete("ls \\"));
+        assert!(is_complete("ls \\\n-al"));
+        assert!(is_complete("cd C:\\"));
+        assert!(is_complete("echo )"));
+    }
+
+    #[test]
+    fn test_join_lines() {
+        assert_eq!(
+            join_lines("for i in 1 2; (\necho $i\necho x\n)"),
+            "for i in 1 2; ( echo $i; echo x )"
+        );
+        assert_eq!(join_lines("ls \\\n-al"), "ls -al");
+        assert_eq!(join_lines("x = [1,\n2]"), "x = [1, 2]");
+        assert_eq!(join_lines("echo \"a\nb\""), "echo \"a\nb\"");
+        assert_eq!(
+            join_lines("if (1) (\necho a # comment\n)\nelse (echo b)"),
+            "if (1) ( echo a ) else (echo b)"
+        );
+        assert_eq!(join_lines("(\necho a;\n\necho b\n)"), "( echo a; echo b )");
+    }
+}
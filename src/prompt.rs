@@ -1,5 +1,6 @@
-use crate::{eval::Value, scope::Scope};
-use colored::Colorize;
+use crate::utils::{format_duration, DurationStyle};
+use crate::{eval::Value, git, job::JOBS, scope::Scope};
+use colored::{Color, Colorize};
 use crossterm::{
     event::{self, Event, KeyCode, KeyEventKind},
     terminal::{disable_raw_mode, enable_raw_mode, is_raw_mode_enabled},
@@ -9,6 +10,7 @@ use std::borrow::Cow;
 use std::env;
 use std::io::{self, Write};
 use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(PartialEq)]
 pub enum Answer {
@@ -51,6 +53,25 @@ pub fn confirm(prompt: String, scope: &Arc<Scope>, one_of_many: bool) -> io::Res
     process_answer(&input, one_of_many)
 }
 
+/// Ask a yes / no question where Enter answers yes.
+pub fn confirm_default_yes(prompt: String, scope: &Arc<Scope>) -> io::Result<Answer> {
+    if scope.lookup("NO_CONFIRM").is_some() {
+        return Ok(Answer::Yes);
+    }
+
+    let options = if scope.use_colors(&std::io::stdout()) {
+        format!("{}/{}", "Y".green().bold(), "n".red().bold())
+    } else {
+        "Y/n".to_string()
+    };
+
+    let input = read_input(&format!("{}? ({}) ", prompt, options))?;
+    match input.trim().chars().next().map(|c| c.to_ascii_lowercase()) {
+        None | Some('y') => Ok(Answer::Yes),
+        _ => Ok(Answer::No),
+    }
+}
+
 pub struct RawMode {
     is_raw_mode: bool,
 }
@@ -144,12 +165,15 @@ fn open_tty_for_writing() -> io::Result<impl Write> {
 }
 
 pub struct PromptBuilder {
-    scope: Arc<Scope>,    // Reference to Scope, to lookup $__prompt spec variable
-    prompt: String,       // The constructed prompt...
-    without_ansi: String, // ... and it's variant stripped of ANSI codes.
-    elevated: bool,       // Windows only: running in elevated mode? Show # instead of $.
-    spec: Arc<String>,    // Specification.
-    strip_ansi: Regex,    // Regular expression for matching ANSI escape codes
+    scope: Arc<Scope>,     // Reference to Scope, to lookup $__prompt spec variable
+    prompt: String,        // The constructed prompt...
+    without_ansi: String,  // ... and it's variant stripped of ANSI codes.
+    elevated: bool,        // Windows only: running in elevated mode? Show # instead of $.
+    spec: Arc<String>,     // Specification.
+    strip_ansi: Regex,     // Regular expression for matching ANSI escape codes
+    history_number: usize, // History number of the next command
+    exit_status: i64,      // Exit status of the last command
+    duration: Duration,    // Wall-clock duration of the last command
 }
 
 impl PromptBuilder {
@@ -161,6 +185,9 @@ impl PromptBuilder {
             elevated: Self::is_elevated(),
             spec: Arc::default(),
             strip_ansi: Regex::new(r"\x1B\[[0-?]*[ -/]*[@-~]").unwrap(),
+            history_number: 1,
+            exit_status: 0,
+            duration: Duration::ZERO,
         }
     }
 
@@ -183,6 +210,29 @@ impl PromptBuilder {
         Cow::Borrowed(&self.prompt)
     }
 
+    /// Use the prompt as is, e.g. the output of $__prompt_command, instead of building it.
+    pub fn set_prompt(&mut self, prompt: &str) -> Cow<'_, str> {
+        self.prompt = prompt.to_string();
+        self.without_ansi = self.strip_ansi.replace_all(&self.prompt, "").into();
+
+        Cow::Borrowed(&self.prompt)
+    }
+
+    /// Set the history number of the next command, shown by \!
+    pub fn set_history_number(&mut self, number: usize) {
+        self.history_number = number;
+    }
+
+    /// Set the exit status of the last command, shown by \? and \X
+    pub fn set_exit_status(&mut self, status: i64) {
+        self.exit_status = status;
+    }
+
+    /// Set the duration of the last command, shown by \D
+    pub fn set_duration(&mut self, duration: Duration) {
+        self.duration = duration;
+    }
+
     pub fn without_ansi(&self) -> Cow<str> {
         Cow::Borrowed(&self.without_ansi)
     }
@@ -253,16 +303,126 @@ impl PromptBuilder {
             .to_string();
 
         // Follow bash behavior and substitute ~ for home dir.
-        // TODO: prompt_trimdir?
-        if let Some(home_dir) = self.scope.lookup("HOME") {
+        let work_dir = if let Some(home_dir) = self.scope.lookup("HOME") {
             #[cfg(windows)]
             let re = Regex::new(&format!(r"(?i)^{}", escape(&home_dir.value().as_str())));
             #[cfg(not(windows))]
             let re = Regex::new(&format!(r"^{}", escape(&home_dir.value().as_str())));
 
-            self.prompt.push_str(&re.unwrap().replace(&work_dir, "~"));
+            re.unwrap().replace(&work_dir, "~").to_string()
         } else {
-            self.prompt.push_str(&work_dir);
+            work_dir
+        };
+
+        let dirtrim = self.lookup_usize("__prompt_dirtrim");
+        let abbrev = self.lookup_usize("__prompt_dirabbrev");
+
+        self.prompt.push_str(&trim_dir(
+            &work_dir,
+            std::path::MAIN_SEPARATOR,
+            dirtrim,
+            abbrev,
+        ));
+    }
+
+    /// Lookup a numeric prompt setting, default to zero (disabled).
+    fn lookup_usize(&self, name: &str) -> usize {
+        self.scope
+            .lookup(name)
+            .and_then(|var| var.value().to_string().parse().ok())
+            .unwrap_or_default()
+    }
+
+    fn push_time(&mut self, format: &str) {
+        let now = chrono::Local::now();
+        self.prompt.push_str(&now.format(format).to_string());
+    }
+
+    fn push_job_count(&mut self) {
+        let count = JOBS.lock().map_or(0, |mut jobs| jobs.jobs().len());
+        self.prompt.push_str(&count.to_string());
+    }
+
+    /// Push the exit status of the last command; if only_errors is true, push
+    /// the status (in red) only when non-zero.
+    fn push_exit_status(&mut self, only_errors: bool) {
+        if !only_errors {
+            self.prompt.push_str(&self.exit_status.to_string());
+        } else if self.exit_status != 0 {
+            let status = self
+                .scope
+                .color(&self.exit_status.to_string(), Color::Red, &io::stdout());
+            self.prompt.push_str(&status.to_string());
+        }
+    }
+
+    /// Push the duration of the last command, if longer than $__prompt_min_duration
+    /// (in seconds, default: 2).
+    fn push_duration(&mut self) {
+        let min_duration = self
+            .scope
+            .lookup("__prompt_min_duration")
+            .and_then(|var| var.value().to_string().parse::<f64>().ok())
+            .unwrap_or(2.0);
+
+        if self.duration.as_secs_f64() >= min_duration {
+            self.prompt
+                .push_str(&format_duration(self.duration, DurationStyle::Units));
+        }
+    }
+
+    /// Push the git branch and status markers, if the current directory is in a repository.
+    fn push_git_status(&mut self) {
+        if let Some(status) = env::current_dir().ok().and_then(|dir| git::status(&dir)) {
+            self.prompt.push_str(&status.to_prompt());
+        }
+    }
+
+    /// Push the ANSI escape sequence for a comma-separated list of colors and attributes,
+    /// e.g. green, bright_blue, on_black, bold, reset. Nothing is pushed if NO_COLOR is set.
+    fn push_style(&mut self, style: &str) {
+        if self.scope.lookup("NO_COLOR").is_some() {
+            return;
+        }
+        let mut codes = Vec::new();
+        for name in style
+            .split(',')
+            .map(|s| s.trim().to_lowercase().replace('_', " "))
+        {
+            let code = match name.as_str() {
+                "reset" | "normal" => "0".into(),
+                "bold" => "1".into(),
+                "dim" => "2".into(),
+                "italic" => "3".into(),
+                "underline" => "4".into(),
+                "blink" => "5".into(),
+                "reverse" => "7".into(),
+                _ => match name.strip_prefix("on ") {
+                    Some(bg) => match bg.parse::<Color>() {
+                        Ok(color) => color.to_bg_str(),
+                        Err(_) => continue,
+                    },
+                    None => match name.parse::<Color>() {
+                        Ok(color) => color.to_fg_str(),
+                        Err(_) => continue,
+                    },
+                },
+            };
+            codes.push(code);
+        }
+        if !codes.is_empty() {
+            self.prompt.push_str(&format!("\x1b[{}m", codes.join(";")));
+        }
+    }
+
+    /// Push the version (major.minor) of the shell, or the full release version.
+    fn push_version(&mut self, release: bool) {
+        let version = env!("CARGO_PKG_VERSION");
+        if release {
+            self.prompt.push_str(version);
+        } else {
+            let short = version.splitn(3, '.').take(2).collect::<Vec<_>>().join(".");
+            self.prompt.push_str(&short);
         }
     }
 
@@ -280,6 +440,24 @@ impl PromptBuilder {
                         'h' => self.push_short_hostname(),
                         'w' => self.push_current_dir(),
                         '$' => self.prompt.push(if self.is_root() { '#' } else { '$' }),
+                        't' => self.push_time("%H:%M:%S"),
+                        'T' => self.push_time("%I:%M:%S"),
+                        '@' => self.push_time("%I:%M %p"),
+                        'd' => self.push_time("%a %b %d"),
+                        'j' => self.push_job_count(),
+                        '!' => self.prompt.push_str(&self.history_number.to_string()),
+                        '?' => self.push_exit_status(false),
+                        'X' => self.push_exit_status(true),
+                        'c' if chars.peek() == Some(&'{') => {
+                            chars.next();
+                            let style =
+                                chars.by_ref().take_while(|&c| c != '}').collect::<String>();
+                            self.push_style(&style);
+                        }
+                        'D' => self.push_duration(),
+                        'g' => self.push_git_status(),
+                        'v' => self.push_version(false),
+                        'V' => self.push_version(true),
                         _ => {
                             self.prompt.push(next_ch);
                         }
@@ -294,6 +472,41 @@ impl PromptBuilder {
     }
 }
 
+/// Keep only the last dirtrim components of the directory (if non-zero), replacing the
+/// others with ..., and abbreviate the parent directories to abbrev characters (if non-zero)
+/// fish-style, e.g. ~/.../src/cmds and ~/P/c/src/cmds respectively. The first component
+/// (root, drive, or ~) is always shown.
+fn trim_dir(dir: &str, sep: char, dirtrim: usize, abbrev: usize) -> String {
+    let mut components = dir.split(sep).collect::<Vec<_>>();
+    if components.len() < 2 || components[1..].iter().all(|c| c.is_empty()) {
+        return dir.to_string();
+    }
+    let first = components.remove(0);
+
+    let mut rest = components.into_iter().map(String::from).collect::<Vec<_>>();
+    let mut trimmed = 0;
+    if dirtrim > 0 && rest.len() > dirtrim {
+        trimmed = 1;
+        rest.drain(..rest.len() - dirtrim);
+        rest.insert(0, "...".to_string());
+    }
+
+    if abbrev > 0 {
+        let last = rest.len() - 1;
+        for component in &mut rest[trimmed..last] {
+            // Keep the dot of hidden directories
+            let len = if component.starts_with('.') {
+                abbrev + 1
+            } else {
+                abbrev
+            };
+            *component = component.chars().take(len).collect();
+        }
+    }
+
+    format!("{}{}{}", first, sep, rest.join(&sep.to_string()))
+}
+
 // Unit tests
 #[cfg(test)]
 mod tests {
@@ -338,4 +551,71 @@ mod tests {
         );
         assert_eq!(builder.build("(\\w)"), format!("({})", current_dir));
     }
+
+    #[test]
+    fn test_trim_dir() {
+        assert_eq!(trim_dir("/", '/', 2, 1), "/");
+        assert_eq!(trim_dir("~", '/', 2, 1), "~");
+        assert_eq!(trim_dir("/usr/local/bin", '/', 0, 0), "/usr/local/bin");
+        assert_eq!(trim_dir("/usr/local/bin", '/', 2, 0), "/.../local/bin");
+        assert_eq!(
+            trim_dir("~/Projects/crate/src", '/', 3, 0),
+            "~/Projects/crate/src"
+        );
+        assert_eq!(trim_dir("~/Projects/crate/src", '/', 0, 1), "~/P/c/src");
+        assert_eq!(trim_dir("~/.config/shmy/x", '/', 0, 1), "~/.c/s/x");
+        assert_eq!(
+            trim_dir("C:\\Users\\me\\src", '\\', 2, 2),
+            "C:\\...\\me\\src"
+        );
+        assert_eq!(trim_dir("/a/bcd/efg/hij", '/', 2, 2), "/.../ef/hij");
+    }
+
+    #[test]
+    fn test_build_escapes() {
+        let mut builder = PromptBuilder::new();
+
+        builder.set_history_number(42);
+        assert_eq!(builder.build("\\!> "), "42> ");
+        assert_eq!(builder.build("\\V"), env!("CARGO_PKG_VERSION").to_string());
+        assert!(env!("CARGO_PKG_VERSION").starts_with(builder.build("\\v.").as_ref()));
+
+        builder.set_exit_status(2);
+        assert_eq!(builder.build("\\?"), "2");
+        assert!(builder.build("\\X").contains("2"));
+        builder.set_exit_status(0);
+        assert_eq!(builder.build("[\\X]\\?"), "[]0");
+
+        builder.set_duration(Duration::from_millis(500));
+        assert_eq!(builder.build("\\D"), "");
+        builder.set_duration(Duration::from_secs(3725));
+        assert_eq!(builder.build("(\\D)"), "(1h 2m 5s)");
+        builder
+            .scope
+            .insert("__prompt_min_duration".to_string(), Value::Real(0.25));
+        builder.set_duration(Duration::from_millis(500));
+        assert_eq!(builder.build("\\D"), "0.50s");
+
+        builder.scope.erase("NO_COLOR");
+        assert_eq!(
+            builder.build("\\c{bold,green}ok\\c{reset}"),
+            "\x1b[1;32mok\x1b[0m"
+        );
+        assert_eq!(builder.build("\\c{on_bright_blue}"), "\x1b[104m");
+        builder.scope.insert(
+            "__prompt".to_string(),
+            Value::new_str("\\c{red}x\\c{reset}> ".to_string()),
+        );
+        assert_eq!(builder.prompt(), "\x1b[31mx\x1b[0m> ");
+        assert_eq!(builder.without_ansi(), "x> ");
+        builder.scope.insert("NO_COLOR".to_string(), Value::Int(1));
+        assert_eq!(builder.build("\\c{green}ok\\c{reset}"), "ok");
+
+        builder.set_prompt("\x1b[32mok\x1b[0m> ");
+        assert_eq!(builder.without_ansi(), "ok> ");
+
+        let re = Regex::new(r"^\d\d:\d\d:\d\d \d\d:\d\d:\d\d \w{3} \w{3} \d\d \d+$").unwrap();
+        let prompt = builder.build("\\t \\T \\d \\j").to_string();
+        assert!(re.is_match(&prompt), "{}", prompt);
+    }
 }
@@ -1,8 +1,11 @@
-use crate::{eval::Value, utils::executable};
+use crate::{
+    eval::Value,
+    utils::{executable, sync_env_vars},
+};
 use colored::*;
-use std::cell::{Ref, RefCell, RefMut};
+use std::cell::{Cell, Ref, RefCell, RefMut};
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
 use std::fmt::{self, Debug};
 use std::hash::{Hash, Hasher};
@@ -10,18 +13,59 @@ use std::io::IsTerminal;
 use std::path::Path;
 use std::sync::Arc;
 
+/// Name of the environment variable that lists the names of the variables
+/// that are passed to child instances of the interpreter without being exported.
+pub const UNEXPORTED: &str = "__unexported";
+
 #[derive(Clone, Debug)]
 pub struct Variable {
     val: RefCell<Value>,
+    local: bool,
+    readonly: Cell<bool>,
+    exported: Cell<bool>,
 }
 
 impl Variable {
     pub fn new(val: Value) -> Self {
         Self {
             val: RefCell::new(val),
+            local: false,
+            readonly: Cell::default(),
+            exported: Cell::default(),
         }
     }
 
+    /// Local variables are confined to their scope: not exported by eval --export.
+    pub fn new_local(val: Value) -> Self {
+        Self {
+            val: RefCell::new(val),
+            local: true,
+            readonly: Cell::default(),
+            exported: Cell::default(),
+        }
+    }
+
+    pub fn is_local(&self) -> bool {
+        self.local
+    }
+
+    pub fn is_readonly(&self) -> bool {
+        self.readonly.get()
+    }
+
+    pub fn set_readonly(&self) {
+        self.readonly.set(true)
+    }
+
+    /// Exported variables are passed in the environment of external commands.
+    pub fn is_exported(&self) -> bool {
+        self.exported.get()
+    }
+
+    pub fn set_exported(&self, exported: bool) {
+        self.exported.set(exported)
+    }
+
     pub fn assign(&self, val: Value) -> Ref<Value> {
         *self.val.borrow_mut() = val;
         self.val.borrow()
@@ -34,9 +78,7 @@ impl Variable {
 
 impl From<&str> for Variable {
     fn from(value: &str) -> Self {
-        Variable {
-            val: RefCell::new(value.parse::<Value>().unwrap()),
-        }
+        Variable::new(value.parse::<Value>().unwrap())
     }
 }
 
@@ -116,7 +158,7 @@ impl Ident {
     }
 
     pub fn is_special_var(&self) -> bool {
-        matches!(self.as_str(), "__errors" | "__stderr" | "__stdout")
+        matches!(self.as_str(), "?" | "__errors" | "__stderr" | "__stdout")
     }
 }
 
@@ -189,10 +231,29 @@ impl Namespace for VarTable {
     }
 }
 
+/// Interpreter options controlled by the `set` command.
+#[derive(Debug)]
+pub struct ShellOptions {
+    pub errexit: Cell<bool>, // Abort on the first failed command
+    pub nounset: Cell<bool>, // Error on expanding undefined variables
+    pub xtrace: Cell<bool>,  // Print commands before executing them
+}
+
+impl Default for ShellOptions {
+    fn default() -> Self {
+        Self {
+            errexit: Cell::new(true),
+            nounset: Cell::default(),
+            xtrace: Cell::default(),
+        }
+    }
+}
+
 pub struct Scope {
     pub parent: Option<Arc<Scope>>,
     vars: VarTable,
     err_arg: RefCell<usize>, // Index of argument with error.
+    options: ShellOptions,   // Meaningful in the global scope only
 }
 
 impl Debug for Scope {
@@ -212,6 +273,7 @@ impl Scope {
             parent: None,
             vars: VarTable::new(),
             err_arg: RefCell::default(),
+            options: ShellOptions::default(),
         })
     }
 
@@ -220,20 +282,43 @@ impl Scope {
             parent,
             vars: VarTable::new(),
             err_arg: RefCell::default(),
+            options: ShellOptions::default(),
         })
     }
 
     pub fn with_env_vars() -> Arc<Scope> {
         env::set_var("SHELL", executable().unwrap_or("shmy".to_string()));
 
-        let vars: HashMap<Ident, Variable> = env::vars()
+        let mut vars: HashMap<Ident, Variable> = env::vars()
             .map(|(key, value)| (Ident::from(key), Variable::from(value.as_str())))
             .collect::<HashMap<_, _>>();
 
+        // The inherited $PWD may be stale, e.g. if the shell was started by a program that changed directory.
+        if let Ok(dir) = env::current_dir() {
+            vars.insert(
+                Ident::from("PWD"),
+                Variable::from(dir.to_string_lossy().as_ref()),
+            );
+        }
+
+        vars.values().for_each(|var| var.set_exported(true));
+
+        // Variables passed by a parent interpreter that were not exported there, see utils.rs
+        env::remove_var(UNEXPORTED);
+        if let Some(unexported) = vars.remove(&Ident::from(UNEXPORTED)) {
+            for name in unexported.value().to_string().split_ascii_whitespace() {
+                if let Some(var) = vars.get(&Ident::from(name)) {
+                    var.set_exported(false);
+                    env::remove_var(name);
+                }
+            }
+        }
+
         Arc::new(Scope {
             parent: None,
             vars: VarTable::with_vars(vars),
             err_arg: RefCell::default(),
+            options: ShellOptions::default(),
         })
     }
 
@@ -244,6 +329,16 @@ impl Scope {
             .unwrap_or(false)
     }
 
+    /// Options of the interpreter that owns the global scope.
+    pub fn options(&self) -> &ShellOptions {
+        &self.global().options
+    }
+
+    /// Set the $? variable in the global scope to the exit status of the last command.
+    pub fn set_exit_status(&self, code: i64) {
+        self.global().insert("?".to_string(), Value::Int(code));
+    }
+
     pub fn clear(&self) {
         self.vars.clear();
         *self.err_arg.borrow_mut() = 0;
@@ -257,6 +352,11 @@ impl Scope {
         self.vars.insert(&Ident(Arc::clone(name)), val);
     }
 
+    /// Declare variable in this scope, shadowing variables with the same name in the outer scopes.
+    pub fn insert_var(&self, name: &Arc<String>, var: Variable) {
+        self.vars.inner_mut().insert(Ident(Arc::clone(name)), var);
+    }
+
     pub fn lookup(&self, name: &str) -> Option<Ref<Variable>> {
         self.lookup_by_ident(&Ident::from(name))
     }
@@ -318,6 +418,54 @@ impl Scope {
         self.vars.inner_mut()
     }
 
+    /// Copy the variables of this scope and of the outer scopes, to restore them later.
+    pub fn save_vars(&self) -> Vec<HashMap<Ident, Variable>> {
+        let mut saved = Vec::new();
+        let mut current_scope = Some(self);
+
+        while let Some(scope) = current_scope {
+            saved.push(scope.vars().clone());
+            current_scope = scope.parent.as_deref();
+        }
+        saved
+    }
+
+    /// Restore the variables saved by save_vars, except for $?, and synchronize
+    /// the environment with the global scope.
+    pub fn restore_vars(&self, saved: Vec<HashMap<Ident, Variable>>) {
+        let status = self.global().lookup_value("?");
+        let mut current_scope = Some(self);
+
+        for vars in saved {
+            let Some(scope) = current_scope else {
+                break;
+            };
+            *scope.vars_mut() = vars;
+            current_scope = scope.parent.as_deref();
+        }
+        if let Some(status) = status {
+            self.global().insert("?".to_string(), status);
+        }
+        sync_env_vars(self.global());
+    }
+
+    /// Collect the variables visible from this scope; inner scopes shadow the outer ones.
+    pub fn visible_vars(&self) -> BTreeMap<Ident, Variable> {
+        let mut all_vars = BTreeMap::new();
+        let mut current_scope = Some(self);
+
+        while let Some(scope) = current_scope {
+            for (key, value) in scope.vars().iter() {
+                if !all_vars.contains_key(key) {
+                    all_vars.insert(key.clone(), value.clone());
+                }
+            }
+            current_scope = scope.parent.as_deref();
+        }
+
+        all_vars
+    }
+
     /// Getter and setter for the index of the argument that caused an error.
     pub fn err_arg(&self) -> usize {
         *self.err_arg.borrow()
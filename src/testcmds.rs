@@ -28,7 +28,7 @@ mod tests {
 
     #[test]
     fn test_chmod_err() {
-        assert_err_loc!("chmod  -r   -v  w+x bogus", Location::new(1, 20));
+        assert_err_loc!("chmod  -r   -v  u+x bogus", Location::new(1, 20));
     }
 
     #[test]
@@ -71,6 +71,229 @@ mod tests {
         // Clean up is automatically done by TempDir when it goes out of scope
     }
 
+    #[test]
+    fn test_redirect() {
+        let temp_dir = TempDir::new().unwrap();
+        let out_path = temp_dir.path().join("out.txt");
+        let err_path = temp_dir.path().join("err.txt");
+
+        let cmd = format!(
+            "echo hello > {out}; echo world >> {out} 2> {err}",
+            out = out_path.display(),
+            err = err_path.display()
+        );
+        assert_eval_cmd_ok!(&cmd);
+
+        let mut content = String::new();
+        File::open(&out_path)
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "hello\nworld\n");
+        assert!(err_path.exists(), "Error output file was not created");
+
+        // Outside of command arguments, > is the greater-than operator
+        assert_eval_ok!("i = 5; echo ($i > 3) 2>&1 | x; $x", Value::Int(1));
+    }
+
+    #[test]
+    fn test_input_redirect() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("input.txt");
+        File::create(&path)
+            .unwrap()
+            .write_all(b"zulu\nalpha\nmike\n")
+            .unwrap();
+
+        let cmd = format!("sort < {} | x; $x", path.display());
+        assert_eval_ok!(&cmd, Value::from("alpha\nmike\nzulu"));
+
+        assert_eval_ok!(
+            "(cat < bogus_file || echo failed) | x; $x",
+            Value::from("failed")
+        );
+    }
+
+    #[test]
+    fn test_heredoc() {
+        assert_eval_ok!(
+            "y = 42; (cat <<EOF\nhello $y\n  world\nEOF\n) | x; $x",
+            Value::from("hello 42\n  world")
+        );
+        assert_eval_ok!(
+            "(wc -l <<END; echo done\none\ntwo\nEND\n) | x; $x",
+            Value::from("2\ndone")
+        );
+        assert_eval_err!("cat <<EOF\nhello\n", "Unterminated heredoc, expecting EOF");
+    }
+
+    #[test]
+    fn test_here_string() {
+        assert_eval_ok!(
+            "y = 42; (cat <<< \"hello $y\") | x; $x",
+            Value::from("hello 42")
+        );
+        assert_eval_ok!("(sort <<< \"b\\na\") | x; $x", Value::from("a\nb"));
+    }
+
+    #[test]
+    fn test_head() {
+        assert_eval_ok!("(head -n 2 <<< \"a\\nb\\nc\") | x; $x", Value::from("a\nb"));
+        assert_eval_ok!(
+            "(head -c 5 <<< \"hello world\") | x; $x",
+            Value::from("hello")
+        );
+    }
+
+    #[test]
+    fn test_redirect_without_final_newline() {
+        let temp_dir = TempDir::new().unwrap();
+        let input = temp_dir.path().join("input.txt");
+        std::fs::write(&input, "abc\nxyz").unwrap();
+        let input = input.display().to_string().replace('\\', "/");
+
+        for (i, (cmd, expected)) in [
+            ("cat", "abc\nxyz"),
+            ("cat -n", "     1: abc\n     2: xyz"),
+            ("head -c 6", "abc\nxy"),
+            ("tail -n 1", "xyz"),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let output = temp_dir.path().join(format!("output{}.txt", i));
+            let output = output.display().to_string().replace('\\', "/");
+            assert_eval_cmd_ok!(&format!("{} {} > {}", cmd, input, output));
+            assert_eq!(
+                std::fs::read_to_string(&output).unwrap(),
+                expected,
+                "{}",
+                cmd
+            );
+        }
+    }
+
+    #[test]
+    fn test_xargs_null() {
+        // -0 is a flag, not the number 0
+        assert_eval_ok!(
+            "echo \"a b\\x00c\" | xargs -0 -n 1 echo | x; $x",
+            Value::from("a b\nc")
+        );
+        assert_eval_ok!("echo -0 -5 -1.50 | x; $x", Value::from("-0 -5 -1.50"));
+    }
+
+    #[test]
+    fn test_du_json_and_null() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("a b.txt"), "0123456789").unwrap();
+        let dir = temp_dir.path().display().to_string().replace('\\', "/");
+
+        let output = eval(&format!("du --json -a {} | x; $x", dir))
+            .unwrap()
+            .to_string();
+        let records = output
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(records.len(), 2);
+        assert!(records[0]["path"].as_str().unwrap().ends_with("a b.txt"));
+        assert_eq!(records[0]["apparent_bytes"], 10);
+
+        let output = eval(&format!("du -0 -a {} | x; $x", dir))
+            .unwrap()
+            .to_string();
+        let records = output.split_terminator('\0').collect::<Vec<_>>();
+        assert_eq!(records.len(), 2);
+        assert!(records[0].contains('\t') && records[0].ends_with("a b.txt"));
+        assert!(!output.contains('\n'));
+    }
+
+    #[test]
+    fn test_find_null() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("a b.txt"), "hello").unwrap();
+        let dir = temp_dir.path().display().to_string().replace('\\', "/");
+
+        let output = eval(&format!(
+            "find -0 --type f --name \"*.txt\" {} | x; $x",
+            dir
+        ))
+        .unwrap()
+        .to_string();
+        assert!(output.ends_with("a b.txt\0"), "{:?}", output);
+        assert_eval_ok!(
+            &format!("find -0 --name \"*.txt\" {} | xargs -0 cat | x; $x", dir),
+            Value::from("hello")
+        );
+    }
+
+    #[test]
+    fn test_background_job() {
+        assert_eval_cmd_ok!("echo hello & fg");
+        assert_eval_ok!(
+            "((exit 3) & fg || echo failed) | x; $x",
+            Value::from("failed")
+        );
+        assert_eval_err!("& echo", "Expecting expression before &");
+    }
+
+    #[test]
+    fn test_history() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("history.txt");
+        File::create(&path)
+            .unwrap()
+            .write_all(b"#V2\nls -al\ncargo build\ncargo test\n")
+            .unwrap();
+
+        let hist = format!(
+            "HISTORY = \"{}\"",
+            path.display().to_string().replace('\\', "/")
+        );
+        assert_eval_ok!(
+            &format!("{}; history -s 2 | x; $x", hist),
+            Value::from("2  cargo build\n    3  cargo test")
+        );
+        assert_eval_ok!(
+            &format!("{}; history -d 2; history cargo | x; $x", hist),
+            Value::from("2  cargo test")
+        );
+        assert_eval_ok!(
+            &format!("{}; history -c; history | x; $x", hist),
+            Value::from("")
+        );
+        assert_eval_err!("history", "History file not set ($HISTORY)");
+    }
+
+    #[test]
+    fn test_fc() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("history.txt");
+        File::create(&path)
+            .unwrap()
+            .write_all(b"#V2\necho one\necho two\nls\n")
+            .unwrap();
+
+        let hist = format!(
+            "HISTORY = \"{}\"",
+            path.display().to_string().replace('\\', "/")
+        );
+        assert_eval_ok!(
+            &format!("{}; fc -l -n -r 1 2 | x; $x", hist),
+            Value::from("echo two\necho one")
+        );
+        assert_eval_ok!(
+            &format!("{}; fc -s two=three ech | x; $x", hist),
+            Value::from("three")
+        );
+        assert_eval_ok!(
+            &format!("{}; fc -l -n -2 | x; $x", hist),
+            Value::from("ls\necho three")
+        );
+        assert_eval_err!("fc -l", "History file not set ($HISTORY)");
+    }
+
     #[test]
     fn test_diff_err() {
         assert_err_loc!("diff  --color x y", Location::new(1, 14));
@@ -134,6 +357,49 @@ mod tests {
         assert_eval_ok!(&sort_command, Value::from("1\n2\n10"));
     }
 
+    #[test]
+    #[cfg(not(windows))]
+    fn test_exec() {
+        // Run exec in a background job, so that it does not replace the test process.
+        assert_eval_ok!(
+            "((exec sh -c \"exit 3\"; echo next) & fg || echo failed) | x; $x",
+            Value::from("failed")
+        );
+        assert_eval_ok!("exec; 42", Value::Int(42));
+        assert_eval_err!("exec echo hi", "echo is not an external command");
+
+        // Redirections of exec without a command last until undone
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("session.log");
+        let path = path.display().to_string().replace('\\', "/");
+        assert_eval_ok!(
+            &format!("exec > {}; echo hello; exec >&-; echo world | x; $x", path),
+            Value::from("world")
+        );
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "hello\n");
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn test_hash() {
+        // Other tests may hash commands concurrently, look for sh among the listed commands.
+        assert_eval_ok!(
+            "hash -r; hash sh; hash | x; found = 0; for word in $x; (if ($word == \"sh\") ($found = 1)); $found",
+            Value::Int(1)
+        );
+        assert_eval_err!("hash bogus_command", "bogus_command: not found");
+    }
+
+    #[test]
+    fn test_which() {
+        assert_eval_ok!("which cd | x; $x", Value::from("cd: built-in"));
+        assert_eval_ok!(
+            "alias which_test \"echo hi\"; type which_test | x; $x",
+            Value::from("which_test: alias for echo hi")
+        );
+        assert_eval_err!("which bogus_command", "bogus_command: not found");
+    }
+
     #[test]
     fn test_which_error() {
         assert_eval_err!("which if", "Expecting IF condition");
@@ -163,6 +163,15 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn test_for_command_output() {
+        assert_eval_ok!(
+            "l = []; for x in (echo a; echo \"b c\"); ($l = $l + $x); $l == [a, \"b c\"]",
+            Value::Int(1)
+        );
+        assert_eval_ok!("x = 3; for i in ($x + 2); ($i)", Value::Int(5));
+    }
+
     #[test]
     fn test_for_tilde() {
         let mut interp = Interp::with_env_vars();
@@ -358,6 +367,7 @@ pub mod tests {
     fn test_arithmetic() {
         assert_eval_ok!("2+2", Value::Int(4));
         assert_eval_ok!("1 - 2 * 2 + 3", Value::Int(0));
+        assert_eval_ok!("2 * (3 + 1)", Value::Int(8));
     }
 
     #[test]
@@ -436,6 +446,118 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn test_status_and_or() {
+        // && and || are left-associative, as in POSIX shells
+        assert_eval_ok!(
+            "(cp -x && echo ran || echo failed) | x; $x",
+            Value::from("failed")
+        );
+        assert_eval_ok!(
+            "(echo ok && echo ran || echo failed) | x; $x",
+            Value::from("ok\nran")
+        );
+        assert_eval_ok!(
+            "(cp -x || echo failed && echo then) | x; $x",
+            Value::from("failed\nthen")
+        );
+        assert_eval_ok!("0 || 1 && 0", Value::Int(0));
+    }
+
+    #[test]
+    fn test_exit_status_var() {
+        assert_eval_ok!("echo; $?", Value::Int(0));
+        assert_eval_ok!("(cp -x || echo $?) | x; $x", Value::from("1"));
+        #[cfg(not(windows))]
+        {
+            assert_eval_ok!("(sh -c \"exit 3\" || echo $?) | x; $x", Value::from("3"));
+            assert_eval_ok!(
+                "if (echo hi | sh -c \"exit 7\" || $? == 7) (\"seven\")",
+                Value::from("seven")
+            );
+            // Killed by SIGTERM
+            assert_eval_ok!(
+                "(sh -c r\"(kill -TERM $$)\" || echo $?) | x; $x",
+                Value::from("143")
+            );
+        }
+    }
+
+    #[test]
+    fn test_return() {
+        assert_eval_ok!("x = 1; return \"abc\"; $x = 2", Value::from("abc"));
+        assert_eval_ok!(
+            "for i in 1 2 3; (if ($i == 2) (return $i - 2)); 5",
+            Value::Int(0)
+        );
+        assert_eval_ok!("if (eval \"return 3\") (0) else ($?)", Value::Int(3));
+        assert_eval_ok!("eval \"return; 1\"; $?", Value::Int(0));
+        assert_eval_err!("return 5", "return code: 5");
+        assert_eval_err!("1 return 2", "Unexpected expression before return");
+    }
+
+    #[test]
+    fn test_subshell_and_group() {
+        let cwd = std::env::current_dir().unwrap();
+        assert_eval_cmd_ok!("(cd ..; ls)");
+        assert_eq!(std::env::current_dir().unwrap(), cwd);
+
+        assert_eval_ok!("(y = 1); $y", Value::from("$y"));
+        assert_eval_ok!("y = 1; ($y = 2; y = 3); $y", Value::Int(1));
+        assert_eval_ok!(
+            "(export SUBSHELL_TEST=2); echo $SUBSHELL_TEST | x; $x",
+            Value::from("$SUBSHELL_TEST")
+        );
+        assert!(std::env::var("SUBSHELL_TEST").is_err());
+        assert_eval_ok!("((cp -x) || echo $?) | x; $x", Value::from("1"));
+        assert_eval_ok!("{ y = 1; }; $y", Value::Int(1));
+        assert_eval_ok!("{ echo a; echo b; } | x; $x", Value::from("a\nb"));
+        assert_eval_ok!(
+            "(cd /no/such/dir && { echo a; } || { echo b; }) | x; $x",
+            Value::from("b")
+        );
+        assert_eval_err!("echo a; }", "Unmatched right brace, missing semicolon?");
+    }
+
+    #[test]
+    fn test_time() {
+        assert_eval_ok!("x = time (y = 2; $y + 3); $x", Value::Int(5));
+        assert_eval_ok!("(time echo hello) | x; $x", Value::from("hello"));
+        assert_eval_err!("1 time 2", "Unexpected expression before time");
+    }
+
+    #[test]
+    fn test_local() {
+        assert_eval_ok!("x = 1; (local x = $x + 1; $x = $x * 5); $x", Value::Int(1));
+        assert_eval_ok!("local x; len $x", Value::Int(0));
+        assert_eval_ok!(
+            "eval --export r\"(local a = 1; b = 2)\"; if (defined a) (0) else ($b)",
+            Value::Int(2)
+        );
+        assert_eval_err!(
+            "local $x = 1",
+            "Identifier expected in local variable declaration"
+        );
+    }
+
+    #[test]
+    fn test_readonly() {
+        assert_eval_ok!("readonly x = 5; $x + 1", Value::Int(6));
+        assert_eval_err!(
+            "readonly x = 5; $x = 6",
+            "Cannot assign to read-only variable: x"
+        );
+        assert_eval_err!(
+            "x = 1; readonly x; (x = 2)",
+            "Cannot assign to read-only variable: x"
+        );
+        assert_eval_err!(
+            "readonly l = [1]; $l[0] =",
+            "Cannot assign to read-only variable: l"
+        );
+        assert_eval_err!("readonly y", "Variable not found: y");
+    }
+
     #[test]
     fn test_negated_status() {
         assert_eval_ok!(
@@ -485,6 +607,136 @@ pub mod tests {
         assert_eval_ok!("$FOO", Value::from("$FOO"));
     }
 
+    #[test]
+    #[cfg(not(windows))]
+    fn test_export_builtin() {
+        // Variables that are not exported are not passed to external commands.
+        assert_eval_ok!(
+            "x = 1; (sh -c r\"(echo \"<$x>\")\") | o; $o",
+            Value::from("<>")
+        );
+        assert_eval_ok!(
+            "export EXPORT_TEST=2; (sh -c r\"(echo \"<$EXPORT_TEST>\")\") | o; $o",
+            Value::from("<2>")
+        );
+        assert_eval_ok!(
+            "x = 3; export x; (sh -c r\"(echo \"<$x>\")\") | o; $o",
+            Value::from("<3>")
+        );
+        assert_eval_ok!(
+            "export -n EXPORT_TEST; env | grep EXPORT_TEST | o; $o",
+            Value::from("")
+        );
+        assert_eval_ok!("(echo CC=gcc) | o; $o", Value::from("CC=gcc"));
+    }
+
+    #[test]
+    fn test_source() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("source.my");
+        std::fs::write(
+            &path,
+            "n = $#; total = $1 + $2; local tmp = 1; if ($1 == 0) (return 5)",
+        )
+        .unwrap();
+        let path = path.display().to_string().replace('\\', "/");
+
+        assert_eval_ok!(&format!("source {} 2 3; $total", path), Value::Int(5));
+        assert_eval_ok!(&format!("source {} 2 3; $n", path), Value::Int(2));
+        // Locals and positional arguments do not outlive the script.
+        assert_eval_ok!(&format!("source {} 2 3; $tmp", path), Value::from("$tmp"));
+        assert_eval_ok!(&format!("source {} 2 3; $1", path), Value::from("$1"));
+        assert!(eval(&format!("source {} 0 3", path)).is_err());
+        assert_eval_err!("source", "Missing script file name");
+    }
+
+    #[test]
+    fn test_set() {
+        assert!(eval("ls /no/such/dir; 42").is_err());
+        assert_eval_ok!("set +e; ls /no/such/dir; 42", Value::Int(42));
+        assert_eval_ok!("echo $no_such_var | x; $x", Value::from("$no_such_var"));
+        assert_eval_err!(
+            "set -u; echo $no_such_var",
+            "Undefined variable: no_such_var"
+        );
+        assert_eval_ok!(
+            "set -x; x = 1; set +x -u; echo \"$x\" | y; $y",
+            Value::Int(1)
+        );
+    }
+
+    #[test]
+    fn test_brace_expansion() {
+        assert_eval_ok!(
+            "echo file.{txt,bak} | x; $x",
+            Value::from("file.txt file.bak")
+        );
+        assert_eval_ok!("echo day{01..03} | x; $x", Value::from("day01 day02 day03"));
+        assert_eval_ok!("echo {a..e..2} {3..1} | x; $x", Value::from("a c e 3 2 1"));
+        assert_eval_ok!(
+            "echo a{b,c{d,e}} \"{x,y}\" {z} | x; $x",
+            Value::from("ab acd ace {x,y} {z}")
+        );
+    }
+
+    #[test]
+    fn test_tilde_expansion() {
+        assert_eval_ok!(
+            "HOME = \"/some/home\"; echo ~ ~/x \"~\" | x; $x",
+            Value::from("/some/home /some/home/x ~")
+        );
+        assert_eval_ok!(
+            "HOME = \"/a\"; echo ~/{b,c} | x; $x",
+            Value::from("/a/b /a/c")
+        );
+        assert_eval_ok!("echo ~no_such_user_ | x; $x", Value::from("~no_such_user_"));
+    }
+
+    #[test]
+    fn test_list() {
+        assert_eval_ok!("l = [1, 2.5, \"a b\", c]; len $l", Value::Int(4));
+        assert_eval_ok!("l = [1, 2, 3]; $l[0] + $l[-1]", Value::Int(4));
+        assert_eval_ok!(
+            "l = [1, 2, 3]; $l[1:]",
+            Value::List(std::sync::Arc::new(vec![Value::Int(2), Value::Int(3)]))
+        );
+        assert_eval_ok!(
+            "l = [1]; l = $l + 2 + [3, 4]; $l[1:3] == [2, 3]",
+            Value::Int(1)
+        );
+        assert_eval_ok!(
+            "l = [\"a b\", c]; (for x in $l; (echo \"<$x>\")) | out; $out",
+            Value::from("<a b>\n<c>")
+        );
+        assert_eval_ok!("[] == [1]", Value::Int(0));
+        assert_eval_err!("l = [1]; $l[1]", "List index out of range: 1");
+        assert_eval_err!(
+            "[1] < [2]",
+            "Lists and maps can only be compared for equality"
+        );
+    }
+
+    #[test]
+    fn test_map() {
+        assert_eval_ok!("m = [a = 1, b = \"x y\"]; $m[b]", Value::from("x y"));
+        assert_eval_ok!("m = [a = 1]; k = a; $m[$k] + 1", Value::Int(2));
+        assert_eval_ok!(
+            "m = [a = 1]; $m[b] = 2; $m[a] =; $m == [b = 2]",
+            Value::Int(1)
+        );
+        assert_eval_ok!(
+            "m = [a = 1] + [a = 2, b = 3]; $m[a] + len $m",
+            Value::Int(4)
+        );
+        assert_eval_ok!("len [=]", Value::Int(0));
+        assert_eval_ok!(
+            "m = [b = 2, a = 1]; (for k in $m; (echo $k $m[$k])) | out; $out",
+            Value::from("a 1\nb 2")
+        );
+        assert_eval_err!("m = [a = 1]; $m[b]", "Key not found: b");
+        assert_eval_err!("[a = 1, 2]", "Expecting KEY = VALUE in map literal");
+    }
+
     #[test]
     fn test_escape_unicode() {
         assert_eval_ok!("\"\\u{1b}\"", Value::from("\x1b"));
@@ -1,29 +1,76 @@
 /// Misc. collection of helper routines used by core interpreter and built-in cmds.
-use crate::scope::Scope;
+use crate::scope::{Scope, UNEXPORTED};
 use std::env;
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
 // Maximum length for displaying user account name (ls, ps)
 pub const MAX_USER_DISPLAY_LEN: usize = 16;
 
 /// Copy variables from the current scope outwards into the environment of the
 /// command to be executed, but do not carry over special redirect variables.
+/// Used for child instances of the interpreter (pipes, background jobs), which
+/// need all variables; the names of the variables that are not exported are
+/// passed along, so that they are not exported by the child either.
 pub fn copy_vars_to_command_env(command: &mut std::process::Command, scope: &Arc<Scope>) {
     // Override existing environment variables
     command.env_clear();
 
-    let mut current_scope = Some(scope);
-    while let Some(scope) = &current_scope {
-        for (key, variable) in scope.vars().iter() {
-            if !key.is_special_var() {
-                command.env(&key.view(), variable.value().to_string());
+    let mut unexported = vec![];
+
+    for (key, variable) in scope.visible_vars() {
+        if !key.is_special_var() {
+            command.env(key.view(), variable.value().to_string());
+            if !variable.is_exported() {
+                unexported.push(key.to_string());
             }
         }
-        current_scope = scope.parent.as_ref();
     }
+    command.env(UNEXPORTED, unexported.join(" "));
+}
+
+/// Build the environment of an external command from the exported variables.
+pub fn copy_exported_vars_to_command_env(command: &mut std::process::Command, scope: &Arc<Scope>) {
+    command.env_clear();
+
+    for (key, variable) in scope.visible_vars() {
+        if variable.is_exported() && !key.is_special_var() {
+            command.env(key.view(), variable.value().to_string());
+        }
+    }
+}
+
+/// Expand a leading ~ or ~user, using the value of $HOME in the given scope for the current user.
+/// Return None if the string does not start with a tilde, or if the user is not known.
+pub fn expand_tilde(s: &str, scope: &Scope) -> Option<String> {
+    let rest = s.strip_prefix('~')?;
+    let (user, tail) = rest.split_at(rest.find(std::path::is_separator).unwrap_or(rest.len()));
+
+    let home = if user.is_empty() {
+        scope.lookup("HOME")?.value().to_string()
+    } else {
+        user_home_dir(user, scope)?
+    };
+    Some(format!("{}{}", home, tail))
+}
+
+#[cfg(unix)]
+fn user_home_dir(user: &str, _: &Scope) -> Option<String> {
+    use uzers::os::unix::UserExt;
+
+    uzers::get_user_by_name(user).map(|u| u.home_dir().to_string_lossy().to_string())
+}
+
+#[cfg(windows)]
+fn user_home_dir(user: &str, scope: &Scope) -> Option<String> {
+    // Assume that user profiles are side by side, i.e. C:\Users\<user>
+    let home = scope.lookup("HOME")?.value().to_string();
+    let path = Path::new(&home).parent()?.join(user);
+
+    path.is_dir().then(|| path.to_string_lossy().to_string())
 }
 
 /// Clear the environment, and copy variables from scope into environment.
@@ -32,7 +79,9 @@ pub fn sync_env_vars(scope: &Scope) {
     env::vars().for_each(|(key, _)| env::remove_var(key));
 
     for (key, var) in scope.vars().iter() {
-        env::set_var(key.as_str(), var.to_string());
+        if var.is_exported() {
+            env::set_var(key.as_str(), var.to_string());
+        }
     }
 }
 
@@ -89,10 +138,128 @@ pub fn format_size(size: u64, block_size: u64, human_readable: bool) -> String {
     format!("{:.1} {}", formatted_size, units[index])
 }
 
+/// Parse a size such as 512, 10K, 1.5M or 2GiB; units are powers of 1024, as in format_size.
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+
+    let number = number
+        .parse::<f64>()
+        .map_err(|_| format!("Invalid size: {}", s))?;
+    let unit = unit.trim().to_uppercase();
+    let unit = unit
+        .strip_suffix("IB")
+        .or_else(|| unit.strip_suffix('B'))
+        .unwrap_or(&unit);
+
+    let exponent = match unit {
+        "" => 0,
+        _ => match "KMGTPEZY".find(unit) {
+            Some(index) if unit.len() == 1 => index as i32 + 1,
+            _ => return Err(format!("Invalid size unit: {}", s)),
+        },
+    };
+    Ok((number * 1024f64.powi(exponent)) as u64)
+}
+
+/// How format_duration shows durations.
+pub enum DurationStyle {
+    Minutes, // 1m5.250s, like the time keyword
+    Units,   // 1h 2m 5s, 2m 5s, 5.25s
+    Clock,   // [DAYS-]HH:MM:SS, like ps and top
+}
+
+/// Format the duration for humans, in the given style.
+pub fn format_duration(duration: Duration, style: DurationStyle) -> String {
+    let secs = duration.as_secs();
+    let (days, hours, minutes, seconds) = (
+        secs / 86400,
+        secs % 86400 / 3600,
+        secs % 3600 / 60,
+        secs % 60,
+    );
+    match style {
+        DurationStyle::Minutes => format!("{}m{:.3}s", secs / 60, duration.as_secs_f64() % 60.0),
+        DurationStyle::Units if secs < 60 => format!("{:.2}s", duration.as_secs_f64()),
+        DurationStyle::Units if secs < 3600 => format!("{}m {}s", minutes, seconds),
+        DurationStyle::Units => format!("{}h {}m {}s", secs / 3600, minutes, seconds),
+        DurationStyle::Clock if days > 0 => {
+            format!("{}-{:02}:{:02}:{:02}", days, hours, minutes, seconds)
+        }
+        DurationStyle::Clock => format!("{:02}:{:02}:{:02}", hours, minutes, seconds),
+    }
+}
+
+/// User and system CPU times of the terminated child processes (and their descendants).
+#[cfg(unix)]
+pub fn child_cpu_times() -> Option<(Duration, Duration)> {
+    use nix::sys::resource::{getrusage, UsageWho};
+
+    let usage = getrusage(UsageWho::RUSAGE_CHILDREN).ok()?;
+    let duration =
+        |t: nix::sys::time::TimeVal| Duration::new(t.tv_sec() as u64, t.tv_usec() as u32 * 1000);
+    Some((duration(usage.user_time()), duration(usage.system_time())))
+}
+
+#[cfg(windows)]
+pub fn child_cpu_times() -> Option<(Duration, Duration)> {
+    None
+}
+
 pub fn terminal_width() -> usize {
     crossterm::terminal::size().unwrap_or((80, 0)).0.into()
 }
 
+/// The text editor specified by $EDITOR, or the platform default.
+pub fn default_editor(scope: &Arc<Scope>) -> String {
+    scope
+        .lookup_value("EDITOR")
+        .map(|v| v.to_string())
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| {
+            if cfg!(windows) {
+                "notepad".to_string()
+            } else {
+                "vi".to_string()
+            }
+        })
+}
+
+/// Open the text in the editor (in a temporary file with the given prefix), and return the edited text.
+pub fn edit_text(
+    scope: &Arc<Scope>,
+    editor: &str,
+    prefix: &str,
+    text: &str,
+) -> Result<String, String> {
+    use std::io::Write;
+
+    let mut file = tempfile::Builder::new()
+        .prefix(prefix)
+        .suffix(".txt")
+        .tempfile()
+        .map_err(|e| format!("Could not create temporary file: {}", e))?;
+    writeln!(file, "{}", text).map_err(|e| e.to_string())?;
+
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or_default();
+    let mut command = std::process::Command::new(program);
+    command.args(words).arg(file.path());
+    copy_exported_vars_to_command_env(&mut command, scope);
+
+    let status = command
+        .status()
+        .map_err(|e| format!("{}: {}", scope.err_str(program), e))?;
+    if !status.success() {
+        return Err(format!("{}: {}", scope.err_str(program), status));
+    }
+
+    fs::read_to_string(file.path()).map_err(|e| e.to_string())
+}
+
 ///
 /// Windows-specific helpers (read WSL symbolic link reparse points, detect elevated mode, etc.)
 /// TODO: Refactor to separate file.
@@ -122,7 +289,9 @@ pub mod win {
         Win32::Storage::FileSystem::{
             FILE_FLAG_OPEN_REPARSE_POINT, FILE_READ_ATTRIBUTES, FILE_SHARE_READ, FILE_SHARE_WRITE,
         },
-        Win32::System::Ioctl::{FSCTL_DELETE_REPARSE_POINT, FSCTL_GET_REPARSE_POINT},
+        Win32::System::Ioctl::{
+            FSCTL_DELETE_REPARSE_POINT, FSCTL_GET_REPARSE_POINT, FSCTL_SET_REPARSE_POINT,
+        },
         Win32::System::IO::DeviceIoControl,
     };
     use windows_sys::Win32::Foundation::LocalFree;
@@ -131,6 +300,7 @@ pub mod win {
     /// Reparse Data Types.
     ///
     pub const IO_REPARSE_TAG_LX_SYMLINK: u32 = 0xA000001D;
+    pub const IO_REPARSE_TAG_MOUNT_POINT: u32 = 0xA0000003;
     pub const MAX_REPARSE_DATA_BUFFER_SIZE: usize = 16 * 1024;
 
     #[repr(C)]
@@ -288,6 +458,83 @@ pub mod win {
         }
     }
 
+    /// Create a directory junction (mount point reparse point) to the target directory.
+    /// Unlike symbolic links, junctions do not require elevation or Developer Mode.
+    pub fn create_junction(target: &Path, link: &Path) -> io::Result<()> {
+        let target = std::path::absolute(target)?;
+        if !target.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The target of a junction must be a directory",
+            ));
+        }
+
+        // The substitute name is the NT path of the target, the print name is the DOS path.
+        let print_name: Vec<u16> = target.as_os_str().encode_wide().collect();
+        let subst_name: Vec<u16> = "\\??\\".encode_utf16().chain(print_name.clone()).collect();
+
+        // ReparseTag, ReparseDataLength, Reserved, followed by the MountPointReparseBuffer:
+        // SubstituteNameOffset, SubstituteNameLength, PrintNameOffset, PrintNameLength, and
+        // the null-terminated names. Offsets and lengths are in bytes.
+        let path_buffer: Vec<u16> = subst_name
+            .iter()
+            .chain(&[0])
+            .chain(&print_name)
+            .chain(&[0])
+            .copied()
+            .collect();
+        let data_length = 8 + path_buffer.len() * 2;
+
+        let mut buffer = Vec::with_capacity(8 + data_length);
+        buffer.extend_from_slice(&IO_REPARSE_TAG_MOUNT_POINT.to_le_bytes());
+        buffer.extend_from_slice(&(data_length as u16).to_le_bytes());
+        buffer.extend_from_slice(&0u16.to_le_bytes());
+        for n in [
+            0,
+            subst_name.len() * 2,
+            (subst_name.len() + 1) * 2,
+            print_name.len() * 2,
+        ] {
+            buffer.extend_from_slice(&(n as u16).to_le_bytes());
+        }
+        for c in path_buffer {
+            buffer.extend_from_slice(&c.to_le_bytes());
+        }
+        if buffer.len() > MAX_REPARSE_DATA_BUFFER_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The target path is too long",
+            ));
+        }
+
+        fs::create_dir(link)?;
+
+        let result = OpenOptions::new()
+            .write(true)
+            .custom_flags(FILE_FLAG_BACKUP_SEMANTICS.0 | FILE_FLAG_OPEN_REPARSE_POINT.0)
+            .open(link)
+            .and_then(|file| {
+                unsafe {
+                    DeviceIoControl(
+                        HANDLE(file.as_raw_handle()),
+                        FSCTL_SET_REPARSE_POINT,
+                        Some(buffer.as_ptr() as *const _),
+                        buffer.len() as u32,
+                        None,
+                        0,
+                        None,
+                        None,
+                    )
+                }
+                .map_err(|_| io::Error::last_os_error())
+            });
+
+        if result.is_err() {
+            _ = fs::remove_dir(link);
+        }
+        result
+    }
+
     ///
     /// Detect if current process is running in elevated mode.
     ///
@@ -503,3 +750,43 @@ pub fn format_error<E: std::fmt::Display>(
 ) -> String {
     format!("{}: {}", scope.err_path_arg(value, args), error)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("10K"), Ok(10 * 1024));
+        assert_eq!(parse_size("1.5m"), Ok(1536 * 1024));
+        assert_eq!(parse_size("2GiB"), Ok(2 << 30));
+        assert_eq!(parse_size("3 KB"), Ok(3 * 1024));
+        assert!(parse_size("").is_err());
+        assert!(parse_size("M").is_err());
+        assert!(parse_size("10X").is_err());
+        assert!(parse_size("10KM").is_err());
+    }
+
+    #[test]
+    fn test_format_duration() {
+        let duration = Duration::from_millis(65_250);
+        assert_eq!(
+            format_duration(duration, DurationStyle::Minutes),
+            "1m5.250s"
+        );
+        assert_eq!(format_duration(duration, DurationStyle::Units), "1m 5s");
+        assert_eq!(format_duration(duration, DurationStyle::Clock), "00:01:05");
+
+        let duration = Duration::from_secs(90000);
+        assert_eq!(format_duration(duration, DurationStyle::Units), "25h 0m 0s");
+        assert_eq!(
+            format_duration(duration, DurationStyle::Clock),
+            "1-01:00:00"
+        );
+        assert_eq!(
+            format_duration(Duration::from_millis(5250), DurationStyle::Units),
+            "5.25s"
+        );
+    }
+}